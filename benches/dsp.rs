@@ -0,0 +1,131 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use nyasynth::{
+    common::{Note, SampleRate, Vel},
+    dsp::{Chorus, Engine, LfoBank, LfoSlot, MeowParameters, NoteShape, Parameters, Voice},
+};
+
+const SAMPLE_RATE: SampleRate = SampleRate(44100.0);
+// The plugin renders in blocks of at most this many samples, regardless of the host's buffer size.
+const BLOCK_SIZE: usize = 64;
+const BUFFER_SIZES: [usize; 4] = [64, 256, 1024, 8192];
+
+/// The synth's engine and some voices, rendered the same way the plugin renders them, but without
+/// a plugin host or any events.
+struct Bench {
+    parameters: Parameters,
+    params: MeowParameters,
+    engine: Engine,
+    voices: Vec<Voice>,
+    left: Vec<f32>,
+    right: Vec<f32>,
+}
+
+impl Bench {
+    fn new(num_voices: usize, buffer_size: usize) -> Bench {
+        let parameters = Parameters::new();
+        let params = MeowParameters::new(&parameters, 120.0, 0);
+        // Spread the voices out over a few octaves, like a big chord.
+        let voices = (0..num_voices)
+            .map(|i| {
+                let note = Note(48 + (i * 3) as u8);
+                Voice::new(&params, None, note, Vel::new(0.8), SAMPLE_RATE)
+            })
+            .collect();
+        Bench {
+            parameters,
+            params,
+            engine: Engine::new(BLOCK_SIZE, SAMPLE_RATE),
+            voices,
+            left: vec![0.0; buffer_size],
+            right: vec![0.0; buffer_size],
+        }
+    }
+
+    /// Render the voices over one block, without the effects.
+    fn render_block(&mut self, block_len: usize) -> f32 {
+        self.engine.render_block(
+            &mut self.voices,
+            (&self.parameters, &self.params),
+            None,
+            SAMPLE_RATE,
+            &mut self.left[..block_len],
+            &mut self.right[..block_len],
+        )
+    }
+
+    /// Render the whole buffer, split into blocks the same way the plugin does, and run the effects
+    /// over it.
+    fn render_buffer(&mut self) -> f32 {
+        let buffer_size = self.left.len();
+        let mut block_start = 0;
+        while block_start < buffer_size {
+            let block_end = (block_start + BLOCK_SIZE).min(buffer_size);
+            self.engine.render_block(
+                &mut self.voices,
+                (&self.parameters, &self.params),
+                None,
+                SAMPLE_RATE,
+                &mut self.left[block_start..block_end],
+                &mut self.right[block_start..block_end],
+            );
+            block_start = block_end;
+        }
+        self.engine
+            .post_process(&self.params, SAMPLE_RATE, &mut self.left, &mut self.right);
+        self.left.iter().sum()
+    }
+}
+
+fn voice(c: &mut Criterion) {
+    let mut bench = Bench::new(1, BLOCK_SIZE);
+    c.bench_function("voice render (1 voice, 64 samples)", |b| {
+        b.iter(|| bench.render_block(black_box(BLOCK_SIZE)))
+    });
+}
+
+fn noise_and_filter(c: &mut Criterion) {
+    // Turning the noise on also runs the right channel's filter, so this measures the filter too.
+    let mut bench = Bench::new(1, BLOCK_SIZE);
+    bench.params.noise_mix = 0.5;
+    bench.params.noise_width = 1.0;
+    c.bench_function(
+        "voice render with stereo noise (1 voice, 64 samples)",
+        |b| b.iter(|| bench.render_block(black_box(BLOCK_SIZE))),
+    );
+}
+
+fn chorus(c: &mut Criterion) {
+    let params = MeowParameters::new(&Parameters::new(), 120.0, 0);
+    let mut chorus = Chorus::new(SAMPLE_RATE);
+    let mut lfo_bank = LfoBank::new();
+    c.bench_function("chorus (64 samples)", |b| {
+        b.iter(|| {
+            let mut sum = 0.0;
+            for i in 0..BLOCK_SIZE {
+                let input = black_box(i as f32 / BLOCK_SIZE as f32);
+                sum += chorus.next_sample(
+                    input,
+                    SAMPLE_RATE,
+                    &params.chorus,
+                    NoteShape::Sine,
+                    lfo_bank.get(LfoSlot::Chorus),
+                );
+            }
+            sum
+        })
+    });
+}
+
+fn polycat(c: &mut Criterion) {
+    let mut group = c.benchmark_group("16 voice polycat");
+    for buffer_size in BUFFER_SIZES {
+        let mut bench = Bench::new(16, buffer_size);
+        group.bench_with_input(BenchmarkId::from_parameter(buffer_size), &(), |b, _| {
+            b.iter(|| bench.render_buffer())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, voice, noise_and_filter, chorus, polycat);
+criterion_main!(benches);
@@ -0,0 +1,95 @@
+use std::cell::Cell;
+
+#[cfg(debug_assertions)]
+use std::alloc::{GlobalAlloc, Layout, System};
+
+// In debug builds, every allocation goes through the CheckingAllocator, so that the audio thread
+// can assert that it never allocates. Allocating may take a lock inside the system allocator, which
+// can stall the audio thread long enough to cause a dropout.
+#[cfg(debug_assertions)]
+#[global_allocator]
+static ALLOCATOR: CheckingAllocator = CheckingAllocator;
+
+thread_local! {
+    // True while allocations are forbidden on this thread.
+    static FORBIDDEN: Cell<bool> = const { Cell::new(false) };
+    // The number of allocations made on this thread while allocations were forbidden.
+    static VIOLATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Asserts that nothing allocates on this thread for as long as the guard is alive. This only
+/// checks anything in debug builds. Note that the assertion happens when the guard is dropped,
+/// since panicking inside of the allocator is not allowed.
+pub struct NoAllocGuard {
+    was_forbidden: bool,
+}
+
+impl NoAllocGuard {
+    pub fn new() -> NoAllocGuard {
+        let was_forbidden = FORBIDDEN.with(|forbidden| forbidden.replace(true));
+        NoAllocGuard { was_forbidden }
+    }
+}
+
+impl Drop for NoAllocGuard {
+    fn drop(&mut self) {
+        FORBIDDEN.with(|forbidden| forbidden.set(self.was_forbidden));
+        if self.was_forbidden || std::thread::panicking() {
+            return;
+        }
+        let violations = VIOLATIONS.with(|violations| violations.replace(0));
+        debug_assert!(
+            violations == 0,
+            "Allocated {} times on the audio thread",
+            violations
+        );
+    }
+}
+
+/// Run `f`, allowing it to allocate even while a [NoAllocGuard] is alive. This is for rare events, such
+/// as MIDI learn, where a one-off allocation is acceptable.
+pub fn permit_alloc<T>(f: impl FnOnce() -> T) -> T {
+    let was_forbidden = FORBIDDEN.with(|forbidden| forbidden.replace(false));
+    let value = f();
+    FORBIDDEN.with(|forbidden| forbidden.set(was_forbidden));
+    value
+}
+
+#[cfg(debug_assertions)]
+struct CheckingAllocator;
+
+#[cfg(debug_assertions)]
+impl CheckingAllocator {
+    fn check(&self) {
+        // The thread locals may already be destroyed if this thread is shutting down, in which case
+        // there is nothing to check.
+        let forbidden = FORBIDDEN
+            .try_with(|forbidden| forbidden.get())
+            .unwrap_or(false);
+        if forbidden {
+            let _ = VIOLATIONS.try_with(|violations| violations.set(violations.get() + 1));
+        }
+    }
+}
+
+#[cfg(debug_assertions)]
+unsafe impl GlobalAlloc for CheckingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.check();
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        self.check();
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        self.check();
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
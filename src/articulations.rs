@@ -0,0 +1,86 @@
+/// A playing style which keyswitches switch between. An articulation is a partial preset: it only
+/// sets the parameters it lists (as parameter ID, plain value pairs, like a preset), and leaves the
+/// rest of the sound alone. Every articulation lists the same parameters, so that switching always
+/// undoes whatever the previous articulation changed.
+pub struct Articulation {
+    pub values: &'static [(&'static str, f32)],
+}
+
+pub const ARTICULATIONS: &[Articulation] = &[
+    // Short Meow
+    Articulation {
+        values: &[
+            ("meow_attack", 0.01),
+            ("meow_decay", 0.25),
+            ("meow_sustain", -48.0),
+            ("meow_release", 0.1),
+            ("vibrato_amount", 0.0),
+            ("noise_mix", 0.0),
+            ("hiss_mode", 0.0),
+            ("purr_amount", 0.0),
+        ],
+    },
+    // Long Meow
+    Articulation {
+        values: &[
+            ("meow_attack", 0.03),
+            ("meow_decay", 1.25),
+            ("meow_sustain", -30.0),
+            ("meow_release", 0.49),
+            ("vibrato_amount", 0.0),
+            ("noise_mix", 0.0),
+            ("hiss_mode", 0.0),
+            ("purr_amount", 0.0),
+        ],
+    },
+    // Purr
+    Articulation {
+        values: &[
+            ("meow_attack", 0.15),
+            ("meow_decay", 2.0),
+            ("meow_sustain", -12.0),
+            ("meow_release", 0.8),
+            ("vibrato_amount", 0.0),
+            ("noise_mix", 0.15),
+            ("hiss_mode", 0.0),
+            ("purr_amount", 0.6),
+        ],
+    },
+    // Hiss
+    Articulation {
+        values: &[
+            ("meow_attack", 0.005),
+            ("meow_decay", 0.6),
+            ("meow_sustain", -48.0),
+            ("meow_release", 0.3),
+            ("vibrato_amount", 0.0),
+            ("noise_mix", 0.0),
+            ("hiss_mode", 1.0),
+            ("purr_amount", 0.0),
+        ],
+    },
+];
+
+// The keyswitch for each articulation, in semitones above the C which starts the keyswitch octave.
+// These are the first white keys of the octave: C, D, E, and F.
+const KEYSWITCH_OFFSETS: [u8; 4] = [0, 2, 4, 5];
+
+pub struct KeyswitchParams {
+    pub enabled: bool,
+    /// The octave which the keyswitches are in, where C4 is middle C (MIDI note 60).
+    pub octave: i32,
+}
+
+impl KeyswitchParams {
+    /// The articulation which the note switches to, or None if the note isn't a keyswitch. Notes
+    /// in the keyswitch octave which aren't keyswitches are played as usual.
+    pub fn articulation(&self, note: u8) -> Option<usize> {
+        if !self.enabled {
+            return None;
+        }
+        let offset = note as i32 - (self.octave + 1) * 12;
+        KEYSWITCH_OFFSETS
+            .iter()
+            .position(|keyswitch| *keyswitch as i32 == offset)
+    }
+}
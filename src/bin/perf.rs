@@ -5,11 +5,11 @@ use derive_more::{Add, AddAssign, From, Into, Sub, SubAssign};
 use nih_plug::context::process::Transport;
 use nih_plug::context::PluginApi;
 use nih_plug::prelude::*;
-use nyasynth::Nyasynth;
 use nyasynth::{
     self,
     common::{SampleRate, SampleTime},
 };
+use nyasynth::{Nyasynth, Task};
 
 type VstEvent = NoteEvent<<Nyasynth as Plugin>::SysExMessage>;
 
@@ -242,7 +242,7 @@ impl InitContext<Nyasynth> for DebugContext {
         PluginApi::Standalone
     }
 
-    fn execute(&self, _task: ()) {}
+    fn execute(&self, _task: Task) {}
 
     fn set_latency_samples(&self, _samples: u32) {}
 
@@ -304,9 +304,9 @@ impl ProcessContext<Nyasynth> for DebugProcessContext {
         PluginApi::Standalone
     }
 
-    fn execute_background(&self, _task: ()) {}
+    fn execute_background(&self, _task: Task) {}
 
-    fn execute_gui(&self, _task: ()) {}
+    fn execute_gui(&self, _task: Task) {}
 
     fn transport(&self) -> &Transport {
         &self.transport
@@ -1,5 +1,6 @@
 use nyasynth::Nyasynth;
 
 fn main() {
+    nyasynth::set_standalone();
     nih_plug::wrapper::standalone::nih_export_standalone::<Nyasynth>();
 }
@@ -0,0 +1,434 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use atomic_float::AtomicF32;
+use nih_plug::{
+    context::{process::Transport, PluginApi},
+    prelude::*,
+};
+
+use crate::{
+    common::{Beats, SampleRate, SampleTime},
+    layers::LayerB,
+    params::Parameters,
+    settings::settings_dir,
+    Nyasynth, Task,
+};
+
+// The most events a recorded phrase can hold. Events past this are dropped, and the phrase ends
+// there. The room is allocated up front, since the audio thread does the recording.
+const MAX_PHRASE_EVENTS: usize = 4096;
+// A new phrase starts at the first note played after no keys have been held for this long.
+const PHRASE_GAP_SECONDS: f32 = 2.0;
+// Bounces keep rendering after the last event until the synth goes quiet, but no longer than this.
+const MAX_TAIL_SECONDS: f32 = 10.0;
+// The size of the buffers that bounces are rendered in.
+const BOUNCE_BUFFER_SIZE: usize = 512;
+
+// The demo riff, as (start, length, note, velocity), with the times in beats at DEMO_TEMPO. It
+// leaves room between some of the notes, so that the release gets heard too.
+const DEMO_TEMPO: f32 = 120.0;
+const DEMO_RIFF: &[(f32, f32, u8, f32)] = &[
+    (0.0, 0.5, 67, 0.7),
+    (0.5, 0.5, 72, 0.8),
+    (1.0, 1.0, 76, 0.9),
+    (2.5, 0.5, 74, 0.6),
+    (3.0, 0.5, 72, 0.7),
+    (3.5, 0.5, 69, 0.6),
+    (4.0, 3.0, 72, 1.0),
+];
+
+/// A sequence of events to bounce.
+pub struct Phrase {
+    /// The events, and when they play in samples from the start of the phrase, in order.
+    events: Vec<(SampleTime, NoteEvent<()>)>,
+    sample_rate: SampleRate,
+    tempo: f32,
+}
+
+impl Phrase {
+    /// The built-in demo riff, for auditioning a preset without playing anything first.
+    pub fn demo_riff(sample_rate: SampleRate) -> Phrase {
+        let samples_per_beat = Beats::new(1.0).to_seconds(DEMO_TEMPO).get() * sample_rate.get();
+        let to_samples = |beats: f32| (beats * samples_per_beat) as SampleTime;
+        let mut events = vec![];
+        for &(start, length, note, velocity) in DEMO_RIFF {
+            let note_on = NoteEvent::NoteOn {
+                timing: 0,
+                voice_id: None,
+                channel: 0,
+                note,
+                velocity,
+            };
+            let note_off = NoteEvent::NoteOff {
+                timing: 0,
+                voice_id: None,
+                channel: 0,
+                note,
+                velocity: 0.5,
+            };
+            events.push((to_samples(start), note_on));
+            events.push((to_samples(start + length), note_off));
+        }
+        // The sort is stable, so a note off at the same time as the next note on stays first.
+        events.sort_by_key(|(time, _)| *time);
+        Phrase {
+            events,
+            sample_rate,
+            tempo: DEMO_TEMPO,
+        }
+    }
+}
+
+/// Records the most recent phrase played on the synth, so that it can be bounced. The audio thread
+/// records every event it plays, and the editor takes a copy when the user bounces.
+pub struct PhraseRecorder {
+    phrase: Mutex<RecordedPhrase>,
+    // The time of the end of the most recent buffer, in samples since the synth started, and the
+    // tempo and sample rate at that time.
+    now: AtomicU64,
+    tempo: AtomicF32,
+    sample_rate: AtomicF32,
+}
+
+struct RecordedPhrase {
+    // The events, and when they played in samples since the synth started.
+    events: Vec<(u64, NoteEvent<()>)>,
+    // The notes held on each channel, as a bit for each note.
+    held: [u128; 16],
+    // When the last held note was released.
+    last_release: u64,
+}
+
+impl PhraseRecorder {
+    pub fn new() -> PhraseRecorder {
+        PhraseRecorder {
+            phrase: Mutex::new(RecordedPhrase {
+                events: Vec::with_capacity(MAX_PHRASE_EVENTS),
+                held: [0; 16],
+                last_release: 0,
+            }),
+            now: AtomicU64::new(0),
+            tempo: AtomicF32::new(120.0),
+            sample_rate: AtomicF32::new(44100.0),
+        }
+    }
+
+    /// Record an event played at `time`, in samples since the synth started. This is called by the
+    /// audio thread, and never blocks: if the editor is copying the phrase, the event is skipped.
+    pub fn record(&self, time: u64, event: NoteEvent<()>, sample_rate: SampleRate) {
+        let Ok(mut phrase) = self.phrase.try_lock() else {
+            return;
+        };
+        let no_keys_held = phrase.held.iter().all(|notes| *notes == 0);
+        if let NoteEvent::NoteOn { .. } = event {
+            let gap = time.saturating_sub(phrase.last_release);
+            if no_keys_held && gap as f32 >= PHRASE_GAP_SECONDS * sample_rate.get() {
+                phrase.events.clear();
+            }
+        }
+        match event {
+            NoteEvent::NoteOn { channel, note, .. } => {
+                phrase.held[channel as usize % 16] |= 1u128 << (note % 128);
+            }
+            NoteEvent::NoteOff { channel, note, .. } => {
+                phrase.held[channel as usize % 16] &= !(1u128 << (note % 128));
+                if phrase.held.iter().all(|notes| *notes == 0) {
+                    phrase.last_release = time;
+                }
+            }
+            _ => (),
+        }
+        if phrase.events.len() < MAX_PHRASE_EVENTS {
+            phrase.events.push((time, event));
+        }
+    }
+
+    /// Note the end of a buffer. This is called by the audio thread after every buffer.
+    pub fn end_buffer(&self, now: u64, tempo: f32, sample_rate: SampleRate) {
+        self.now.store(now, Ordering::Relaxed);
+        self.tempo.store(tempo, Ordering::Relaxed);
+        self.sample_rate.store(sample_rate.get(), Ordering::Relaxed);
+    }
+
+    /// The sample rate that the synth is running at.
+    pub fn sample_rate(&self) -> SampleRate {
+        SampleRate(self.sample_rate.load(Ordering::Relaxed))
+    }
+
+    /// A copy of the most recent phrase, or None if nothing has been played. Notes which are still
+    /// held are released at the current time.
+    pub fn phrase(&self) -> Option<Phrase> {
+        let phrase = self.phrase.lock().unwrap();
+        let start = phrase.events.first()?.0;
+        let now = self.now.load(Ordering::Relaxed).max(start);
+        let mut events: Vec<_> = phrase
+            .events
+            .iter()
+            .map(|(time, event)| ((time - start) as SampleTime, *event))
+            .collect();
+        for (channel, notes) in phrase.held.iter().enumerate() {
+            for note in (0..128u8).filter(|note| notes & (1u128 << note) != 0) {
+                let note_off = NoteEvent::NoteOff {
+                    timing: 0,
+                    voice_id: None,
+                    channel: channel as u8,
+                    note,
+                    velocity: 0.5,
+                };
+                events.push(((now - start) as SampleTime, note_off));
+            }
+        }
+        Some(Phrase {
+            events,
+            sample_rate: self.sample_rate(),
+            tempo: self.tempo.load(Ordering::Relaxed),
+        })
+    }
+}
+
+/// A copy of everything which makes up the current sound, so that it can be bounced on another
+/// thread while the user carries on editing.
+pub struct Sound {
+    // The normalized value of every parameter, except for the bypass and the preset buttons.
+    values: Vec<(String, f32)>,
+    layer_b: LayerB,
+}
+
+impl Sound {
+    pub fn capture(params: &Parameters) -> Sound {
+        let values = params
+            .param_map()
+            .into_iter()
+            .filter(|(_, _, group)| !matches!(group.as_str(), "Presets" | "Bypass"))
+            // Safety: The pointers point into `params`, which is still alive.
+            .map(|(id, ptr, _)| (id, unsafe { ptr.unmodulated_normalized_value() }))
+            .collect();
+        Sound {
+            values,
+            layer_b: params.layer_b.read().unwrap().clone(),
+        }
+    }
+}
+
+/// Render the phrase with the sound on a fresh copy of the synth, and save it as a WAV file in the
+/// bounces folder. The file is named after `name`. Returns the path of the file.
+pub fn bounce(sound: &Sound, phrase: &Phrase, name: &str) -> Result<PathBuf, String> {
+    let dir = settings_dir()
+        .ok_or("Couldn't find the settings directory")?
+        .join("bounces");
+    std::fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+    // Characters which aren't allowed in file names on some platforms are replaced, and earlier
+    // bounces of the same preset are never overwritten.
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect();
+    let path = (1..)
+        .map(|i| match i {
+            1 => dir.join(format!("{}.wav", name)),
+            i => dir.join(format!("{} {}.wav", name, i)),
+        })
+        .find(|path| !path.exists())
+        .unwrap();
+
+    let (left, right) = render(sound, phrase);
+    write_wav(&path, &left, &right, phrase.sample_rate).map_err(|err| err.to_string())?;
+    Ok(path)
+}
+
+/// Render the phrase offline. Rendering carries on after the last event until the synth has gone
+/// quiet, so that the release and the effects' tails are included.
+fn render(sound: &Sound, phrase: &Phrase) -> (Vec<f32>, Vec<f32>) {
+    let sample_rate = phrase.sample_rate;
+    let mut nyasynth = Nyasynth::default();
+    for (id, normalized) in &sound.values {
+        nyasynth
+            .param_table
+            .set_normalized(id, *normalized, sample_rate);
+    }
+    *nyasynth.params.layer_b.write().unwrap() = sound.layer_b.clone();
+    let buffer_config = BufferConfig {
+        sample_rate: sample_rate.get(),
+        min_buffer_size: None,
+        max_buffer_size: BOUNCE_BUFFER_SIZE as u32,
+        process_mode: ProcessMode::Offline,
+    };
+    nyasynth.initialize(
+        &Nyasynth::AUDIO_IO_LAYOUTS[0],
+        &buffer_config,
+        &mut OfflineContext::new(vec![], phrase.tempo, sample_rate),
+    );
+    nyasynth.reset();
+
+    let end = phrase.events.last().map_or(0, |(time, _)| *time);
+    let max_len = end + (MAX_TAIL_SECONDS * sample_rate.get()) as SampleTime;
+    let mut left = vec![];
+    let mut right = vec![];
+    let mut remaining = phrase.events.iter().peekable();
+    let mut start = 0;
+    while start <= end
+        || (start < max_len
+            && !(nyasynth.notes.is_empty() && nyasynth.silence.is_asleep(sample_rate)))
+    {
+        let mut events = vec![];
+        while let Some((time, event)) =
+            remaining.next_if(|(time, _)| *time < start + BOUNCE_BUFFER_SIZE)
+        {
+            let mut event = *event;
+            set_timing(&mut event, (time - start) as u32);
+            events.push(event);
+        }
+        let mut context = OfflineContext::new(events, phrase.tempo, sample_rate);
+
+        let mut block = [vec![0.0; BOUNCE_BUFFER_SIZE], vec![0.0; BOUNCE_BUFFER_SIZE]];
+        let mut buffer = Buffer::default();
+        // Safety: The slices outlive the buffer.
+        unsafe {
+            buffer.set_slices(BOUNCE_BUFFER_SIZE, |output_slices| {
+                let [block_left, block_right] = &mut block;
+                *output_slices = vec![block_left, block_right];
+            });
+        }
+        let mut aux = AuxiliaryBuffers {
+            inputs: &mut [],
+            outputs: &mut [],
+        };
+        nyasynth.process(&mut buffer, &mut aux, &mut context);
+        left.extend_from_slice(&block[0]);
+        right.extend_from_slice(&block[1]);
+        start += BOUNCE_BUFFER_SIZE;
+    }
+    (left, right)
+}
+
+fn set_timing(event: &mut NoteEvent<()>, new_timing: u32) {
+    match event {
+        NoteEvent::NoteOn { timing, .. }
+        | NoteEvent::NoteOff { timing, .. }
+        | NoteEvent::Choke { timing, .. }
+        | NoteEvent::VoiceTerminated { timing, .. }
+        | NoteEvent::PolyModulation { timing, .. }
+        | NoteEvent::MonoAutomation { timing, .. }
+        | NoteEvent::PolyPressure { timing, .. }
+        | NoteEvent::PolyVolume { timing, .. }
+        | NoteEvent::PolyPan { timing, .. }
+        | NoteEvent::PolyTuning { timing, .. }
+        | NoteEvent::PolyVibrato { timing, .. }
+        | NoteEvent::PolyExpression { timing, .. }
+        | NoteEvent::PolyBrightness { timing, .. }
+        | NoteEvent::MidiChannelPressure { timing, .. }
+        | NoteEvent::MidiPitchBend { timing, .. }
+        | NoteEvent::MidiCC { timing, .. }
+        | NoteEvent::MidiProgramChange { timing, .. }
+        | NoteEvent::MidiSysEx { timing, .. } => *timing = new_timing,
+        _ => (),
+    }
+}
+
+/// Write a stereo, 32-bit float WAV file.
+fn write_wav(
+    path: &Path,
+    left: &[f32],
+    right: &[f32],
+    sample_rate: SampleRate,
+) -> std::io::Result<()> {
+    const CHANNELS: u16 = 2;
+    const BYTES_PER_SAMPLE: u16 = 4;
+    // WAVE_FORMAT_IEEE_FLOAT
+    const FORMAT: u16 = 3;
+
+    let sample_rate = sample_rate.get().round() as u32;
+    let block_align = CHANNELS * BYTES_PER_SAMPLE;
+    let data_len = (left.len() * block_align as usize) as u32;
+    let mut bytes = Vec::with_capacity(44 + data_len as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+    bytes.extend_from_slice(b"WAVE");
+    bytes.extend_from_slice(b"fmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&FORMAT.to_le_bytes());
+    bytes.extend_from_slice(&CHANNELS.to_le_bytes());
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+    bytes.extend_from_slice(&block_align.to_le_bytes());
+    bytes.extend_from_slice(&(BYTES_PER_SAMPLE * 8).to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_len.to_le_bytes());
+    for (left, right) in left.iter().zip(right) {
+        bytes.extend_from_slice(&left.to_le_bytes());
+        bytes.extend_from_slice(&right.to_le_bytes());
+    }
+    std::fs::File::create(path)?.write_all(&bytes)
+}
+
+/// A minimal host for rendering offline. It hands the plugin one buffer's events, and ignores
+/// everything the plugin sends back.
+struct OfflineContext {
+    events: Vec<NoteEvent<()>>,
+    event_index: usize,
+    transport: Transport,
+}
+
+impl OfflineContext {
+    fn new(events: Vec<NoteEvent<()>>, tempo: f32, sample_rate: SampleRate) -> OfflineContext {
+        let mut transport = Transport::new(sample_rate.get());
+        transport.tempo = Some(tempo as f64);
+        OfflineContext {
+            events,
+            event_index: 0,
+            transport,
+        }
+    }
+}
+
+impl InitContext<Nyasynth> for OfflineContext {
+    fn plugin_api(&self) -> PluginApi {
+        PluginApi::Standalone
+    }
+
+    fn execute(&self, _task: Task) {}
+
+    fn set_latency_samples(&self, _samples: u32) {}
+
+    fn set_current_voice_capacity(&self, _capacity: u32) {}
+}
+
+impl ProcessContext<Nyasynth> for OfflineContext {
+    fn plugin_api(&self) -> PluginApi {
+        PluginApi::Standalone
+    }
+
+    fn execute_background(&self, _task: Task) {}
+
+    fn execute_gui(&self, _task: Task) {}
+
+    fn transport(&self) -> &Transport {
+        &self.transport
+    }
+
+    fn next_event(&mut self) -> Option<NoteEvent<()>> {
+        let event = self.events.get(self.event_index);
+        self.event_index += 1;
+        event.copied()
+    }
+
+    fn peek_event(&self) -> Option<&NoteEvent<()>> {
+        self.events.get(self.event_index)
+    }
+
+    fn send_event(&mut self, _event: NoteEvent<()>) {}
+
+    fn set_latency_samples(&self, _samples: u32) {}
+
+    fn set_current_voice_capacity(&self, _capacity: u32) {}
+}
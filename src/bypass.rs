@@ -0,0 +1,49 @@
+use crate::common::{SampleRate, Seconds};
+
+// How long the output takes to fade in or out. This is short enough to feel instant, but long
+// enough that switching the bypass doesn't click.
+const FADE_TIME: Seconds = Seconds::new(10.0 / 1000.0);
+
+/// Fades the output in and out when the synth is bypassed, since some hosts leave soft bypass up to
+/// the plugin and switching straight to silence would click.
+pub struct SoftBypass {
+    // The gain applied to the output. This is 1.0 unless the output is faded out.
+    gain: f32,
+}
+
+impl SoftBypass {
+    pub fn new() -> SoftBypass {
+        SoftBypass { gain: 1.0 }
+    }
+
+    /// Fade the output towards silence if `muted` is true, or back to full volume if it isn't.
+    /// Returns true if the output finished fading out during this buffer.
+    pub fn process(
+        &mut self,
+        left: &mut [f32],
+        right: &mut [f32],
+        muted: bool,
+        sample_rate: SampleRate,
+    ) -> bool {
+        let target = if muted { 0.0 } else { 1.0 };
+        if self.gain == target {
+            if muted {
+                left.fill(0.0);
+                right.fill(0.0);
+            }
+            return false;
+        }
+
+        let step = 1.0 / (FADE_TIME.get() * sample_rate.get());
+        for (left, right) in left.iter_mut().zip(right.iter_mut()) {
+            self.gain = if muted {
+                (self.gain - step).max(0.0)
+            } else {
+                (self.gain + step).min(1.0)
+            };
+            *left *= self.gain;
+            *right *= self.gain;
+        }
+        muted && self.gain == 0.0
+    }
+}
@@ -1,17 +1,37 @@
 use biquad::{Biquad, ToHertz};
+use nih_plug::prelude::Enum;
 
 use crate::{
     common::SampleRate,
+    ease::lerp,
+    lfo_bank::Lfo,
     params::{ChorusParams, MAX_CHORUS_DEPTH, MAX_CHORUS_DISTANCE},
-    sound_gen::{NoteShape, Oscillator},
+    sound_gen::NoteShape,
 };
 
+/// How the chorus reads between the samples of its delay line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum ChorusQuality {
+    /// Linear interpolation. This is cheap, but dulls the high end and adds some modulation noise
+    /// at large depths.
+    #[name = "Low"]
+    Low,
+    /// 4-point cubic Hermite interpolation.
+    #[name = "High"]
+    High,
+}
+
 const CHORUS_SIZE: usize = (100.0 + 2.0 * MAX_CHORUS_DEPTH + MAX_CHORUS_DISTANCE) as usize;
 
+// The chorus depth and distance are given in samples at this sample rate. At other sample rates,
+// they are scaled so that the chorus sounds the same.
+const REFERENCE_SAMPLE_RATE: f32 = 44100.0;
+
 pub struct Chorus {
     delay_line: Vec<f32>,
     write_head: usize,
-    read_head_oscillator: Oscillator,
+    // The number of samples at the current sample rate per sample at the reference sample rate.
+    delay_scale: f32,
     // To remove crackling
     filter: biquad::DirectForm1<f32>,
 }
@@ -19,82 +39,113 @@ pub struct Chorus {
 impl Chorus {
     pub fn new(sample_rate: SampleRate) -> Chorus {
         let coefficients = get_coefficients(sample_rate);
+        let delay_scale = get_delay_scale(sample_rate);
         Chorus {
-            delay_line: vec![0.0; CHORUS_SIZE],
+            delay_line: vec![0.0; get_delay_line_size(delay_scale)],
             write_head: 0,
-            read_head_oscillator: Oscillator::new(),
+            delay_scale,
             filter: biquad::DirectForm1::<f32>::new(coefficients),
         }
     }
 
+    /// Update the filter and the delay line for a new sample rate. The delay line is cleared, since
+    /// the samples in it were recorded at the old sample rate. Note that this allocates if the delay
+    /// line needs to grow.
     pub fn set_sample_rate(&mut self, sample_rate: SampleRate) {
         let new_coefficients = get_coefficients(sample_rate);
         self.filter.update_coefficients(new_coefficients);
+
+        self.delay_scale = get_delay_scale(sample_rate);
+        self.delay_line.clear();
+        self.delay_line
+            .resize(get_delay_line_size(self.delay_scale), 0.0);
+        self.write_head = 0;
     }
 
+    /// Clear out the delay line and the filter, without reallocating.
+    pub fn reset(&mut self) {
+        self.delay_line.fill(0.0);
+        self.filter.reset_state();
+    }
+
+    /// Run a sample through the chorus. The read heads are moved by `lfo`, which is the chorus's
+    /// LFO from the LFO bank.
     pub fn next_sample(
         &mut self,
         in_sample: f32,
         sample_rate: SampleRate,
         params: &ChorusParams,
         shape: NoteShape,
+        lfo: &mut Lfo,
     ) -> f32 {
         self.write_head = (self.write_head + 1).rem_euclid(self.delay_line.len());
         self.delay_line[self.write_head] = in_sample;
 
-        let read_head_mod = self
-            .read_head_oscillator
-            .next_sample(sample_rate, shape, params.rate);
-
-        let offset = params.min_distance + ((read_head_mod + 1.0) * params.depth);
+        // Each voice has its own read head. The read heads all share one LFO, but their phases are
+        // evenly spread out so that they don't move together.
+        let mut value = 0.0;
+        for voice in 1..params.voices {
+            let phase_offset = voice as f32 / params.voices as f32;
+            let read_head_mod = lfo.sample_at(shape, phase_offset);
+            value += self.read_head(read_head_mod, params);
+        }
+        let read_head_mod = lfo.next_sample(sample_rate, shape, params.rate, 1);
+        value += self.read_head(read_head_mod, params);
 
-        let value = self.fractional_lookup(offset);
+        // The voices are mostly uncorrelated, so they add in power rather than amplitude.
+        let value = value / (params.voices as f32).sqrt();
         self.filter.run(value)
     }
 
+    fn read_head(&self, read_head_mod: f32, params: &ChorusParams) -> f32 {
+        let offset = params.min_distance + ((read_head_mod + 1.0) * params.depth);
+        self.fractional_lookup(offset * self.delay_scale, params.quality)
+    }
+
     // Do fractional delay interpolation. The offset value is in samples and will be how many samples
-    // behind the write head to look at. This function does linear interpolation.
-    fn fractional_lookup(&self, offset: f32) -> f32 {
-        let index = self.write_head as f32 - offset;
-
-        let index_upper = index.ceil() as isize;
-        let index_lower = index_upper - 1;
-        let index_lower2 = index_upper - 2;
-        let index_lower3 = index_upper - 3;
-
-        let index_upper = index_upper.rem_euclid(self.delay_line.len() as isize) as usize;
-        let index_lower = index_lower.rem_euclid(self.delay_line.len() as isize) as usize;
-        let index_lower2 = index_lower2.rem_euclid(self.delay_line.len() as isize) as usize;
-        let index_lower3 = index_lower3.rem_euclid(self.delay_line.len() as isize) as usize;
-
-        let index_fractional = index.fract();
-
-        let sample_upper = self.delay_line[index_upper];
-        let sample_lower = self.delay_line[index_lower];
-        let sample_lower2 = self.delay_line[index_lower2];
-        let sample_lower3 = self.delay_line[index_lower3];
-
-        cubic_interpolate(
-            sample_lower3,
-            sample_lower2,
-            sample_lower,
-            sample_upper,
-            index_fractional,
-        )
+    // behind the write head to look at.
+    fn fractional_lookup(&self, offset: f32, quality: ChorusQuality) -> f32 {
+        // Keep one sample between the read head and the write head, since the cubic interpolation
+        // looks one sample past the read position.
+        let index = self.write_head as f32 - offset.max(1.0);
+
+        let index_lower = index.floor() as isize;
+        let t = index - index_lower as f32;
+        let sample = |i: isize| {
+            let i = (index_lower + i).rem_euclid(self.delay_line.len() as isize) as usize;
+            self.delay_line[i]
+        };
+
+        match quality {
+            ChorusQuality::Low => lerp(sample(0), sample(1), t),
+            ChorusQuality::High => {
+                hermite_interpolate(sample(-1), sample(0), sample(1), sample(2), t)
+            }
+        }
     }
 }
 
-/// Interpolates between x2 and x3, where x0, x1, x2, and x3 are all evenly spaced points.
-/// See https://www.desmos.com/calculator/hsiestmj4o for quadratic interpolation.
-/// See https://www.desmos.com/calculator/bxcarpst5l for cubic interpolation.
-fn cubic_interpolate(x0: f32, x1: f32, x2: f32, x3: f32, t: f32) -> f32 {
-    let c0 = (x0 / -6.0) + (x1 / 2.0) + (x2 / -2.0) + (x3 / 6.0);
-    let c1 = (x1 / 2.0) - x2 + (x3 / 2.0);
-    let c2 = (x0 / 6.0) - x1 + (x2 / 2.0) + (x3 / 3.0);
-    let c3 = x2;
+/// Interpolates between x1 and x2, where x0, x1, x2, and x3 are all evenly spaced points. This is
+/// 4-point, 3rd order Hermite interpolation (a Catmull-Rom spline). Since the points used are
+/// centered around the interpolated region, this is much more accurate than a cubic which passes
+/// through all four points but interpolates between the last two.
+fn hermite_interpolate(x0: f32, x1: f32, x2: f32, x3: f32, t: f32) -> f32 {
+    let c0 = x1;
+    let c1 = 0.5 * (x2 - x0);
+    let c2 = x0 - 2.5 * x1 + 2.0 * x2 - 0.5 * x3;
+    let c3 = 0.5 * (x3 - x0) + 1.5 * (x1 - x2);
+
+    ((c3 * t + c2) * t + c1) * t + c0
+}
+
+fn get_delay_scale(sample_rate: SampleRate) -> f32 {
+    sample_rate.get() / REFERENCE_SAMPLE_RATE
+}
 
-    c0 * t * t * t + c1 * t * t + c2 * t + c3
+fn get_delay_line_size(delay_scale: f32) -> usize {
+    (CHORUS_SIZE as f32 * delay_scale.max(1.0)).ceil() as usize
 }
+
 fn get_coefficients(sample_rate: SampleRate) -> biquad::Coefficients<f32> {
     biquad::Coefficients::<f32>::from_params(
         biquad::Type::LowPass,
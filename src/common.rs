@@ -34,8 +34,14 @@ impl SampleRate {
     }
 
     pub fn to_seconds(&self, samples: SampleTime) -> Seconds {
-        let seconds = samples as f32 / self.get();
-        Seconds::new(seconds)
+        // This is done in f64 since an f32 can only exactly represent sample counts up to 2^24,
+        // which is only about six minutes of audio at 44.1 kHz.
+        let seconds = samples as f64 / self.get() as f64;
+        Seconds::new(seconds as f32)
+    }
+
+    pub fn to_samples(&self, seconds: Seconds) -> SampleTime {
+        (seconds.get() * self.get()).round() as SampleTime
     }
 
     pub fn hz(&self) -> biquad::Hertz<f32> {
@@ -129,10 +135,54 @@ impl From<f32> for Seconds {
     }
 }
 
+/// A duration measured in beats (quarter notes), for things which are synced to the tempo. Tempos
+/// are given in beats per minute.
+#[derive(Debug, Clone, Copy, Add, Sub, PartialEq, PartialOrd)]
+pub struct Beats(pub f32);
+
+impl Beats {
+    pub const fn new(beats: f32) -> Beats {
+        Beats(beats)
+    }
+
+    pub const fn get(&self) -> f32 {
+        self.0
+    }
+
+    /// How long this many beats lasts at the given tempo.
+    pub fn to_seconds(&self, tempo: f32) -> Seconds {
+        Seconds::new(self.get() * 60.0 / tempo)
+    }
+
+    /// How many beats pass in the given time at the given tempo.
+    pub fn from_seconds(seconds: Seconds, tempo: f32) -> Beats {
+        Beats::new(seconds.get() * tempo / 60.0)
+    }
+}
+
+impl std::ops::Mul<f32> for Beats {
+    type Output = Beats;
+
+    fn mul(self, rhs: f32) -> Self::Output {
+        Beats::new(self.get() * rhs)
+    }
+}
+
 /// A MIDI note
-#[derive(Debug, Clone, Copy, PartialEq, From, Into)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, From, Into)]
 pub struct Note(pub u8);
 
+impl Note {
+    /// The name of the note, such as "C4" for middle C.
+    pub fn name(&self) -> String {
+        const NAMES: [&str; 12] = [
+            "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+        ];
+        let octave = (self.0 / 12) as i32 - 1;
+        format!("{}{}", NAMES[(self.0 % 12) as usize], octave)
+    }
+}
+
 /// A struct representing linear pitch space. This exists so that portamento and filter cutoff
 /// sweep do not need to recompute their start and end frequencies every sample.
 #[derive(Debug, Clone, Copy, PartialEq, Add, Sub, From, Into)]
@@ -150,6 +200,21 @@ impl Pitch {
     pub fn into_hertz(&self) -> Hertz {
         Hertz(self.0.exp2())
     }
+
+    /// The pitch the given number of semitones higher (or lower, if negative).
+    pub fn transpose_semitones(&self, semitones: f32) -> Pitch {
+        Pitch(self.0 + semitones / 12.0)
+    }
+
+    /// The pitch the given number of cents higher (or lower, if negative).
+    pub fn transpose_cents(&self, cents: f32) -> Pitch {
+        self.transpose_semitones(cents / 100.0)
+    }
+
+    /// The number of semitones from this pitch up to `other`. This is negative if `other` is lower.
+    pub fn interval_to(&self, other: Pitch) -> f32 {
+        (other.0 - self.0) * 12.0
+    }
 }
 
 impl std::ops::Mul<f32> for Pitch {
@@ -190,6 +255,31 @@ impl Hertz {
         }
     }
 
+    /// The frequency the given number of semitones higher (or lower, if negative).
+    pub fn transpose_semitones(&self, semitones: f32) -> Hertz {
+        Hertz(self.0 * (semitones / 12.0).exp2())
+    }
+
+    /// The frequency the given number of cents higher (or lower, if negative).
+    pub fn transpose_cents(&self, cents: f32) -> Hertz {
+        self.transpose_semitones(cents / 100.0)
+    }
+
+    /// The number of semitones from this frequency up to `other`. This is negative if `other` is
+    /// lower.
+    pub fn interval_to(&self, other: Hertz) -> f32 {
+        12.0 * (other.0 / self.0).log2()
+    }
+
+    /// Returns the nearest MIDI note to this frequency, along with how far this frequency is from
+    /// that note, in cents.
+    pub fn to_note(&self) -> (Note, f32) {
+        // A4 (MIDI note 69) is 440 Hz.
+        let note = 69.0 + Hertz(440.0).interval_to(*self);
+        let nearest = note.round().clamp(0.0, 127.0);
+        (Note(nearest as u8), (note - nearest) * 100.0)
+    }
+
     pub fn clamp(&self, min: f32, max: f32) -> Hertz {
         Hertz(self.get().clamp(min, max))
     }
@@ -226,7 +316,7 @@ impl std::ops::Div<Hertz> for Hertz {
 
 /// A pitchbend value in [-1.0, +1.0] range, where +1.0 means "max upward bend"
 /// and -1.0 means "max downward bend"
-#[derive(Debug, Clone, Copy, From, Into)]
+#[derive(Debug, Clone, Copy, PartialEq, From, Into)]
 pub struct Pitchbend(f32);
 
 impl Pitchbend {
@@ -241,14 +331,33 @@ impl Pitchbend {
     /// Convert a pitchbend value that is in the range [0.0, 1.0] to a NormalizedPitchbend.
     /// The pitchbend value is assumed such that 0.5 is considered to be "no bend", 0.0 is "max
     /// downward bend", and 1.0 is "max upward bend".
+    /// Hosts compute the value by dividing MIDI's 14-bit pitch bend by 16383, so the 14-bit value
+    /// is recovered first. This keeps every step of the controller, and makes the center (8192)
+    /// exactly "no bend", which it would be slightly off from otherwise.
     pub fn from_zero_one_range(value: f32) -> Pitchbend {
         nih_debug_assert!(0.0 <= value && value <= 1.0);
-        Pitchbend((value * 2.0) - 1.0)
+        Pitchbend::from_14_bit((value.clamp(0.0, 1.0) * 16383.0).round() as u16)
+    }
+
+    /// Convert a 14-bit MIDI pitch bend, where 8192 is "no bend", to a Pitchbend. There are more
+    /// steps below the center than above it, so each side is scaled separately to reach exactly
+    /// -1.0 and +1.0.
+    pub fn from_14_bit(value: u16) -> Pitchbend {
+        let offset = value.min(16383) as f32 - 8192.0;
+        if offset < 0.0 {
+            Pitchbend(offset / 8192.0)
+        } else {
+            Pitchbend(offset / 8191.0)
+        }
     }
 
     /// Returns an iterator of size num_samples which linearly interpolates between the
     /// points specified by pitch_bend. last_pitch_bend is assumed to be the "-1th"
     /// value and is used as the starting point.
+    /// After the last point, the pitch bend is linearly extrapolated from the last two points (and
+    /// clamped to the valid range), since the bend is probably still moving. The value at the end
+    /// of the block is returned, and should be passed as the starting point of the next block so
+    /// that there is no jump at the block boundary.
     /// Thank you to Cassie for this code!
     pub fn to_pitch_envelope(
         pitch_bend: &[(Pitchbend, i32)],
@@ -261,22 +370,31 @@ impl Pitchbend {
         }
 
         // We first make the first and last points to interpolate over. The first
-        // point is just prev_pitch_bend, and the last point either gets the value
-        // of the last point in pitch_bend, or just prev_pitch_bend if pitch_bend
-        // is empty. If pitch_bend is nonempty, this means that the last "segment"
-        // is constant value, which is okay since we can't see into the future
-        // TODO: Use linear extrapolation for the last segment.
-        let first = Some((prev_pitch_bend, 0));
-
-        let last_bend = pitch_bend
-            .last()
-            .map(|&(bend, _)| bend)
-            .unwrap_or(prev_pitch_bend);
+        // point is just prev_pitch_bend, and the last point is extrapolated from the
+        // last two points, or is just prev_pitch_bend if pitch_bend is empty.
+        let first = (prev_pitch_bend, 0);
+
+        let last_bend = match pitch_bend {
+            [] => prev_pitch_bend,
+            [.., (end, end_time)] => {
+                let (start, start_time) = match pitch_bend {
+                    [.., second_last, _] => *second_last,
+                    _ => first,
+                };
+                let slope = if *end_time == start_time {
+                    0.0
+                } else {
+                    (end.0 - start.0) / (end_time - start_time) as f32
+                };
+                let extrapolated = end.0 + slope * (num_samples as i32 - end_time) as f32;
+                Pitchbend(extrapolated.clamp(-1.0, 1.0))
+            }
+        };
         let last = Some((last_bend, num_samples as i32));
 
         // Now we make a list of points, starting with the first point, then all of
         // pitch_bend, then the last point
-        let iter = first
+        let iter = Some(first)
             .into_iter()
             .chain(pitch_bend.iter().copied())
             .chain(last)
@@ -343,8 +461,9 @@ pub struct Decibel(pub f32);
 
 impl Decibel {
     /// The threshold for which Decibel values below it will be treated as negative
-    /// infinity dB.
-    pub const NEG_INF_DB_THRESHOLD: f32 = -70.0;
+    /// infinity dB. Before 1.4, this was -70 on the old half-size scale, which is -140 dB here, so
+    /// levels which fade towards silence (such as releases lerped in dB) keep the same shape.
+    pub const NEG_INF_DB_THRESHOLD: f32 = -140.0;
 
     pub const fn from_db(db: f32) -> Decibel {
         Decibel(db)
@@ -358,8 +477,10 @@ impl Decibel {
         Decibel::from_db(0.0)
     }
 
+    /// The level of a signal with the given peak amplitude, using the usual 20 * log10 amplitude
+    /// decibels.
     pub fn from_amp(amp: f32) -> Decibel {
-        Decibel::from_db(f32::log10(amp) * 10.0)
+        Decibel::from_db(f32::log10(amp) * 20.0)
     }
 
     // Linearly interpolate in amplitude space.
@@ -374,24 +495,12 @@ impl Decibel {
         Decibel::from_db(db)
     }
 
-    // Linearly interpolate in Decibel space, but values of t below 0.125 will
-    // lerp from `start` to `Decibel::zero()`. This function is meant for use
-    // with user-facing parameter knobs.
-    pub const fn ease_db(start: f32, end: f32) -> Easing<Decibel> {
-        Easing::SplitLinear {
-            start: Decibel::neg_inf_db(),
-            mid: Decibel::from_db(start),
-            end: Decibel::from_db(end),
-            split_at: 0.125,
-        }
-    }
-
     /// Get the peak amplitude a signal played at the given Decibel amount would produce.
     pub fn get_amp(&self) -> f32 {
         if self.get_db() <= Decibel::NEG_INF_DB_THRESHOLD {
             0.0
         } else {
-            10.0f32.powf(self.get_db() / 10.0)
+            10.0f32.powf(self.get_db() / 20.0)
         }
     }
 
@@ -401,6 +510,51 @@ impl Decibel {
     }
 }
 
+/// How a decibel knob tapers off to silence at the bottom of its travel. Each decibel parameter has
+/// its own taper, which is set in the parameter's `PARAM_INFO` entry.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DbTaper {
+    /// Levels at or below this are silent, and are shown as "-inf". This can raise the point at which
+    /// a parameter goes silent, but not lower it below `Decibel::NEG_INF_DB_THRESHOLD`.
+    pub neg_inf_db: f32,
+    /// The fraction of the knob's travel, at the bottom, which fades from the level at this point
+    /// down to `neg_inf_db`. Zero leaves the knob's range as it is.
+    pub split_at: f32,
+}
+
+impl DbTaper {
+    /// The taper of a parameter which doesn't ask for one: the range is left as it is, and only
+    /// levels below `Decibel::NEG_INF_DB_THRESHOLD` are silent.
+    pub const NONE: DbTaper = DbTaper::new(Decibel::NEG_INF_DB_THRESHOLD, 0.0);
+
+    pub const fn new(neg_inf_db: f32, split_at: f32) -> DbTaper {
+        DbTaper {
+            neg_inf_db,
+            split_at,
+        }
+    }
+
+    /// Apply the taper to a knob. `normalized` is the knob's position, `level` is the level that the
+    /// knob's range gives at that position, and `split_level` is the level it gives at the split
+    /// point.
+    pub fn apply(&self, normalized: f32, level: Decibel, split_level: Decibel) -> Decibel {
+        let level = if normalized < self.split_at {
+            Decibel::lerp_db(
+                self.neg_inf_db,
+                split_level.get_db(),
+                normalized / self.split_at,
+            )
+        } else {
+            level
+        };
+        if level.get_db() <= self.neg_inf_db {
+            Decibel::neg_inf_db()
+        } else {
+            level
+        }
+    }
+}
+
 impl EnvelopeType for Decibel {
     fn lerp_attack(start: Self, end: Self, t: f32) -> Self {
         // Lerp in amplitude space during the attack phase. This is useful
@@ -416,6 +570,9 @@ impl EnvelopeType for Decibel {
     fn lerp_retrigger(start: Self, end: Self, t: f32) -> Self {
         Decibel::lerp_amp(start, end, t)
     }
+    fn lerp_analog(start: Self, end: Self, t: f32) -> Self {
+        Decibel::lerp_amp(start, end, t)
+    }
     fn one() -> Self {
         Decibel::zero_db()
     }
@@ -482,6 +639,49 @@ impl From<FilterType> for biquad::Type<f32> {
     }
 }
 
+/// How the second filter is combined with the main filter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum FilterRouting {
+    /// Only the main filter is used.
+    #[name = "Single"]
+    Single,
+    /// The main filter's output goes through the second filter.
+    #[name = "Serial"]
+    Serial,
+    /// Both filters process the signal, and their outputs are mixed together.
+    #[name = "Parallel"]
+    Parallel,
+    /// The signal is split into a low and a high band. The main filter processes the low band and
+    /// the second filter processes the high band.
+    #[name = "Split"]
+    Split,
+}
+
+/// The crossfade law used when mixing a dry and wet signal together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum MixLaw {
+    /// Linearly crossfade between the signals. This dips in loudness at 50% for uncorrelated
+    /// signals.
+    #[name = "Linear"]
+    Linear,
+    /// Crossfade with a quarter sine/cosine curve, which keeps the total power constant.
+    #[name = "Equal Power"]
+    EqualPower,
+}
+
+impl MixLaw {
+    /// Mix the dry and wet signals. A `t` of 0.0 is fully dry and a `t` of 1.0 is fully wet.
+    pub fn mix(&self, dry: f32, wet: f32, t: f32) -> f32 {
+        match self {
+            MixLaw::Linear => lerp(dry, wet, t),
+            MixLaw::EqualPower => {
+                let angle = t.clamp(0.0, 1.0) * std::f32::consts::FRAC_PI_2;
+                dry * angle.cos() + wet * angle.sin()
+            }
+        }
+    }
+}
+
 pub const fn ease_exp(min: f32, max: f32) -> FloatRange {
     FloatRange::Skewed {
         min,
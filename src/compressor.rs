@@ -0,0 +1,65 @@
+use crate::{
+    common::{Decibel, SampleRate},
+    envelope_follower::one_pole_coefficient,
+    params::CompressorParams,
+};
+
+// The width of the soft knee, in dB. The ratio eases in over this range around the threshold,
+// instead of kicking in all at once.
+const KNEE_WIDTH: f32 = 12.0;
+
+/// A soft-knee compressor for the master bus. Both channels are compressed by the same amount, so
+/// that the stereo image doesn't shift.
+pub struct Compressor {
+    // The current gain reduction, in dB. This is zero or negative.
+    gain_reduction: f32,
+}
+
+impl Compressor {
+    pub fn new() -> Compressor {
+        Compressor {
+            gain_reduction: 0.0,
+        }
+    }
+
+    /// Compress the next sample of each channel.
+    pub fn next_sample(
+        &mut self,
+        left: f32,
+        right: f32,
+        sample_rate: SampleRate,
+        params: &CompressorParams,
+    ) -> (f32, f32) {
+        let level = Decibel::from_amp(left.abs().max(right.abs())).get_db();
+        let target = static_curve(level - params.threshold.get_db(), params.ratio);
+
+        // The attack applies while the gain reduction is increasing, and the release while it is
+        // recovering.
+        let time = if target < self.gain_reduction {
+            params.attack
+        } else {
+            params.release
+        };
+        let coefficient = one_pole_coefficient(time, sample_rate);
+        self.gain_reduction = target + (self.gain_reduction - target) * coefficient;
+        if !self.gain_reduction.is_finite() {
+            self.gain_reduction = 0.0;
+        }
+
+        let gain = Decibel::from_db(self.gain_reduction + params.makeup.get_db()).get_amp();
+        (left * gain, right * gain)
+    }
+}
+
+// The gain reduction, in dB, for a signal which is `overshoot` dB above the threshold.
+fn static_curve(overshoot: f32, ratio: f32) -> f32 {
+    let slope = 1.0 / ratio.max(1.0) - 1.0;
+    if 2.0 * overshoot < -KNEE_WIDTH {
+        0.0
+    } else if 2.0 * overshoot.abs() <= KNEE_WIDTH {
+        let knee = overshoot + KNEE_WIDTH / 2.0;
+        slope * knee * knee / (2.0 * KNEE_WIDTH)
+    } else {
+        slope * overshoot
+    }
+}
@@ -7,8 +7,12 @@ pub trait Lerpable =
 pub trait InvLerpable = Sub<Self, Output = Self> + Div<Self, Output = f32> + PartialEq + Copy;
 
 pub trait Easer<T> {
+    /// Ease using the given interpolation value `t`. `t` is expected to be in [0.0, 1.0] range.
     fn ease(&self, t: f32) -> T;
-    fn inv_ease(&self, val: T) -> f32;
+    /// Given a value, return the `t` interpolation value such that `ease(t) == val`. This is
+    /// used to turn a human readable value (such as one typed in by the user) back into a
+    /// normalized value.
+    fn inverse(&self, val: T) -> f32;
 }
 
 /// An enum representing an ease.
@@ -30,6 +34,13 @@ pub enum Easing<T> {
     SteppedLinear { start: T, end: T, steps: usize },
     /// Exponentially ease from start to end.
     Exponential { start: T, end: T },
+    /// Ease from start to end using an S-curve (smoothstep). This eases slowly near the start and
+    /// end and quickly in the middle.
+    SmoothStep { start: T, end: T },
+    /// Ease from start to end using a 1D cubic bezier curve. The control points `p1` and `p2` are
+    /// given in normalized [0.0, 1.0] space (so `p1 = 1/3, p2 = 2/3` is linear). For the ease to be
+    /// invertible, the control points should be in the [0.0, 1.0] range.
+    CubicBezier { start: T, end: T, p1: f32, p2: f32 },
 }
 
 impl<T> Easing<T> {
@@ -39,8 +50,6 @@ impl<T> Easing<T> {
 }
 
 impl<T: Lerpable + InvLerpable> Easer<T> for Easing<T> {
-    /// Ease using the given interpolation value `t`. `t` is expected to be in
-    /// [0.0, 1.0] range.
     fn ease(&self, t: f32) -> T {
         match *self {
             Easing::Linear { start, end } => lerp(start, end, t),
@@ -66,13 +75,14 @@ impl<T: Lerpable + InvLerpable> Easer<T> for Easing<T> {
                 let expo_t = ease_in_expo(t);
                 lerp(start, end, expo_t)
             }
+            Easing::SmoothStep { start, end } => lerp(start, end, smoothstep(t)),
+            Easing::CubicBezier { start, end, p1, p2 } => lerp(start, end, cubic_bezier(t, p1, p2)),
         }
     }
 
-    /// Given a value, return the `t` interpolation value such that `ease(t) == val`.
-    /// inv_ease assumes easing functions are invertible, which might not be true
-    /// for all functions (ex: SplitLinear that does not ease all the way to 1.0)
-    fn inv_ease(&self, val: T) -> f32 {
+    /// This assumes easing functions are invertible, which might not be true for all functions
+    /// (ex: SplitLinear that does not ease all the way to 1.0)
+    fn inverse(&self, val: T) -> f32 {
         match *self {
             Easing::Linear { start, end } => inv_lerp(start, end, val),
             Easing::SplitLinear {
@@ -93,13 +103,25 @@ impl<T: Lerpable + InvLerpable> Easer<T> for Easing<T> {
                 }
             }
             Easing::SteppedLinear { start, end, steps } => {
+                // Find the nearest step, then return the middle of the region of `t` values which
+                // snap to that step. The middle is used (instead of the start of the region) so
+                // that floating point error can't cause `ease` to snap to the neighboring step.
                 let t = inv_lerp(start, end, val);
-                snap_float(t, steps)
+                let step = (t * (steps as f32 - 1.0)).round();
+                (step + 0.5) / steps as f32
             }
             Easing::Exponential { start, end } => {
                 let t = inv_lerp(start, end, val);
                 inv_ease_in_expo(t)
             }
+            Easing::SmoothStep { start, end } => {
+                let t = inv_lerp(start, end, val);
+                inv_smoothstep(t)
+            }
+            Easing::CubicBezier { start, end, p1, p2 } => {
+                let t = inv_lerp(start, end, val);
+                inv_cubic_bezier(t, p1, p2)
+            }
         }
     }
 }
@@ -110,9 +132,55 @@ impl<T: PartialEq + Copy, const N: usize> Easer<T> for [T; N] {
         self[index.clamp(0, self.len() - 1)]
     }
 
-    fn inv_ease(&self, val: T) -> f32 {
+    fn inverse(&self, val: T) -> f32 {
+        // As with SteppedLinear, return the middle of the region which eases to the value.
         match self.iter().position(|&x| x == val) {
-            Some(index) => (index as f32) / (self.len() as f32),
+            Some(index) => (index as f32 + 0.5) / (self.len() as f32),
+            None => 0.0,
+        }
+    }
+}
+
+/// Ease between a set of discrete values, where each value is given an amount of knob travel
+/// proportional to its weight. An array `[T; N]` also implements [Easer], and is equivalent to a
+/// DiscreteLinear where every weight is the same.
+#[derive(Debug)]
+pub struct DiscreteLinear<T, const N: usize> {
+    pub values: [T; N],
+    pub weights: [f32; N],
+}
+
+impl<T, const N: usize> DiscreteLinear<T, N> {
+    pub const fn new(values: [T; N], weights: [f32; N]) -> DiscreteLinear<T, N> {
+        DiscreteLinear { values, weights }
+    }
+
+    fn total_weight(&self) -> f32 {
+        self.weights.iter().sum()
+    }
+}
+
+impl<T: PartialEq + Copy, const N: usize> Easer<T> for DiscreteLinear<T, N> {
+    fn ease(&self, t: f32) -> T {
+        // Walk along the knob travel until we reach the region containing `t`.
+        let target = t.clamp(0.0, 1.0) * self.total_weight();
+        let mut travel = 0.0;
+        for (&value, &weight) in self.values.iter().zip(self.weights.iter()) {
+            travel += weight;
+            if target < travel {
+                return value;
+            }
+        }
+        self.values[N - 1]
+    }
+
+    fn inverse(&self, val: T) -> f32 {
+        // Return the middle of the region which eases to the value.
+        match self.values.iter().position(|&x| x == val) {
+            Some(index) => {
+                let travel: f32 = self.weights[..index].iter().sum();
+                (travel + self.weights[index] / 2.0) / self.total_weight()
+            }
             None => 0.0,
         }
     }
@@ -159,12 +227,49 @@ pub fn inv_ease_in_expo(x: f32) -> f32 {
     }
 }
 
+/// The smoothstep S-curve, 3x^2 - 2x^3. This function is clamped.
+pub fn smoothstep(x: f32) -> f32 {
+    let x = x.clamp(0.0, 1.0);
+    x * x * (3.0 - 2.0 * x)
+}
+
+/// The inverse of [smoothstep]. See https://iquilezles.org/articles/ismoothstep/ for details.
+pub fn inv_smoothstep(y: f32) -> f32 {
+    let y = y.clamp(0.0, 1.0);
+    0.5 - ((1.0 - 2.0 * y).asin() / 3.0).sin()
+}
+
+/// A 1D cubic bezier curve from 0.0 to 1.0, with the control points `p1` and `p2`.
+pub fn cubic_bezier(x: f32, p1: f32, p2: f32) -> f32 {
+    let x = x.clamp(0.0, 1.0);
+    let inv_x = 1.0 - x;
+    3.0 * inv_x * inv_x * x * p1 + 3.0 * inv_x * x * x * p2 + x * x * x
+}
+
+/// The inverse of [cubic_bezier]. There isn't a nice closed form for this, so this bisects
+/// instead. This assumes the curve is increasing, which is true if the control points are in the
+/// [0.0, 1.0] range.
+pub fn inv_cubic_bezier(y: f32, p1: f32, p2: f32) -> f32 {
+    let (mut low, mut high) = (0.0, 1.0);
+    // Each iteration halves the range, so 24 iterations is about as precise as an f32 gets.
+    for _ in 0..24 {
+        let mid = (low + high) / 2.0;
+        if cubic_bezier(mid, p1, p2) < y {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    (low + high) / 2.0
+}
+
 /// Snap a float value in range 0.0-1.0 to the nearest f32 region
 /// For example, snap_float(_, 4) will snap a float to either:
 /// 0.0, 0.333, 0.666, or 1.0
 pub fn snap_float(value: f32, num_regions: usize) -> f32 {
     // We subtract one from this denominator because we want there to only be
     // four jumps. See also https://www.desmos.com/calculator/esnnnbfzml
+    // The region is clamped so that a value of exactly 1.0 doesn't snap past 1.0.
     let num_regions = num_regions as f32;
-    (num_regions * value).floor() / (num_regions - 1.0)
+    (num_regions * value).floor().min(num_regions - 1.0) / (num_regions - 1.0)
 }
@@ -0,0 +1,320 @@
+use crate::{
+    alloc_check::permit_alloc,
+    chorus::Chorus,
+    common::{Beats, Decibel, Pitchbend, SampleRate},
+    compressor::Compressor,
+    ease::lerp,
+    envelope_follower::EnvelopeFollower,
+    layers::Layer,
+    lfo_bank::{LfoBank, LfoSlot},
+    params::{MeowParameters, Parameters},
+    sound_gen::{NoiseGenerator, SampleModulation, Voice},
+    stereo_width::StereoWidth,
+    watchdog::{is_sane, WATCHDOG_ENABLED},
+};
+
+#[cfg(feature = "multithreaded")]
+use crate::render_pool::RenderPool;
+
+/// The minimum number of voices before voices are rendered on multiple threads.
+#[cfg(feature = "multithreaded")]
+const MIN_PARALLEL_VOICES: usize = 4;
+
+/// The per-block buffers used while rendering. These are allocated ahead of time, so that rendering
+/// never allocates. Each buffer holds one block, which is at most `block_size` samples long.
+struct ScratchBuffers {
+    /// The pitch bend for each sample. This is shared by all of the voices.
+    pitch_bends: Vec<Pitchbend>,
+    /// The filter dry/wet for each sample, for each layer.
+    filter_dry_wet: [Vec<f32>; 2],
+    /// The modulation for each sample, which is shared by all of the voices on a layer.
+    modulation: [Vec<SampleModulation>; 2],
+    /// The sum of every voice's output.
+    left: Vec<f32>,
+    right: Vec<f32>,
+}
+
+impl ScratchBuffers {
+    fn new(block_size: usize) -> ScratchBuffers {
+        ScratchBuffers {
+            pitch_bends: vec![Pitchbend::default(); block_size],
+            filter_dry_wet: [vec![0.0; block_size], vec![0.0; block_size]],
+            modulation: [
+                vec![SampleModulation::default(); block_size],
+                vec![SampleModulation::default(); block_size],
+            ],
+            left: vec![0.0; block_size],
+            right: vec![0.0; block_size],
+        }
+    }
+}
+
+/// The synth's signal path, from the voices to the output: the modulation which is shared by the
+/// voices, the voices themselves, and the effects after them. The plugin drives this one block at
+/// a time, between handling events, and the benchmarks drive it the same way without a host.
+///
+/// A layer is passed as its shared parameters, which the per-sample smoothed values are read
+/// from, and its parameters for the block.
+pub struct Engine {
+    /// The buffers which each block is rendered into.
+    scratch: ScratchBuffers,
+    /// The threads which voices are rendered on. This is started by [Engine::start_render_pool].
+    #[cfg(feature = "multithreaded")]
+    render_pool: Option<RenderPool>,
+    // The vibrato and chorus LFOs.
+    lfo_bank: LfoBank,
+    // The chorus effect is also global.
+    chorus: Chorus,
+    /// Tracks the loudness of the voices' output, for modulating the voices.
+    envelope_follower: EnvelopeFollower,
+    // The stereo width control, which runs after the chorus.
+    stereo_width: StereoWidth,
+    // The master bus compressor, which runs after the stereo width.
+    compressor: Compressor,
+    /// The global noise generator
+    noise_generator: NoiseGenerator,
+}
+
+impl Engine {
+    pub fn new(block_size: usize, sample_rate: SampleRate) -> Engine {
+        Engine {
+            scratch: ScratchBuffers::new(block_size),
+            #[cfg(feature = "multithreaded")]
+            render_pool: None,
+            lfo_bank: LfoBank::new(),
+            chorus: Chorus::new(sample_rate),
+            envelope_follower: EnvelopeFollower::new(),
+            stereo_width: StereoWidth::new(),
+            compressor: Compressor::new(),
+            noise_generator: NoiseGenerator::new(),
+        }
+    }
+
+    /// The largest block which can be rendered at once.
+    pub fn block_size(&self) -> usize {
+        self.scratch.left.len()
+    }
+
+    /// Reallocate the scratch buffers to hold blocks of up to `block_size` samples. This must not
+    /// be called on the audio thread.
+    pub fn set_block_size(&mut self, block_size: usize) {
+        self.scratch = ScratchBuffers::new(block_size);
+    }
+
+    /// Start the threads which voices are rendered on, if they aren't running already.
+    #[cfg(feature = "multithreaded")]
+    pub fn start_render_pool(&mut self) {
+        self.render_pool = Some(RenderPool::new(self.block_size()));
+    }
+
+    /// Update everything which depends on the sample rate.
+    pub fn set_sample_rate(&mut self, sample_rate: SampleRate) {
+        // The chorus's delay line may need to grow. This only happens when the sample rate
+        // changes, so the allocation is acceptable.
+        permit_alloc(|| self.chorus.set_sample_rate(sample_rate));
+    }
+
+    /// Line the tempo synced LFOs up with the host's bar.
+    pub fn sync_to_bar(&mut self, beats_into_bar: Beats, tempo: f32) {
+        self.lfo_bank.sync_to_bar(beats_into_bar, tempo);
+    }
+
+    /// The noise generator, which is also used for anything else random.
+    pub fn noise_generator(&mut self) -> &mut NoiseGenerator {
+        &mut self.noise_generator
+    }
+
+    /// Clear out the effects' tails.
+    pub fn reset_effects(&mut self) {
+        self.chorus.reset();
+    }
+
+    /// The pitch bend for each sample of the next block, which must be filled in before the block
+    /// is rendered.
+    pub fn pitch_bends(&mut self, block_len: usize) -> &mut [Pitchbend] {
+        &mut self.scratch.pitch_bends[..block_len]
+    }
+
+    /// Render the voices over one block into `left` and `right`, which must be no longer than
+    /// [Engine::block_size]. Layer B is only rendered if it is given. Returns the largest volume
+    /// envelope value of any of the voices over the block.
+    pub fn render_block(
+        &mut self,
+        voices: &mut [Voice],
+        layer_a: (&Parameters, &MeowParameters),
+        layer_b: Option<(&Parameters, &MeowParameters)>,
+        sample_rate: SampleRate,
+        left: &mut [f32],
+        right: &mut [f32],
+    ) -> f32 {
+        let block_len = left.len();
+        let scratch = &mut self.scratch;
+
+        // The envelope follower is only updated after the whole block is rendered, so its level is
+        // held for the block.
+        let follower_level = self.envelope_follower.level();
+
+        // The rest of the modulation is the same for every voice on a layer.
+        for layer in Layer::ALL {
+            let (parameters, layer_params) = match (layer, layer_b) {
+                (Layer::A, _) => layer_a,
+                (Layer::B, Some(layer_b)) => layer_b,
+                (Layer::B, None) => continue,
+            };
+            let filter_dry_wet = &mut scratch.filter_dry_wet[layer.index()][..block_len];
+            parameters.filter.dry_wet_block(filter_dry_wet);
+            let vibrato_lfo = self.lfo_bank.get(LfoSlot::Vibrato(layer));
+            let vibrato_params = &layer_params.vibrato_lfo;
+            let interval = layer_params.quality.lfo_update_interval();
+            let modulation = &mut scratch.modulation[layer.index()][..block_len];
+            for i in 0..block_len {
+                let vibrato = vibrato_lfo.next_sample(
+                    sample_rate,
+                    layer_params.vibrato_note_shape,
+                    vibrato_params.speed,
+                    interval,
+                );
+                modulation[i] = SampleModulation {
+                    pitch_bend: scratch.pitch_bends[i],
+                    vibrato: vibrato * vibrato_params.amount,
+                    filter_dry_wet: filter_dry_wet[i],
+                    follower_level,
+                };
+            }
+        }
+
+        let params = layer_a.1;
+        self.scratch.left[..block_len].fill(0.0);
+        self.scratch.right[..block_len].fill(0.0);
+        let mut total_volume = 0.0f32;
+        if let Some((_, layer_b)) = layer_b {
+            total_volume = self.render_voices(voices, Layer::B, layer_b, sample_rate, block_len);
+            // Layer A's preset trim is applied to the whole output at the end, so layer B is
+            // rendered first and given the difference between the two trims.
+            let trim = layer_b.preset_trim.get_db() - params.preset_trim.get_db();
+            let trim = Decibel::from_db(trim).get_amp();
+            for sample in self.scratch.left[..block_len]
+                .iter_mut()
+                .chain(self.scratch.right[..block_len].iter_mut())
+            {
+                *sample *= trim;
+            }
+        }
+        let volume = self.render_voices(voices, Layer::A, params, sample_rate, block_len);
+        total_volume = total_volume.max(volume);
+
+        left.copy_from_slice(&self.scratch.left[..block_len]);
+        right.copy_from_slice(&self.scratch.right[..block_len]);
+
+        // The follower listens to the output of the voices.
+        for (left, right) in left.iter().zip(right.iter()) {
+            self.envelope_follower
+                .next_sample(*left, *right, sample_rate, &params.follower);
+        }
+        total_volume
+    }
+
+    /// Render each voice on the layer over a block, adding them to the scratch buffers. The
+    /// layer's modulation for the block must already be in the scratch buffers. Returns the
+    /// largest volume envelope value of any of the voices over the block.
+    fn render_voices(
+        &mut self,
+        voices: &mut [Voice],
+        layer: Layer,
+        params: &MeowParameters,
+        sample_rate: SampleRate,
+        block_len: usize,
+    ) -> f32 {
+        let scratch = &mut self.scratch;
+        let modulation = &scratch.modulation[layer.index()][..block_len];
+        let left = &mut scratch.left[..block_len];
+        let right = &mut scratch.right[..block_len];
+
+        // Waking up the render threads has some overhead, so it is only worth it when there are
+        // enough voices to share.
+        #[cfg(feature = "multithreaded")]
+        if let Some(pool) = &self.render_pool {
+            if voices.len() >= MIN_PARALLEL_VOICES {
+                return pool.render(
+                    voices,
+                    layer,
+                    params,
+                    &mut self.noise_generator,
+                    sample_rate,
+                    modulation,
+                    left,
+                    right,
+                );
+            }
+        }
+
+        let mut max_volume = 0.0f32;
+        for voice in voices.iter_mut().filter(|voice| voice.layer == layer) {
+            let volume = voice.render(
+                params,
+                &mut self.noise_generator,
+                sample_rate,
+                modulation,
+                left,
+                right,
+            );
+            max_volume = max_volume.max(volume);
+        }
+        max_volume
+    }
+
+    /// Run the chorus and the other effects over the voices' output, and apply the output gain.
+    pub fn post_process(
+        &mut self,
+        params: &MeowParameters,
+        sample_rate: SampleRate,
+        left_out: &mut [f32],
+        right_out: &mut [f32],
+    ) {
+        let chorus_params = &params.chorus;
+        let output_gain = params.master_vol.get_amp() * params.preset_trim.get_amp();
+        // Chorus  and other post processing effects
+        for (left_out, right_out) in left_out.iter_mut().zip(right_out.iter_mut()) {
+            let left = *left_out;
+            let right = *right_out;
+
+            // Get the chorus effect
+            let chorus = self.chorus.next_sample(
+                left,
+                sample_rate,
+                chorus_params,
+                params.chorus_note_shape,
+                self.lfo_bank.get(LfoSlot::Chorus),
+            );
+
+            let left = lerp(left, chorus, chorus_params.mix);
+            let right = lerp(right, chorus, chorus_params.mix);
+
+            let (left, right) =
+                self.stereo_width
+                    .next_sample(left, right, sample_rate, &params.stereo);
+
+            let (left, right) =
+                self.compressor
+                    .next_sample(left, right, sample_rate, &params.compressor);
+
+            *left_out = left * output_gain;
+            *right_out = right * output_gain;
+        }
+
+        // The voices are checked as they are rendered, but the effects can still blow up. If they
+        // do, silence the garbage and clear out the chorus, rather than sending it to the speakers.
+        if WATCHDOG_ENABLED {
+            let mut sanitized = false;
+            for sample in left_out.iter_mut().chain(right_out.iter_mut()) {
+                if !is_sane(*sample) {
+                    *sample = 0.0;
+                    sanitized = true;
+                }
+            }
+            if sanitized {
+                self.chorus.reset();
+            }
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use crate::{
+    common::{SampleRate, Seconds},
+    params::FollowerParams,
+};
+
+/// Tracks the loudness of the synth's own output, after the voices' filters. The level rises with
+/// the attack time and falls with the release time, and is used as a modulation source.
+pub struct EnvelopeFollower {
+    level: f32,
+}
+
+impl EnvelopeFollower {
+    pub fn new() -> EnvelopeFollower {
+        EnvelopeFollower { level: 0.0 }
+    }
+
+    /// The current level. This is roughly in the [0.0, 1.0] range, but may go above 1.0 if the
+    /// output is clipping.
+    pub fn level(&self) -> f32 {
+        self.level
+    }
+
+    /// Update the level with the next output sample.
+    pub fn next_sample(
+        &mut self,
+        left: f32,
+        right: f32,
+        sample_rate: SampleRate,
+        params: &FollowerParams,
+    ) {
+        let input = left.abs().max(right.abs());
+        let time = if input > self.level {
+            params.attack
+        } else {
+            params.release
+        };
+        let coefficient = one_pole_coefficient(time, sample_rate);
+        self.level = input + (self.level - input) * coefficient;
+
+        if !self.level.is_finite() {
+            self.level = 0.0;
+        }
+    }
+}
+
+// The coefficient for a one-pole filter which gets about 63% of the way to its target in the given
+// time.
+pub fn one_pole_coefficient(time: Seconds, sample_rate: SampleRate) -> f32 {
+    let samples = time.get() * sample_rate.get();
+    if samples <= 0.0 {
+        0.0
+    } else {
+        (-1.0 / samples).exp()
+    }
+}
@@ -0,0 +1,124 @@
+use std::f32::consts::TAU;
+
+use nih_plug::prelude::Enum;
+
+use crate::common::{Hertz, SampleRate};
+
+// How far the growl sweeps the filter cutoff at full growl, in octaves up and down.
+const MAX_GROWL_OCTAVES: f32 = 2.0;
+// How hard the voice is driven into the saturator at full growl.
+const MAX_DRIVE: f32 = 8.0;
+
+/// What the growl's modulation is applied to. The saturation is applied either way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum GrowlTarget {
+    /// The growl sweeps the filter cutoff up and down, which snarls.
+    #[name = "Filter"]
+    Filter,
+    /// The growl pulses the volume, which rumbles.
+    #[name = "Amplitude"]
+    Amplitude,
+}
+
+pub struct GrowlParams {
+    /// The growl amount, from 0.0 (off) to 1.0.
+    pub amount: f32,
+    /// The rate of the growl's modulation.
+    pub speed: Hertz,
+    pub target: GrowlTarget,
+    /// How much aftertouch adds to the growl amount.
+    pub aftertouch: f32,
+}
+
+impl GrowlParams {
+    /// The growl amount for a voice with the given aftertouch pressure.
+    pub fn amount(&self, pressure: f32) -> f32 {
+        (self.amount + self.aftertouch * pressure).clamp(0.0, 1.0)
+    }
+}
+
+/// A sub-audio modulator and saturator, which roughens a meow into a snarl.
+#[derive(Debug)]
+pub struct Growl {
+    phase: f32,
+    // The growl amount and modulator value as of the most recent sample.
+    amount: f32,
+    value: f32,
+    // The last sample of each channel to go into the saturator, which oversampling interpolates
+    // from.
+    last_input: [f32; 2],
+}
+
+impl Growl {
+    pub fn new() -> Growl {
+        Growl {
+            phase: 0.0,
+            amount: 0.0,
+            value: 0.0,
+            last_input: [0.0; 2],
+        }
+    }
+
+    /// Advance the modulator by a sample.
+    pub fn next_sample(&mut self, params: &GrowlParams, pressure: f32, sample_rate: SampleRate) {
+        self.amount = params.amount(pressure);
+        if self.amount <= 0.0 {
+            return;
+        }
+        self.value = (self.phase * TAU).sin();
+        self.phase = (self.phase + params.speed.get() / sample_rate.get()).fract();
+    }
+
+    /// The amount to move the filter cutoff by, in octaves.
+    pub fn filter_octaves(&self, target: GrowlTarget) -> f32 {
+        match target {
+            GrowlTarget::Filter => self.value * self.amount * MAX_GROWL_OCTAVES,
+            GrowlTarget::Amplitude => 0.0,
+        }
+    }
+
+    /// The gain to apply to the voice. At full growl, the volume swings all the way down to silence.
+    pub fn gain(&self, target: GrowlTarget) -> f32 {
+        match target {
+            GrowlTarget::Filter => 1.0,
+            GrowlTarget::Amplitude => 1.0 - self.amount * (1.0 - self.value) / 2.0,
+        }
+    }
+
+    /// Saturate a sample of each channel. The saturator runs `oversampling` times for each sample,
+    /// on the input linearly interpolated up from the last sample, and the results are averaged
+    /// back down. This keeps the harmonics which the saturator adds from folding back down as
+    /// aliasing, at the cost of up to half a sample of delay.
+    pub fn saturate(&mut self, left: f32, right: f32, oversampling: usize) -> (f32, f32) {
+        let [last_left, last_right] = self.last_input;
+        self.last_input = [left, right];
+        if self.amount <= 0.0 {
+            return (left, right);
+        }
+        (
+            self.saturate_oversampled(last_left, left, oversampling),
+            self.saturate_oversampled(last_right, right, oversampling),
+        )
+    }
+
+    fn saturate_oversampled(&self, last: f32, value: f32, oversampling: usize) -> f32 {
+        if oversampling <= 1 {
+            return self.saturate_sample(value);
+        }
+        let step = (value - last) / oversampling as f32;
+        let sum: f32 = (1..=oversampling)
+            .map(|i| self.saturate_sample(last + step * i as f32))
+            .sum();
+        sum / oversampling as f32
+    }
+
+    /// Saturate a single sample. The dry signal is blended in at lower growl amounts, so that the
+    /// growl fades in smoothly from nothing.
+    fn saturate_sample(&self, value: f32) -> f32 {
+        let drive = 1.0 + self.amount * MAX_DRIVE;
+        // Dividing by the square root of the drive keeps the level roughly even as the drive goes
+        // up, since the saturator flattens loud signals but boosts quiet ones.
+        let saturated = (value * drive).tanh() / drive.sqrt();
+        value + (saturated - value) * self.amount
+    }
+}
@@ -0,0 +1,93 @@
+use std::{
+    marker::PhantomData,
+    ptr,
+    sync::{
+        atomic::{AtomicPtr, Ordering},
+        Mutex,
+    },
+};
+
+/// Hands values from other threads to the audio thread without locking. Any thread can send a
+/// value, and the audio thread picks up the newest one with [Handoff::receive]. The audio thread
+/// must never free memory, so when it swaps in a new value, the value it replaces is handed back,
+/// and dropped by the next thread to send a value (or to call [Handoff::collect]).
+///
+/// Only one thread may receive values from a handoff.
+pub struct Handoff<T> {
+    // The newest value which the audio thread hasn't picked up yet, or null.
+    incoming: AtomicPtr<T>,
+    // The value which the audio thread last replaced, waiting to be dropped, or null.
+    retired: AtomicPtr<T>,
+    // Values are moved between threads, so the handoff is only Send and Sync if T is Send, just
+    // like a Mutex.
+    _marker: PhantomData<Mutex<T>>,
+}
+
+impl<T: Send> Handoff<T> {
+    pub fn new() -> Handoff<T> {
+        Handoff {
+            incoming: AtomicPtr::new(ptr::null_mut()),
+            retired: AtomicPtr::new(ptr::null_mut()),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Send a value to the audio thread. If the audio thread hasn't picked up the previous value
+    /// yet, that value is dropped instead. This allocates and frees, so it must not be called from
+    /// the audio thread.
+    pub fn send(&self, value: T) {
+        self.collect();
+        let value = Box::into_raw(Box::new(value));
+        let unreceived = self.incoming.swap(value, Ordering::AcqRel);
+        // Safety: Every non-null pointer in the handoff came from Box::into_raw, and swapping it out
+        // gives this thread sole ownership of it.
+        unsafe { drop_raw(unreceived) };
+    }
+
+    /// Drop the value which the audio thread last replaced, if there is one.
+    pub fn collect(&self) {
+        let retired = self.retired.swap(ptr::null_mut(), Ordering::AcqRel);
+        // Safety: See `send`.
+        unsafe { drop_raw(retired) };
+    }
+
+    /// Swap the newest value into `current`, if a new value has been sent. This is called by the
+    /// audio thread, and never blocks, allocates, or frees. Returns true if `current` changed.
+    pub fn receive(&self, current: &mut Option<Box<T>>) -> bool {
+        // The value replaced last time must be collected before another one can be handed back.
+        // Only the receiving thread ever fills the retired slot, so it stays empty until then.
+        if !self.retired.load(Ordering::Acquire).is_null() {
+            return false;
+        }
+        let incoming = self.incoming.swap(ptr::null_mut(), Ordering::AcqRel);
+        if incoming.is_null() {
+            return false;
+        }
+        // Safety: See `send`.
+        let old = current.replace(unsafe { Box::from_raw(incoming) });
+        if let Some(old) = old {
+            self.retired.store(Box::into_raw(old), Ordering::Release);
+        }
+        true
+    }
+}
+
+impl<T> Drop for Handoff<T> {
+    fn drop(&mut self) {
+        // Safety: See `send`. Nothing else can be using the handoff anymore.
+        unsafe {
+            drop_raw(*self.incoming.get_mut());
+            drop_raw(*self.retired.get_mut());
+        }
+    }
+}
+
+/// Drop a value which was leaked with Box::into_raw, if the pointer isn't null.
+///
+/// # Safety
+/// The pointer must be null, or have come from Box::into_raw and not been freed since.
+unsafe fn drop_raw<T>(value: *mut T) {
+    if !value.is_null() {
+        drop(Box::from_raw(value));
+    }
+}
@@ -0,0 +1,51 @@
+/// Combines 14-bit MIDI CC pairs. CCs 0-31 are the most significant 7 bits (MSB) of a controller,
+/// and CCs 32-63 are the least significant 7 bits (LSB) of the same controllers.
+///
+/// The MIDI spec says that the MSB is sent first, and that an MSB resets the LSB to zero. However,
+/// some controllers send the LSB first. To handle both orders, an LSB which arrives without a
+/// following MSB is kept, and is combined with the next MSB. Either way, the value is correct once
+/// both halves of a pair have arrived.
+pub struct HiResCCs {
+    msb: [u8; 32],
+    lsb: [u8; 32],
+    // True if the LSB was received since the last MSB.
+    lsb_pending: [bool; 32],
+}
+
+impl HiResCCs {
+    pub fn new() -> HiResCCs {
+        HiResCCs {
+            msb: [0; 32],
+            lsb: [0; 32],
+            lsb_pending: [false; 32],
+        }
+    }
+
+    /// Process a CC, with a 7-bit value in the [0.0, 1.0] range. Returns the controller number
+    /// and its value. For CCs which are part of a 14-bit pair, the controller number is that of the
+    /// MSB, and the value has the full 14 bits of resolution. Other CCs are returned unchanged.
+    pub fn process(&mut self, cc: u8, value: f32) -> (u8, f32) {
+        let raw = (value * 127.0).round() as u8;
+        let controller = match cc {
+            0..=31 => {
+                let controller = cc as usize;
+                self.msb[controller] = raw;
+                if !self.lsb_pending[controller] {
+                    self.lsb[controller] = 0;
+                }
+                self.lsb_pending[controller] = false;
+                controller
+            }
+            32..=63 => {
+                let controller = cc as usize - 32;
+                self.lsb[controller] = raw;
+                self.lsb_pending[controller] = true;
+                controller
+            }
+            _ => return (cc, value),
+        };
+
+        let combined = ((self.msb[controller] as u16) << 7) | self.lsb[controller] as u16;
+        (controller as u8, combined as f32 / 16383.0)
+    }
+}
@@ -0,0 +1,101 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+use nih_plug::prelude::GuiContext;
+
+use crate::{
+    param_changes::ParamChanges,
+    params::{ParamTable, Parameters},
+};
+
+/// Tells the host about the parameters which the synth sets by itself: the presets loaded by
+/// program changes and the preset buttons, keyswitched articulations, and the targets of the
+/// macros, the morph knob, and MIDI mapped CCs. Otherwise, the host would never see these changes,
+/// so it couldn't record or undo them, and it would put the old values back when it next saved.
+///
+/// The audio thread sets the parameters itself, so that the notes it is about to play hear the new
+/// values, and the [ParamTable] marks them in `host_updates`. The audio thread then asks the GUI
+/// thread to run [crate::Task::UpdateHost], which sets them again through the host. Only a
+/// [GuiContext] can set parameters through the host, and the host only hands one to the editor, so
+/// the host can only be told while the editor is open. Changes made while it is closed are passed
+/// on once it opens.
+pub struct HostLink {
+    /// The editor's context, while the editor is open.
+    context: Mutex<Option<Arc<dyn GuiContext>>>,
+    /// True while the editor is open. The audio thread checks this rather than locking `context`.
+    connected: AtomicBool,
+    /// Set while an update is waiting to run on the GUI thread, so that only one is queued at once.
+    update_requested: AtomicBool,
+    /// The parameters which have been set since the host was last told.
+    host_updates: Arc<ParamChanges>,
+    /// Used to look up the parameters in `host_updates`. Its pointers point into `_params`, which
+    /// is kept alive for them.
+    param_table: ParamTable,
+    _params: Arc<Parameters>,
+}
+
+impl HostLink {
+    pub fn new(
+        params: Arc<Parameters>,
+        param_changes: Arc<ParamChanges>,
+        host_updates: Arc<ParamChanges>,
+    ) -> HostLink {
+        HostLink {
+            context: Mutex::new(None),
+            connected: AtomicBool::new(false),
+            update_requested: AtomicBool::new(false),
+            host_updates,
+            param_table: ParamTable::new(&params, param_changes),
+            _params: params,
+        }
+    }
+
+    /// Start passing changes on through the editor's context. This is called when the editor opens.
+    pub fn connect(&self, context: Arc<dyn GuiContext>) {
+        *self.context.lock().unwrap() = Some(context);
+        self.connected.store(true, Ordering::Release);
+    }
+
+    /// Stop using the editor's context. This is called when the editor closes, since holding on to
+    /// the context would keep the plugin's wrapper alive.
+    pub fn disconnect(&self) {
+        self.connected.store(false, Ordering::Release);
+        *self.context.lock().unwrap() = None;
+    }
+
+    /// Returns true if the audio thread should ask the GUI thread to run an update: the editor is
+    /// open, some parameters need passing on, and no update is already waiting. This never blocks.
+    pub fn request_update(&self) -> bool {
+        self.connected.load(Ordering::Acquire)
+            && self.host_updates.any_changed()
+            && !self.update_requested.swap(true, Ordering::AcqRel)
+    }
+
+    /// Set the parameters which have changed through the host. Every parameter's gesture is open
+    /// while they are set, so that a whole preset or articulation is a single edit, like when the
+    /// editor loads a preset. This must be called on the GUI thread.
+    pub fn update_host(&self) {
+        self.update_requested.store(false, Ordering::Release);
+        let context = self.context.lock().unwrap();
+        let Some(context) = context.as_ref() else {
+            return;
+        };
+        let mut ptrs = Vec::new();
+        self.host_updates
+            .drain(|id| ptrs.extend(self.param_table.ptr(id)));
+        // Safety: The pointers point into `_params`, which is still alive.
+        unsafe {
+            for ptr in &ptrs {
+                context.raw_begin_set_parameter(*ptr);
+            }
+            for ptr in &ptrs {
+                context.raw_set_parameter_normalized(*ptr, ptr.unmodulated_normalized_value());
+            }
+            for ptr in &ptrs {
+                context.raw_end_set_parameter(*ptr);
+            }
+        }
+    }
+}
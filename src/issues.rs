@@ -0,0 +1,102 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use nih_plug::prelude::nih_log;
+use once_cell::sync::Lazy;
+
+// How many issues the log keeps. Older issues are forgotten.
+const MAX_ISSUES: usize = 100;
+
+// Issues can come from places which don't belong to any one instance of the plugin, such as loading
+// the settings, so the log is shared by every instance in the process, like the settings are.
+static ISSUES: Lazy<IssueLog> = Lazy::new(IssueLog::new);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// Something was skipped or fell back to a default, such as a preset file which couldn't be
+    /// read.
+    Warning,
+    /// Something the user asked for failed.
+    Error,
+}
+
+/// A problem which the plugin could carry on after, but which the user should hear about.
+#[derive(Debug, Clone)]
+pub struct Issue {
+    /// Issues are numbered in the order they were reported.
+    pub id: u64,
+    pub severity: Severity,
+    pub text: String,
+}
+
+/// The non-fatal problems reported so far. The editor shows new issues at the bottom of the
+/// window, and keeps them all in its log.
+pub struct IssueLog {
+    issues: Mutex<VecDeque<Issue>>,
+    next_id: AtomicU64,
+}
+
+impl IssueLog {
+    fn new() -> IssueLog {
+        IssueLog {
+            issues: Mutex::new(VecDeque::with_capacity(MAX_ISSUES)),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    pub fn get() -> &'static IssueLog {
+        &ISSUES
+    }
+
+    /// Report an issue. It is also written to the plugin's log. This allocates and locks, so it
+    /// must not be called on the audio thread.
+    pub fn report(&self, severity: Severity, text: String) {
+        nih_log!("{}", text);
+        let mut issues = self.issues.lock().unwrap();
+        if issues.len() == MAX_ISSUES {
+            issues.pop_front();
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        issues.push_back(Issue { id, severity, text });
+    }
+
+    /// The ID that the next issue will get. Every issue reported from now on will have at least
+    /// this ID.
+    pub fn next_id(&self) -> u64 {
+        self.next_id.load(Ordering::Relaxed)
+    }
+
+    /// The issues with at least the given ID, oldest first.
+    pub fn since(&self, id: u64) -> Vec<Issue> {
+        let issues = self.issues.lock().unwrap();
+        issues
+            .iter()
+            .filter(|issue| issue.id >= id)
+            .cloned()
+            .collect()
+    }
+
+    /// Every issue still in the log, oldest first.
+    pub fn all(&self) -> Vec<Issue> {
+        self.since(0)
+    }
+
+    pub fn clear(&self) {
+        self.issues.lock().unwrap().clear();
+    }
+}
+
+/// Report a warning to the shared issue log.
+pub fn warn(text: String) {
+    IssueLog::get().report(Severity::Warning, text);
+}
+
+/// Report an error to the shared issue log.
+pub fn error(text: String) {
+    IssueLog::get().report(Severity::Error, text);
+}
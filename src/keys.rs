@@ -1,8 +1,21 @@
 use crate::common::{Note, Vel};
 
+// Each note can only be held once, so there can never be more held keys than there are notes.
+const MAX_HELD_KEYS: usize = u8::MAX as usize + 1;
+
+/// A held key, along with the host's voice ID and the channel that it was played on, so that a
+/// voice which is started for it later on (such as when monocat mode returns to it) belongs to it.
+#[derive(Debug, Clone, Copy)]
+pub struct HeldKey {
+    pub note: Note,
+    pub vel: Vel,
+    pub voice_id: Option<i32>,
+    pub channel: u8,
+}
+
 pub struct KeyTracker {
-    /// A list of the currently held keys.
-    pub held_keys: Vec<(Note, Vel)>,
+    /// A list of the currently held keys, in the order they were pressed.
+    pub held_keys: Vec<HeldKey>,
     /// The note from which the next held note will be pitchbent from. If this is None, then
     /// the next held note will not have any pitchbend.
     pub portamento_key: Option<Note>,
@@ -11,22 +24,28 @@ pub struct KeyTracker {
 impl KeyTracker {
     pub fn new() -> KeyTracker {
         KeyTracker {
-            held_keys: Vec::with_capacity(16),
+            held_keys: Vec::with_capacity(MAX_HELD_KEYS),
             portamento_key: None,
         }
     }
 
     /// Handle a NoteOn event. This function returns Some if the note passed into the function should
     /// have portamento, and None if not.
-    pub fn note_on(&mut self, note: Note, vel: Vel, polycat: bool) -> Option<Note> {
-        self.held_keys.push((note, vel));
+    pub fn note_on(&mut self, key: HeldKey, polycat: bool) -> Option<Note> {
+        let note = key.note;
+        // Pressing a key which is already held moves it to the top of the stack, rather than adding
+        // it twice. This keeps the list from ever growing past MAX_HELD_KEYS.
+        if let Some(index) = self.held_keys.iter().position(|x| x.note == note) {
+            self.held_keys.remove(index);
+        }
+        self.held_keys.push(key);
         if polycat {
             let portamento = self.portamento_key;
             self.portamento_key = Some(note);
             portamento
         } else {
-            match self.held_keys.last().copied() {
-                Some((top_note, _)) => Some(top_note),
+            match self.held_keys.last() {
+                Some(top_key) => Some(top_key.note),
                 None => todo!(),
             }
         }
@@ -36,13 +55,13 @@ impl KeyTracker {
     /// of the stack to change. The returned value is the new top of stack. This is used in monocat
     /// mode, where removing the top-most note (aka: the only currently playing note) causes an
     /// internal note on event to occur.
-    pub fn note_off(&mut self, note: Note) -> Option<(Note, Vel)> {
+    pub fn note_off(&mut self, note: Note) -> Option<HeldKey> {
         if self.portamento_key == Some(note) {
             self.portamento_key = None;
         }
 
         // If the released key is actually in the key stack, then remove it. Otherwise, do nothing.
-        if let Some(index) = self.held_keys.iter().position(|x| x.0 == note) {
+        if let Some(index) = self.held_keys.iter().position(|x| x.note == note) {
             self.held_keys.remove(index);
 
             // If the top-of-stack key was released, then we need to return the second to last note
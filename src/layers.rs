@@ -0,0 +1,100 @@
+use nih_plug::prelude::Enum;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::{Note, Vel},
+    macros::Snapshot,
+    params::MeowParameters,
+};
+
+/// Which of the two layers a voice plays on. Layer A is the sound which the editor and the host
+/// see. Layer B is a stored copy of another sound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layer {
+    A,
+    B,
+}
+
+impl Layer {
+    pub const ALL: [Layer; 2] = [Layer::A, Layer::B];
+
+    /// The position of the layer in arrays which hold something for each layer.
+    pub fn index(self) -> usize {
+        match self {
+            Layer::A => 0,
+            Layer::B => 1,
+        }
+    }
+
+    /// The parameters that voices on this layer play with. If layer B's parameters aren't
+    /// available, layer B plays with layer A's parameters.
+    pub fn params<'a>(
+        self,
+        params: &'a MeowParameters,
+        layer_b: Option<&'a MeowParameters>,
+    ) -> &'a MeowParameters {
+        match (self, layer_b) {
+            (Layer::B, Some(layer_b)) => layer_b,
+            _ => params,
+        }
+    }
+}
+
+/// How incoming notes are shared between the two layers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum LayerMode {
+    /// Only layer A plays.
+    #[name = "Off"]
+    Off,
+    /// Every note plays on both layers.
+    #[name = "Stack"]
+    Stack,
+    /// Notes below the split note play on layer B, and the rest play on layer A.
+    #[name = "Key Split"]
+    KeySplit,
+    /// Notes at or above the split velocity play on layer B, and the rest play on layer A.
+    #[name = "Velocity Switch"]
+    VelocitySwitch,
+}
+
+pub struct LayerParams {
+    pub mode: LayerMode,
+    pub split_note: Note,
+    pub split_velocity: f32,
+}
+
+impl LayerParams {
+    /// Whether a new note plays on the given layer.
+    pub fn plays_on(&self, layer: Layer, note: Note, vel: Vel) -> bool {
+        let on_b = match self.mode {
+            LayerMode::Off => false,
+            LayerMode::Stack => return true,
+            LayerMode::KeySplit => note.0 < self.split_note.0,
+            LayerMode::VelocitySwitch => vel.raw >= self.split_velocity,
+        };
+        on_b == (layer == Layer::B)
+    }
+}
+
+/// The sound that layer B plays, stored as a snapshot of every parameter. Until a snapshot is
+/// stored, layer B plays the default sound.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LayerB {
+    pub snapshot: Option<Snapshot>,
+    // Counts the changes made by the editor, so that the audio thread can tell when to reload the
+    // snapshot. This isn't saved, since the plugin reloads the snapshot whenever it is initialized.
+    #[serde(skip)]
+    generation: u64,
+}
+
+impl LayerB {
+    /// Replace layer B's sound.
+    pub fn store(&mut self, snapshot: Snapshot) {
+        self.snapshot = Some(snapshot);
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+}
@@ -0,0 +1,131 @@
+use crate::{
+    common::{Beats, Hertz, SampleRate, Seconds},
+    layers::Layer,
+    sound_gen::{Angle, NoteShape, Oscillator},
+};
+
+// Where the chorus sits in its cycle relative to the vibrato, in cycles. When the modulation is
+// synced to the bar, the LFOs would otherwise all peak together at the same rate, and the chorus
+// sweep would just make the vibrato deeper.
+const CHORUS_PHASE_OFFSET: Angle = 0.25;
+
+/// The things which draw from the LFO bank. Each one gets its own LFO, running at its own rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LfoSlot {
+    /// The vibrato of a layer. The vibrato LFO is shared by every voice on the layer, although
+    /// each voice gets its own vibrato envelope.
+    Vibrato(Layer),
+    /// The chorus's read heads.
+    Chorus,
+}
+
+impl LfoSlot {
+    const COUNT: usize = Layer::ALL.len() + 1;
+
+    fn index(&self) -> usize {
+        match self {
+            LfoSlot::Vibrato(layer) => layer.index(),
+            LfoSlot::Chorus => Layer::ALL.len(),
+        }
+    }
+
+    fn phase_offset(&self) -> Angle {
+        match self {
+            LfoSlot::Vibrato(_) => 0.0,
+            LfoSlot::Chorus => CHORUS_PHASE_OFFSET,
+        }
+    }
+}
+
+/// A low frequency oscillator in the [LfoBank].
+#[derive(Debug)]
+pub struct Lfo {
+    oscillator: Oscillator,
+    // The rate as of the most recent sample, which is used to line the LFO up with the bar.
+    rate: Hertz,
+    phase_offset: Angle,
+    // The most recent value of the LFO, and the number of samples until it is next updated. Some
+    // LFOs are only updated every few samples at lower qualities.
+    value: f32,
+    countdown: usize,
+}
+
+impl Lfo {
+    fn new(phase_offset: Angle) -> Lfo {
+        Lfo {
+            oscillator: Oscillator::with_phase(phase_offset),
+            rate: Hertz::new(0.0),
+            phase_offset,
+            value: 0.0,
+            countdown: 0,
+        }
+    }
+
+    /// Advance the LFO by one sample. The value is only recomputed every `interval` samples, and
+    /// held in between.
+    pub fn next_sample(
+        &mut self,
+        sample_rate: SampleRate,
+        shape: NoteShape,
+        rate: Hertz,
+        interval: usize,
+    ) -> f32 {
+        self.rate = rate;
+        if self.countdown == 0 {
+            // Advance the LFO by the whole update interval at once.
+            self.value = self
+                .oscillator
+                .next_sample(sample_rate, shape, rate * interval as f32);
+            self.countdown = interval;
+        }
+        self.countdown -= 1;
+        self.value
+    }
+
+    /// The LFO's value shifted by `phase_offset` cycles, for consumers which need several copies of
+    /// the same LFO spread out in phase. This doesn't advance the LFO.
+    pub fn sample_at(&self, shape: NoteShape, phase_offset: Angle) -> f32 {
+        self.oscillator.sample_at(shape, phase_offset)
+    }
+
+    // Move the LFO to where it would be if it had started at the beginning of the bar, `elapsed`
+    // ago.
+    fn sync(&mut self, elapsed: Seconds) {
+        let cycles = elapsed.get() as f64 * self.rate.get() as f64 + self.phase_offset as f64;
+        self.oscillator = Oscillator::with_phase(cycles.fract() as Angle);
+        self.countdown = 0;
+    }
+}
+
+/// The LFOs which modulate the synth. Keeping them together lets them all be lined up with the
+/// host's bar at once.
+#[derive(Debug)]
+pub struct LfoBank {
+    lfos: [Lfo; LfoSlot::COUNT],
+}
+
+impl LfoBank {
+    pub fn new() -> LfoBank {
+        let slots = [
+            LfoSlot::Vibrato(Layer::A),
+            LfoSlot::Vibrato(Layer::B),
+            LfoSlot::Chorus,
+        ];
+        LfoBank {
+            lfos: slots.map(|slot| Lfo::new(slot.phase_offset())),
+        }
+    }
+
+    pub fn get(&mut self, slot: LfoSlot) -> &mut Lfo {
+        &mut self.lfos[slot.index()]
+    }
+
+    /// Line every LFO up with the host's bar, as if they had all started at the beginning of the
+    /// bar. `beats_into_bar` is how far into the bar the current buffer starts.
+    pub fn sync_to_bar(&mut self, beats_into_bar: Beats, tempo: f32) {
+        let elapsed = beats_into_bar.to_seconds(tempo);
+        for lfo in &mut self.lfos {
+            lfo.sync(elapsed);
+        }
+    }
+}
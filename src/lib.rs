@@ -3,56 +3,251 @@
 #![feature(portable_simd)]
 #![feature(let_chains)]
 
+mod alloc_check;
+mod articulations;
+mod bounce;
+mod bypass;
 mod chorus;
 pub mod common;
+mod compressor;
 pub mod ease;
+mod engine;
+mod envelope_follower;
+mod growl;
+mod handoff;
+mod hires_cc;
+mod host_link;
+mod issues;
 mod keys;
+mod layers;
+mod lfo_bank;
+mod macros;
+pub mod meters;
+mod midi_learn;
+mod midi_player;
 mod neighbor_pairs;
+mod normalizer;
+mod param_changes;
+pub mod param_info;
 mod params;
+mod presets;
+mod purr;
+mod quantizer;
+#[cfg(feature = "multithreaded")]
+mod render_pool;
+mod resonator;
+mod scheduler;
+mod settings;
+mod silence;
+mod skin;
 mod sound_gen;
+mod speaker_protection;
+mod state;
+mod stereo_width;
+mod supermeow;
 mod ui;
 mod ui_knob;
+mod voice_pitches;
+mod watchdog;
 
-use std::sync::{atomic::Ordering, Arc};
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc,
+};
 
+use alloc_check::NoAllocGuard;
+use articulations::ARTICULATIONS;
 use atomic_float::AtomicF32;
-use chorus::Chorus;
-use common::{Note, Pitch, Pitchbend, SampleRate, Vel};
-use ease::lerp;
-use keys::KeyTracker;
-use nih_plug::{nih_export_vst3, prelude::*};
-use params::{MeowParameters, Parameters};
+use bounce::PhraseRecorder;
+use bypass::SoftBypass;
+use common::{Beats, Note, Pitch, Pitchbend, SampleRate, Vel};
+use engine::Engine;
+use hires_cc::HiResCCs;
+use host_link::HostLink;
+use keys::{HeldKey, KeyTracker};
+use layers::{Layer, LayerMode};
+use macros::NUM_MACROS;
+use meters::OutputMeters;
+use midi_learn::MidiLearn;
+use midi_player::{MidiLoop, MidiPlayer, Playhead};
+use nih_plug::{nih_export_clap, nih_export_vst3, prelude::*};
+use normalizer::EventNormalizer;
+use param_changes::ParamChanges;
+use params::{BypassTails, LegatoMode, MeowParameters, ParamTable, Parameters, MAX_VOICES};
+use presets::{LibraryPreset, PresetLibrary, BANKS};
+use quantizer::Quantizer;
+use scheduler::Scheduler;
 
-use sound_gen::{NoiseGenerator, Oscillator, Voice, RETRIGGER_TIME};
+use settings::Settings;
+use silence::SilenceDetector;
+use sound_gen::{MidiOutState, Voice, RETRIGGER_TIME};
+use speaker_protection::SpeakerProtection;
+use voice_pitches::VoicePitches;
+
+/// The building blocks of the synth, for the benchmarks and tests. This is not a stable API.
+#[doc(hidden)]
+pub mod dsp {
+    pub use crate::chorus::Chorus;
+    pub use crate::engine::Engine;
+    pub use crate::growl::{Growl, GrowlParams, GrowlTarget};
+    pub use crate::hires_cc::HiResCCs;
+    pub use crate::lfo_bank::{Lfo, LfoBank, LfoSlot};
+    pub use crate::macros::{MorphSnapshots, Snapshot};
+    pub use crate::midi_learn::MidiMappings;
+    pub use crate::normalizer::EventNormalizer;
+    pub use crate::params::{
+        EnvelopeCurve, MeowParameters, Parameters, StrayCatParams, VolumeEnvelopeParams,
+    };
+    pub use crate::quantizer::{Root, Scale};
+    pub use crate::settings::{Settings, SETTINGS_DIR_VAR};
+    pub use crate::sound_gen::{NoiseGenerator, NoteShape, SampleModulation, Voice};
+}
+
+/// The maximum number of samples rendered at once. Buffers are split into blocks of at most this
+/// size, and blocks are further split at each event. This keeps the modulation resolution the same
+/// no matter how large the host's buffers are, and bounds the size of the scratch buffers.
+const MAX_BLOCK_SIZE: usize = 64;
+
+/// The most voices which can exist at once, including the ones which are fading out after being
+/// stolen or retriggered. Each layer can play up to MAX_VOICES voices. The voices are stored in a
+/// vector with exactly this much room, which is never allowed to grow, so that note storms can't
+/// make the audio thread allocate.
+const MAX_NOTES: usize = Layer::ALL.len() * MAX_VOICES * 2;
+
+/// The most events which can be waiting to be played at once. A buffer's events are all scheduled
+/// at its start, so this is also the most events a buffer can have. Hosts send far fewer than this,
+/// but past it, note ons and CCs are dropped rather than growing the queue on the audio thread.
+const MAX_SCHEDULED_EVENTS: usize = 4096;
+
+/// Set when running as the standalone application rather than as a plugin.
+static STANDALONE: AtomicBool = AtomicBool::new(false);
+
+/// Turn on the features which only make sense without a host, such as the MIDI player. The
+/// standalone application calls this before starting the synth.
+#[doc(hidden)]
+pub fn set_standalone() {
+    STANDALONE.store(true, Ordering::Relaxed);
+}
+
+/// Work which is done on a background thread, so that neither the audio thread nor the editor has
+/// to wait on the disk.
+pub enum Task {
+    /// Reload the user's presets from the presets folder.
+    RescanPresets,
+    /// Tell the host about the parameters which the synth has set by itself. This runs on the GUI
+    /// thread.
+    UpdateHost,
+}
 
 /// The main plugin struct.
 pub struct Nyasynth {
     /// All the notes to be played.
     notes: Vec<Voice>,
+    /// Voices which were cut off to make room for new voices, or which never found room, and keys
+    /// which a voice slid away from. The host is told that they ended after the event which ended
+    /// them is handled.
+    ended_voices: Vec<EndedVoice>,
     /// The parameters which are shared with the VST host
     params: Arc<Parameters>,
     pitch_bend_smoother: Smoother<Pitchbend>,
-    key_tracker: KeyTracker,
-    // The vibrato LFO is global--the vibrato amount is shared across all generators, although each
-    // generator gets it's own vibrato envelope.
-    vibrato_lfo: Oscillator,
-    // The chorus effect is also global.
-    chorus: Chorus,
-    /// The global noise generator
-    noise_generator: NoiseGenerator,
+    /// The pitch bends received for the current block, as (bend, time) pairs, with the time in
+    /// samples from the start of the block. These are joined up into the block's pitch bend
+    /// envelope. There is room for one pitch bend on each sample of a block.
+    pitch_bend_points: Vec<(Pitchbend, i32)>,
+    /// The pitch bend envelope's value at the end of the last block, which the next block starts
+    /// from.
+    pitch_bend: Pitchbend,
+    /// The most recently received pitch bend. The envelope may have extrapolated past it, in which
+    /// case it returns to it over the next block.
+    pitch_bend_target: Pitchbend,
+    /// Renders the voices and the effects, one block at a time.
+    engine: Engine,
+    /// The most recent pitch bend on each MPE member channel, in semitones.
+    mpe_bends: [f32; 16],
+    /// The most recent channel pressure on each channel.
+    channel_pressures: [f32; 16],
+    /// The keys held on each layer.
+    key_trackers: [KeyTracker; 2],
+    /// Drops duplicate and unmatched note events from the host.
+    normalizer: EventNormalizer,
+    /// Snaps incoming notes to the selected scale.
+    quantizer: Quantizer,
+    /// Combines 14-bit CC pairs.
+    hires_ccs: HiResCCs,
+    // The last (channel, CC, 7-bit value) sent for the volume and filter envelopes, so that a
+    // value is only sent again once it changes.
+    envelope_ccs_sent: [Option<(u8, u8, u8)>; 2],
     sample_rate: SampleRate,
     envelope_amount: Arc<AtomicF32>,
+    /// The current pitch of each voice, for the editor's pitch display.
+    voice_pitches: Arc<VoicePitches>,
+    /// The output levels, for the editor's meters.
+    meters: Arc<OutputMeters>,
+    /// Limits the output when a preset with extreme settings is first loaded.
+    speaker_protection: SpeakerProtection,
+    /// Fades the output in and out when the synth is bypassed.
+    bypass: SoftBypass,
+    /// Notices when the synth has gone quiet, so that rendering can be skipped.
+    silence: SilenceDetector,
+    /// The parameters which have changed since the editor last redrew them.
+    param_changes: Arc<ParamChanges>,
+    /// Tells the host about the parameters which the synth sets by itself, such as the macros'
+    /// targets and the presets loaded by program changes.
+    host_link: Arc<HostLink>,
+    /// The parameter waiting to be bound to a CC, if any.
+    midi_learn: Arc<MidiLearn>,
+    /// Used to switch presets on MIDI program changes, and to set the parameters driven by macros.
+    /// Everything it sets is passed on to the host by `host_link`.
+    param_table: ParamTable,
+    /// The value of each macro when its targets were last set.
+    macro_values: [f32; NUM_MACROS],
+    /// The value of the morph knob when the parameters were last morphed.
+    morph_value: f32,
+    /// The currently selected preset bank, set by MIDI bank select messages. The upper 7 bits are
+    /// the bank select MSB (CC 0) and the lower 7 bits are the LSB (CC 32).
+    bank: usize,
+    /// The presets in the preset browser, which the next and previous preset buttons step through.
+    presets: Arc<PresetLibrary>,
+    /// The audio thread's copy of the presets in the preset browser, received from `presets`.
+    preset_list: Option<Box<Vec<LibraryPreset>>>,
+    /// Set once the user's presets have been asked to be scanned.
+    presets_requested: bool,
+    /// The values of the next and previous preset buttons as of the last block.
+    preset_buttons: (bool, bool),
+    /// The articulation picked by the most recent keyswitch, which is switched to at the start of
+    /// the next note.
+    pending_articulation: Option<usize>,
+    /// The parameters which layer B plays with. These aren't shared with the host--they're loaded
+    /// from the snapshot which the editor stores in `params.layer_b`.
+    layer_b: Arc<Parameters>,
+    /// Used to load layer B's snapshot into `layer_b`.
+    layer_b_table: ParamTable,
+    /// The generation of layer B's snapshot when it was last loaded.
+    layer_b_generation: u64,
+    /// The standalone's MIDI player, which the editor loads loops into.
+    midi_player: Arc<MidiPlayer>,
+    /// Plays the MIDI player's loop.
+    playhead: Playhead,
+    /// Records the most recent phrase, so that the editor can bounce it.
+    phrase_recorder: Arc<PhraseRecorder>,
+    /// The time at the start of the current buffer, in samples since the synth started.
+    clock: u64,
+    /// The events waiting to be played, from the host, the MIDI player, and anything else which
+    /// plays notes, timed on `clock`. Blocks are split wherever an event is due.
+    scheduler: Scheduler<NoteEvent<()>>,
+    /// How many events have been dropped because too many arrived at once. The editor reports them.
+    dropped_events: Arc<AtomicUsize>,
 }
 
 impl Plugin for Nyasynth {
     type SysExMessage = ();
-    type BackgroundTask = ();
+    type BackgroundTask = Task;
 
     const NAME: &'static str = "Nyasynth";
     const VENDOR: &'static str = "a2aaron";
     const URL: &'static str = "https://a2aaron.github.io/";
     const EMAIL: &'static str = "aaronko@umich.edu";
-    const VERSION: &'static str = "1.0";
+    const VERSION: &'static str = "1.5";
 
     const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[AudioIOLayout {
         main_input_channels: None,
@@ -63,9 +258,14 @@ impl Plugin for Nyasynth {
     }];
 
     const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
-    const MIDI_OUTPUT: MidiConfig = MidiConfig::None;
+    // MIDI CCs are output so that the envelopes can be sent to other plugins.
+    const MIDI_OUTPUT: MidiConfig = if cfg!(feature = "midi-output") {
+        MidiConfig::MidiCCs
+    } else {
+        MidiConfig::None
+    };
 
-    const SAMPLE_ACCURATE_AUTOMATION: bool = false;
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
     const HARD_REALTIME_ONLY: bool = false;
 
     fn initialize(
@@ -81,6 +281,21 @@ impl Plugin for Nyasynth {
         // of RETRIGGER_TIME. Note that this latency doesn't exist for non-retriggered notes.
         context.set_latency_samples(RETRIGGER_TIME as u32);
         self.set_sample_rate(SampleRate(buffer_config.sample_rate));
+        // Buffers are never split into blocks larger than the host's largest buffer.
+        let block_size = (buffer_config.max_buffer_size as usize).clamp(1, MAX_BLOCK_SIZE);
+        self.engine.set_block_size(block_size);
+        // The plugin is initialized again whenever the host loads a new state, so this is where
+        // loaded presets are checked.
+        self.speaker_protection
+            .preset_loaded(&self.params, Settings::get().speaker_protection());
+        self.load_layer_b(self.sample_rate, true);
+        // The loaded state's macro and morph knobs already match the parameters they drive, so
+        // they shouldn't be applied again until they move. Otherwise, the values stored in the
+        // state would be overwritten as soon as it loads.
+        self.macro_values = self.params.macros.macros().map(|param| param.value());
+        self.morph_value = self.params.macros.morph.value();
+        #[cfg(feature = "multithreaded")]
+        self.engine.start_render_pool();
         true
     }
 
@@ -90,13 +305,66 @@ impl Plugin for Nyasynth {
         _aux: &mut AuxiliaryBuffers,
         context: &mut impl ProcessContext<Self>,
     ) -> ProcessStatus {
+        // Allocating on the audio thread can stall it, so make sure that never happens.
+        let _no_alloc = NoAllocGuard::new();
+
+        // The user's presets are scanned the first time the synth runs, rather than holding up the
+        // host while the plugin is created.
+        if !self.presets_requested {
+            context.execute_background(Task::RescanPresets);
+            self.presets_requested = true;
+        }
+        self.presets.receive(&mut self.preset_list);
+
         let sample_rate = SampleRate(context.transport().sample_rate);
         self.set_sample_rate(sample_rate);
 
         let num_samples = buffer.samples();
-        let tempo = context.transport().tempo.unwrap_or(120.0) as f32;
 
-        let params = MeowParameters::new(&self.params, tempo);
+        // The MIDI player's loop is read for the whole buffer. The Arc is cloned so that the loop
+        // can be borrowed while the synth is changed.
+        let midi_player = self.midi_player.clone();
+        let midi_loop = midi_player.try_midi_loop();
+        let midi_loop = midi_loop.as_ref().and_then(|midi_loop| midi_loop.as_ref());
+        self.playhead
+            .start_buffer(&midi_player, midi_loop, num_samples);
+        // While the loop plays, it sets the tempo, so that tempo synced modulation follows it.
+        let tempo = match midi_loop {
+            Some(midi_loop) if self.playhead.is_playing() => midi_loop.tempo,
+            _ => context.transport().tempo.unwrap_or(120.0) as f32,
+        };
+        self.phrase_recorder
+            .end_buffer(self.clock + num_samples as u64, tempo, sample_rate);
+
+        // This is only used until the first block, so the smoothers are not advanced here.
+        let mut params = MeowParameters::new(&self.params, tempo, 0);
+        // The bar position only means something while the host is playing.
+        if params.sync_modulation {
+            let transport = context.transport();
+            if let (true, Some(position), Some(bar_start)) = (
+                transport.playing,
+                transport.pos_beats(),
+                transport.bar_start_pos_beats(),
+            ) {
+                self.engine
+                    .sync_to_bar(Beats::new((position - bar_start) as f32), tempo);
+            }
+        }
+        // Layer B's parameters are only computed while layer B is in use, since they cost as much
+        // as layer A's.
+        self.load_layer_b(sample_rate, false);
+        let mut layer_b = self
+            .layer_b_in_use(&params)
+            .then(|| MeowParameters::new(&self.layer_b, tempo, 0));
+        self.schedule_events(
+            &mut params,
+            layer_b.as_ref(),
+            tempo,
+            midi_loop,
+            sample_rate,
+            num_samples,
+            context,
+        );
 
         // remove "dead" notes
         // we do this _before_ processing any events
@@ -113,7 +381,19 @@ impl Plugin for Nyasynth {
             // if you pass it `self.sample_rate` or `self.params`. Doing it like this allows it to
             // only capture the `params` field, which avoids the issue of cannot borrow while
             // mutably borrowed
-            self.notes.retain(|gen| gen.is_alive(sample_rate, &params));
+            self.notes.retain(|gen| {
+                let alive = gen.is_alive(sample_rate, gen.layer.params(&params, layer_b.as_ref()));
+                if !alive {
+                    // Let the host know the voice has ended, so it can stop sending it events.
+                    context.send_event(NoteEvent::VoiceTerminated {
+                        timing: 0,
+                        voice_id: gen.voice_id,
+                        channel: gen.channel,
+                        note: gen.note.0,
+                    });
+                }
+                alive
+            });
         }
 
         let (left_out, right_out) = {
@@ -123,93 +403,205 @@ impl Plugin for Nyasynth {
             (left_out, right_out)
         };
 
+        // If nothing has played for a while, the effects' tails have died out, and there are no
+        // events which could start a note, then there is nothing to render. The control parameters
+        // are still applied, since the preset buttons, macros, and morph can be automated while
+        // the synth is silent.
+        if self.notes.is_empty()
+            && self.scheduler.is_empty()
+            && !self.playhead.is_active()
+            && self.silence.is_asleep(sample_rate)
+        {
+            left_out.fill(0.0);
+            right_out.fill(0.0);
+            self.apply_preset_buttons(&params, sample_rate);
+            self.apply_morph(&params, sample_rate);
+            self.apply_macros(&params, sample_rate);
+            self.meters.update(left_out, right_out, sample_rate);
+            self.request_host_update(context);
+            self.clock += num_samples as u64;
+            // Hosts may stop calling process() until the next event arrives.
+            return ProcessStatus::Normal;
+        }
+
         let mut block_start = 0;
         let mut max_envelope = 0.0f32;
         while block_start < num_samples {
-            // Initially set the block size to 64 (or, if the number of samples in the buffer
-            // is smaller than 64, to just that value)
-            let mut block_len = (num_samples - block_start).min(64);
-            // Consume all events from the context which happen before or at the start
-            // of the block. This also shrinks the current block if there would be an event within
-            // the block.
-            while let Some(next_event) = context.peek_event() {
-                let timing = next_event.timing() as usize;
-                // If the event occurs before or at the start of this block, then process the event
-                if timing <= block_start {
-                    self.process_event(&params, sample_rate, context.next_event().unwrap())
-                } else if timing < block_start + block_len {
-                    // If the event would occur in the middle of the block, then do not process the
-                    // event and cut this block short such that the event occurs on the first
-                    // sample of the next block.
-                    block_len = timing - block_start;
-                } else {
-                    break;
+            // Initially set the block size to the scratch buffer size (or, if the number of samples
+            // left in the buffer is smaller than that, to just that value). The scratch buffers are
+            // sized from the host's maximum buffer size, but some hosts send larger buffers anyway,
+            // so this does not rely on the host's buffers fitting.
+            let mut block_len = (num_samples - block_start).min(self.engine.block_size());
+            // Play every event which is due by the start of the block.
+            let now = self.clock + block_start as u64;
+            while let Some(event) = self.scheduler.pop_due(now) {
+                self.play_event(
+                    &mut params,
+                    layer_b.as_ref(),
+                    tempo,
+                    sample_rate,
+                    event,
+                    block_start as u32,
+                    context,
+                );
+            }
+            // Pitch bends in the middle of the block don't cut it short. Instead, the pitch bend
+            // envelope joins them up, so that the bend moves smoothly from one to the next.
+            let mpe_zone = params.mpe_zone;
+            let is_pitch_bend = |event: &NoteEvent<()>| {
+                matches!(event, NoteEvent::MidiPitchBend { channel, .. }
+                    if !mpe_zone.is_member_channel(*channel))
+            };
+            while let Some((time, event)) = self
+                .scheduler
+                .pop_before(now + block_len as u64, is_pitch_bend)
+            {
+                self.phrase_recorder.record(time, event, sample_rate);
+                if let NoteEvent::MidiPitchBend { value, .. } = event {
+                    let bend = Pitchbend::from_zero_one_range(value);
+                    self.push_pitch_bend(bend, (time - now) as i32);
                 }
             }
+            // If the next event would occur in the middle of the block, then cut this block short
+            // such that the event occurs on the first sample of the next block.
+            if let Some(next_time) = self.scheduler.next_time() {
+                let until_next = (next_time - now) as usize;
+                block_len = block_len.min(until_next);
+            }
 
             let block_end = block_start + block_len;
 
-            // Fill each block with zeros
-            left_out[block_start..block_end].fill(0.0);
-            right_out[block_start..block_end].fill(0.0);
+            self.apply_preset_buttons(&params, sample_rate);
+            self.apply_morph(&params, sample_rate);
+            self.apply_macros(&params, sample_rate);
 
-            let vibrato_params = &params.vibrato_lfo;
-
-            for i in 0..block_len {
-                // Get the vibrato modifier, which is global across all of the voices. (Note that each
-                // generator gets it's own vibrato envelope).
-                let vibrato_mod = self.vibrato_lfo.next_sample(
-                    sample_rate,
-                    params.vibrato_note_shape,
-                    vibrato_params.speed,
-                ) * vibrato_params.amount;
+            // Now that the block length is known, advance the smoothed parameters over the block.
+            // Since automation is sample accurate, nih-plug splits the buffer at every parameter
+            // change, so stepped parameters switch exactly at their automation points.
+            params = MeowParameters::new(&self.params, tempo, block_len as u32);
+            layer_b = self
+                .layer_b_in_use(&params)
+                .then(|| MeowParameters::new(&self.layer_b, tempo, block_len as u32));
 
-                let pitch_bend = self.pitch_bend_smoother.next();
+            // The pitch bend is the same for every voice, so compute it once for the whole block.
+            // If the envelope overshot the last pitch bend on an earlier block, it returns to it
+            // over this one.
+            if self.pitch_bend_points.is_empty() && self.pitch_bend != self.pitch_bend_target {
+                self.pitch_bend_points
+                    .push((self.pitch_bend_target, block_len as i32));
+            }
+            let (envelope, end_bend) =
+                Pitchbend::to_pitch_envelope(&self.pitch_bend_points, self.pitch_bend, block_len);
+            // The smoother follows the envelope, so that the bend still glides when pitch bend
+            // smoothing is turned on. With no smoothing, it follows the envelope exactly.
+            let smoothing_ms = params.pitchbend_smoothing.get() * 1000.0;
+            self.pitch_bend_smoother.style = SmoothingStyle::Linear(smoothing_ms);
+            let mut target = self.pitch_bend;
+            for (pitch_bend, bend) in self.engine.pitch_bends(block_len).iter_mut().zip(envelope) {
+                if bend != target {
+                    self.pitch_bend_smoother.set_target(sample_rate.get(), bend);
+                    target = bend;
+                }
+                *pitch_bend = self.pitch_bend_smoother.next();
+            }
+            self.pitch_bend = end_bend;
+            self.pitch_bend_points.clear();
 
-                for voice in &mut self.notes {
-                    let (left, right, total_volume) = voice.next_sample(
-                        &params,
-                        &mut self.noise_generator,
-                        sample_rate,
-                        pitch_bend,
-                        vibrato_mod,
-                    );
-                    max_envelope = max_envelope.max(total_volume);
+            let volume = self.engine.render_block(
+                &mut self.notes,
+                (&self.params, &params),
+                layer_b.as_ref().map(|layer_b| (&*self.layer_b, layer_b)),
+                sample_rate,
+                &mut left_out[block_start..block_end],
+                &mut right_out[block_start..block_end],
+            );
+            max_envelope = max_envelope.max(volume);
 
-                    left_out[block_start + i] += left;
-                    right_out[block_start + i] += right;
+            // Send out the envelope values when they change, if enabled.
+            if let Some(envelope_cc) = &params.envelope_cc {
+                let (amp_env, filter_env) = self
+                    .notes
+                    .iter()
+                    .map(|voice| voice.envelope_values())
+                    .fold((0.0f32, 0.0f32), |(a, b), (x, y)| (a.max(x), b.max(y)));
+                for ((cc, value), sent) in [
+                    (envelope_cc.amp_cc, amp_env),
+                    (envelope_cc.filter_cc, filter_env),
+                ]
+                .into_iter()
+                .zip(self.envelope_ccs_sent.iter_mut())
+                {
+                    let value = (value.clamp(0.0, 1.0) * 127.0).round() as u8;
+                    let message = (envelope_cc.channel, cc, value);
+                    if *sent != Some(message) {
+                        *sent = Some(message);
+                        context.send_event(NoteEvent::MidiCC {
+                            timing: block_start as u32,
+                            channel: envelope_cc.channel,
+                            cc,
+                            value: value as f32 / 127.0,
+                        });
+                    }
                 }
+            } else {
+                self.envelope_ccs_sent = [None; 2];
             }
 
             block_start = block_end;
         }
 
-        self.envelope_amount.store(max_envelope, Ordering::Relaxed);
-
-        let chorus_params = &params.chorus;
-        // Chorus  and other post processing effects
-        for i in 0..num_samples {
-            let left = left_out[i];
-            let right = right_out[i];
+        // An empty buffer never enters the loop above, so its events are played now, rather than
+        // being held until the next buffer.
+        if num_samples == 0 {
+            while let Some(event) = self.scheduler.pop_due(self.clock) {
+                self.play_event(
+                    &mut params,
+                    layer_b.as_ref(),
+                    tempo,
+                    sample_rate,
+                    event,
+                    0,
+                    context,
+                );
+            }
+        }
+        self.playhead.end_buffer(&midi_player, sample_rate);
 
-            // Get the chorus effect
-            let chorus = self.chorus.next_sample(
-                left,
-                sample_rate,
-                &chorus_params,
-                params.chorus_note_shape,
-            );
+        self.envelope_amount.store(max_envelope, Ordering::Relaxed);
+        let playing_voices = self.notes.iter().filter(|voice| !voice.is_fading_out());
+        self.voice_pitches
+            .store(playing_voices.map(|voice| voice.current_pitch()));
 
-            let left = lerp(left, chorus, chorus_params.mix);
-            let right = lerp(right, chorus, chorus_params.mix);
+        self.engine
+            .post_process(&params, sample_rate, left_out, right_out);
 
-            left_out[i] = left * params.master_vol.get_amp();
-            right_out[i] = right * params.master_vol.get_amp();
+        // When bypassed, the output either rings out (new notes are ignored while bypassed, so it
+        // eventually goes quiet by itself) or is faded out.
+        let cut = params.bypass && params.bypass_tails == BypassTails::Cut;
+        if self.bypass.process(left_out, right_out, cut, sample_rate) {
+            // Nothing can be heard anymore, so stop the voices instead of rendering them silently.
+            // The chorus is cleared so that its tail doesn't come back when un-bypassed.
+            for voice in self.notes.drain(..) {
+                EndedVoice::of(&voice).send(0, context);
+            }
+            self.engine.reset_effects();
         }
-        ProcessStatus::Normal
+
+        self.speaker_protection
+            .process(left_out, right_out, sample_rate);
+        self.meters.update(left_out, right_out, sample_rate);
+        self.silence
+            .update(left_out, right_out, !self.notes.is_empty());
+        self.request_host_update(context);
+        self.clock += num_samples as u64;
+        // Voices can be very quiet (such as at the start of a slow attack) without being finished,
+        // so hosts are asked to keep processing until the synth is fully asleep.
+        ProcessStatus::KeepAlive
     }
 
-    fn filter_state(_state: &mut PluginState) {}
+    fn filter_state(state: &mut PluginState) {
+        state::migrate(state);
+    }
 
     fn reset(&mut self) {}
 
@@ -219,6 +611,14 @@ impl Plugin for Nyasynth {
         for note in &mut self.notes {
             note.note_off();
         }
+        // The host won't send note offs for the keys which were held, so forget about them too.
+        self.normalizer.reset();
+        for key_tracker in &mut self.key_trackers {
+            key_tracker.held_keys.clear();
+        }
+        // The MIDI player starts from the top of the loop when the synth is reactivated.
+        self.playhead = Playhead::new();
+        self.scheduler.clear();
     }
 
     fn params(&self) -> Arc<dyn Params> {
@@ -226,30 +626,109 @@ impl Plugin for Nyasynth {
     }
 
     fn task_executor(&self) -> TaskExecutor<Self> {
-        Box::new(|_| ())
+        let presets = self.presets.clone();
+        let host_link = self.host_link.clone();
+        Box::new(move |task| match task {
+            Task::RescanPresets => presets.rescan(),
+            Task::UpdateHost => host_link.update_host(),
+        })
     }
 
-    fn editor(&self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
-        ui::get_editor(self.params.clone(), self.envelope_amount.clone())
+    fn editor(&self, async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        ui::get_editor(
+            async_executor,
+            self.params.clone(),
+            self.envelope_amount.clone(),
+            self.voice_pitches.clone(),
+            self.meters.clone(),
+            self.presets.clone(),
+            self.speaker_protection.engaged(),
+            self.param_changes.clone(),
+            self.host_link.clone(),
+            self.dropped_events.clone(),
+            self.midi_learn.clone(),
+            self.phrase_recorder.clone(),
+            STANDALONE
+                .load(Ordering::Relaxed)
+                .then(|| self.midi_player.clone()),
+        )
     }
 }
 impl Default for Nyasynth {
     fn default() -> Self {
         let sample_rate = SampleRate::from(44100.0);
+        // Load the global settings now, rather than the first time the audio thread needs them.
+        Settings::get();
+        let params = Arc::new(Parameters::new());
+        let param_changes = Arc::new(ParamChanges::new(&params));
+        let layer_b = Arc::new(Parameters::new());
+        let layer_b_changes = Arc::new(ParamChanges::new(&layer_b));
+        let host_updates = Arc::new(ParamChanges::new(&params));
         Nyasynth {
-            params: Arc::new(Parameters::new()),
-            notes: Vec::with_capacity(16),
-            key_tracker: KeyTracker::new(),
-            vibrato_lfo: Oscillator::new(),
-            chorus: Chorus::new(sample_rate),
-            noise_generator: NoiseGenerator::new(),
+            layer_b_table: ParamTable::new(&layer_b, layer_b_changes),
+            layer_b,
+            layer_b_generation: 0,
+            midi_player: Arc::new(MidiPlayer::new()),
+            playhead: Playhead::new(),
+            phrase_recorder: Arc::new(PhraseRecorder::new()),
+            clock: 0,
+            scheduler: Scheduler::new(MAX_SCHEDULED_EVENTS),
+            dropped_events: Arc::new(AtomicUsize::new(0)),
+            param_table: ParamTable::new(&params, param_changes.clone())
+                .reporting_to_host(host_updates.clone()),
+            host_link: Arc::new(HostLink::new(
+                params.clone(),
+                param_changes.clone(),
+                host_updates,
+            )),
+            param_changes,
+            midi_learn: Arc::new(MidiLearn::new()),
+            macro_values: params.macros.macros().map(|param| param.value()),
+            morph_value: params.macros.morph.value(),
+            bank: 0,
+            presets: Arc::new(PresetLibrary::new()),
+            preset_list: None,
+            presets_requested: false,
+            preset_buttons: (false, false),
+            pending_articulation: None,
+            params,
+            notes: Vec::with_capacity(MAX_NOTES),
+            ended_voices: Vec::with_capacity(MAX_NOTES),
+            key_trackers: [KeyTracker::new(), KeyTracker::new()],
+            normalizer: EventNormalizer::new(),
+            quantizer: Quantizer::new(),
+            hires_ccs: HiResCCs::new(),
+            envelope_ccs_sent: [None; 2],
             sample_rate: SampleRate(44100.0),
             pitch_bend_smoother: Smoother::new(SmoothingStyle::Linear(0.1)),
+            pitch_bend_points: Vec::with_capacity(MAX_BLOCK_SIZE),
+            pitch_bend: Pitchbend::default(),
+            pitch_bend_target: Pitchbend::default(),
+            engine: Engine::new(MAX_BLOCK_SIZE, sample_rate),
+            mpe_bends: [0.0; 16],
+            channel_pressures: [0.0; 16],
             envelope_amount: Arc::new(0.0.into()),
+            voice_pitches: Arc::new(VoicePitches::new()),
+            meters: Arc::new(OutputMeters::new()),
+            speaker_protection: SpeakerProtection::new(),
+            bypass: SoftBypass::new(),
+            silence: SilenceDetector::new(),
         }
     }
 }
 
+impl ClapPlugin for Nyasynth {
+    const CLAP_ID: &'static str = "com.a2aaron.nyasynth";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some("The World's Second Meowizer");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::Instrument,
+        ClapFeature::Synthesizer,
+        ClapFeature::Stereo,
+    ];
+}
+
 impl Vst3Plugin for Nyasynth {
     const VST3_CLASS_ID: [u8; 16] = *b"nyasynth.a2aaron";
 
@@ -257,112 +736,765 @@ impl Vst3Plugin for Nyasynth {
 }
 
 impl Nyasynth {
+    /// Update everything which depends on the sample rate. Hosts may change the sample rate in the
+    /// middle of a session, so this may be called from process().
     fn set_sample_rate(&mut self, sample_rate: SampleRate) {
         if sample_rate != self.sample_rate {
             self.sample_rate = sample_rate;
-            self.chorus.set_sample_rate(sample_rate);
+            self.engine.set_sample_rate(sample_rate);
+            self.param_table.update_smoothers(sample_rate);
+            self.layer_b_table.update_smoothers(sample_rate);
+            // The pitch bend smoother's ramp was computed for the old sample rate, so finish it
+            // immediately.
+            let pitch_bend = self.pitch_bend_smoother.previous_value();
+            self.pitch_bend_smoother.reset(pitch_bend);
+            for voice in &mut self.notes {
+                voice.invalidate_filter();
+            }
         }
     }
 
+    /// Schedule the events from the host and the MIDI player which play in this buffer. Events
+    /// should never be timed past the end of the buffer, but some hosts send them anyway, so they
+    /// are played on the buffer's last sample rather than being dropped.
+    #[allow(clippy::too_many_arguments)]
+    fn schedule_events(
+        &mut self,
+        params: &mut MeowParameters,
+        layer_b: Option<&MeowParameters>,
+        tempo: f32,
+        midi_loop: Option<&MidiLoop>,
+        sample_rate: SampleRate,
+        num_samples: usize,
+        context: &mut impl ProcessContext<Self>,
+    ) {
+        let last_sample = num_samples.saturating_sub(1) as u64;
+        while let Some(event) = context.next_event() {
+            let time = self.clock + (event.timing() as u64).min(last_sample);
+            self.schedule_event(params, layer_b, tempo, sample_rate, time, event, context);
+        }
+        while let Some((timing, event)) = self.playhead.next(midi_loop, sample_rate) {
+            let time = self.clock + timing as u64;
+            self.schedule_event(params, layer_b, tempo, sample_rate, time, event, context);
+        }
+    }
+
+    /// Schedule an event at the given time. If too many events arrive at once, note ons and CCs
+    /// are dropped to make room for note offs and chokes, so that notes are never left hanging.
+    #[allow(clippy::too_many_arguments)]
+    fn schedule_event(
+        &mut self,
+        params: &mut MeowParameters,
+        layer_b: Option<&MeowParameters>,
+        tempo: f32,
+        sample_rate: SampleRate,
+        time: u64,
+        event: NoteEvent<()>,
+        context: &mut impl ProcessContext<Self>,
+    ) {
+        let droppable = |event: &NoteEvent<()>| {
+            !matches!(event, NoteEvent::NoteOff { .. } | NoteEvent::Choke { .. })
+        };
+        match self.scheduler.schedule_or_evict(time, event, droppable) {
+            Ok(None) => (),
+            Ok(Some(_)) => {
+                self.dropped_events.fetch_add(1, Ordering::Relaxed);
+            }
+            // Everything waiting is a note off or a choke, so there is no note on for this to
+            // overtake, and it can be played straight away.
+            Err(event) => self.play_event(params, layer_b, tempo, sample_rate, event, 0, context),
+        }
+    }
+
+    /// Handle an event from the host or the MIDI player at the given timing in the buffer. If the
+    /// event switches to a keyswitched articulation, the parameters are recomputed.
+    fn play_event(
+        &mut self,
+        params: &mut MeowParameters,
+        layer_b: Option<&MeowParameters>,
+        tempo: f32,
+        sample_rate: SampleRate,
+        event: NoteEvent<()>,
+        timing: u32,
+        context: &mut impl ProcessContext<Self>,
+    ) {
+        self.phrase_recorder
+            .record(self.clock + timing as u64, event, sample_rate);
+        if self.apply_pending_articulation(params, &event, sample_rate) {
+            *params = MeowParameters::new(&self.params, tempo, 0);
+        }
+        self.process_event(params, layer_b, sample_rate, event);
+        for voice in self.ended_voices.drain(..) {
+            voice.send(timing, context);
+        }
+        if params.midi_out {
+            self.send_midi_out(timing, context);
+        }
+    }
+
+    /// Handle an event. `layer_b` is layer B's parameters, if layer B is playing.
     fn process_event(
         &mut self,
         params: &MeowParameters,
+        layer_b: Option<&MeowParameters>,
         sample_rate: SampleRate,
         event: NoteEvent<()>,
     ) {
+        let Some(event) = self.normalizer.process(event) else {
+            return;
+        };
+        // Keyswitches are caught before the scale quantizer, which could move other notes onto
+        // them. Their note offs are dropped too.
+        if let NoteEvent::NoteOn { note, .. } | NoteEvent::NoteOff { note, .. } = event {
+            if let Some(articulation) = params.keyswitches.articulation(note) {
+                if matches!(event, NoteEvent::NoteOn { .. }) {
+                    self.pending_articulation = Some(articulation);
+                }
+                return;
+            }
+        }
+        let event = self
+            .quantizer
+            .process(event, params.scale, params.scale_root);
+        // Notes which are already playing can still be released while bypassed, but no new notes
+        // are started.
+        if params.bypass && matches!(event, NoteEvent::NoteOn { .. }) {
+            return;
+        }
         match event {
-            NoteEvent::NoteOn { note, velocity, .. } => {
+            NoteEvent::NoteOn {
+                note,
+                velocity,
+                voice_id,
+                channel,
+                ..
+            } => {
                 let vel = Vel::new(velocity);
                 let note = Note(note);
-                let polycat = params.polycat;
-                let bend_note = self.key_tracker.note_on(note, vel, polycat);
-                if polycat {
-                    // In polycat mode, we simply add the new note.
-                    let start_pitch = bend_note.map(Pitch::from_note);
-                    let gen = Voice::new(&params, start_pitch, note, vel, sample_rate);
-                    self.notes.push(gen);
-                } else {
-                    // Monocat mode.
-
-                    // If there are no generators playing, start a new note
-                    if self.notes.len() == 0 {
-                        let gen = Voice::new(&params, None, note, vel, sample_rate);
-                        self.notes.push(gen);
-                    } else {
-                        // If there is a generator playing, retrigger it. If the generator is release state
-                        // then also do portamento.
-                        let last_note = self.notes.last_mut().unwrap();
-                        let bend_from_current = !last_note.is_released();
-                        let new_gen = last_note.start_crossfade(
-                            params,
+                for layer in Layer::ALL {
+                    if params.layers.plays_on(layer, note, vel) {
+                        let layer_params = layer.params(params, layer_b);
+                        self.note_on(
+                            layer,
+                            layer_params,
                             sample_rate,
-                            params.portamento_time,
-                            bend_from_current,
                             note,
                             vel,
+                            voice_id,
+                            channel,
                         );
-                        self.notes.push(new_gen);
                     }
-                };
+                }
             }
-            NoteEvent::NoteOff { note, .. } => {
-                let polycat = params.polycat;
-                let note = Note(note);
-                let top_of_stack = self.key_tracker.note_off(note);
-
-                if polycat {
-                    // On note off, send note off to all sound generators matching the note
-                    // This is done only to notes which are not yet released
-                    for gen in self
-                        .notes
-                        .iter_mut()
-                        .filter(|gen| !gen.is_released() && gen.note == note)
-                    {
-                        gen.note_off();
+            NoteEvent::NoteOff { note, velocity, .. } => {
+                // The note off goes to both layers, since the note may have been played on either.
+                // A layer which never played the note ignores it.
+                for layer in Layer::ALL {
+                    let layer_params = layer.params(params, layer_b);
+                    self.note_off(layer, layer_params, sample_rate, Note(note), velocity);
+                }
+            }
+            NoteEvent::MidiPitchBend { channel, value, .. } => {
+                let pitch_bend = Pitchbend::from_zero_one_range(value);
+                if params.mpe_zone.is_member_channel(channel) {
+                    // Pitch bends on MPE member channels only bend the notes on that channel.
+                    let bend = pitch_bend.get() * params.mpe_bend_range as f32;
+                    self.mpe_bends[channel as usize] = bend;
+                    for voice in self.notes.iter_mut().filter(|x| x.channel == channel) {
+                        voice.mpe_bend = bend;
                     }
                 } else {
-                    // Monocat mode.
-
-                    if self.key_tracker.held_keys.len() == 0 {
-                        // If there aren't any notes currently being held anymore, just send note off
-                        self.notes.iter_mut().for_each(|x| x.note_off());
-                    } else {
-                        // If there is a sound playing and the key tracker has a new top-of-stack note,
-                        // then ask the generator retrigger.
-                        match (self.notes.last_mut(), top_of_stack) {
-                            (None, None) => (),
-                            (None, Some(_)) => (),
-                            (Some(_), None) => (),
-                            (Some(gen), Some((new_note, new_vel))) => {
-                                let new_gen = gen.start_crossfade(
-                                    params,
-                                    sample_rate,
-                                    params.portamento_time,
-                                    true,
-                                    new_note,
-                                    new_vel,
-                                );
-                                self.notes.push(new_gen)
-                            }
-                        }
+                    // Events are only played at the start of a block.
+                    self.push_pitch_bend(pitch_bend, 0);
+                }
+            }
+            NoteEvent::MidiCC { cc, value, .. } if cc == 0 || cc == 32 => {
+                let value = (value * 127.0).round() as usize;
+                self.bank = if cc == 0 {
+                    (value << 7) | (self.bank & 0x7F)
+                } else {
+                    (self.bank & !0x7F) | value
+                };
+            }
+            NoteEvent::MidiCC { cc, value, .. } => {
+                let (cc, value) = if params.hires_cc {
+                    self.hires_ccs.process(cc, value)
+                } else {
+                    (cc, value)
+                };
+                // The editor stores the mapping, since that allocates and may block.
+                self.midi_learn.receive(cc);
+                let param_table = &self.param_table;
+                midi_learn::try_read_mappings(&self.params.midi_mappings, |mappings| {
+                    for id in mappings.params_for(cc) {
+                        let value = mappings.curve_for(id).apply(value);
+                        param_table.set_normalized(id, value, sample_rate);
                     }
+                });
+            }
+            NoteEvent::MidiProgramChange { program, .. } => {
+                let preset = BANKS
+                    .get(self.bank)
+                    .and_then(|bank| bank.get(program as usize));
+                if let Some(preset) = preset {
+                    self.param_table.apply_preset(preset.values, sample_rate);
+                    // The factory presets come first in the preset browser, in bank order.
+                    let index = BANKS[..self.bank]
+                        .iter()
+                        .map(|bank| bank.len())
+                        .sum::<usize>()
+                        + program as usize;
+                    self.presets.set_current(index);
+                    self.speaker_protection
+                        .preset_loaded(&self.params, Settings::get().speaker_protection());
                 }
             }
-            NoteEvent::MidiPitchBend { value, .. } => {
-                let pitch_bend = Pitchbend::from_zero_one_range(value);
-                self.pitch_bend_smoother
-                    .set_target(sample_rate.get(), pitch_bend);
+            NoteEvent::MidiChannelPressure {
+                channel, pressure, ..
+            } => {
+                if let Some(channel_pressure) = self.channel_pressures.get_mut(channel as usize) {
+                    *channel_pressure = pressure;
+                }
+                for voice in self.notes.iter_mut().filter(|x| x.channel == channel) {
+                    voice.expression.pressure = pressure;
+                }
+            }
+            NoteEvent::PolyPressure {
+                voice_id,
+                channel,
+                note,
+                pressure,
+                ..
+            } => {
+                for voice in self.matching_voices(voice_id, channel, note) {
+                    voice.expression.pressure = pressure;
+                }
+            }
+            NoteEvent::PolyVolume {
+                voice_id,
+                channel,
+                note,
+                gain,
+                ..
+            } => {
+                for voice in self.matching_voices(voice_id, channel, note) {
+                    voice.expression.gain = gain;
+                }
+            }
+            NoteEvent::PolyPan {
+                voice_id,
+                channel,
+                note,
+                pan,
+                ..
+            } => {
+                for voice in self.matching_voices(voice_id, channel, note) {
+                    voice.expression.pan = pan;
+                }
+            }
+            NoteEvent::PolyTuning {
+                voice_id,
+                channel,
+                note,
+                tuning,
+                ..
+            } => {
+                for voice in self.matching_voices(voice_id, channel, note) {
+                    voice.expression.tuning = tuning;
+                }
             }
             _ => (),
         }
     }
+
+    /// Start a note on one of the layers. Each layer keeps track of its own held keys, so that
+    /// monocat mode works separately on each layer.
+    #[allow(clippy::too_many_arguments)]
+    fn note_on(
+        &mut self,
+        layer: Layer,
+        params: &MeowParameters,
+        sample_rate: SampleRate,
+        note: Note,
+        vel: Vel,
+        voice_id: Option<i32>,
+        channel: u8,
+    ) {
+        let polycat = params.polycat;
+        let key = HeldKey {
+            note,
+            vel,
+            voice_id,
+            channel,
+        };
+        let bend_note = self.key_trackers[layer.index()].note_on(key, polycat);
+        if polycat {
+            // In polycat mode, we simply add the new note, stealing voices if needed.
+            self.steal_voices(layer, params, sample_rate);
+            let start_pitch = bend_note.map(Pitch::from_note);
+            let mut gen = Voice::new(&params, start_pitch, note, vel, sample_rate);
+            self.init_voice(&mut gen, layer, params, key);
+            self.push_voice(gen);
+        } else {
+            // Monocat mode.
+            match self.last_voice(layer) {
+                // If there is a generator playing, retrigger it. If the generator is release state
+                // then also do portamento.
+                // In slide mode, a held note slides to the new note without restarting.
+                Some(last_note)
+                    if params.legato_mode == LegatoMode::Slide && !last_note.is_released() =>
+                {
+                    self.slide_voice(layer, params, sample_rate, key);
+                }
+                Some(last_note) => {
+                    let bend_from_current = !last_note.is_released();
+                    let mut new_gen = last_note.start_crossfade(
+                        params,
+                        sample_rate,
+                        params.portamento_time,
+                        bend_from_current,
+                        note,
+                        vel,
+                    );
+                    self.init_voice(&mut new_gen, layer, params, key);
+                    self.push_voice(new_gen);
+                }
+                // If there are no generators playing, start a new note
+                None => {
+                    let mut gen = Voice::new(&params, None, note, vel, sample_rate);
+                    self.init_voice(&mut gen, layer, params, key);
+                    self.push_voice(gen);
+                }
+            }
+        }
+    }
+
+    /// Release a note on one of the layers.
+    fn note_off(
+        &mut self,
+        layer: Layer,
+        params: &MeowParameters,
+        sample_rate: SampleRate,
+        note: Note,
+        velocity: f32,
+    ) {
+        let key_tracker = &mut self.key_trackers[layer.index()];
+        let top_of_stack = key_tracker.note_off(note);
+        let no_keys_held = key_tracker.held_keys.is_empty();
+        let layer_voices = self.notes.iter_mut().filter(|gen| gen.layer == layer);
+
+        if params.polycat {
+            // On note off, send note off to all sound generators matching the note
+            // This is done only to notes which are not yet released
+            for gen in layer_voices.filter(|gen| !gen.is_released() && gen.note == note) {
+                gen.note_off_with_velocity(params, velocity);
+            }
+        } else {
+            // Monocat mode.
+
+            if no_keys_held {
+                // If there aren't any notes currently being held anymore, just send note off
+                layer_voices.for_each(|x| x.note_off_with_velocity(params, velocity));
+            } else {
+                // If there is a sound playing and the key tracker has a new top-of-stack note,
+                // then ask the generator retrigger.
+                match (layer_voices.last(), top_of_stack) {
+                    (None, None) => (),
+                    (None, Some(_)) => (),
+                    (Some(_), None) => (),
+                    (Some(gen), Some(key))
+                        if params.legato_mode == LegatoMode::Slide && !gen.is_released() =>
+                    {
+                        self.slide_voice(layer, params, sample_rate, key);
+                    }
+                    (Some(gen), Some(key)) => {
+                        let mut new_gen = gen.start_crossfade(
+                            params,
+                            sample_rate,
+                            params.portamento_time,
+                            true,
+                            key.note,
+                            key.vel,
+                        );
+                        // The new voice belongs to the key it returns to, and not to the key which
+                        // was just released.
+                        self.init_voice(&mut new_gen, layer, params, key);
+                        self.push_voice(new_gen)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Set up a new voice for the key which started it: the layer it plays on, the host's voice ID
+    /// and channel, the per-note expression it starts with, and its detune. Every new voice goes
+    /// through this, whether it comes from a note on or from monocat mode returning to a held key.
+    fn init_voice(
+        &mut self,
+        voice: &mut Voice,
+        layer: Layer,
+        params: &MeowParameters,
+        key: HeldKey,
+    ) {
+        voice.layer = layer;
+        voice.voice_id = key.voice_id;
+        voice.channel = key.channel;
+        voice.mpe_bend = self.mpe_bend(params, key.channel);
+        // New notes start with the channel's pressure, since it may have been sent beforehand.
+        voice.expression.pressure = self.channel_pressures[key.channel as usize];
+        voice.detune = params
+            .stray_cat
+            .detune(self.engine.noise_generator().next(), key.note.0);
+    }
+
+    /// Slide the layer's newest voice to the key, for legato in Slide mode. The voice now belongs
+    /// to the new key, so it takes on the key's voice ID, channel, and expression, and the host is
+    /// told that the voice it knew for the old key has ended.
+    fn slide_voice(
+        &mut self,
+        layer: Layer,
+        params: &MeowParameters,
+        sample_rate: SampleRate,
+        key: HeldKey,
+    ) {
+        let mpe_bend = self.mpe_bend(params, key.channel);
+        let pressure = self.channel_pressures[key.channel as usize];
+        let Some(voice) = self
+            .notes
+            .iter_mut()
+            .rev()
+            .find(|voice| voice.layer == layer)
+        else {
+            return;
+        };
+        // The MIDI output's note off for the old key is sent with the new key's note on.
+        self.ended_voices.push(EndedVoice {
+            midi_note_off: None,
+            ..EndedVoice::of(voice)
+        });
+        voice.slide_to(params, sample_rate, key.note);
+        voice.voice_id = key.voice_id;
+        voice.channel = key.channel;
+        voice.mpe_bend = mpe_bend;
+        voice.expression.pressure = pressure;
+    }
+
+    /// The most recently started voice on the layer, if there is one.
+    fn last_voice(&mut self, layer: Layer) -> Option<&mut Voice> {
+        self.notes
+            .iter_mut()
+            .rev()
+            .find(|voice| voice.layer == layer)
+    }
+
+    /// Set the parameters driven by each macro whose value has changed since its targets were last
+    /// set. Targets are only set when the macro moves, so that the targets can still be adjusted
+    /// by hand afterwards.
+    fn apply_macros(&mut self, params: &MeowParameters, sample_rate: SampleRate) {
+        if params.macros == self.macro_values {
+            return;
+        }
+        // The GUI may be editing the assignments. If so, try again on the next block.
+        let assignments = match self.params.macro_assignments.try_read() {
+            Ok(assignments) => assignments,
+            Err(_) => return,
+        };
+        for (i, macro_) in assignments.macros.iter().enumerate() {
+            let value = params.macros[i];
+            if value == self.macro_values[i] {
+                continue;
+            }
+            for target in &macro_.targets {
+                let normalized = target.normalized_value(value);
+                self.param_table
+                    .set_normalized(&target.param_id, normalized, sample_rate);
+            }
+            self.macro_values[i] = value;
+        }
+    }
+
+    /// Ask the GUI thread to tell the host about the parameters which were set during the buffer.
+    fn request_host_update(&self, context: &mut impl ProcessContext<Self>) {
+        if self.host_link.request_update() {
+            context.execute_gui(Task::UpdateHost);
+        }
+    }
+
+    /// Switch to the articulation chosen by the last keyswitch, if the event starts a note. Waiting
+    /// for the next note means that the notes which are already playing don't change partway
+    /// through. Returns true if the parameters were changed.
+    fn apply_pending_articulation(
+        &mut self,
+        params: &MeowParameters,
+        event: &NoteEvent<()>,
+        sample_rate: SampleRate,
+    ) -> bool {
+        let NoteEvent::NoteOn { note, .. } = event else {
+            return false;
+        };
+        if params.keyswitches.articulation(*note).is_some() {
+            return false;
+        }
+        let Some(articulation) = self.pending_articulation.take() else {
+            return false;
+        };
+        self.param_table
+            .apply_partial(ARTICULATIONS[articulation].values, sample_rate);
+        true
+    }
+
+    /// Load layer B's snapshot if the editor has stored a new one since it was last loaded, or
+    /// unconditionally if `force` is true.
+    fn load_layer_b(&mut self, sample_rate: SampleRate, force: bool) {
+        // If the editor is storing a snapshot right now, it'll be picked up on the next buffer.
+        let Ok(layer_b) = self.params.layer_b.try_read() else {
+            return;
+        };
+        if !force && layer_b.generation() == self.layer_b_generation {
+            return;
+        }
+        self.layer_b_generation = layer_b.generation();
+        match &layer_b.snapshot {
+            Some(snapshot) => self.layer_b_table.apply_snapshot(snapshot, sample_rate),
+            None => self
+                .layer_b_table
+                .apply_preset(&[] as &[(&str, f32)], sample_rate),
+        }
+    }
+
+    /// True if layer B needs to be rendered, either because new notes play on it or because
+    /// voices on it are still ringing out.
+    fn layer_b_in_use(&self, params: &MeowParameters) -> bool {
+        params.layers.mode != LayerMode::Off
+            || self.notes.iter().any(|voice| voice.layer == Layer::B)
+    }
+
+    /// Step through the preset browser's presets when the next or previous preset button is turned
+    /// on. Presets which the editor loaded are also checked for speaker protection here.
+    fn apply_preset_buttons(&mut self, params: &MeowParameters, sample_rate: SampleRate) {
+        if self.presets.take_editor_load() {
+            self.speaker_protection
+                .preset_loaded(&self.params, Settings::get().speaker_protection());
+        }
+
+        let buttons = (params.next_preset, params.previous_preset);
+        let step: isize = match (buttons, self.preset_buttons) {
+            ((true, _), (false, _)) => 1,
+            ((_, true), (_, false)) => -1,
+            _ => 0,
+        };
+        if step == 0 {
+            self.preset_buttons = buttons;
+            return;
+        }
+        self.preset_buttons = buttons;
+        let Some(presets) = &self.preset_list else {
+            return;
+        };
+        if presets.is_empty() {
+            return;
+        }
+        let index =
+            (self.presets.current() as isize + step).rem_euclid(presets.len() as isize) as usize;
+        let preset = &presets[index];
+        self.param_table.apply_preset(&preset.values, sample_rate);
+        self.presets.set_current(index);
+        self.speaker_protection
+            .preset_loaded(&self.params, Settings::get().speaker_protection());
+    }
+
+    /// Morph every parameter between the two snapshots, if the morph knob has moved since the
+    /// parameters were last morphed. Like the macros, this is only done when the knob moves.
+    fn apply_morph(&mut self, params: &MeowParameters, sample_rate: SampleRate) {
+        if params.morph == self.morph_value {
+            return;
+        }
+        let snapshots = match self.params.morph_snapshots.try_read() {
+            Ok(snapshots) => snapshots,
+            Err(_) => return,
+        };
+        for (id, normalized) in snapshots.morph(params.morph) {
+            self.param_table.set_normalized(id, normalized, sample_rate);
+        }
+        self.morph_value = params.morph;
+    }
+
+    /// Returns the voices which a per-note event applies to. If the host provided a voice ID, then
+    /// only the voice with that ID is matched. Otherwise, all voices with the same channel and note
+    /// are matched.
+    fn matching_voices(
+        &mut self,
+        voice_id: Option<i32>,
+        channel: u8,
+        note: u8,
+    ) -> impl Iterator<Item = &mut Voice> {
+        self.notes.iter_mut().filter(move |voice| match voice_id {
+            Some(voice_id) => voice.voice_id == Some(voice_id),
+            None => voice.channel == channel && voice.note == Note(note),
+        })
+    }
+
+    /// Add a pitch bend to the current block's pitch bend envelope, `time` samples into the block.
+    /// Of several pitch bends on the same sample, only the last one counts.
+    fn push_pitch_bend(&mut self, bend: Pitchbend, time: i32) {
+        self.pitch_bend_target = bend;
+        match self.pitch_bend_points.last_mut() {
+            Some(last) if last.1 == time => *last = (bend, time),
+            _ => self.pitch_bend_points.push((bend, time)),
+        }
+    }
+
+    /// Returns the pitch bend, in semitones, that a new note on the given channel should start with.
+    /// MPE controllers may send a pitch bend before the note on, so this is remembered per channel.
+    fn mpe_bend(&self, params: &MeowParameters, channel: u8) -> f32 {
+        if params.mpe_zone.is_member_channel(channel) {
+            self.mpe_bends[channel as usize]
+        } else {
+            0.0
+        }
+    }
+
+    /// Send a MIDI note on for every voice which has started, and a note off for every voice which
+    /// has been released, since the last time this was called.
+    fn send_midi_out(&mut self, timing: u32, context: &mut impl ProcessContext<Self>) {
+        for voice in &mut self.notes {
+            if let MidiOutState::Slid(old_note, old_channel) = voice.midi_out {
+                context.send_event(NoteEvent::NoteOff {
+                    timing,
+                    voice_id: None,
+                    channel: old_channel,
+                    note: old_note.0,
+                    velocity: 0.0,
+                });
+                voice.midi_out = MidiOutState::Pending;
+            }
+
+            if voice.midi_out == MidiOutState::Pending {
+                context.send_event(NoteEvent::NoteOn {
+                    timing,
+                    voice_id: None,
+                    channel: voice.channel,
+                    note: voice.note.0,
+                    velocity: voice.vel().raw,
+                });
+                voice.midi_out = MidiOutState::NoteOn;
+            }
+
+            if voice.midi_out == MidiOutState::NoteOn && voice.is_released() {
+                context.send_event(NoteEvent::NoteOff {
+                    timing,
+                    voice_id: None,
+                    channel: voice.channel,
+                    note: voice.note.0,
+                    velocity: 0.0,
+                });
+                voice.midi_out = MidiOutState::NoteOff;
+            }
+        }
+    }
+
+    /// Add a voice, first making room for it if there are already MAX_NOTES voices. The oldest voice
+    /// which is fading out is cut off to make room, since it is on its way out anyway, or failing
+    /// that, the oldest released voice. Voice stealing keeps the number of playing voices on each
+    /// layer under MAX_VOICES, so one of these should always exist, but if every voice is somehow
+    /// still held, the new voice is dropped rather than cutting off a note which is playing.
+    fn push_voice(&mut self, voice: Voice) {
+        if self.notes.len() >= MAX_NOTES {
+            let cut = self
+                .notes
+                .iter()
+                .position(|voice| voice.is_fading_out())
+                .or_else(|| self.notes.iter().position(|voice| voice.is_released()));
+            match cut {
+                Some(index) => {
+                    let cut = self.notes.remove(index);
+                    self.ended_voices.push(EndedVoice::of(&cut));
+                }
+                None => {
+                    self.ended_voices.push(EndedVoice::of(&voice));
+                    return;
+                }
+            }
+        }
+        self.notes.push(voice);
+    }
+
+    /// Steal voices until there is room for one more voice under the max voices limit. Released
+    /// voices are stolen first, and then held voices, oldest first in both cases. Voices which are
+    /// already fading out do not count towards the limit.
+    fn steal_voices(&mut self, layer: Layer, params: &MeowParameters, sample_rate: SampleRate) {
+        let stealable = |voice: &Voice| voice.layer == layer && !voice.is_fading_out();
+        while self.notes.iter().filter(|voice| stealable(voice)).count() >= params.max_voices {
+            let oldest = self
+                .notes
+                .iter_mut()
+                .filter(|voice| stealable(voice))
+                .min_by_key(|voice| !voice.is_released());
+            match oldest {
+                Some(voice) => voice.steal(params, sample_rate),
+                None => break,
+            }
+        }
+    }
+}
+
+/// A voice which has ended, or a key which a voice stopped playing, which the host still needs to
+/// be told about.
+struct EndedVoice {
+    voice_id: Option<i32>,
+    channel: u8,
+    note: Note,
+    /// The note and channel of the MIDI note off which is still owed for the voice, if any.
+    midi_note_off: Option<(Note, u8)>,
+}
+
+impl EndedVoice {
+    /// A voice which is ending without finishing.
+    fn of(voice: &Voice) -> EndedVoice {
+        let midi_note_off = match voice.midi_out {
+            MidiOutState::NoteOn => Some((voice.note, voice.channel)),
+            MidiOutState::Slid(old_note, old_channel) => Some((old_note, old_channel)),
+            _ => None,
+        };
+        EndedVoice {
+            voice_id: voice.voice_id,
+            channel: voice.channel,
+            note: voice.note,
+            midi_note_off,
+        }
+    }
+
+    /// Tell the host that the voice has ended, sending the MIDI note off for it if one is owed.
+    fn send(&self, timing: u32, context: &mut impl ProcessContext<Nyasynth>) {
+        if let Some((note, channel)) = self.midi_note_off {
+            context.send_event(NoteEvent::NoteOff {
+                timing,
+                voice_id: None,
+                channel,
+                note: note.0,
+                velocity: 0.0,
+            });
+        }
+        context.send_event(NoteEvent::VoiceTerminated {
+            timing,
+            voice_id: self.voice_id,
+            channel: self.channel,
+            note: self.note.0,
+        });
+    }
 }
 
 impl Nyasynth {
     pub fn debug_params(&mut self) -> &mut Arc<Parameters> {
         &mut self.params
     }
+
+    /// The output level meters, which are updated after every buffer.
+    pub fn meters(&self) -> Arc<OutputMeters> {
+        self.meters.clone()
+    }
 }
 
 // Export symbols for main
+nih_export_clap!(Nyasynth);
 nih_export_vst3!(Nyasynth);
@@ -0,0 +1,130 @@
+use nih_plug::prelude::Enum;
+use serde::{Deserialize, Serialize};
+
+use crate::ease::{ease_in_expo, lerp, smoothstep};
+
+pub const NUM_MACROS: usize = 4;
+
+/// The shape of the mapping from a macro knob to one of its target parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum, Serialize, Deserialize)]
+pub enum MacroCurve {
+    #[name = "Linear"]
+    Linear,
+    #[name = "Exponential"]
+    Exponential,
+    #[name = "Smooth Step"]
+    SmoothStep,
+}
+
+impl MacroCurve {
+    fn shape(&self, t: f32) -> f32 {
+        match self {
+            MacroCurve::Linear => t,
+            MacroCurve::Exponential => ease_in_expo(t),
+            MacroCurve::SmoothStep => smoothstep(t),
+        }
+    }
+}
+
+/// A single parameter driven by a macro. The `start` and `end` are normalized values of the target
+/// parameter, which are used when the macro is at 0% and 100% respectively.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MacroTarget {
+    pub param_id: String,
+    pub start: f32,
+    pub end: f32,
+    pub curve: MacroCurve,
+}
+
+impl MacroTarget {
+    pub fn new(param_id: &str, start: f32, end: f32, curve: MacroCurve) -> MacroTarget {
+        MacroTarget {
+            param_id: param_id.to_string(),
+            start,
+            end,
+            curve,
+        }
+    }
+
+    /// Returns the normalized value that the target parameter should be set to, given the
+    /// macro's value.
+    pub fn normalized_value(&self, macro_value: f32) -> f32 {
+        lerp(self.start, self.end, self.curve.shape(macro_value)).clamp(0.0, 1.0)
+    }
+}
+
+/// A macro knob's name and the parameters that it drives.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Macro {
+    pub name: String,
+    pub targets: Vec<MacroTarget>,
+}
+
+/// The parameters driven by each of the macro knobs. This is stored in the plugin state and edited
+/// from the GUI.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MacroAssignments {
+    pub macros: [Macro; NUM_MACROS],
+}
+
+impl Default for MacroAssignments {
+    fn default() -> Self {
+        let empty = |name: &str| Macro {
+            name: name.to_string(),
+            targets: vec![],
+        };
+        MacroAssignments {
+            macros: [
+                Macro {
+                    name: "Meow Intensity".to_string(),
+                    targets: vec![
+                        MacroTarget::new("filter_cutoff_freq", 0.35, 0.75, MacroCurve::Linear),
+                        MacroTarget::new("noise_mix", 0.0, 0.3, MacroCurve::Exponential),
+                        MacroTarget::new("vibrato_amount", 0.0, 0.5, MacroCurve::SmoothStep),
+                    ],
+                },
+                empty("Macro 2"),
+                empty("Macro 3"),
+                empty("Macro 4"),
+            ],
+        }
+    }
+}
+
+/// The normalized value of every parameter at some point in time, as (parameter ID, normalized
+/// value) pairs. The macro knobs and the morph knob are not included.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub values: Vec<(String, f32)>,
+}
+
+/// The two snapshots that the morph knob interpolates between. The morph knob does nothing until
+/// both snapshots have been stored.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MorphSnapshots {
+    pub a: Option<Snapshot>,
+    pub b: Option<Snapshot>,
+}
+
+impl MorphSnapshots {
+    /// Returns each parameter which is in both snapshots, along with the normalized value it should
+    /// be set to for the given morph amount. The interpolation is done on normalized values, so
+    /// that each parameter is morphed in the same space that its knob moves in. The values are
+    /// matched up by parameter ID, since a snapshot stored by an older version may be missing
+    /// parameters. Parameters which are only in one of the snapshots are left alone.
+    pub fn morph(&self, amount: f32) -> impl Iterator<Item = (&str, f32)> {
+        let (a, b) = match (&self.a, &self.b) {
+            (Some(a), Some(b)) => (a.values.as_slice(), b.values.as_slice()),
+            _ => (&[][..], &[][..]),
+        };
+        a.iter().enumerate().filter_map(move |(i, (id, a))| {
+            // Snapshots almost always have the same parameters in the same order, so the same
+            // position is checked before searching the whole snapshot.
+            let b = match b.get(i) {
+                Some((b_id, b)) if b_id == id => b,
+                _ => &b.iter().find(|(b_id, _)| b_id == id)?.1,
+            };
+            Some((id.as_str(), lerp(*a, *b, amount)))
+        })
+    }
+}
@@ -0,0 +1,141 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use atomic_float::AtomicF32;
+
+use crate::common::SampleRate;
+
+// How fast the peak level falls back after a peak, in dB per second.
+const PEAK_FALLOFF_DB_PER_SECOND: f32 = 20.0;
+// The time constant of the RMS average, in seconds. 300 ms is the usual VU-style integration time.
+const RMS_WINDOW: f32 = 0.3;
+// Samples at or past full scale count as clipping.
+const CLIP_LEVEL: f32 = 1.0;
+
+/// Output level meters for the left and right channels. The audio thread updates the meters once
+/// per buffer, and anything else (such as the editor) can take a [MeterSnapshot] at any time
+/// without locking. The channels are stored separately, so a snapshot may occasionally mix two
+/// buffers' levels, which is fine for a meter.
+pub struct OutputMeters {
+    channels: [ChannelMeter; 2],
+}
+
+struct ChannelMeter {
+    // The peak and RMS levels, as linear amplitudes.
+    peak: AtomicF32,
+    rms: AtomicF32,
+    // Set when the channel clips, and only cleared by `clear_clipped`.
+    clipped: AtomicBool,
+}
+
+/// The levels of one channel at the time the snapshot was taken.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChannelLevels {
+    /// The peak level, as a linear amplitude. This falls back slowly after each peak.
+    pub peak: f32,
+    /// The RMS level over roughly the last 300 ms, as a linear amplitude.
+    pub rms: f32,
+    /// True if the channel has clipped since the clip indicator was last cleared.
+    pub clipped: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeterSnapshot {
+    pub left: ChannelLevels,
+    pub right: ChannelLevels,
+}
+
+impl OutputMeters {
+    pub fn new() -> OutputMeters {
+        OutputMeters {
+            channels: [ChannelMeter::new(), ChannelMeter::new()],
+        }
+    }
+
+    /// Update the meters with the next buffer of output. This must only be called from the audio
+    /// thread.
+    pub fn update(&self, left: &[f32], right: &[f32], sample_rate: SampleRate) {
+        self.channels[0].update(left, sample_rate);
+        self.channels[1].update(right, sample_rate);
+    }
+
+    pub fn snapshot(&self) -> MeterSnapshot {
+        MeterSnapshot {
+            left: self.channels[0].load(),
+            right: self.channels[1].load(),
+        }
+    }
+
+    /// Reset the clip indicators, such as when the user clicks on them.
+    pub fn clear_clipped(&self) {
+        for channel in &self.channels {
+            channel.clipped.store(false, Ordering::Relaxed);
+        }
+    }
+}
+
+impl ChannelMeter {
+    fn new() -> ChannelMeter {
+        ChannelMeter {
+            peak: AtomicF32::new(0.0),
+            rms: AtomicF32::new(0.0),
+            clipped: AtomicBool::new(false),
+        }
+    }
+
+    fn update(&self, samples: &[f32], sample_rate: SampleRate) {
+        if samples.is_empty() {
+            return;
+        }
+        let buffer_len = samples.len() as f32;
+        let block_peak = samples.iter().fold(0.0f32, |peak, x| peak.max(x.abs()));
+        let block_mean_square = samples.iter().map(|x| x * x).sum::<f32>() / buffer_len;
+
+        // Only the audio thread writes to the meters, so the previous values can be read back
+        // without worrying about another writer.
+        let seconds = buffer_len / sample_rate.get();
+        let falloff = db_to_amp(-PEAK_FALLOFF_DB_PER_SECOND * seconds);
+        let peak = block_peak.max(self.peak.load(Ordering::Relaxed) * falloff);
+
+        let previous = self.rms.load(Ordering::Relaxed);
+        let weight = (-seconds / RMS_WINDOW).exp();
+        let mean_square = previous * previous * weight + block_mean_square * (1.0 - weight);
+
+        // A blown up buffer shouldn't stick in the meter forever.
+        let sanitize = |x: f32| if x.is_finite() { x } else { 0.0 };
+        self.peak.store(sanitize(peak), Ordering::Relaxed);
+        self.rms
+            .store(sanitize(mean_square.sqrt()), Ordering::Relaxed);
+        if block_peak >= CLIP_LEVEL {
+            self.clipped.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn load(&self) -> ChannelLevels {
+        ChannelLevels {
+            peak: self.peak.load(Ordering::Relaxed),
+            rms: self.rms.load(Ordering::Relaxed),
+            clipped: self.clipped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl ChannelLevels {
+    /// The peak level in dBFS.
+    pub fn peak_dbfs(&self) -> f32 {
+        amp_to_db(self.peak)
+    }
+
+    /// The RMS level in dBFS.
+    pub fn rms_dbfs(&self) -> f32 {
+        amp_to_db(self.rms)
+    }
+}
+
+// Meters use the usual 20 * log10 amplitude decibels, so that they agree with the host's meters.
+fn amp_to_db(amp: f32) -> f32 {
+    20.0 * amp.max(1.0e-6).log10()
+}
+
+fn db_to_amp(db: f32) -> f32 {
+    10.0f32.powf(db / 20.0)
+}
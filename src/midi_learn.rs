@@ -0,0 +1,235 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicU16, Ordering},
+    Mutex, RwLock,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::settings::Settings;
+
+/// The mod wheel's CC, which can be bound to a parameter without going through MIDI learn.
+pub const MOD_WHEEL_CC: u8 = 1;
+
+/// How a CC's value is shaped before it drives a parameter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CcCurve {
+    #[default]
+    Linear,
+    /// Changes slowly at the bottom of the CC's travel and quickly at the top.
+    SlowStart,
+    /// Changes quickly at the bottom of the CC's travel and slowly at the top.
+    FastStart,
+    /// The parameter goes down as the CC goes up.
+    Inverted,
+}
+
+impl CcCurve {
+    pub const ALL: [CcCurve; 4] = [
+        CcCurve::Linear,
+        CcCurve::SlowStart,
+        CcCurve::FastStart,
+        CcCurve::Inverted,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CcCurve::Linear => "Linear",
+            CcCurve::SlowStart => "Slow Start",
+            CcCurve::FastStart => "Fast Start",
+            CcCurve::Inverted => "Inverted",
+        }
+    }
+
+    /// Shape a CC value, from 0.0 to 1.0, into a normalized parameter value.
+    pub fn apply(self, value: f32) -> f32 {
+        match self {
+            CcCurve::Linear => value,
+            CcCurve::SlowStart => value * value,
+            CcCurve::FastStart => 1.0 - (1.0 - value) * (1.0 - value),
+            CcCurve::Inverted => 1.0 - value,
+        }
+    }
+}
+
+/// The MIDI CCs which have been bound to parameters with MIDI learn, as (CC, parameter ID) pairs.
+/// A CC may drive several parameters, but each parameter is driven by at most one CC.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MidiMappings {
+    pub ccs: Vec<(u8, String)>,
+    /// The curve of each bound parameter, as (parameter ID, curve) pairs. Parameters which aren't
+    /// listed use a linear curve.
+    #[serde(default)]
+    pub curves: Vec<(String, CcCurve)>,
+}
+
+impl MidiMappings {
+    /// Bind a CC to a parameter, replacing the parameter's previous CC if it had one. The
+    /// parameter keeps its curve.
+    pub fn learn(&mut self, cc: u8, id: String) {
+        self.ccs.retain(|(_, param_id)| *param_id != id);
+        self.ccs.push((cc, id));
+    }
+
+    /// Unbind the parameter from its CC, if it had one.
+    pub fn forget(&mut self, id: &str) {
+        self.ccs.retain(|(_, param_id)| param_id != id);
+        self.curves.retain(|(param_id, _)| param_id != id);
+    }
+
+    pub fn curve_for(&self, id: &str) -> CcCurve {
+        self.curves
+            .iter()
+            .find(|(param_id, _)| param_id == id)
+            .map_or(CcCurve::Linear, |(_, curve)| *curve)
+    }
+
+    pub fn set_curve(&mut self, id: &str, curve: CcCurve) {
+        self.curves.retain(|(param_id, _)| param_id != id);
+        if curve != CcCurve::Linear {
+            self.curves.push((id.to_string(), curve));
+        }
+    }
+
+    /// Returns the CC bound to the parameter, if any.
+    pub fn cc_for(&self, id: &str) -> Option<u8> {
+        self.ccs
+            .iter()
+            .find(|(_, param_id)| param_id == id)
+            .map(|(cc, _)| *cc)
+    }
+
+    /// Returns the IDs of the parameters bound to the CC.
+    pub fn params_for(&self, cc: u8) -> impl Iterator<Item = &str> {
+        self.ccs
+            .iter()
+            .filter(move |(mapped_cc, _)| *mapped_cc == cc)
+            .map(|(_, id)| id.as_str())
+    }
+}
+
+/// The parameter which is waiting to be bound to the next incoming CC. The editor arms this, the
+/// audio thread records the CC without locking or allocating, and the editor then stores the new
+/// mapping.
+pub struct MidiLearn {
+    learning: Mutex<Option<String>>,
+    armed: AtomicBool,
+    // The CC received while armed, or `NO_CC`.
+    learned: AtomicU16,
+}
+
+const NO_CC: u16 = u16::MAX;
+
+impl MidiLearn {
+    pub fn new() -> MidiLearn {
+        MidiLearn {
+            learning: Mutex::new(None),
+            armed: AtomicBool::new(false),
+            learned: AtomicU16::new(NO_CC),
+        }
+    }
+
+    /// Bind the next incoming CC to the parameter with the given ID.
+    pub fn start(&self, id: &str) {
+        let mut learning = self.learning.lock().unwrap();
+        *learning = Some(id.to_string());
+        self.learned.store(NO_CC, Ordering::Release);
+        self.armed.store(true, Ordering::Release);
+    }
+
+    pub fn cancel(&self) {
+        let mut learning = self.learning.lock().unwrap();
+        self.armed.store(false, Ordering::Release);
+        self.learned.store(NO_CC, Ordering::Release);
+        *learning = None;
+    }
+
+    pub fn is_learning(&self, id: &str) -> bool {
+        self.learning.lock().unwrap().as_deref() == Some(id)
+    }
+
+    /// Record an incoming CC, if a parameter is waiting for one. Only the first CC is kept. This
+    /// never blocks or allocates, so it is safe to call from the audio thread.
+    pub fn receive(&self, cc: u8) {
+        if self.armed.load(Ordering::Acquire) {
+            let _ = self.learned.compare_exchange(
+                NO_CC,
+                cc as u16,
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+            );
+        }
+    }
+
+    /// If a CC has been received for the waiting parameter, pass both to `store`, and only then
+    /// stop waiting. This is called by the editor.
+    pub fn finish(&self, store: impl FnOnce(u8, String)) {
+        let mut learning = self.learning.lock().unwrap();
+        let cc = self.learned.load(Ordering::Acquire);
+        if cc == NO_CC {
+            return;
+        }
+        if let Some(id) = learning.clone() {
+            store(cc as u8, id);
+        }
+        self.armed.store(false, Ordering::Release);
+        self.learned.store(NO_CC, Ordering::Release);
+        *learning = None;
+    }
+}
+
+/// Where an instance's MIDI mappings are kept. By default, every instance shares the global
+/// mappings in the settings. The user can instead save the mappings with the project, in which
+/// case the instance keeps its own copy in its state (`Some` here), and the global mappings are
+/// left alone.
+pub type ProjectMappings = RwLock<Option<MidiMappings>>;
+
+/// Run `f` on the mappings that the instance uses, for the editor. Changes to the global mappings
+/// are saved; changes to the project's mappings are saved by the host with the project.
+pub fn edit_mappings<R>(project: &ProjectMappings, f: impl FnOnce(&mut MidiMappings) -> R) -> R {
+    let mut project = project.write().unwrap();
+    match project.as_mut() {
+        Some(mappings) => f(mappings),
+        None => {
+            let settings = Settings::get();
+            let result = f(&mut settings.midi_mappings.write().unwrap());
+            settings.save();
+            result
+        }
+    }
+}
+
+/// Read the mappings that the instance uses, for the editor.
+pub fn read_mappings<R>(project: &ProjectMappings, f: impl FnOnce(&MidiMappings) -> R) -> R {
+    match project.read().unwrap().as_ref() {
+        Some(mappings) => f(mappings),
+        None => f(&Settings::get().midi_mappings.read().unwrap()),
+    }
+}
+
+/// Like [read_mappings], but for the audio thread. This never blocks: if the editor holds a lock,
+/// `f` isn't called.
+pub fn try_read_mappings(project: &ProjectMappings, f: impl FnOnce(&MidiMappings)) {
+    let Ok(project) = project.try_read() else {
+        return;
+    };
+    match project.as_ref() {
+        Some(mappings) => f(mappings),
+        None => {
+            if let Ok(mappings) = Settings::get().midi_mappings.try_read() {
+                f(&mappings);
+            }
+        }
+    }
+}
+
+/// Choose whether the instance's mappings are saved with the project. Switching to the project
+/// starts from a copy of the global mappings. Switching back to the global mappings drops the
+/// project's copy.
+pub fn set_saved_with_project(project: &ProjectMappings, with_project: bool) {
+    let mut project = project.write().unwrap();
+    if with_project && project.is_none() {
+        *project = Some(Settings::get().midi_mappings.read().unwrap().clone());
+    } else if !with_project {
+        *project = None;
+    }
+}
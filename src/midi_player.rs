@@ -0,0 +1,369 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    RwLock, RwLockReadGuard,
+};
+
+use atomic_float::AtomicF32;
+use nih_plug::prelude::NoteEvent;
+
+use crate::common::SampleRate;
+
+// Loops end at the end of the bar containing the last event, assuming four beats to the bar, so
+// that a riff which ends on a rest still loops in time.
+const BEATS_PER_BAR: u32 = 4;
+// The tempo of a MIDI file with no tempo events, in microseconds per beat (120 BPM).
+const DEFAULT_TEMPO: u32 = 500_000;
+
+/// A MIDI file, loaded to be looped by the [MidiPlayer].
+pub struct MidiLoop {
+    /// The file's name, for the editor.
+    pub name: String,
+    /// The file's tempo, in beats per minute. Only the first tempo in the file is used.
+    pub tempo: f32,
+    /// The events and when they play, in seconds from the start of the loop, in order.
+    events: Vec<(f64, NoteEvent<()>)>,
+    /// The length of the loop, in seconds.
+    length: f64,
+}
+
+impl MidiLoop {
+    /// Read a standard MIDI file. The tracks are all merged together.
+    pub fn parse(name: String, bytes: &[u8]) -> Result<MidiLoop, String> {
+        let smf = midly::Smf::parse(bytes).map_err(|err| err.to_string())?;
+
+        let microseconds_per_beat = smf
+            .tracks
+            .iter()
+            .flatten()
+            .find_map(|event| match event.kind {
+                midly::TrackEventKind::Meta(midly::MetaMessage::Tempo(tempo)) => {
+                    Some(tempo.as_int())
+                }
+                _ => None,
+            })
+            .unwrap_or(DEFAULT_TEMPO);
+        let seconds_per_beat = microseconds_per_beat as f64 / 1_000_000.0;
+        let (ticks_per_second, ticks_per_bar) = match smf.header.timing {
+            midly::Timing::Metrical(ticks_per_beat) => {
+                let ticks_per_beat = ticks_per_beat.as_int() as u32;
+                (
+                    ticks_per_beat as f64 / seconds_per_beat,
+                    Some(ticks_per_beat * BEATS_PER_BAR),
+                )
+            }
+            // Timecode files aren't in beats, so they loop right after their last event.
+            midly::Timing::Timecode(frames_per_second, ticks_per_frame) => (
+                ticks_per_frame as f64 * frames_per_second.as_f32() as f64,
+                None,
+            ),
+        };
+
+        let mut events = vec![];
+        let mut last_tick = 0;
+        for track in &smf.tracks {
+            let mut tick = 0;
+            for track_event in track {
+                tick += track_event.delta.as_int();
+                last_tick = last_tick.max(tick);
+                if let midly::TrackEventKind::Midi { channel, message } = track_event.kind {
+                    if let Some(event) = to_note_event(channel.as_int(), message) {
+                        events.push((tick as f64 / ticks_per_second, event));
+                    }
+                }
+            }
+        }
+        if !events
+            .iter()
+            .any(|(_, event)| matches!(event, NoteEvent::NoteOn { .. }))
+        {
+            return Err("The file doesn't have any notes".to_string());
+        }
+        // The sort is stable, so events at the same time keep their order within each track.
+        events.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+
+        let last_tick = match ticks_per_bar {
+            Some(ticks_per_bar) => (last_tick / ticks_per_bar + 1) * ticks_per_bar,
+            None => last_tick.max(1),
+        };
+        Ok(MidiLoop {
+            name,
+            tempo: (60.0 / seconds_per_beat) as f32,
+            events,
+            length: last_tick as f64 / ticks_per_second,
+        })
+    }
+
+    /// The length of the loop, in seconds.
+    pub fn length(&self) -> f32 {
+        self.length as f32
+    }
+}
+
+/// Convert a MIDI message into an event. Program changes are left out, since switching presets
+/// would get in the way of auditioning them.
+fn to_note_event(channel: u8, message: midly::MidiMessage) -> Option<NoteEvent<()>> {
+    fn normalize_u7(u7: midly::num::u7) -> f32 {
+        u7.as_int() as f32 / 127.0
+    }
+
+    let event = match message {
+        midly::MidiMessage::NoteOn { key, vel } if vel.as_int() > 0 => NoteEvent::NoteOn {
+            timing: 0,
+            voice_id: None,
+            channel,
+            note: key.as_int(),
+            velocity: normalize_u7(vel),
+        },
+        // Note ons with a velocity of 0 are note offs.
+        midly::MidiMessage::NoteOn { key, vel } | midly::MidiMessage::NoteOff { key, vel } => {
+            NoteEvent::NoteOff {
+                timing: 0,
+                voice_id: None,
+                channel,
+                note: key.as_int(),
+                velocity: normalize_u7(vel),
+            }
+        }
+        midly::MidiMessage::Aftertouch { key, vel } => NoteEvent::PolyPressure {
+            timing: 0,
+            voice_id: None,
+            channel,
+            note: key.as_int(),
+            pressure: normalize_u7(vel),
+        },
+        midly::MidiMessage::Controller { controller, value } => NoteEvent::MidiCC {
+            timing: 0,
+            channel,
+            cc: controller.as_int(),
+            value: normalize_u7(value),
+        },
+        midly::MidiMessage::ChannelAftertouch { vel } => NoteEvent::MidiChannelPressure {
+            timing: 0,
+            channel,
+            pressure: normalize_u7(vel),
+        },
+        // midly's pitch bends go from -1.0 to 1.0, but nih-plug's go from 0.0 to 1.0.
+        midly::MidiMessage::PitchBend { bend } => NoteEvent::MidiPitchBend {
+            timing: 0,
+            channel,
+            value: (bend.as_f32() + 1.0) / 2.0,
+        },
+        midly::MidiMessage::ProgramChange { .. } => return None,
+    };
+    Some(event)
+}
+
+/// The standalone's MIDI player, which loops a MIDI file so that presets can be auditioned
+/// hands-free. The editor loads the loop and starts and stops it, and the audio thread plays it
+/// with a [Playhead].
+pub struct MidiPlayer {
+    midi_loop: RwLock<Option<MidiLoop>>,
+    playing: AtomicBool,
+    // Counts the loops loaded, so that the audio thread can tell when to start from the top.
+    generation: AtomicU64,
+    // How far through the loop the playhead is, in seconds, for the editor.
+    position: AtomicF32,
+}
+
+impl MidiPlayer {
+    pub fn new() -> MidiPlayer {
+        MidiPlayer {
+            midi_loop: RwLock::new(None),
+            playing: AtomicBool::new(false),
+            generation: AtomicU64::new(0),
+            position: AtomicF32::new(0.0),
+        }
+    }
+
+    /// Replace the loop, and start playing it.
+    pub fn load(&self, midi_loop: MidiLoop) {
+        *self.midi_loop.write().unwrap() = Some(midi_loop);
+        self.generation.fetch_add(1, Ordering::Release);
+        self.playing.store(true, Ordering::Relaxed);
+    }
+
+    /// Get the loop, if one is loaded. This must not be called from the audio thread, since it
+    /// blocks while a loop is being loaded.
+    pub fn midi_loop(&self) -> RwLockReadGuard<'_, Option<MidiLoop>> {
+        self.midi_loop.read().unwrap()
+    }
+
+    /// Get the loop without blocking, for the audio thread. Returns None while a loop is being
+    /// loaded.
+    pub fn try_midi_loop(&self) -> Option<RwLockReadGuard<'_, Option<MidiLoop>>> {
+        self.midi_loop.try_read().ok()
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing.load(Ordering::Relaxed)
+    }
+
+    /// Start or stop the loop. Stopping goes back to the start of the loop.
+    pub fn set_playing(&self, playing: bool) {
+        self.playing.store(playing, Ordering::Relaxed);
+    }
+
+    /// How far through the loop playback is, in seconds.
+    pub fn position(&self) -> f32 {
+        self.position.load(Ordering::Relaxed)
+    }
+}
+
+/// The audio thread's side of the [MidiPlayer]. Each buffer, the playhead hands out the loop's
+/// events which play during the buffer, with their timing in the buffer, just like the host's
+/// events. The notes which it has started are tracked, so that they can be released whenever the
+/// loop stops or starts over.
+pub struct Playhead {
+    // The generation of the loop which is playing.
+    generation: u64,
+    playing: bool,
+    // How far through the loop the current buffer starts, in seconds.
+    position: f64,
+    // The number of samples in the current buffer.
+    buffer_len: usize,
+    // The index of the next event to play.
+    next: usize,
+    // How much later than their time in the loop the upcoming events play. When the loop starts
+    // over partway through a buffer, this is bumped up by the loop's length.
+    offset: f64,
+    // The notes held on each channel, as a bit for each note.
+    held: [u128; 16],
+    // Set to the timing of the note offs for the held notes, when they need to be released.
+    release_at: Option<usize>,
+}
+
+impl Playhead {
+    pub fn new() -> Playhead {
+        Playhead {
+            generation: 0,
+            playing: false,
+            position: 0.0,
+            buffer_len: 0,
+            next: 0,
+            offset: 0.0,
+            held: [0; 16],
+            release_at: None,
+        }
+    }
+
+    /// Catch up with the editor at the start of a buffer. `midi_loop` is None if the loop couldn't
+    /// be read, which stops playback for the buffer.
+    pub fn start_buffer(
+        &mut self,
+        player: &MidiPlayer,
+        midi_loop: Option<&MidiLoop>,
+        buffer_len: usize,
+    ) {
+        self.buffer_len = buffer_len;
+        let generation = player.generation.load(Ordering::Acquire);
+        let playing = player.is_playing() && midi_loop.is_some();
+        if generation != self.generation || (self.playing && !playing) {
+            self.generation = generation;
+            self.rewind();
+        }
+        self.playing = playing;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Whether the playhead has anything to play in this buffer, including note offs.
+    pub fn is_active(&self) -> bool {
+        self.playing || self.release_at.is_some()
+    }
+
+    /// The next event, and its timing in the buffer, if it plays in this buffer.
+    fn peek(
+        &mut self,
+        midi_loop: Option<&MidiLoop>,
+        sample_rate: SampleRate,
+    ) -> Option<(usize, NoteEvent<()>)> {
+        loop {
+            if let Some(timing) = self.release_at {
+                if let Some((channel, note)) = self.first_held() {
+                    let note_off = NoteEvent::NoteOff {
+                        timing: timing as u32,
+                        voice_id: None,
+                        channel,
+                        note,
+                        velocity: 0.0,
+                    };
+                    return Some((timing, note_off));
+                }
+                self.release_at = None;
+            }
+            if !self.playing {
+                return None;
+            }
+            let midi_loop = midi_loop?;
+            if let Some((time, event)) = midi_loop.events.get(self.next) {
+                let timing = self.timing(*time, sample_rate);
+                return (timing < self.buffer_len).then_some((timing, *event));
+            }
+            // At the end of the loop, release the held notes and start over.
+            let end = self.timing(midi_loop.length, sample_rate);
+            if end >= self.buffer_len {
+                return None;
+            }
+            self.next = 0;
+            self.offset += midi_loop.length;
+            self.release_at = Some(end);
+        }
+    }
+
+    /// Take the next event, if it plays in this buffer.
+    pub fn next(
+        &mut self,
+        midi_loop: Option<&MidiLoop>,
+        sample_rate: SampleRate,
+    ) -> Option<(usize, NoteEvent<()>)> {
+        let (timing, event) = self.peek(midi_loop, sample_rate)?;
+        match event {
+            NoteEvent::NoteOn { channel, note, .. } => {
+                self.held[channel as usize] |= 1u128 << note;
+            }
+            NoteEvent::NoteOff { channel, note, .. } => {
+                self.held[channel as usize] &= !(1u128 << note);
+            }
+            _ => (),
+        }
+        // Releases don't come from the loop.
+        if self.release_at.is_none() {
+            self.next += 1;
+        }
+        Some((timing, event))
+    }
+
+    /// Move on to the next buffer.
+    pub fn end_buffer(&mut self, player: &MidiPlayer, sample_rate: SampleRate) {
+        if self.playing {
+            self.position += self.buffer_len as f64 / sample_rate.get() as f64 - self.offset;
+            self.offset = 0.0;
+        }
+        player
+            .position
+            .store(self.position as f32, Ordering::Relaxed);
+    }
+
+    /// Go back to the start of the loop, releasing the held notes.
+    fn rewind(&mut self) {
+        self.position = 0.0;
+        self.next = 0;
+        self.offset = 0.0;
+        self.release_at = Some(0);
+    }
+
+    fn first_held(&self) -> Option<(u8, u8)> {
+        self.held
+            .iter()
+            .position(|notes| *notes != 0)
+            .map(|channel| (channel as u8, self.held[channel].trailing_zeros() as u8))
+    }
+
+    // The timing in the current buffer of something at the given time in the loop.
+    fn timing(&self, time: f64, sample_rate: SampleRate) -> usize {
+        let seconds = time + self.offset - self.position;
+        (seconds * sample_rate.get() as f64).max(0.0).round() as usize
+    }
+}
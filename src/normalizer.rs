@@ -0,0 +1,72 @@
+use nih_plug::prelude::NoteEvent;
+
+// MIDI has 16 channels of 128 notes. Hosts shouldn't send anything outside of that, but events
+// which do are dropped rather than trusted.
+const NUM_CHANNELS: usize = 16;
+const NUM_NOTES: u8 = 128;
+
+/// A MIDI filter which cleans up the note events sent by hosts, before they reach the voices. Some
+/// hosts send a second note on for a key which is already held (such as for overlapping notes in a
+/// piano roll), or a note off for a key which was never pressed (such as after the transport jumps).
+/// These are dropped, so that every note on which gets through is matched by exactly one note off.
+pub struct EventNormalizer {
+    // The keys which are held down on each channel, as a bit per note.
+    held: [u128; NUM_CHANNELS],
+}
+
+impl EventNormalizer {
+    pub fn new() -> EventNormalizer {
+        EventNormalizer {
+            held: [0; NUM_CHANNELS],
+        }
+    }
+
+    /// Forget every held key, such as when the plugin is deactivated and the voices are released.
+    pub fn reset(&mut self) {
+        self.held = [0; NUM_CHANNELS];
+    }
+
+    /// Check an incoming event, returning it if it should be played. Events other than note ons,
+    /// note offs, and chokes are always passed through.
+    pub fn process(&mut self, event: NoteEvent<()>) -> Option<NoteEvent<()>> {
+        match event {
+            NoteEvent::NoteOn { channel, note, .. } => {
+                let (held, bit) = self.key(channel, note)?;
+                if *held & bit != 0 {
+                    return None;
+                }
+                *held |= bit;
+            }
+            NoteEvent::NoteOff { channel, note, .. } => {
+                let (held, bit) = self.key(channel, note)?;
+                if *held & bit == 0 {
+                    return None;
+                }
+                *held &= !bit;
+            }
+            NoteEvent::Choke { channel, note, .. } => {
+                let (held, bit) = self.key(channel, note)?;
+                *held &= !bit;
+            }
+            _ => (),
+        }
+        Some(event)
+    }
+
+    /// True if the key is held down on the channel.
+    pub fn is_held(&self, channel: u8, note: u8) -> bool {
+        self.held
+            .get(channel as usize)
+            .is_some_and(|held| note < NUM_NOTES && held & (1 << note) != 0)
+    }
+
+    // The channel's held keys and the bit for the note, or None if the channel or note is out of
+    // range.
+    fn key(&mut self, channel: u8, note: u8) -> Option<(&mut u128, u128)> {
+        if note >= NUM_NOTES {
+            return None;
+        }
+        let held = self.held.get_mut(channel as usize)?;
+        Some((held, 1 << note))
+    }
+}
@@ -0,0 +1,66 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use nih_plug::prelude::Params;
+
+use crate::params::Parameters;
+
+/// A lock-free set of the parameters which have changed since the editor last checked. Changes come
+/// from the host (through the editor) and from the plugin itself, such as when a macro or preset
+/// sets a parameter. Multiple changes to the same parameter are coalesced, so this never allocates
+/// and never fills up.
+pub struct ParamChanges {
+    ids: Vec<String>,
+    changed: Vec<AtomicBool>,
+    any_changed: AtomicBool,
+}
+
+impl ParamChanges {
+    pub fn new(parameters: &Parameters) -> ParamChanges {
+        let ids: Vec<String> = parameters
+            .param_map()
+            .into_iter()
+            .map(|(id, _ptr, _group)| id)
+            .collect();
+        let changed = ids.iter().map(|_| AtomicBool::new(false)).collect();
+        ParamChanges {
+            ids,
+            changed,
+            any_changed: AtomicBool::new(false),
+        }
+    }
+
+    /// Mark the parameter with the given ID as changed. Unknown IDs are ignored.
+    pub fn notify(&self, id: &str) {
+        if let Some(i) = self.ids.iter().position(|param_id| param_id == id) {
+            self.changed[i].store(true, Ordering::Release);
+            self.any_changed.store(true, Ordering::Release);
+        }
+    }
+
+    /// Mark every parameter as changed. This is used when the whole state is replaced at once.
+    pub fn notify_all(&self) {
+        for changed in &self.changed {
+            changed.store(true, Ordering::Release);
+        }
+        self.any_changed.store(true, Ordering::Release);
+    }
+
+    /// Returns true if any parameter has changed since the last call to [ParamChanges::drain].
+    pub fn any_changed(&self) -> bool {
+        self.any_changed.load(Ordering::Acquire)
+    }
+
+    /// Call `f` with the ID of each parameter which has changed since the last call, and clear
+    /// them. Returns true if any parameter had changed.
+    pub fn drain(&self, mut f: impl FnMut(&str)) -> bool {
+        if !self.any_changed.swap(false, Ordering::Acquire) {
+            return false;
+        }
+        for (id, changed) in self.ids.iter().zip(&self.changed) {
+            if changed.swap(false, Ordering::Acquire) {
+                f(id);
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,742 @@
+use nih_plug::prelude::Param;
+
+use crate::common::DbTaper;
+
+/// The help text for a parameter, shown by the editor's help mode, and the few details about it
+/// which nih-plug's parameters have no place for.
+///
+/// [PARAM_INFO] is written by hand, next to the parameters in params.rs, rather than generated
+/// from them. The Params derive already generates the IDs, the lookup, and the state from the
+/// parameters, but it can't carry anything else. So tests/param_info.rs checks that the two agree:
+/// that every parameter has exactly one entry, and that each entry's details fit its parameter.
+pub struct ParamInfo {
+    pub id: &'static str,
+    pub description: &'static str,
+    /// The built in modulation sources which change the parameter, or the value derived from it,
+    /// while notes play. Macros, the morph knob, and MIDI learn are set up by the user, so they
+    /// aren't listed here.
+    pub modulated_by: &'static [ModSource],
+    /// If set, the parameter is bipolar, with a neutral value (such as 0% or 0 dB). Values close
+    /// to it lock to it, from the editor and from automation alike, so that the parameter can be
+    /// set to exactly neutral.
+    pub detent: Option<Detent>,
+    /// How a decibel parameter tapers off to silence at the bottom of its range. This is
+    /// `DbTaper::NONE` for every other parameter.
+    pub db_taper: DbTaper,
+}
+
+/// Where a bipolar parameter's neutral value is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Detent {
+    /// The center of the range, such as 0% on a bipolar percentage.
+    Center,
+    /// A plain value which isn't at the center of the range. The decibel parameters are skewed, so
+    /// 0 dB is well above the center of their range.
+    At(f32),
+}
+
+impl Detent {
+    /// The normalized value of the parameter's neutral value.
+    pub fn normalized(self, param: &impl Param) -> f32 {
+        match self {
+            Detent::Center => 0.5,
+            Detent::At(plain) => unsafe { param.as_ptr().preview_normalized(plain) },
+        }
+    }
+}
+
+/// A built in modulation source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModSource {
+    Velocity,
+    ReleaseVelocity,
+    KeyTracking,
+    Envelope,
+    Follower,
+    Oscillator,
+}
+
+impl ModSource {
+    pub fn name(self) -> &'static str {
+        match self {
+            ModSource::Velocity => "Velocity",
+            ModSource::ReleaseVelocity => "Release Velocity",
+            ModSource::KeyTracking => "Key Tracking",
+            ModSource::Envelope => "Meow Envelope",
+            ModSource::Follower => "Envelope Follower",
+            ModSource::Oscillator => "Oscillator (Filter FM)",
+        }
+    }
+}
+
+/// How far from its neutral value, in normalized units, a parameter with a detent locks to it.
+pub const DETENT_WIDTH: f32 = 0.02;
+
+/// Returns the help text for the parameter with the given ID.
+pub fn param_info(id: &str) -> Option<&'static ParamInfo> {
+    PARAM_INFO.iter().find(|info| info.id == id)
+}
+
+/// Returns the decibel taper of the parameter with the given ID. This is a `const fn` so that the
+/// parameters can look up their tapers once, at compile time.
+pub const fn db_taper(id: &str) -> DbTaper {
+    let mut i = 0;
+    while i < PARAM_INFO.len() {
+        if str_eq(PARAM_INFO[i].id, id) {
+            return PARAM_INFO[i].db_taper;
+        }
+        i += 1;
+    }
+    DbTaper::NONE
+}
+
+// `==` on strings can't be used in a const fn.
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// True if a normalized value is close enough to the detent's normalized value to lock to it.
+pub fn in_detent(normalized: f32, detent: f32) -> bool {
+    (normalized - detent).abs() < DETENT_WIDTH
+}
+
+const fn info(
+    id: &'static str,
+    description: &'static str,
+    modulated_by: &'static [ModSource],
+) -> ParamInfo {
+    ParamInfo {
+        id,
+        description,
+        modulated_by,
+        detent: None,
+        db_taper: DbTaper::NONE,
+    }
+}
+
+impl ParamInfo {
+    const fn with_center_detent(mut self) -> ParamInfo {
+        self.detent = Some(Detent::Center);
+        self
+    }
+
+    const fn with_detent_at(mut self, plain: f32) -> ParamInfo {
+        self.detent = Some(Detent::At(plain));
+        self
+    }
+
+    const fn with_db_taper(mut self, neg_inf_db: f32, split_at: f32) -> ParamInfo {
+        self.db_taper = DbTaper::new(neg_inf_db, split_at);
+        self
+    }
+}
+
+use ModSource::*;
+
+/// The help text for every parameter, in the same order as the parameters.
+pub const PARAM_INFO: &[ParamInfo] = &[
+    // Envelope
+    info(
+        "meow_attack",
+        "How long each meow takes to fade in.",
+        &[Velocity, KeyTracking],
+    ),
+    info(
+        "meow_decay",
+        "How long each meow takes to fall from its peak to the sustain level.",
+        &[Velocity, KeyTracking],
+    ),
+    // The bottom of the sustain knob is a sustain of zero, so it reads as -inf rather than as the
+    // bottom of the range.
+    info(
+        "meow_sustain",
+        "The level that a held meow settles at after the decay.",
+        &[],
+    )
+    .with_db_taper(-48.0, 0.0),
+    info(
+        "meow_release",
+        "How long each meow takes to fade out after the key is released.",
+        &[KeyTracking, ReleaseVelocity],
+    ),
+    info(
+        "meow_sustain_fade",
+        "How long a held meow takes to fade out from the sustain level. Off holds it forever.",
+        &[],
+    ),
+    info(
+        "envelope_tempo_sync",
+        "Set the attack, decay, and release in note lengths relative to the host's tempo.",
+        &[],
+    ),
+    info(
+        "meow_attack_sync",
+        "The attack time, as a note length, when tempo sync is on.",
+        &[],
+    ),
+    info(
+        "meow_decay_sync",
+        "The decay time, as a note length, when tempo sync is on.",
+        &[],
+    ),
+    info(
+        "meow_release_sync",
+        "The release time, as a note length, when tempo sync is on.",
+        &[],
+    ),
+    info(
+        "envelope_keytrack",
+        "How much higher notes shorten the decay and release. At 100%, the times halve every \
+         octave above middle C.",
+        &[],
+    ),
+    info(
+        "attack_keytrack",
+        "How much higher notes shorten the attack.",
+        &[],
+    ),
+    info(
+        "vel_to_attack",
+        "How much harder notes shorten the attack. Negative amounts lengthen it instead.",
+        &[],
+    )
+    .with_center_detent(),
+    info(
+        "vel_to_decay",
+        "How much harder notes shorten the decay. Negative amounts lengthen it instead.",
+        &[],
+    )
+    .with_center_detent(),
+    info(
+        "envelope_cc_output",
+        "Send the volume and filter envelopes out as MIDI CCs, to modulate other plugins.",
+        &[],
+    ),
+    info(
+        "amp_envelope_cc",
+        "The CC that the volume envelope is sent on.",
+        &[],
+    ),
+    info(
+        "filter_envelope_cc",
+        "The CC that the filter envelope is sent on.",
+        &[],
+    ),
+    info(
+        "envelope_cc_channel",
+        "The MIDI channel that the envelope CCs are sent on.",
+        &[],
+    ),
+    info(
+        "vol_env_curve",
+        "The shape of the volume envelope's decay and release.",
+        &[],
+    ),
+    info(
+        "filter_env_curve",
+        "The shape of the filter envelope's decay and release.",
+        &[],
+    ),
+    info(
+        "vol_env_time_constants",
+        "How quickly the volume envelope's analog curve falls off at the start of each segment.",
+        &[],
+    ),
+    info(
+        "filter_env_time_constants",
+        "How quickly the filter envelope's analog curve falls off at the start of each segment.",
+        &[],
+    ),
+    // Vibrato
+    info(
+        "vibrato_amount",
+        "How far the vibrato bends the pitch, in cents.",
+        &[],
+    ),
+    info(
+        "vibrato_attack",
+        "How long the vibrato takes to fade in after each note starts.",
+        &[],
+    ),
+    info(
+        "vibrato_rate",
+        "How fast the vibrato wobbles, as a note length.",
+        &[],
+    ),
+    info(
+        "vibrato_note_shape",
+        "The waveform of the vibrato LFO.",
+        &[],
+    ),
+    info(
+        "vibrato_mode",
+        "Whether the vibrato bends the pitch smoothly, or in semitone steps for trills.",
+        &[],
+    ),
+    info(
+        "trill_range",
+        "How many semitones the pitch can jump by in trill mode.",
+        &[],
+    ),
+    // Filter
+    info(
+        "filter_envlope_mod",
+        "How far the meow envelope moves the filter cutoff. Negative amounts close the filter.",
+        &[Velocity],
+    )
+    .with_center_detent(),
+    info(
+        "filter_dry_wet",
+        "The mix between the unfiltered and filtered sound.",
+        &[],
+    ),
+    info(
+        "filter_mix_law",
+        "How the dry and filtered sounds are crossfaded.",
+        &[],
+    ),
+    info(
+        "filter_pre_gain",
+        "The gain of the sound going into the filter.",
+        &[],
+    )
+    .with_detent_at(0.0),
+    info(
+        "filter_post_gain",
+        "The gain of the sound coming out of the filter.",
+        &[],
+    )
+    .with_detent_at(0.0),
+    info(
+        "filter_release_vel",
+        "How much the release velocity changes the filter envelope's release. Hard releases \
+         shorten it.",
+        &[],
+    ),
+    info(
+        "filter_env_vel",
+        "How much the note's velocity scales the filter envelope mod.",
+        &[],
+    ),
+    info(
+        "filter_q",
+        "The filter's resonance. High values ring at the cutoff frequency.",
+        &[],
+    ),
+    info(
+        "filter_q_compensation",
+        "How much the output is turned down as the resonance rises.",
+        &[],
+    ),
+    info(
+        "filter_q_limiter",
+        "Soft clip the filter's output, so that the resonance can't get much louder than full \
+         scale.",
+        &[],
+    ),
+    info(
+        "filter_self_osc",
+        "How loudly the filter whistles at its cutoff when the resonance is high.",
+        &[],
+    ),
+    info(
+        "filter_keytrack",
+        "Make the cutoff follow the note being played.",
+        &[],
+    ),
+    info(
+        "filter_fm",
+        "How far the oscillator sweeps the cutoff at audio rate, for a gnarlier sound.",
+        &[],
+    ),
+    info("filter_type", "The kind of filter.", &[]),
+    info(
+        "filter_cutoff_freq",
+        "The frequency that the filter cuts off at.",
+        &[Envelope, Velocity, KeyTracking, Follower, Oscillator],
+    ),
+    info(
+        "filter_vintage_cap",
+        "Cap the cutoff at 20 kHz like the original Meowsynth, even at high sample rates.",
+        &[],
+    ),
+    // Filter 2
+    info(
+        "filter_routing",
+        "How the second filter is combined with the first: unused, in series, in parallel, or \
+         on the high band of a split.",
+        &[],
+    ),
+    info(
+        "filter_balance",
+        "The mix between the two filters. At 50%, both are at full volume.",
+        &[],
+    )
+    .with_center_detent(),
+    info(
+        "filter_split_freq",
+        "The crossover frequency between the two bands in split mode.",
+        &[],
+    ),
+    info(
+        "filter_2_type",
+        "The kind of filter used by the second filter.",
+        &[],
+    ),
+    info(
+        "filter_2_cutoff_freq",
+        "The frequency that the second filter cuts off at.",
+        &[],
+    ),
+    info("filter_2_q", "The second filter's resonance.", &[]),
+    // Chorus
+    info(
+        "chorus_mix",
+        "The mix between the dry sound and the chorus.",
+        &[],
+    ),
+    info(
+        "chorus_depth",
+        "How far the chorus sweeps its delay time.",
+        &[],
+    ),
+    info(
+        "chorus_distance",
+        "The shortest delay time of the chorus.",
+        &[],
+    ),
+    info("chorus_rate", "How fast the chorus sweeps.", &[]),
+    info(
+        "chorus_quality",
+        "The interpolation used to read the chorus delay.",
+        &[],
+    ),
+    info(
+        "chorus_voices",
+        "The number of chorus copies, with their sweeps spread evenly apart.",
+        &[],
+    ),
+    info("chorus_note_shape", "The waveform of the chorus LFO.", &[]),
+    // Supermeow
+    info(
+        "supermeow_voices",
+        "The number of detuned oscillators per note. At 1, supermeow is off.",
+        &[],
+    ),
+    info(
+        "supermeow_detune",
+        "How far the outermost supermeow oscillators are detuned, in cents.",
+        &[],
+    ),
+    info(
+        "supermeow_blend",
+        "The mix between the main oscillator and the detuned copies.",
+        &[],
+    ),
+    // Resonator
+    info(
+        "resonator_mix",
+        "The mix of the comb resonator, which is tuned to each note's pitch.",
+        &[],
+    ),
+    info(
+        "resonator_damping",
+        "How quickly the resonator's high harmonics die out. Higher values sound duller.",
+        &[],
+    ),
+    // Purr
+    info(
+        "purr_amount",
+        "How deeply the purr pulses the volume. The purr is strongest below C3, and fades out by C5.",
+        &[KeyTracking],
+    ),
+    info("purr_speed", "How many times a second the purr pulses.", &[]),
+    info(
+        "purr_roughness",
+        "How much each pulse of the purr randomly varies, for a rattlier purr.",
+        &[],
+    ),
+    // Growl
+    info(
+        "growl_amount",
+        "How much the meow snarls. The growl rapidly sweeps the filter or the volume, and saturates the meow.",
+        &[],
+    ),
+    info("growl_speed", "How fast the growl sweeps.", &[]),
+    info(
+        "growl_target",
+        "Whether the growl sweeps the filter cutoff or the volume.",
+        &[],
+    ),
+    info(
+        "growl_aftertouch",
+        "How much aftertouch adds to the growl, so that pressing harder snarls more.",
+        &[],
+    ),
+    // Follower
+    info(
+        "follower_attack",
+        "How quickly the envelope follower responds as the output gets louder.",
+        &[],
+    ),
+    info(
+        "follower_release",
+        "How quickly the envelope follower falls as the output gets quieter.",
+        &[],
+    ),
+    info(
+        "follower_to_noise",
+        "How much the envelope follower raises the noise. Negative amounts duck it.",
+        &[],
+    )
+    .with_center_detent(),
+    info(
+        "follower_to_filter",
+        "How much the envelope follower opens the filter. Negative amounts close it.",
+        &[],
+    )
+    .with_center_detent(),
+    // Compressor
+    info(
+        "comp_threshold",
+        "The level above which the compressor starts squashing the output.",
+        &[],
+    ),
+    info(
+        "comp_ratio",
+        "How hard the compressor squashes. 1:1 turns it off.",
+        &[],
+    ),
+    info(
+        "comp_attack",
+        "How quickly the compressor clamps down.",
+        &[],
+    ),
+    info("comp_release", "How quickly the compressor lets go.", &[]),
+    info(
+        "comp_makeup",
+        "Gain added after the compressor, to make up for the squashing.",
+        &[],
+    ),
+    // Stereo
+    info(
+        "stereo_width",
+        "The stereo width of the output. 0% is mono and 200% is extra wide.",
+        &[],
+    )
+    .with_center_detent(),
+    info(
+        "mono_bass",
+        "Keep everything below the mono bass frequency in the center.",
+        &[],
+    ),
+    info(
+        "mono_bass_freq",
+        "The frequency below which mono bass keeps the sound in the center.",
+        &[],
+    ),
+    // Global
+    info(
+        "portamento_time",
+        "How long the pitch takes to glide from one note to the next.",
+        &[],
+    ),
+    info(
+        "portamento_mode",
+        "Whether the pitch slides smoothly between notes, or steps through each semitone.",
+        &[],
+    ),
+    info(
+        "legato_mode",
+        "In monocat mode, whether a key pressed while another is held restarts the meow, or only slides its pitch while the envelopes keep going.",
+        &[],
+    ),
+    info(
+        "slide_time",
+        "How long the pitch takes to slide to the new note in Slide legato mode.",
+        &[],
+    ),
+    info(
+        "noise_mix",
+        "How much noise is mixed into each meow.",
+        &[Follower],
+    ),
+    info(
+        "noise_width",
+        "How different the noise is between the left and right channels.",
+        &[],
+    ),
+    info(
+        "hiss_mode",
+        "Mutes the meow and plays only the noise, at full level, for hisses. The filter and its envelope still shape the noise.",
+        &[],
+    ),
+    info(
+        "pitch_bend",
+        "How many semitones the pitch wheel bends by.",
+        &[],
+    ),
+    info(
+        "pitch_bend_smoothing",
+        "How long the pitch bend takes to glide to each new value.",
+        &[],
+    ),
+    info(
+        "mpe_zone",
+        "The MPE zone that the synth listens to. Off applies every pitch bend to every note.",
+        &[],
+    ),
+    info(
+        "mpe_bend_range",
+        "How many semitones the MPE member channels bend by.",
+        &[],
+    ),
+    info(
+        "polycat",
+        "Play several meows at once, instead of one meow at a time.",
+        &[],
+    ),
+    info(
+        "max_voices",
+        "The most meows that can play at once in polycat mode.",
+        &[],
+    ),
+    info(
+        "midi_out",
+        "Send the notes that the synth plays out as MIDI.",
+        &[],
+    ),
+    info(
+        "hires_cc",
+        "Combine CCs 0-31 and 32-63 into 14-bit controllers for MIDI learn.",
+        &[],
+    ),
+    info("scale", "Snap incoming notes to this scale.", &[]),
+    info("scale_root", "The root note of the scale.", &[]),
+    info(
+        "stray_cat",
+        "The most that each note is randomly detuned by.",
+        &[],
+    ),
+    info(
+        "stray_cat_quantize",
+        "Snap each detuned note to the nearest note in the scale, or to the nearest semitone if the scale is off.",
+        &[],
+    ),
+    info(
+        "retrigger_fade",
+        "How long a retriggered meow takes to fade out. Short fades are punchier, but may click.",
+        &[],
+    ),
+    info(
+        "quality",
+        "Trade CPU usage for sound quality. Eco is for tracking on slow machines, and HQ is for bouncing.",
+        &[],
+    ),
+    info(
+        "sync_modulation",
+        "Restarts the vibrato and chorus at the start of every bar while the host is playing.",
+        &[],
+    ),
+    info(
+        "mod_depth",
+        "Scales the vibrato, chorus, purr, growl, filter envelope, filter FM, and envelope \
+         follower amounts all at once. 100% plays the sound as it was made.",
+        &[],
+    )
+    .with_center_detent(),
+    // The bottom eighth of the master volume fades smoothly out to silence, rather than stopping
+    // at a quiet level and then dropping out.
+    info("gain", "The master volume.", &[]).with_db_taper(-70.0, 0.125),
+    info(
+        "preset_trim",
+        "Evens out the loudness of presets. This is saved with each preset and applied after the master volume.",
+        &[],
+    ),
+    // Macros
+    info(
+        "macro_1",
+        "Drives the parameters assigned to it in the Macros window.",
+        &[],
+    ),
+    info(
+        "macro_2",
+        "Drives the parameters assigned to it in the Macros window.",
+        &[],
+    ),
+    info(
+        "macro_3",
+        "Drives the parameters assigned to it in the Macros window.",
+        &[],
+    ),
+    info(
+        "macro_4",
+        "Drives the parameters assigned to it in the Macros window.",
+        &[],
+    ),
+    info(
+        "morph",
+        "Morphs every parameter between snapshots A and B.",
+        &[],
+    ),
+    // Presets
+    info(
+        "next_preset",
+        "Turning this on switches to the next preset in the preset browser.",
+        &[],
+    ),
+    info(
+        "previous_preset",
+        "Turning this on switches to the previous preset in the preset browser.",
+        &[],
+    ),
+    // Bypass
+    info(
+        "bypass",
+        "Fades the synth out. New notes aren't played while bypassed.",
+        &[],
+    ),
+    info(
+        "bypass_tails",
+        "Whether the notes playing when the synth is bypassed ring out, or are cut off.",
+        &[],
+    ),
+    // Layers
+    info(
+        "layer_mode",
+        "How notes are shared between layer A, the current sound, and layer B, the sound stored in the Layers window. Stack plays every note on both.",
+        &[],
+    ),
+    info(
+        "layer_split_note",
+        "In Key Split mode, the lowest note which plays on layer A.",
+        &[],
+    ),
+    info(
+        "layer_split_velocity",
+        "In Velocity Switch mode, the softest velocity which plays on layer B.",
+        &[],
+    ),
+    // Keyswitches
+    info(
+        "keyswitches",
+        "Turns on the keyswitches. The C, D, E, and F of the keyswitch octave switch to the Short Meow, Long Meow, Purr, and Hiss articulations, starting from the next note.",
+        &[],
+    ),
+    info(
+        "keyswitch_octave",
+        "The octave which the keyswitches are in.",
+        &[],
+    ),
+];
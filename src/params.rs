@@ -1,158 +1,747 @@
-use std::sync::Arc;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, RwLock};
 
 use nih_plug::prelude::{
-    BoolParam, Enum, EnumParam, FloatParam, FloatRange, IntParam, IntRange, Param, Params,
+    BoolParam, Enum, EnumParam, FloatParam, FloatRange, IntParam, IntRange, Param, ParamPtr,
+    Params, SmoothingStyle,
 };
 
-use crate::common::{self, Decibel, Seconds};
-use crate::common::{FilterType, Hertz};
+use crate::articulations::KeyswitchParams;
+use crate::chorus::ChorusQuality;
+use crate::common::{self, Beats, DbTaper, Decibel, MixLaw, Note, SampleTime, Seconds, Vel};
+use crate::common::{FilterRouting, FilterType, Hertz, SampleRate};
+use crate::ease::{inv_lerp, lerp};
+use crate::growl::{GrowlParams, GrowlTarget};
+use crate::layers::{LayerB, LayerMode, LayerParams};
+use crate::macros::{MacroAssignments, MorphSnapshots, Snapshot, NUM_MACROS};
+use crate::midi_learn::ProjectMappings;
+use crate::param_changes::ParamChanges;
+use crate::param_info::{db_taper, in_detent};
+use crate::quantizer::{Root, Scale};
 use crate::sound_gen::NoteShape;
+use crate::supermeow::MAX_SUPERMEOW_VOICES;
 
 // Default values for master volume
-const DEFAULT_MASTER_VOL: Decibel = Decibel::from_db(-6.0);
+const DEFAULT_MASTER_VOL: Decibel = Decibel::from_db(-12.0);
+const DEFAULT_PRESET_TRIM: Decibel = Decibel::from_db(0.0);
+
+// How each decibel parameter tapers off to silence, from the PARAM_INFO table
+const MASTER_VOL_TAPER: DbTaper = db_taper("gain");
+const PRESET_TRIM_TAPER: DbTaper = db_taper("preset_trim");
+const MEOW_SUSTAIN_TAPER: DbTaper = db_taper("meow_sustain");
+const FILTER_PRE_GAIN_TAPER: DbTaper = db_taper("filter_pre_gain");
+const FILTER_POST_GAIN_TAPER: DbTaper = db_taper("filter_post_gain");
+const COMP_THRESHOLD_TAPER: DbTaper = db_taper("comp_threshold");
+const COMP_MAKEUP_TAPER: DbTaper = db_taper("comp_makeup");
 
 // Default values for volume envelope
 const DEFAULT_MEOW_ATTACK: Seconds = Seconds::new(30.0 / 1000.0);
 const DEFAULT_MEOW_DECAY: Seconds = Seconds::new(1.25);
-const DEFAULT_MEOW_SUSTAIN: Decibel = Decibel::from_db(-15.0);
+const DEFAULT_MEOW_SUSTAIN: Decibel = Decibel::from_db(-30.0);
 const DEFAULT_MEOW_RELEASE: Seconds = Seconds::new(490.0 / 1000.0);
+const DEFAULT_MEOW_SUSTAIN_FADE: Seconds = Seconds::ZERO; // Off
+const DEFAULT_ENVELOPE_KEYTRACK: f32 = 0.0;
+const DEFAULT_ATTACK_KEYTRACK: f32 = 0.0;
+const DEFAULT_VEL_TO_ATTACK: f32 = 0.0;
+const DEFAULT_VEL_TO_DECAY: f32 = 0.0;
+const DEFAULT_ENVELOPE_TEMPO_SYNC: bool = false; // Off
+const DEFAULT_MEOW_ATTACK_SYNC: VibratoRate = VibratoRate::Sixteenth;
+const DEFAULT_MEOW_DECAY_SYNC: VibratoRate = VibratoRate::Half;
+const DEFAULT_MEOW_RELEASE_SYNC: VibratoRate = VibratoRate::Quarter;
+
+const DEFAULT_ENVELOPE_CC_OUTPUT: bool = false; // Off
+const DEFAULT_AMP_ENVELOPE_CC: u8 = 20;
+const DEFAULT_FILTER_ENVELOPE_CC: u8 = 21;
+const DEFAULT_ENVELOPE_CC_CHANNEL: u8 = 1;
+const DEFAULT_ENVELOPE_CURVE: EnvelopeCurve = EnvelopeCurve::Classic;
+const DEFAULT_ENVELOPE_TIME_CONSTANTS: f32 = 5.0;
 
-const DEFAULT_VIBRATO_AMOUNT: f32 = 0.0;
+const DEFAULT_VIBRATO_AMOUNT: f32 = 0.0; // cents
 const DEFAULT_VIBRATO_ATTACK: Seconds = Seconds::new(0.0);
 const DEFAULT_VIBRATO_RATE: VibratoRate = VibratoRate::Eighth;
+const DEFAULT_VIBRATO_MODE: VibratoMode = VibratoMode::Smooth;
+const DEFAULT_TRILL_RANGE: u8 = 1; // +/- 1 semi
 
 const DEFAULT_FILTER_ENVLOPE_MOD: Hertz = Hertz(7000.0);
 const DEFAULT_FILTER_DRY_WET: f32 = 1.0; // 100% filter
+const DEFAULT_FILTER_MIX_LAW: MixLaw = MixLaw::EqualPower;
+const DEFAULT_FILTER_PRE_GAIN: Decibel = Decibel::zero_db();
+const DEFAULT_FILTER_POST_GAIN: Decibel = Decibel::zero_db();
+const DEFAULT_FILTER_RELEASE_VEL: f32 = 0.0;
+const DEFAULT_FILTER_ENV_VEL: f32 = 1.0;
 const DEFAULT_FILTER_Q: f32 = 2.5;
 const DEFAULT_FILTER_TYPE: FilterType = FilterType::LowPass; // Low Pass
 const DEFAULT_FILTER_CUTOFF_FREQ: Hertz = Hertz(350.0); // this which will be around 7350 at max meow sustain on max velocity.
+const DEFAULT_FILTER_VINTAGE_CAP: bool = false;
+const DEFAULT_FILTER_Q_COMPENSATION: f32 = 0.0;
+const DEFAULT_FILTER_Q_LIMITER: bool = false;
+const DEFAULT_FILTER_SELF_OSC: f32 = 0.0;
+const DEFAULT_FILTER_KEYTRACK: bool = false;
+const DEFAULT_FILTER_FM: f32 = 0.0;
+
+const DEFAULT_FILTER_ROUTING: FilterRouting = FilterRouting::Single;
+const DEFAULT_FILTER_BALANCE: f32 = 0.5;
+const DEFAULT_FILTER_SPLIT_FREQ: Hertz = Hertz(1000.0);
+const DEFAULT_FILTER_2_TYPE: FilterType = FilterType::Notch;
+const DEFAULT_FILTER_2_CUTOFF_FREQ: Hertz = Hertz(2000.0);
+const DEFAULT_FILTER_2_Q: f32 = 1.0;
 
 const DEFAULT_CHORUS_MIX: f32 = 0.0;
 const DEFAULT_CHORUS_DEPTH: f32 = 44.0;
 const DEFAULT_CHORUS_DISTANCE: f32 = 450.0;
 const DEFAULT_CHORUS_RATE: Hertz = Hertz(0.33);
+const DEFAULT_CHORUS_QUALITY: ChorusQuality = ChorusQuality::High;
+const DEFAULT_CHORUS_VOICES: u8 = 1;
+
+const DEFAULT_SUPERMEOW_VOICES: u8 = 1; // Off
+const DEFAULT_SUPERMEOW_DETUNE: f32 = 20.0; // cents
+const DEFAULT_SUPERMEOW_BLEND: f32 = 0.5;
+
+const DEFAULT_RESONATOR_MIX: f32 = 0.0;
+const DEFAULT_RESONATOR_DAMPING: f32 = 0.5;
+
+const DEFAULT_PURR_AMOUNT: f32 = 0.0; // Off
+const DEFAULT_PURR_SPEED: Hertz = Hertz(26.0);
+const DEFAULT_PURR_ROUGHNESS: f32 = 0.3;
+
+const DEFAULT_GROWL_AMOUNT: f32 = 0.0; // Off
+const DEFAULT_GROWL_SPEED: Hertz = Hertz(45.0);
+const DEFAULT_GROWL_TARGET: GrowlTarget = GrowlTarget::Filter;
+const DEFAULT_GROWL_AFTERTOUCH: f32 = 0.0;
+
+const DEFAULT_FOLLOWER_ATTACK: Seconds = Seconds::new(10.0 / 1000.0);
+const DEFAULT_FOLLOWER_RELEASE: Seconds = Seconds::new(150.0 / 1000.0);
+const DEFAULT_FOLLOWER_TO_NOISE: f32 = 0.0;
+const DEFAULT_FOLLOWER_TO_FILTER: f32 = 0.0;
+
+const DEFAULT_COMP_THRESHOLD: Decibel = Decibel::from_db(-24.0);
+const DEFAULT_COMP_RATIO: f32 = 1.0;
+const DEFAULT_COMP_ATTACK: Seconds = Seconds::new(5.0 / 1000.0);
+const DEFAULT_COMP_RELEASE: Seconds = Seconds::new(100.0 / 1000.0);
+const DEFAULT_COMP_MAKEUP: Decibel = Decibel::zero_db();
+
+const DEFAULT_STEREO_WIDTH: f32 = 1.0; // Unchanged
+const DEFAULT_MONO_BASS: bool = false; // Off
+const DEFAULT_MONO_BASS_FREQ: Hertz = Hertz(120.0);
 
 const DEFAULT_NOISE_MIX: f32 = 0.0;
+const DEFAULT_NOISE_WIDTH: f32 = 0.0; // Mono
+const DEFAULT_HISS_MODE: bool = false; // Off
 
 const DEFAULT_PITCHBEND: u8 = 12; // +12 semis
+const DEFAULT_PITCHBEND_SMOOTHING: Seconds = Seconds::new(10.0 / 1000.0);
+const DEFAULT_MPE_ZONE: MpeZone = MpeZone::Off;
+const DEFAULT_MPE_BEND_RANGE: u8 = 48; // +48 semis, the MPE spec default
 const DEFAULT_PORTAMENTO: Seconds = Seconds::new(120.0 / 1000.0);
+const DEFAULT_PORTAMENTO_MODE: PortamentoMode = PortamentoMode::Glide;
+const DEFAULT_LEGATO_MODE: LegatoMode = LegatoMode::Retrigger;
+const DEFAULT_SLIDE_TIME: Seconds = Seconds::new(40.0 / 1000.0);
 const DEFAULT_POLYCAT: bool = false; // Off
+const DEFAULT_SCALE: Scale = Scale::Off;
+const DEFAULT_SCALE_ROOT: Root = Root::C;
+const DEFAULT_MIDI_OUT: bool = false; // Off
+const DEFAULT_HIRES_CC: bool = false; // Off
+const DEFAULT_STRAY_CAT: f32 = 0.0; // cents
+const DEFAULT_STRAY_CAT_QUANTIZE: bool = false; // Off
+const DEFAULT_MAX_VOICES: usize = 16;
+const DEFAULT_RETRIGGER_FADE: Seconds = Seconds::new(1.0 / 1000.0);
+const DEFAULT_QUALITY: Quality = Quality::Normal;
+const DEFAULT_SYNC_MODULATION: bool = false; // Off
+const DEFAULT_MOD_DEPTH: f32 = 1.0; // 100%
+
+const DEFAULT_MACRO: f32 = 0.0;
+const DEFAULT_MORPH: f32 = 0.0; // Snapshot A
+
+// Default values for the preset navigation buttons
+const DEFAULT_PRESET_BUTTON: bool = false; // Off
+
+const DEFAULT_BYPASS: bool = false; // Off
+const DEFAULT_BYPASS_TAILS: BypassTails = BypassTails::Ring;
+
+const DEFAULT_LAYER_MODE: LayerMode = LayerMode::Off;
+const DEFAULT_LAYER_SPLIT_NOTE: u8 = 60; // C4
+const DEFAULT_LAYER_SPLIT_VELOCITY: f32 = 0.5;
+
+const DEFAULT_KEYSWITCHES: bool = false;
+const DEFAULT_KEYSWITCH_OCTAVE: i32 = 0; // C0 to F0
 
+// The smoothing used for continuous parameters. Stepped parameters (enums, bools, and ints) are not
+// smoothed at all.
+const CONTINUOUS_SMOOTHING: SmoothingStyle = SmoothingStyle::Linear(20.0);
+
+pub const MAX_VOICES: usize = 32;
+pub const MAX_VIBRATO_AMOUNT: f32 = 200.0; // cents
 pub const MAX_CHORUS_DEPTH: f32 = 100.0;
 pub const MAX_CHORUS_DISTANCE: f32 = 1000.0;
+pub const MAX_FOLLOWER_TO_FILTER: f32 = 4.0; // octaves
+pub const MAX_FILTER_Q: f32 = 10.0;
+pub const MAX_FILTER_FM: f32 = 4.0; // octaves
+pub const MAX_MOD_DEPTH: f32 = 2.0;
+// The Q at which the filter starts to self-oscillate.
+const SELF_OSCILLATION_MIN_Q: f32 = 5.0;
 
 /// The public facing parameters struct containing the computed values for each parameter value.
 /// Avoid constructing too many of these--it is expensive to do so.
 pub struct MeowParameters {
     pub master_vol: Decibel,
+    /// The preset's loudness correction, which is applied along with the master volume.
+    pub preset_trim: Decibel,
     pub noise_mix: f32,
+    pub noise_width: f32,
+    pub hiss_mode: bool,
     pub portamento_time: Seconds,
+    pub portamento_mode: PortamentoMode,
+    pub legato_mode: LegatoMode,
+    pub slide_time: Seconds,
+    pub retrigger_fade: Seconds,
+    pub quality: Quality,
+    /// If true, the vibrato and chorus LFOs restart at the start of every bar.
+    pub sync_modulation: bool,
     pub pitchbend_max: u8,
+    pub pitchbend_smoothing: Seconds,
+    pub mpe_zone: MpeZone,
+    pub mpe_bend_range: u8,
     pub polycat: bool,
+    pub max_voices: usize,
+    pub midi_out: bool,
+    pub hires_cc: bool,
+    pub scale: Scale,
+    pub scale_root: Root,
+    pub stray_cat: StrayCatParams,
     pub vol_envelope: VolumeEnvelopeParams,
+    pub envelope_keytrack: f32,
+    pub attack_keytrack: f32,
+    pub vel_to_attack: f32,
+    pub vel_to_decay: f32,
+    pub envelope_cc: Option<EnvelopeCCParams>,
     pub filter: FilterParams,
+    pub filter_2: SecondFilterParams,
     pub filter_envelope: FilterEnvelopeParams,
     pub chorus: ChorusParams,
+    pub supermeow: SupermeowParams,
+    pub resonator: ResonatorParams,
+    pub purr: PurrParams,
+    pub growl: GrowlParams,
+    pub follower: FollowerParams,
+    pub compressor: CompressorParams,
+    pub stereo: StereoParams,
     pub vibrato_attack: VibratoEnvelopeParams,
     pub vibrato_lfo: VibratoLFOParams,
     pub vibrato_note_shape: NoteShape,
     pub chorus_note_shape: NoteShape,
+    pub macros: [f32; NUM_MACROS],
+    pub morph: f32,
+    pub next_preset: bool,
+    pub previous_preset: bool,
+    pub bypass: bool,
+    pub bypass_tails: BypassTails,
+    pub layers: LayerParams,
+    pub keyswitches: KeyswitchParams,
 }
 
 impl MeowParameters {
     /// Construct a MeowParameters from a normal Parameters. Doing this calls a lot of easing functions
     /// so avoid calling it too often (once per block, or ideally only once every time a parameter
     /// updates).
-    pub fn new(parameters: &Parameters, tempo: f32) -> MeowParameters {
-        fn seconds(param: &FloatParam) -> Seconds {
-            Seconds::from(param.value())
-        }
+    /// The continuous parameters are smoothed, and their smoothers are advanced by `block_len`
+    /// samples. If `block_len` is zero, the current smoothed values are used without advancing the
+    /// smoothers. The stepped parameters (enums, bools, and ints) are not smoothed, so that
+    /// automating them switches exactly where the automation point is.
+    pub fn new(parameters: &Parameters, tempo: f32, block_len: u32) -> MeowParameters {
+        let smoothed = |param: &FloatParam| {
+            if block_len == 0 {
+                param.smoothed.previous_value()
+            } else {
+                param.smoothed.next_step(block_len)
+            }
+        };
 
-        fn hertz(param: &FloatParam) -> Hertz {
-            Hertz::from(param.value())
-        }
+        let seconds = |param: &FloatParam| Seconds::from(smoothed(param));
+
+        let hertz = |param: &FloatParam| Hertz::from(smoothed(param));
 
-        fn decibel(param: &FloatParam) -> Decibel {
-            Decibel::from_db(param.value())
+        let decibel = |param: &FloatParam, taper: DbTaper| {
+            let value = smoothed(param);
+            taper.apply(
+                param.preview_normalized(value),
+                Decibel::from_db(value),
+                Decibel::from_db(param.preview_plain(taper.split_at)),
+            )
+        };
+
+        // The bipolar parameters lock to their neutral value when they are close to it. These are
+        // the parameters with a detent in the PARAM_INFO table. Most are neutral at the center of
+        // their range, but the decibel parameters are skewed, so they are neutral at 0 dB instead.
+        let detent_at = |param: &FloatParam, neutral: f32| {
+            let value = smoothed(param);
+            if in_detent(
+                param.preview_normalized(value),
+                param.preview_normalized(neutral),
+            ) {
+                neutral
+            } else {
+                value
+            }
+        };
+        let centered = |param: &FloatParam| detent_at(param, param.preview_plain(0.5));
+
+        // A sustain fade of zero means that the sustain is held forever. This is a stepped
+        // parameter, since smoothing between Off and a fade time would sweep through very short
+        // fade times.
+        fn sustain_fade(param: &FloatParam) -> Option<Seconds> {
+            if param.value() == 0.0 {
+                None
+            } else {
+                Some(Seconds::from(param.value()))
+            }
         }
 
         // This exhaustive destructuring helps ensure that if you add a field to Parameters, that you
         // also need to add a field to MeowParameters.
         let Parameters {
+            envelope,
+            vibrato,
+            filter,
+            filter_2,
+            chorus,
+            supermeow,
+            resonator,
+            purr,
+            growl,
+            follower,
+            compressor,
+            stereo,
+            global,
+            macros,
+            presets,
+            bypass,
+            layers,
+            keyswitches,
+            // The macro assignments and morph snapshots are read directly by the plugin when a
+            // macro or the morph changes, and layer B's parameters are kept by the plugin. The MIDI
+            // mappings are read when a CC arrives.
+            macro_assignments: _,
+            morph_snapshots: _,
+            layer_b: _,
+            midi_mappings: _,
+        } = parameters;
+        let EnvelopeGroup {
             meow_attack,
             meow_decay,
             meow_sustain,
             meow_release,
+            meow_sustain_fade,
+            envelope_tempo_sync,
+            meow_attack_sync,
+            meow_decay_sync,
+            meow_release_sync,
+            envelope_keytrack,
+            attack_keytrack,
+            vel_to_attack,
+            vel_to_decay,
+            envelope_cc_output,
+            amp_envelope_cc,
+            filter_envelope_cc,
+            envelope_cc_channel,
+            vol_env_curve,
+            filter_env_curve,
+            vol_env_time_constants,
+            filter_env_time_constants,
+        } = envelope;
+        let VibratoGroup {
             vibrato_amount,
             vibrato_attack,
             vibrato_rate,
-            portamento_time,
-            noise_mix,
-            chorus_mix,
-            pitch_bend,
-            polycat,
-            gain,
+            vibrato_note_shape,
+            vibrato_mode,
+            trill_range,
+        } = vibrato;
+        let FilterGroup {
             filter_envlope_mod,
-            filter_dry_wet,
+            // The dry/wet is smoothed per sample by the plugin. See FilterGroup::dry_wet_block.
+            filter_dry_wet: _,
+            filter_mix_law,
+            filter_pre_gain,
+            filter_post_gain,
+            filter_release_vel,
+            filter_env_vel,
             filter_q,
+            filter_q_compensation,
+            filter_q_limiter,
+            filter_self_osc,
+            filter_keytrack,
+            filter_fm,
             filter_type,
             filter_cutoff_freq,
+            filter_vintage_cap,
+        } = filter;
+        let SecondFilterGroup {
+            filter_routing,
+            filter_balance,
+            filter_split_freq,
+            filter_2_type,
+            filter_2_cutoff_freq,
+            filter_2_q,
+        } = filter_2;
+        let ChorusGroup {
+            chorus_mix,
             chorus_depth,
             chorus_distance,
             chorus_rate,
-            vibrato_note_shape,
+            chorus_quality,
+            chorus_voices,
             chorus_note_shape,
-        } = parameters;
+        } = chorus;
+        let SupermeowGroup {
+            supermeow_voices,
+            supermeow_detune,
+            supermeow_blend,
+        } = supermeow;
+        let ResonatorGroup {
+            resonator_mix,
+            resonator_damping,
+        } = resonator;
+        let PurrGroup {
+            purr_amount,
+            purr_speed,
+            purr_roughness,
+        } = purr;
+        let GrowlGroup {
+            growl_amount,
+            growl_speed,
+            growl_target,
+            growl_aftertouch,
+        } = growl;
+        let FollowerGroup {
+            follower_attack,
+            follower_release,
+            follower_to_noise,
+            follower_to_filter,
+        } = follower;
+        let CompressorGroup {
+            comp_threshold,
+            comp_ratio,
+            comp_attack,
+            comp_release,
+            comp_makeup,
+        } = compressor;
+        let StereoGroup {
+            stereo_width,
+            mono_bass,
+            mono_bass_freq,
+        } = stereo;
+        let GlobalGroup {
+            portamento_time,
+            portamento_mode,
+            legato_mode,
+            slide_time,
+            noise_mix,
+            noise_width,
+            hiss_mode,
+            pitch_bend,
+            pitch_bend_smoothing,
+            mpe_zone,
+            mpe_bend_range,
+            polycat,
+            max_voices,
+            midi_out,
+            hires_cc,
+            scale,
+            scale_root,
+            stray_cat,
+            stray_cat_quantize,
+            retrigger_fade,
+            quality,
+            sync_modulation,
+            mod_depth,
+            gain,
+            preset_trim,
+        } = global;
+        let MacroGroup {
+            macro_1,
+            macro_2,
+            macro_3,
+            macro_4,
+            morph,
+        } = macros;
+        let PresetGroup {
+            next_preset,
+            previous_preset,
+        } = presets;
+        let BypassGroup {
+            bypass,
+            bypass_tails,
+        } = bypass;
+        let LayerGroup {
+            layer_mode,
+            layer_split_note,
+            layer_split_velocity,
+        } = layers;
+        let KeyswitchGroup {
+            keyswitches,
+            keyswitch_octave,
+        } = keyswitches;
+
+        // If the envelope is tempo synced, then the times are given as note divisions instead. Note
+        // that the unsynced times are always read so that their smoothers keep advancing.
+        let envelope_time = |param: &FloatParam, synced: &EnumParam<VibratoRate>| {
+            let time = seconds(param);
+            if envelope_tempo_sync.value() {
+                synced.value().as_seconds(tempo)
+            } else {
+                time
+            }
+        };
+        let meow_attack = envelope_time(meow_attack, meow_attack_sync);
+        let meow_decay = envelope_time(meow_decay, meow_decay_sync);
+        let meow_release = envelope_time(meow_release, meow_release_sync);
+        let meow_sustain = meow_sustain.preview_normalized(smoothed(meow_sustain));
+        let q_value = smoothed(filter_q);
+        // The mod depth scales every modulation amount at once. Amounts which have a hard limit are
+        // kept within it.
+        let mod_depth = centered(mod_depth);
+
         MeowParameters {
-            master_vol: decibel(gain),
-            noise_mix: noise_mix.value(),
+            master_vol: decibel(gain, MASTER_VOL_TAPER),
+            preset_trim: decibel(preset_trim, PRESET_TRIM_TAPER),
+            noise_mix: smoothed(noise_mix),
+            noise_width: smoothed(noise_width),
+            hiss_mode: hiss_mode.value(),
             portamento_time: seconds(portamento_time),
+            portamento_mode: portamento_mode.value(),
+            legato_mode: legato_mode.value(),
+            slide_time: seconds(slide_time),
+            retrigger_fade: seconds(retrigger_fade),
+            quality: quality.value(),
+            sync_modulation: sync_modulation.value(),
             pitchbend_max: pitch_bend.value() as u8,
+            pitchbend_smoothing: seconds(pitch_bend_smoothing),
+            mpe_zone: mpe_zone.value(),
+            mpe_bend_range: mpe_bend_range.value() as u8,
             polycat: polycat.value(),
+            max_voices: max_voices.value() as usize,
+            midi_out: midi_out.value(),
+            hires_cc: hires_cc.value(),
+            scale: scale.value(),
+            scale_root: scale_root.value(),
+            stray_cat: StrayCatParams {
+                range: smoothed(stray_cat) / 100.0,
+                quantize: stray_cat_quantize.value(),
+                scale: scale.value(),
+                root: scale_root.value(),
+            },
             vol_envelope: VolumeEnvelopeParams {
-                attack: seconds(meow_attack),
-                decay: seconds(meow_decay),
-                sustain: meow_sustain.modulated_normalized_value(),
-                release: seconds(meow_release),
+                attack: meow_attack,
+                decay: meow_decay,
+                sustain: meow_sustain,
+                release: meow_release,
+                sustain_fade: sustain_fade(meow_sustain_fade),
+                curve: vol_env_curve.value(),
+                time_constants: smoothed(vol_env_time_constants),
             },
             filter: FilterParams {
                 cutoff_freq: hertz(filter_cutoff_freq),
-                q_value: filter_q.value(),
+                q_value,
+                resonance_gain: resonance_gain(q_value, smoothed(filter_q_compensation)),
+                q_limiter: filter_q_limiter.value(),
+                self_oscillation: self_oscillation(q_value, smoothed(filter_self_osc)),
+                keytrack: filter_keytrack.value(),
+                fm_amount: smoothed(filter_fm) * mod_depth,
                 filter_type: filter_type.value().into(),
-                dry_wet: filter_dry_wet.value(),
+                mix_law: filter_mix_law.value(),
+                pre_gain: Decibel::from_db(detent_at(filter_pre_gain, 0.0)),
+                post_gain: Decibel::from_db(detent_at(filter_post_gain, 0.0)),
+                vintage_cap: filter_vintage_cap.value(),
+            },
+            filter_2: SecondFilterParams {
+                routing: filter_routing.value(),
+                balance: centered(filter_balance),
+                split_freq: hertz(filter_split_freq),
+                filter_type: filter_2_type.value().into(),
+                cutoff_freq: hertz(filter_2_cutoff_freq),
+                q_value: smoothed(filter_2_q),
+            },
+            envelope_keytrack: smoothed(envelope_keytrack),
+            attack_keytrack: smoothed(attack_keytrack),
+            vel_to_attack: centered(vel_to_attack),
+            vel_to_decay: centered(vel_to_decay),
+            envelope_cc: if envelope_cc_output.value() {
+                Some(EnvelopeCCParams {
+                    amp_cc: amp_envelope_cc.value() as u8,
+                    filter_cc: filter_envelope_cc.value() as u8,
+                    channel: envelope_cc_channel.value() as u8 - 1,
+                })
+            } else {
+                None
             },
             filter_envelope: FilterEnvelopeParams {
-                attack: seconds(meow_attack),
-                decay: seconds(meow_decay),
-                sustain: meow_sustain.modulated_normalized_value(),
-                release: seconds(meow_release),
-                env_mod: hertz(filter_envlope_mod),
+                attack: meow_attack,
+                decay: meow_decay,
+                sustain: meow_sustain,
+                release: meow_release,
+                sustain_fade: sustain_fade(meow_sustain_fade),
+                curve: filter_env_curve.value(),
+                time_constants: smoothed(filter_env_time_constants),
+                env_mod: Hertz::from(centered(filter_envlope_mod) * mod_depth),
+                release_vel: smoothed(filter_release_vel),
+                env_vel: smoothed(filter_env_vel),
             },
             chorus: ChorusParams {
-                rate: Hertz(chorus_rate.value()),
-                depth: chorus_depth.value(),
-                min_distance: chorus_distance.value(),
-                mix: chorus_mix.value(),
+                rate: hertz(chorus_rate),
+                depth: (smoothed(chorus_depth) * mod_depth).min(MAX_CHORUS_DEPTH),
+                min_distance: smoothed(chorus_distance),
+                mix: smoothed(chorus_mix),
+                quality: quality.value().chorus_quality(chorus_quality.value()),
+                voices: chorus_voices.value() as u8,
+            },
+            supermeow: SupermeowParams {
+                voices: supermeow_voices.value() as u8,
+                detune: smoothed(supermeow_detune),
+                blend: smoothed(supermeow_blend),
+            },
+            resonator: ResonatorParams {
+                mix: smoothed(resonator_mix),
+                damping: smoothed(resonator_damping),
+            },
+            purr: PurrParams {
+                amount: (smoothed(purr_amount) * mod_depth).min(1.0),
+                speed: hertz(purr_speed),
+                roughness: smoothed(purr_roughness),
+            },
+            growl: GrowlParams {
+                amount: (smoothed(growl_amount) * mod_depth).min(1.0),
+                speed: hertz(growl_speed),
+                target: growl_target.value(),
+                aftertouch: smoothed(growl_aftertouch),
+            },
+            compressor: CompressorParams {
+                threshold: decibel(comp_threshold, COMP_THRESHOLD_TAPER),
+                ratio: smoothed(comp_ratio),
+                attack: seconds(comp_attack),
+                release: seconds(comp_release),
+                makeup: decibel(comp_makeup, COMP_MAKEUP_TAPER),
+            },
+            stereo: StereoParams {
+                width: centered(stereo_width),
+                mono_below: if mono_bass.value() {
+                    Some(hertz(mono_bass_freq))
+                } else {
+                    None
+                },
+            },
+            follower: FollowerParams {
+                attack: seconds(follower_attack),
+                release: seconds(follower_release),
+                to_noise: centered(follower_to_noise) * mod_depth,
+                to_filter: centered(follower_to_filter) * mod_depth,
             },
             vibrato_attack: VibratoEnvelopeParams {
-                attack: Seconds::from(vibrato_attack.value()),
+                attack: seconds(vibrato_attack),
             },
             vibrato_lfo: VibratoLFOParams {
                 speed: vibrato_rate.value().as_hz(tempo),
-                amount: vibrato_amount.value(),
+                amount: smoothed(vibrato_amount) / MAX_VIBRATO_AMOUNT * mod_depth,
+                mode: vibrato_mode.value(),
+                trill_range: trill_range.value() as u8,
             },
             vibrato_note_shape: vibrato_note_shape.value(),
             chorus_note_shape: chorus_note_shape.value(),
+            // The macros are not smoothed, since the parameters they drive are smoothed already.
+            macros: [
+                macro_1.value(),
+                macro_2.value(),
+                macro_3.value(),
+                macro_4.value(),
+            ],
+            morph: morph.value(),
+            next_preset: next_preset.value(),
+            previous_preset: previous_preset.value(),
+            bypass: bypass.value(),
+            bypass_tails: bypass_tails.value(),
+            layers: LayerParams {
+                mode: layer_mode.value(),
+                split_note: Note(layer_split_note.value() as u8),
+                split_velocity: layer_split_velocity.value(),
+            },
+            keyswitches: KeyswitchParams {
+                enabled: keyswitches.value(),
+                octave: keyswitch_octave.value(),
+            },
         }
     }
 }
 
 // This deny is triggered if you have a field that isn't read from. The places that you probably need
 // to add code are in Parameters::get() and also a corresponding field in MeowParameters.
+//
+// The parameters are split into groups so that hosts which support grouped automation lanes can
+// organize them. Note that none of the groups use an `id_prefix`, so the parameter IDs are the
+// same as they were before the grouping was introduced.
+//
+// The state and the hosts only know parameters by their `#[id]`, never by their position, so fields
+// can be moved around freely. The IDs themselves must never change. tests/param_ids.rs checks this.
+//
+// Parameters marked as internal are built with `.hide()`. Hosts don't list them or automate them,
+// but they are still saved in the state and in presets, and the editor's "Internal" window can
+// change them.
 #[deny(dead_code)]
 #[derive(Params)]
 pub struct Parameters {
-    // Public parameters (exposed in UI)
+    #[nested(group = "Envelope")]
+    pub envelope: EnvelopeGroup,
+    #[nested(group = "Vibrato")]
+    pub vibrato: VibratoGroup,
+    #[nested(group = "Filter")]
+    pub filter: FilterGroup,
+    #[nested(group = "Filter 2")]
+    pub filter_2: SecondFilterGroup,
+    #[nested(group = "Chorus")]
+    pub chorus: ChorusGroup,
+    #[nested(group = "Supermeow")]
+    pub supermeow: SupermeowGroup,
+    #[nested(group = "Resonator")]
+    pub resonator: ResonatorGroup,
+    #[nested(group = "Purr")]
+    pub purr: PurrGroup,
+    #[nested(group = "Growl")]
+    pub growl: GrowlGroup,
+    #[nested(group = "Follower")]
+    pub follower: FollowerGroup,
+    #[nested(group = "Compressor")]
+    pub compressor: CompressorGroup,
+    #[nested(group = "Stereo")]
+    pub stereo: StereoGroup,
+    #[nested(group = "Global")]
+    pub global: GlobalGroup,
+    #[nested(group = "Macros")]
+    pub macros: MacroGroup,
+    #[nested(group = "Presets")]
+    pub presets: PresetGroup,
+    #[nested(group = "Bypass")]
+    pub bypass: BypassGroup,
+    #[nested(group = "Layers")]
+    pub layers: LayerGroup,
+    #[nested(group = "Keyswitches")]
+    pub keyswitches: KeyswitchGroup,
+    // The parameters which each macro drives. This is not a parameter itself, but it is stored
+    // in the plugin state.
+    #[persist = "macro_assignments"]
+    pub macro_assignments: RwLock<MacroAssignments>,
+    // The snapshots that the morph knob interpolates between.
+    #[persist = "morph_snapshots"]
+    pub morph_snapshots: RwLock<MorphSnapshots>,
+    // The sound that layer B plays.
+    #[persist = "layer_b"]
+    pub layer_b: RwLock<LayerB>,
+    // The MIDI mappings, if the user chose to save them with the project instead of globally.
+    #[persist = "project_midi_mappings"]
+    pub midi_mappings: ProjectMappings,
+}
+
+/// The "Meow" envelope parameters. These control both the volume and the filter envelopes.
+#[deny(dead_code)]
+#[derive(Params)]
+pub struct EnvelopeGroup {
     #[id = "meow_attack"]
     pub meow_attack: FloatParam,
     #[id = "meow_decay"]
@@ -161,46 +750,442 @@ pub struct Parameters {
     pub meow_sustain: FloatParam,
     #[id = "meow_release"]
     pub meow_release: FloatParam,
+    // How long it takes the sustain to fade to silence while the note is held. Zero means that the
+    // sustain never fades.
+    #[id = "meow_sustain_fade"]
+    pub meow_sustain_fade: FloatParam,
+    // If enabled, the attack, decay, and release times are set in note divisions relative to the
+    // host tempo, using the synced parameters below instead of the times above.
+    #[id = "envelope_tempo_sync"]
+    envelope_tempo_sync: BoolParam,
+    #[id = "meow_attack_sync"]
+    meow_attack_sync: EnumParam<VibratoRate>,
+    #[id = "meow_decay_sync"]
+    meow_decay_sync: EnumParam<VibratoRate>,
+    #[id = "meow_release_sync"]
+    meow_release_sync: EnumParam<VibratoRate>,
+    // How much higher notes shorten the decay and release times. At 100%, the times are halved for
+    // every octave above middle C (and doubled for every octave below).
+    #[id = "envelope_keytrack"]
+    envelope_keytrack: FloatParam,
+    // The same as the envelope keytrack, but for the attack time.
+    #[id = "attack_keytrack"]
+    attack_keytrack: FloatParam,
+    // How much the note on velocity changes the attack and decay times. At +100%, a maximum
+    // velocity note halves the time and a zero velocity note doubles it. Negative amounts do the
+    // opposite.
+    #[id = "vel_to_attack"]
+    vel_to_attack: FloatParam,
+    #[id = "vel_to_decay"]
+    vel_to_decay: FloatParam,
+    // If enabled, the amp and filter envelope values are sent as MIDI CCs so that they can be used
+    // to modulate other plugins.
+    #[id = "envelope_cc_output"]
+    envelope_cc_output: BoolParam,
+    #[id = "amp_envelope_cc"]
+    amp_envelope_cc: IntParam,
+    #[id = "filter_envelope_cc"]
+    filter_envelope_cc: IntParam,
+    #[id = "envelope_cc_channel"]
+    envelope_cc_channel: IntParam,
+    // The shape of the decay and release of the volume and filter envelopes.
+    #[id = "vol_env_curve"]
+    vol_env_curve: EnumParam<EnvelopeCurve>,
+    #[id = "filter_env_curve"]
+    filter_env_curve: EnumParam<EnvelopeCurve>,
+    // How many RC time constants fit in each segment of the analog curve. Higher values fall
+    // faster at the start of a segment and leave a longer, quieter tail.
+    #[id = "vol_env_time_constants"]
+    vol_env_time_constants: FloatParam,
+    #[id = "filter_env_time_constants"]
+    filter_env_time_constants: FloatParam,
+}
+
+#[deny(dead_code)]
+#[derive(Params)]
+pub struct VibratoGroup {
+    // The maximum amount that the vibrato moves the pitch by, in cents.
     #[id = "vibrato_amount"]
     pub vibrato_amount: FloatParam,
     #[id = "vibrato_attack"]
     pub vibrato_attack: FloatParam,
     #[id = "vibrato_rate"]
     pub vibrato_rate: EnumParam<VibratoRate>,
-    #[id = "portamento_time"]
-    pub portamento_time: FloatParam,
-    #[id = "noise_mix"]
-    pub noise_mix: FloatParam,
-    #[id = "chorus_mix"]
-    pub chorus_mix: FloatParam,
-    #[id = "pitch_bend"]
-    pub pitch_bend: IntParam,
-    #[id = "polycat"]
-    pub polycat: BoolParam,
-    // Internal parameter (not exposed by the original Meowsynth)
-    #[id = "gain"]
-    gain: FloatParam,
+    // "Debug" parameter (this might become not "debug" pretty soon). This is internal.
+    #[id = "vibrato_note_shape"]
+    pub vibrato_note_shape: EnumParam<NoteShape>,
+    #[id = "vibrato_mode"]
+    vibrato_mode: EnumParam<VibratoMode>,
+    // In Trill mode, the maximum number of semitones the vibrato moves the pitch by.
+    #[id = "trill_range"]
+    trill_range: IntParam,
+}
+
+// Internal parameters (not exposed by the original Meowsynth)
+#[deny(dead_code)]
+#[derive(Params)]
+pub struct FilterGroup {
     #[id = "filter_envlope_mod"]
     filter_envlope_mod: FloatParam,
     #[id = "filter_dry_wet"]
     filter_dry_wet: FloatParam,
+    // Internal, since the equal power law is almost always what is wanted.
+    #[id = "filter_mix_law"]
+    pub filter_mix_law: EnumParam<MixLaw>,
+    // Gain applied to the signal going into the filter, and to the signal coming out of it.
+    #[id = "filter_pre_gain"]
+    filter_pre_gain: FloatParam,
+    #[id = "filter_post_gain"]
+    filter_post_gain: FloatParam,
+    // How much the note off velocity changes the filter envelope's release time. Hard releases
+    // shorten the release, and soft releases lengthen it.
+    #[id = "filter_release_vel"]
+    filter_release_vel: FloatParam,
+    // How much the note on velocity scales the filter envelope mod. At 0%, every note gets the full
+    // envelope mod, regardless of how hard it was played.
+    #[id = "filter_env_vel"]
+    filter_env_vel: FloatParam,
     #[id = "filter_q"]
     filter_q: FloatParam,
+    // How much the filter's output is turned down as the Q rises, to make up for the resonant peak.
+    // This is internal, since it only needs to be changed to match old presets.
+    #[id = "filter_q_compensation"]
+    pub filter_q_compensation: FloatParam,
+    // Soft clips the filter's output, so that the resonance can't get much louder than full scale.
+    #[id = "filter_q_limiter"]
+    filter_q_limiter: BoolParam,
+    // How loudly the filter whistles at its cutoff frequency when the Q is high.
+    #[id = "filter_self_osc"]
+    filter_self_osc: FloatParam,
+    // If enabled, the cutoff (and so the self-oscillation pitch) follows the note being played.
+    #[id = "filter_keytrack"]
+    filter_keytrack: BoolParam,
+    // How far the oscillator sweeps the cutoff at audio rate, as a fraction of MAX_FILTER_FM.
+    #[id = "filter_fm"]
+    filter_fm: FloatParam,
     #[id = "filter_type"]
     filter_type: EnumParam<FilterType>,
     #[id = "filter_cutoff_freq"]
     filter_cutoff_freq: FloatParam,
+    // Caps the cutoff at 20 kHz, like the original Meowsynth, instead of letting it open up to
+    // just below Nyquist at high sample rates.
+    #[id = "filter_vintage_cap"]
+    filter_vintage_cap: BoolParam,
+}
+
+impl FilterGroup {
+    /// Fill `dry_wet` with the filter dry/wet for each sample of the block. The dry/wet is smoothed
+    /// per sample (instead of per block) since it is applied directly to the signal.
+    pub fn dry_wet_block(&self, dry_wet: &mut [f32]) {
+        self.filter_dry_wet
+            .smoothed
+            .next_block(dry_wet, dry_wet.len());
+    }
+}
+
+/// The second filter, which is a plain static filter with no envelope.
+#[deny(dead_code)]
+#[derive(Params)]
+pub struct SecondFilterGroup {
+    #[id = "filter_routing"]
+    filter_routing: EnumParam<FilterRouting>,
+    // The mix between the two filters. At 50%, both filters are at full volume.
+    #[id = "filter_balance"]
+    filter_balance: FloatParam,
+    // The crossover frequency between the two bands in split mode.
+    #[id = "filter_split_freq"]
+    filter_split_freq: FloatParam,
+    #[id = "filter_2_type"]
+    filter_2_type: EnumParam<FilterType>,
+    #[id = "filter_2_cutoff_freq"]
+    filter_2_cutoff_freq: FloatParam,
+    #[id = "filter_2_q"]
+    filter_2_q: FloatParam,
+}
+
+#[deny(dead_code)]
+#[derive(Params)]
+pub struct ChorusGroup {
+    #[id = "chorus_mix"]
+    pub chorus_mix: FloatParam,
+    // Internal parameters (not exposed by the original Meowsynth)
     #[id = "chorus_depth"]
     chorus_depth: FloatParam,
     #[id = "chorus_distance"]
     chorus_distance: FloatParam,
     #[id = "chorus_rate"]
     chorus_rate: FloatParam,
-    // "Debug" parameters (these might become not "debug" pretty soon)
-    #[id = "vibrato_note_shape"]
-    vibrato_note_shape: EnumParam<NoteShape>,
+    #[id = "chorus_quality"]
+    chorus_quality: EnumParam<ChorusQuality>,
+    // The number of read heads. The read heads are modulated by the same LFO, with their phases
+    // spread evenly apart.
+    #[id = "chorus_voices"]
+    chorus_voices: IntParam,
+    // "Debug" parameter (this might become not "debug" pretty soon). This is internal.
     #[id = "chorus_note_shape"]
-    chorus_note_shape: EnumParam<NoteShape>,
+    pub chorus_note_shape: EnumParam<NoteShape>,
+}
+
+/// The detuned oscillator copies ("supermeow" mode).
+#[deny(dead_code)]
+#[derive(Params)]
+pub struct SupermeowGroup {
+    // The total number of oscillators, including the main oscillator. At 1, supermeow mode is off.
+    #[id = "supermeow_voices"]
+    supermeow_voices: IntParam,
+    // How far the outermost copies are detuned, in cents.
+    #[id = "supermeow_detune"]
+    supermeow_detune: FloatParam,
+    // The mix between the main oscillator and the detuned copies.
+    #[id = "supermeow_blend"]
+    supermeow_blend: FloatParam,
+}
+
+/// The comb resonator, which is tuned to each note's pitch.
+#[deny(dead_code)]
+#[derive(Params)]
+pub struct ResonatorGroup {
+    #[id = "resonator_mix"]
+    resonator_mix: FloatParam,
+    // How quickly the high harmonics die out. Higher damping gives a duller, more muted body.
+    #[id = "resonator_damping"]
+    resonator_damping: FloatParam,
+}
+
+/// The purr, which pulses the volume of low notes. See [Purr](crate::purr::Purr).
+#[deny(dead_code)]
+#[derive(Params)]
+pub struct PurrGroup {
+    // How deeply the purr pulses the volume.
+    #[id = "purr_amount"]
+    purr_amount: FloatParam,
+    // How many pulses there are each second. Real cats purr at around 25 Hz.
+    #[id = "purr_speed"]
+    purr_speed: FloatParam,
+    // How much each pulse randomly varies in length and height.
+    #[id = "purr_roughness"]
+    purr_roughness: FloatParam,
+}
+
+/// The growl, which modulates the filter or the volume at a low audio rate and saturates the voice,
+/// for snarling meows. See [Growl](crate::growl::Growl).
+#[deny(dead_code)]
+#[derive(Params)]
+pub struct GrowlGroup {
+    #[id = "growl_amount"]
+    growl_amount: FloatParam,
+    #[id = "growl_speed"]
+    growl_speed: FloatParam,
+    #[id = "growl_target"]
+    growl_target: EnumParam<GrowlTarget>,
+    // How much aftertouch (channel or polyphonic pressure) adds to the growl amount.
+    #[id = "growl_aftertouch"]
+    growl_aftertouch: FloatParam,
+}
+
+/// The envelope follower, which tracks the loudness of the synth's output and uses it to modulate
+/// the voices.
+#[deny(dead_code)]
+#[derive(Params)]
+pub struct FollowerGroup {
+    #[id = "follower_attack"]
+    follower_attack: FloatParam,
+    #[id = "follower_release"]
+    follower_release: FloatParam,
+    // How much the follower raises (or, if negative, ducks) the noise mix.
+    #[id = "follower_to_noise"]
+    follower_to_noise: FloatParam,
+    // How much the follower opens (or, if negative, closes) the filter cutoff.
+    #[id = "follower_to_filter"]
+    follower_to_filter: FloatParam,
+}
+
+/// The "meow squash" compressor on the master bus.
+#[deny(dead_code)]
+#[derive(Params)]
+pub struct CompressorGroup {
+    #[id = "comp_threshold"]
+    comp_threshold: FloatParam,
+    // A ratio of 1:1 turns the compressor off.
+    #[id = "comp_ratio"]
+    comp_ratio: FloatParam,
+    #[id = "comp_attack"]
+    comp_attack: FloatParam,
+    #[id = "comp_release"]
+    comp_release: FloatParam,
+    #[id = "comp_makeup"]
+    comp_makeup: FloatParam,
+}
+
+/// The mid/side width control on the master output, which runs after the chorus.
+#[deny(dead_code)]
+#[derive(Params)]
+pub struct StereoGroup {
+    // The amount that the side signal is scaled by. 0% is mono and 200% is extra wide.
+    #[id = "stereo_width"]
+    stereo_width: FloatParam,
+    // If on, everything below the mono bass frequency is kept in the center.
+    #[id = "mono_bass"]
+    mono_bass: BoolParam,
+    #[id = "mono_bass_freq"]
+    mono_bass_freq: FloatParam,
+}
+
+#[deny(dead_code)]
+#[derive(Params)]
+pub struct GlobalGroup {
+    #[id = "portamento_time"]
+    pub portamento_time: FloatParam,
+    #[id = "portamento_mode"]
+    portamento_mode: EnumParam<PortamentoMode>,
+    // What happens in monocat mode when a key is pressed while another is held. In slide mode, the
+    // pitch slides to the new note over the slide time, and the envelopes keep running.
+    #[id = "legato_mode"]
+    legato_mode: EnumParam<LegatoMode>,
+    #[id = "slide_time"]
+    slide_time: FloatParam,
+    #[id = "noise_mix"]
+    pub noise_mix: FloatParam,
+    // How decorrelated the noise is between the left and right channels. At 0%, the noise is mono,
+    // and at 100%, each channel gets independent noise.
+    #[id = "noise_width"]
+    noise_width: FloatParam,
+    // If enabled, the tonal oscillator is muted and only the noise plays, at full level. Along with
+    // the filter envelope, this makes hissing sounds.
+    #[id = "hiss_mode"]
+    hiss_mode: BoolParam,
+    #[id = "pitch_bend"]
+    pub pitch_bend: IntParam,
+    // How long the pitch bend takes to glide to each new value. This hides the steps between
+    // values from coarse (7-bit) controllers.
+    #[id = "pitch_bend_smoothing"]
+    pitch_bend_smoothing: FloatParam,
+    // The MPE zone, and the pitch bend range of the zone's member channels. The pitch bend range
+    // of the master channel is the normal pitch bend range.
+    #[id = "mpe_zone"]
+    mpe_zone: EnumParam<MpeZone>,
+    #[id = "mpe_bend_range"]
+    mpe_bend_range: IntParam,
+    #[id = "polycat"]
+    pub polycat: BoolParam,
+    // The maximum number of voices which may play at once in polycat mode.
+    #[id = "max_voices"]
+    max_voices: IntParam,
+    // If enabled, the notes which the synth plays are sent out as MIDI, so that other instruments
+    // can follow along.
+    #[id = "midi_out"]
+    midi_out: BoolParam,
+    // If enabled, CCs 0-31 and 32-63 are combined into 14-bit controllers for MIDI learn.
+    #[id = "hires_cc"]
+    hires_cc: BoolParam,
+    // Incoming notes are snapped to this scale before being played.
+    #[id = "scale"]
+    scale: EnumParam<Scale>,
+    #[id = "scale_root"]
+    scale_root: EnumParam<Root>,
+    // The maximum random detune applied to each note, in cents. If quantized, the detuned note is
+    // snapped to the scale.
+    #[id = "stray_cat"]
+    stray_cat: FloatParam,
+    #[id = "stray_cat_quantize"]
+    stray_cat_quantize: BoolParam,
+    // How long a retriggered voice takes to fade out. Shorter fades are punchier, but may click.
+    #[id = "retrigger_fade"]
+    retrigger_fade: FloatParam,
+    // Trades CPU usage for sound quality. This sets several internal update rates, interpolation
+    // modes, and the saturator's oversampling at once.
+    #[id = "quality"]
+    quality: EnumParam<Quality>,
+    // If enabled, the vibrato and chorus LFOs are lined up with the host's bar while it plays, so
+    // that tempo synced modulation lands on the beat.
+    #[id = "sync_modulation"]
+    sync_modulation: BoolParam,
+    // A performance control which scales every modulation amount at once, from none at all up to
+    // twice the preset's amounts. 100% (the center) leaves the preset as it is.
+    #[id = "mod_depth"]
+    mod_depth: FloatParam,
+    // Internal parameter (not exposed by the original Meowsynth)
+    #[id = "gain"]
+    gain: FloatParam,
+    // Internal parameter. A gain correction which is saved with each preset, so that presets can be
+    // matched in loudness without touching the master volume, which belongs to the user.
+    #[id = "preset_trim"]
+    pub preset_trim: FloatParam,
+}
+
+/// The macro knobs. Each macro drives several other parameters at once, as set by the
+/// [MacroAssignments]. The morph knob drives every other parameter at once, by interpolating
+/// between the two [MorphSnapshots].
+#[deny(dead_code)]
+#[derive(Params)]
+pub struct MacroGroup {
+    #[id = "macro_1"]
+    pub macro_1: FloatParam,
+    #[id = "macro_2"]
+    pub macro_2: FloatParam,
+    #[id = "macro_3"]
+    pub macro_3: FloatParam,
+    #[id = "macro_4"]
+    pub macro_4: FloatParam,
+    #[id = "morph"]
+    pub morph: FloatParam,
+}
+
+impl MacroGroup {
+    pub fn macros(&self) -> [&FloatParam; NUM_MACROS] {
+        [&self.macro_1, &self.macro_2, &self.macro_3, &self.macro_4]
+    }
+}
+
+/// Buttons which step through the preset browser's presets. These are parameters so that they can
+/// be mapped to buttons on a controller. Turning a button on switches presets, and it must be
+/// turned off again before it can be used again.
+#[deny(dead_code)]
+#[derive(Params)]
+pub struct PresetGroup {
+    #[id = "next_preset"]
+    pub next_preset: BoolParam,
+    #[id = "previous_preset"]
+    pub previous_preset: BoolParam,
+}
+
+/// The host's bypass switch. Bypassing fades the output out, and un-bypassing fades it back in.
+#[deny(dead_code)]
+#[derive(Params)]
+pub struct BypassGroup {
+    #[id = "bypass"]
+    pub bypass: BoolParam,
+    // Whether the notes which were playing when the synth was bypassed ring out, or are cut off.
+    #[id = "bypass_tails"]
+    bypass_tails: EnumParam<BypassTails>,
+}
+
+/// How notes are shared between layer A, which is the sound shown in the editor, and layer B,
+/// which is a stored copy of another sound. See [LayerMode].
+#[deny(dead_code)]
+#[derive(Params)]
+pub struct LayerGroup {
+    #[id = "layer_mode"]
+    pub layer_mode: EnumParam<LayerMode>,
+    // In key split mode, notes below this note play on layer B.
+    #[id = "layer_split_note"]
+    pub layer_split_note: IntParam,
+    // In velocity switch mode, notes at or above this velocity play on layer B.
+    #[id = "layer_split_velocity"]
+    pub layer_split_velocity: FloatParam,
+}
+
+/// Keyswitches switch between the [ARTICULATIONS](crate::articulations::ARTICULATIONS). The new
+/// articulation takes effect from the next note, so notes which are already playing don't jump.
+#[deny(dead_code)]
+#[derive(Params)]
+pub struct KeyswitchGroup {
+    #[id = "keyswitches"]
+    pub keyswitches: BoolParam,
+    // The octave which the keyswitches are in. The keys in it which switch articulations don't play
+    // notes.
+    #[id = "keyswitch_octave"]
+    pub keyswitch_octave: IntParam,
 }
 
 impl Default for Parameters {
@@ -219,25 +1204,107 @@ impl Parameters {
             }
         }
 
-        fn time(name: &'static str, default: Seconds, min: f32, max: f32) -> FloatParam {
-            fn formatter(value: f32) -> String {
-                if value < 1.0 {
-                    format!("{:.1} ms", value * 1000.0)
-                } else {
-                    format!("{:.2} sec", value)
-                }
+        // MIDI notes are shown by name, with middle C (note 60) as C4.
+        const NOTE_NAMES: [&str; 12] = [
+            "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+        ];
+
+        fn note_formatter(note: i32) -> String {
+            let name = NOTE_NAMES[note.rem_euclid(12) as usize];
+            format!("{}{}", name, note.div_euclid(12) - 1)
+        }
+
+        // Either a note name, such as "C#4", or a note number.
+        fn note_parser(string: &str) -> Option<i32> {
+            let string = string.trim().to_uppercase();
+            if let Ok(note) = string.parse() {
+                return Some(note);
+            }
+            // Sharps are checked first, so that "C#4" isn't read as "C" with an octave of "#4".
+            let (pitch_class, octave) = NOTE_NAMES
+                .iter()
+                .enumerate()
+                .rev()
+                .find_map(|(i, name)| Some((i as i32, string.strip_prefix(name)?)))?;
+            let octave: i32 = octave.trim().parse().ok()?;
+            Some((octave + 1) * 12 + pitch_class)
+        }
+
+        fn time_formatter(value: f32) -> String {
+            // Checking the rounded value avoids showing "1000.0 ms" for values just under a second.
+            if value * 1000.0 < 999.95 {
+                format!("{:.1} ms", value * 1000.0)
+            } else {
+                format!("{:.2} sec", value)
             }
+        }
 
+        fn time_parser(string: &str) -> Option<f32> {
+            let string = string.trim().to_lowercase();
+            if let Some(ms) = string.strip_suffix("ms") {
+                ms.trim().parse::<f32>().ok().map(|ms| ms / 1000.0)
+            } else {
+                let seconds = string.trim_end_matches("sec").trim_end_matches('s');
+                seconds.trim().parse().ok()
+            }
+        }
+
+        fn sustain_fade_formatter(value: f32) -> String {
+            if value == 0.0 {
+                "Off".to_string()
+            } else {
+                // Tiny fades are shown as the smallest displayable time, rather than as "0.0 ms",
+                // which would read back as "Off".
+                time_formatter(value.max(0.0001))
+            }
+        }
+
+        fn sustain_fade_parser(string: &str) -> Option<f32> {
+            if string.trim().eq_ignore_ascii_case("off") {
+                Some(0.0)
+            } else {
+                time_parser(string)
+            }
+        }
+
+        fn time(name: &'static str, default: Seconds, min: f32, max: f32) -> FloatParam {
             let range = FloatRange::Skewed {
                 min,
                 max,
                 factor: FloatRange::skew_factor(-2.0),
             };
-            FloatParam::new(name, default.get(), range).with_value_to_string(Arc::new(formatter))
+            FloatParam::new(name, default.get(), range)
+                .with_smoother(CONTINUOUS_SMOOTHING)
+                .with_value_to_string(Arc::new(time_formatter))
+                .with_string_to_value(Arc::new(time_parser))
         }
 
-        fn decibel(name: &'static str, default: Decibel, min: f32, max: f32) -> FloatParam {
-            fn formatter(decibel: f32) -> String {
+        fn decibel(
+            name: &'static str,
+            default: Decibel,
+            min: f32,
+            max: f32,
+            taper: DbTaper,
+        ) -> FloatParam {
+            // Before 1.4, decibels were half of what they should have been, and so were these
+            // ranges. The skew is still computed from the old range, so that normalized values
+            // (and so host automation) land on the same levels as before.
+            let range = FloatRange::Skewed {
+                min,
+                max,
+                factor: FloatRange::gain_skew_factor(min / 2.0, max / 2.0),
+            };
+
+            // The level is shown after the taper, so that the bottom of the knob reads as the
+            // level that is actually played.
+            let formatter = move |decibel: f32| {
+                let decibel = taper
+                    .apply(
+                        range.normalize(decibel),
+                        Decibel::from_db(decibel),
+                        Decibel::from_db(range.unnormalize(taper.split_at)),
+                    )
+                    .get_db();
                 if decibel <= Decibel::NEG_INF_DB_THRESHOLD {
                     "-inf".to_string()
                 } else if decibel < 0.0 {
@@ -245,112 +1312,876 @@ impl Parameters {
                 } else {
                     format!("+{:.2}", decibel)
                 }
-            }
-
-            let range = FloatRange::Skewed {
-                min,
-                max,
-                factor: FloatRange::gain_skew_factor(min, max),
             };
+
             FloatParam::new(name, default.get_db(), range)
+                .with_smoother(CONTINUOUS_SMOOTHING)
                 .with_unit(" db")
                 .with_value_to_string(Arc::new(formatter))
         }
 
+        // Percentages are displayed out of 100, so typed in values need to be scaled back down.
+        fn percent_parser(string: &str) -> Option<f32> {
+            let percent: f32 = string.trim().trim_end_matches('%').trim().parse().ok()?;
+            Some(percent / 100.0)
+        }
+
         fn percent(name: &'static str, default: f32) -> FloatParam {
             fn formatter(percent: f32) -> String {
                 format!("{:.1}", percent * 100.0)
             }
             let range = FloatRange::Linear { min: 0.0, max: 1.0 };
             FloatParam::new(name, default, range)
+                .with_smoother(CONTINUOUS_SMOOTHING)
+                .with_unit(" %")
+                .with_value_to_string(Arc::new(formatter))
+                .with_string_to_value(Arc::new(percent_parser))
+        }
+
+        fn time_constants(name: &'static str) -> FloatParam {
+            let range = FloatRange::Linear {
+                min: 1.0,
+                max: 10.0,
+            };
+            FloatParam::new(name, DEFAULT_ENVELOPE_TIME_CONSTANTS, range)
+                .with_smoother(CONTINUOUS_SMOOTHING)
+                .with_value_to_string(Arc::new(|time_constants| format!("{:.1}", time_constants)))
+        }
+
+        fn bipolar_percent(name: &'static str, default: f32) -> FloatParam {
+            fn formatter(percent: f32) -> String {
+                let percent = percent * 100.0;
+                // Avoid showing "-0.0" for tiny negative values.
+                if percent.abs() < 0.05 {
+                    "0.0".to_string()
+                } else {
+                    format!("{:+.1}", percent)
+                }
+            }
+            let range = FloatRange::Linear {
+                min: -1.0,
+                max: 1.0,
+            };
+            FloatParam::new(name, default, range)
+                .with_smoother(CONTINUOUS_SMOOTHING)
                 .with_unit(" %")
                 .with_value_to_string(Arc::new(formatter))
+                .with_string_to_value(Arc::new(percent_parser))
         }
 
         pub fn freq(name: &'static str, default: Hertz, range: FloatRange) -> FloatParam {
             fn formatter(hz: f32) -> String {
-                if hz < 1000.0 {
+                // Bipolar frequencies shouldn't show "-0.00 Hz" for tiny negative values.
+                let hz = if hz.abs() < 0.005 { 0.0 } else { hz };
+                if hz.abs() < 999.995 {
                     format!("{:.2} Hz", hz)
                 } else {
                     format!("{:.2} kHz", hz / 1000.0)
                 }
             }
-            FloatParam::new(name, default.get(), range).with_value_to_string(Arc::new(formatter))
+            fn parser(string: &str) -> Option<f32> {
+                let string = string.trim().to_lowercase();
+                if let Some(khz) = string.strip_suffix("khz") {
+                    khz.trim().parse::<f32>().ok().map(|khz| khz * 1000.0)
+                } else {
+                    string.trim_end_matches("hz").trim().parse().ok()
+                }
+            }
+            FloatParam::new(name, default.get(), range)
+                .with_smoother(CONTINUOUS_SMOOTHING)
+                .with_value_to_string(Arc::new(formatter))
+                .with_string_to_value(Arc::new(parser))
         }
 
-        let filter_envelope_mod = Hertz::ease_exp(0.0, 22100.0);
+        // The envelope mod is bipolar, so that the envelope can close the filter as well as open
+        // it. The range is skewed towards the center on both sides, which makes it easy to dial in
+        // exactly zero.
+        let filter_envelope_mod = FloatRange::SymmetricalSkewed {
+            min: -22100.0,
+            max: 22100.0,
+            factor: 6.0,
+            center: 0.0,
+        };
         let filter_cutoff_freq = Hertz::ease_exp(20.0, 22100.0);
-        let filter_q = common::ease_linear(0.01, 10.0);
+        let filter_q = common::ease_linear(0.01, MAX_FILTER_Q);
 
         let chorus_rate = Hertz::ease_exp(0.1, 10.0);
         let chorus_depth = common::ease_linear(0.0, MAX_CHORUS_DEPTH);
         let chorus_distance = common::ease_linear(0.0, MAX_CHORUS_DISTANCE);
 
         Parameters {
-            meow_attack: time("Meow Attack", DEFAULT_MEOW_ATTACK, 0.001, 10.0),
-            meow_decay: time("Meow Decay", DEFAULT_MEOW_DECAY, 0.001, 5.0),
-            meow_sustain: decibel("Meow Sustain", DEFAULT_MEOW_SUSTAIN, -24.0, 0.0),
-            meow_release: time("Meow Release", DEFAULT_MEOW_RELEASE, 0.001, 4.0),
-            vibrato_amount: percent("Vibrato Amount", DEFAULT_VIBRATO_AMOUNT),
-            vibrato_attack: time("Vibrato Attack", DEFAULT_VIBRATO_ATTACK, 0.001, 5.0),
-            vibrato_rate: EnumParam::new("Vibrato Rate", DEFAULT_VIBRATO_RATE),
-            portamento_time: time("Portamento", DEFAULT_PORTAMENTO, 0.0001, 5.0),
-            noise_mix: percent("Noise", DEFAULT_NOISE_MIX),
-            chorus_mix: percent("Chorus", DEFAULT_CHORUS_MIX),
-            pitch_bend: IntParam::new(
-                "Pitchbend",
-                DEFAULT_PITCHBEND as i32,
-                IntRange::Linear { min: 1, max: 12 },
-            ),
-            polycat: BoolParam::new("Polycat", DEFAULT_POLYCAT)
+            envelope: EnvelopeGroup {
+                // The attack and decay can go all the way down to 0 ms. The envelope adds a tiny
+                // ramp of its own to avoid clicks.
+                meow_attack: time("Meow Attack", DEFAULT_MEOW_ATTACK, 0.0, 10.0),
+                meow_decay: time("Meow Decay", DEFAULT_MEOW_DECAY, 0.0, 5.0),
+                meow_sustain: decibel(
+                    "Meow Sustain",
+                    DEFAULT_MEOW_SUSTAIN,
+                    -48.0,
+                    0.0,
+                    MEOW_SUSTAIN_TAPER,
+                ),
+                meow_release: time("Meow Release", DEFAULT_MEOW_RELEASE, 0.001, 4.0),
+                meow_sustain_fade: time("Meow Sustain Fade", DEFAULT_MEOW_SUSTAIN_FADE, 0.0, 30.0)
+                    .with_value_to_string(Arc::new(sustain_fade_formatter))
+                    .with_string_to_value(Arc::new(sustain_fade_parser)),
+                envelope_tempo_sync: BoolParam::new(
+                    "Envelope Tempo Sync",
+                    DEFAULT_ENVELOPE_TEMPO_SYNC,
+                )
+                .with_value_to_string(Arc::new(polycat_formatter)),
+                meow_attack_sync: EnumParam::new("Meow Attack (Synced)", DEFAULT_MEOW_ATTACK_SYNC),
+                meow_decay_sync: EnumParam::new("Meow Decay (Synced)", DEFAULT_MEOW_DECAY_SYNC),
+                meow_release_sync: EnumParam::new(
+                    "Meow Release (Synced)",
+                    DEFAULT_MEOW_RELEASE_SYNC,
+                ),
+                envelope_keytrack: percent("Envelope Keytrack", DEFAULT_ENVELOPE_KEYTRACK),
+                attack_keytrack: percent("Attack Keytrack", DEFAULT_ATTACK_KEYTRACK),
+                vel_to_attack: bipolar_percent("Velocity > Attack", DEFAULT_VEL_TO_ATTACK),
+                vel_to_decay: bipolar_percent("Velocity > Decay", DEFAULT_VEL_TO_DECAY),
+                envelope_cc_output: BoolParam::new(
+                    "Envelope CC Output",
+                    DEFAULT_ENVELOPE_CC_OUTPUT,
+                )
                 .with_value_to_string(Arc::new(polycat_formatter)),
-            // Internal parameters (might not be exposed)
-            gain: decibel("Master Volume", DEFAULT_MASTER_VOL, -36.0, 12.0),
-            filter_envlope_mod: freq(
-                "Filter EnvMod",
-                DEFAULT_FILTER_ENVLOPE_MOD,
-                filter_envelope_mod,
-            ),
-            filter_dry_wet: percent("Filter DryWet", DEFAULT_FILTER_DRY_WET),
-            filter_q: FloatParam::new("Filter Q", DEFAULT_FILTER_Q, filter_q),
-            filter_type: EnumParam::new("Filter Type", DEFAULT_FILTER_TYPE),
-            filter_cutoff_freq: freq(
-                "Filter Cutoff",
-                DEFAULT_FILTER_CUTOFF_FREQ,
-                filter_cutoff_freq,
-            ),
-            chorus_depth: FloatParam::new("Chorus Depth", DEFAULT_CHORUS_DEPTH, chorus_depth),
-            chorus_distance: FloatParam::new(
-                "Chorus Distance",
-                DEFAULT_CHORUS_DISTANCE,
-                chorus_distance,
-            ),
-            chorus_rate: freq("Chorus Rate", DEFAULT_CHORUS_RATE, chorus_rate),
-            vibrato_note_shape: EnumParam::new("Vibrato Note Shape", NoteShape::Triangle),
-            chorus_note_shape: EnumParam::new("Chorus Note Shape", NoteShape::Sine),
+                amp_envelope_cc: IntParam::new(
+                    "Amp Envelope CC",
+                    DEFAULT_AMP_ENVELOPE_CC as i32,
+                    IntRange::Linear { min: 0, max: 127 },
+                ),
+                filter_envelope_cc: IntParam::new(
+                    "Filter Envelope CC",
+                    DEFAULT_FILTER_ENVELOPE_CC as i32,
+                    IntRange::Linear { min: 0, max: 127 },
+                ),
+                envelope_cc_channel: IntParam::new(
+                    "Envelope CC Channel",
+                    DEFAULT_ENVELOPE_CC_CHANNEL as i32,
+                    IntRange::Linear { min: 1, max: 16 },
+                ),
+                vol_env_curve: EnumParam::new("Meow Env Curve", DEFAULT_ENVELOPE_CURVE),
+                filter_env_curve: EnumParam::new("Filter Env Curve", DEFAULT_ENVELOPE_CURVE),
+                vol_env_time_constants: time_constants("Meow Env Time Constants"),
+                filter_env_time_constants: time_constants("Filter Env Time Constants"),
+            },
+            vibrato: VibratoGroup {
+                // This is linear so that the knob moves the same way it did when the amount was
+                // a percentage.
+                vibrato_amount: FloatParam::new(
+                    "Vibrato Amount",
+                    DEFAULT_VIBRATO_AMOUNT,
+                    FloatRange::Linear {
+                        min: 0.0,
+                        max: MAX_VIBRATO_AMOUNT,
+                    },
+                )
+                .with_smoother(CONTINUOUS_SMOOTHING)
+                .with_step_size(1.0)
+                .with_unit(" cents")
+                .with_value_to_string(Arc::new(|cents| format!("{:.0}", cents))),
+                vibrato_attack: time("Vibrato Attack", DEFAULT_VIBRATO_ATTACK, 0.001, 5.0),
+                vibrato_rate: EnumParam::new("Vibrato Rate", DEFAULT_VIBRATO_RATE),
+                vibrato_note_shape: EnumParam::new("Vibrato Note Shape", NoteShape::Triangle)
+                    .hide(),
+                vibrato_mode: EnumParam::new("Vibrato Mode", DEFAULT_VIBRATO_MODE),
+                trill_range: IntParam::new(
+                    "Trill Range",
+                    DEFAULT_TRILL_RANGE as i32,
+                    IntRange::Linear { min: 1, max: 12 },
+                )
+                .with_unit(" semis"),
+            },
+            filter: FilterGroup {
+                filter_envlope_mod: freq(
+                    "Filter EnvMod",
+                    DEFAULT_FILTER_ENVLOPE_MOD,
+                    filter_envelope_mod,
+                ),
+                filter_dry_wet: percent("Filter DryWet", DEFAULT_FILTER_DRY_WET),
+                filter_mix_law: EnumParam::new("Filter Mix Law", DEFAULT_FILTER_MIX_LAW).hide(),
+                filter_pre_gain: decibel(
+                    "Pre-Filter Gain",
+                    DEFAULT_FILTER_PRE_GAIN,
+                    -48.0,
+                    48.0,
+                    FILTER_PRE_GAIN_TAPER,
+                ),
+                filter_post_gain: decibel(
+                    "Post-Filter Gain",
+                    DEFAULT_FILTER_POST_GAIN,
+                    -48.0,
+                    48.0,
+                    FILTER_POST_GAIN_TAPER,
+                ),
+                filter_release_vel: percent("Filter Release Vel", DEFAULT_FILTER_RELEASE_VEL),
+                filter_env_vel: percent("Filter Env Vel", DEFAULT_FILTER_ENV_VEL),
+                filter_q: FloatParam::new("Filter Q", DEFAULT_FILTER_Q, filter_q)
+                    .with_smoother(CONTINUOUS_SMOOTHING)
+                    .with_value_to_string(Arc::new(|q| format!("{:.2}", q))),
+                filter_q_compensation: percent("Q Compensation", DEFAULT_FILTER_Q_COMPENSATION)
+                    .hide(),
+                filter_q_limiter: BoolParam::new("Q Limiter", DEFAULT_FILTER_Q_LIMITER)
+                    .with_value_to_string(Arc::new(polycat_formatter)),
+                filter_self_osc: percent("Self-Oscillation", DEFAULT_FILTER_SELF_OSC),
+                filter_keytrack: BoolParam::new("Filter Keytrack", DEFAULT_FILTER_KEYTRACK)
+                    .with_value_to_string(Arc::new(polycat_formatter)),
+                filter_fm: percent("Filter FM", DEFAULT_FILTER_FM),
+                filter_type: EnumParam::new("Filter Type", DEFAULT_FILTER_TYPE),
+                filter_cutoff_freq: freq(
+                    "Filter Cutoff",
+                    DEFAULT_FILTER_CUTOFF_FREQ,
+                    filter_cutoff_freq,
+                ),
+                filter_vintage_cap: BoolParam::new("Vintage 20k Cap", DEFAULT_FILTER_VINTAGE_CAP)
+                    .with_value_to_string(Arc::new(polycat_formatter)),
+            },
+            filter_2: SecondFilterGroup {
+                filter_routing: EnumParam::new("Filter Routing", DEFAULT_FILTER_ROUTING),
+                filter_balance: percent("Filter Balance", DEFAULT_FILTER_BALANCE),
+                filter_split_freq: freq(
+                    "Filter Split",
+                    DEFAULT_FILTER_SPLIT_FREQ,
+                    filter_cutoff_freq,
+                ),
+                filter_2_type: EnumParam::new("Filter 2 Type", DEFAULT_FILTER_2_TYPE),
+                filter_2_cutoff_freq: freq(
+                    "Filter 2 Cutoff",
+                    DEFAULT_FILTER_2_CUTOFF_FREQ,
+                    filter_cutoff_freq,
+                ),
+                filter_2_q: FloatParam::new("Filter 2 Q", DEFAULT_FILTER_2_Q, filter_q)
+                    .with_smoother(CONTINUOUS_SMOOTHING)
+                    .with_value_to_string(Arc::new(|q| format!("{:.2}", q))),
+            },
+            chorus: ChorusGroup {
+                chorus_mix: percent("Chorus", DEFAULT_CHORUS_MIX),
+                chorus_depth: FloatParam::new("Chorus Depth", DEFAULT_CHORUS_DEPTH, chorus_depth)
+                    .with_smoother(CONTINUOUS_SMOOTHING)
+                    .with_value_to_string(Arc::new(|depth| format!("{:.1}", depth))),
+                chorus_distance: FloatParam::new(
+                    "Chorus Distance",
+                    DEFAULT_CHORUS_DISTANCE,
+                    chorus_distance,
+                )
+                .with_smoother(CONTINUOUS_SMOOTHING)
+                .with_value_to_string(Arc::new(|distance| format!("{:.1}", distance))),
+                chorus_rate: freq("Chorus Rate", DEFAULT_CHORUS_RATE, chorus_rate),
+                chorus_quality: EnumParam::new("Chorus Quality", DEFAULT_CHORUS_QUALITY),
+                chorus_voices: IntParam::new(
+                    "Chorus Voices",
+                    DEFAULT_CHORUS_VOICES as i32,
+                    IntRange::Linear { min: 1, max: 4 },
+                ),
+                chorus_note_shape: EnumParam::new("Chorus Note Shape", NoteShape::Sine).hide(),
+            },
+            supermeow: SupermeowGroup {
+                supermeow_voices: IntParam::new(
+                    "Supermeow Voices",
+                    DEFAULT_SUPERMEOW_VOICES as i32,
+                    IntRange::Linear {
+                        min: 1,
+                        max: MAX_SUPERMEOW_VOICES as i32,
+                    },
+                ),
+                supermeow_detune: FloatParam::new(
+                    "Supermeow Detune",
+                    DEFAULT_SUPERMEOW_DETUNE,
+                    FloatRange::Linear {
+                        min: 0.0,
+                        max: 100.0,
+                    },
+                )
+                .with_smoother(CONTINUOUS_SMOOTHING)
+                .with_unit(" cents")
+                .with_value_to_string(Arc::new(|cents| format!("{:.1}", cents))),
+                supermeow_blend: percent("Supermeow Blend", DEFAULT_SUPERMEOW_BLEND),
+            },
+            resonator: ResonatorGroup {
+                resonator_mix: percent("Resonator", DEFAULT_RESONATOR_MIX),
+                resonator_damping: percent("Resonator Damping", DEFAULT_RESONATOR_DAMPING),
+            },
+            purr: PurrGroup {
+                purr_amount: percent("Purr", DEFAULT_PURR_AMOUNT),
+                purr_speed: freq(
+                    "Purr Speed",
+                    DEFAULT_PURR_SPEED,
+                    FloatRange::Linear {
+                        min: 20.0,
+                        max: 35.0,
+                    },
+                ),
+                purr_roughness: percent("Purr Roughness", DEFAULT_PURR_ROUGHNESS),
+            },
+            growl: GrowlGroup {
+                growl_amount: percent("Growl", DEFAULT_GROWL_AMOUNT),
+                growl_speed: freq(
+                    "Growl Speed",
+                    DEFAULT_GROWL_SPEED,
+                    FloatRange::Linear {
+                        min: 30.0,
+                        max: 70.0,
+                    },
+                ),
+                growl_target: EnumParam::new("Growl Target", DEFAULT_GROWL_TARGET),
+                growl_aftertouch: percent("Growl Aftertouch", DEFAULT_GROWL_AFTERTOUCH),
+            },
+            follower: FollowerGroup {
+                follower_attack: time("Follower Attack", DEFAULT_FOLLOWER_ATTACK, 0.0001, 1.0),
+                follower_release: time("Follower Release", DEFAULT_FOLLOWER_RELEASE, 0.001, 5.0),
+                follower_to_noise: bipolar_percent("Follower > Noise", DEFAULT_FOLLOWER_TO_NOISE),
+                follower_to_filter: bipolar_percent(
+                    "Follower > Filter",
+                    DEFAULT_FOLLOWER_TO_FILTER,
+                ),
+            },
+            compressor: CompressorGroup {
+                comp_threshold: decibel(
+                    "Squash Threshold",
+                    DEFAULT_COMP_THRESHOLD,
+                    -96.0,
+                    0.0,
+                    COMP_THRESHOLD_TAPER,
+                ),
+                comp_ratio: FloatParam::new(
+                    "Squash Ratio",
+                    DEFAULT_COMP_RATIO,
+                    FloatRange::Skewed {
+                        min: 1.0,
+                        max: 20.0,
+                        factor: FloatRange::skew_factor(-1.5),
+                    },
+                )
+                .with_smoother(CONTINUOUS_SMOOTHING)
+                .with_value_to_string(Arc::new(|ratio| format!("{:.1}:1", ratio)))
+                .with_string_to_value(Arc::new(|string: &str| {
+                    string.trim().trim_end_matches(":1").trim().parse().ok()
+                })),
+                comp_attack: time("Squash Attack", DEFAULT_COMP_ATTACK, 0.0001, 0.5),
+                comp_release: time("Squash Release", DEFAULT_COMP_RELEASE, 0.001, 2.0),
+                comp_makeup: decibel(
+                    "Squash Makeup",
+                    DEFAULT_COMP_MAKEUP,
+                    0.0,
+                    48.0,
+                    COMP_MAKEUP_TAPER,
+                ),
+            },
+            stereo: StereoGroup {
+                stereo_width: FloatParam::new(
+                    "Stereo Width",
+                    DEFAULT_STEREO_WIDTH,
+                    FloatRange::Linear { min: 0.0, max: 2.0 },
+                )
+                .with_smoother(CONTINUOUS_SMOOTHING)
+                .with_unit(" %")
+                .with_value_to_string(Arc::new(|width| format!("{:.1}", width * 100.0)))
+                .with_string_to_value(Arc::new(percent_parser)),
+                mono_bass: BoolParam::new("Mono Bass", DEFAULT_MONO_BASS)
+                    .with_value_to_string(Arc::new(polycat_formatter)),
+                mono_bass_freq: freq(
+                    "Mono Bass Freq",
+                    DEFAULT_MONO_BASS_FREQ,
+                    FloatRange::Skewed {
+                        min: 20.0,
+                        max: 500.0,
+                        factor: FloatRange::skew_factor(-1.0),
+                    },
+                ),
+            },
+            global: GlobalGroup {
+                portamento_time: time("Portamento", DEFAULT_PORTAMENTO, 0.0001, 5.0),
+                portamento_mode: EnumParam::new("Portamento Mode", DEFAULT_PORTAMENTO_MODE),
+                legato_mode: EnumParam::new("Legato Mode", DEFAULT_LEGATO_MODE),
+                slide_time: time("Slide Time", DEFAULT_SLIDE_TIME, 0.001, 1.0),
+                noise_mix: percent("Noise", DEFAULT_NOISE_MIX),
+                noise_width: percent("Noise Width", DEFAULT_NOISE_WIDTH),
+                hiss_mode: BoolParam::new("Hiss Mode", DEFAULT_HISS_MODE)
+                    .with_value_to_string(Arc::new(polycat_formatter)),
+                pitch_bend: IntParam::new(
+                    "Pitchbend",
+                    DEFAULT_PITCHBEND as i32,
+                    IntRange::Linear { min: 1, max: 12 },
+                ),
+                pitch_bend_smoothing: time(
+                    "Pitchbend Smoothing",
+                    DEFAULT_PITCHBEND_SMOOTHING,
+                    0.0001,
+                    0.5,
+                ),
+                mpe_zone: EnumParam::new("MPE Zone", DEFAULT_MPE_ZONE),
+                mpe_bend_range: IntParam::new(
+                    "MPE Bend Range",
+                    DEFAULT_MPE_BEND_RANGE as i32,
+                    IntRange::Linear { min: 1, max: 96 },
+                )
+                .with_unit(" semis"),
+                polycat: BoolParam::new("Polycat", DEFAULT_POLYCAT)
+                    .with_value_to_string(Arc::new(polycat_formatter)),
+                max_voices: IntParam::new(
+                    "Max Voices",
+                    DEFAULT_MAX_VOICES as i32,
+                    IntRange::Linear {
+                        min: 1,
+                        max: MAX_VOICES as i32,
+                    },
+                ),
+                midi_out: BoolParam::new("MIDI Out", DEFAULT_MIDI_OUT)
+                    .with_value_to_string(Arc::new(polycat_formatter)),
+                hires_cc: BoolParam::new("14-bit CCs", DEFAULT_HIRES_CC)
+                    .with_value_to_string(Arc::new(polycat_formatter)),
+                scale: EnumParam::new("Scale", DEFAULT_SCALE),
+                scale_root: EnumParam::new("Scale Root", DEFAULT_SCALE_ROOT),
+                stray_cat: FloatParam::new(
+                    "Stray Cat",
+                    DEFAULT_STRAY_CAT,
+                    FloatRange::Skewed {
+                        min: 0.0,
+                        max: 1200.0,
+                        factor: FloatRange::skew_factor(-1.0),
+                    },
+                )
+                .with_smoother(CONTINUOUS_SMOOTHING)
+                .with_unit(" cents")
+                .with_value_to_string(Arc::new(|cents| format!("{:.0}", cents))),
+                stray_cat_quantize: BoolParam::new(
+                    "Stray Cat Quantize",
+                    DEFAULT_STRAY_CAT_QUANTIZE,
+                )
+                .with_value_to_string(Arc::new(polycat_formatter)),
+                retrigger_fade: time("Retrigger Fade", DEFAULT_RETRIGGER_FADE, 0.0001, 0.1),
+                quality: EnumParam::new("Quality", DEFAULT_QUALITY),
+                sync_modulation: BoolParam::new("Bar Sync", DEFAULT_SYNC_MODULATION)
+                    .with_value_to_string(Arc::new(polycat_formatter)),
+                mod_depth: FloatParam::new(
+                    "Mod Depth",
+                    DEFAULT_MOD_DEPTH,
+                    FloatRange::Linear {
+                        min: 0.0,
+                        max: MAX_MOD_DEPTH,
+                    },
+                )
+                .with_smoother(CONTINUOUS_SMOOTHING)
+                .with_unit(" %")
+                .with_value_to_string(Arc::new(|depth| format!("{:.1}", depth * 100.0)))
+                .with_string_to_value(Arc::new(percent_parser)),
+                gain: decibel(
+                    "Master Volume",
+                    DEFAULT_MASTER_VOL,
+                    -72.0,
+                    24.0,
+                    MASTER_VOL_TAPER,
+                ),
+                preset_trim: decibel(
+                    "Preset Trim",
+                    DEFAULT_PRESET_TRIM,
+                    -24.0,
+                    24.0,
+                    PRESET_TRIM_TAPER,
+                )
+                .hide(),
+            },
+            macros: MacroGroup {
+                macro_1: percent("Macro 1", DEFAULT_MACRO),
+                macro_2: percent("Macro 2", DEFAULT_MACRO),
+                macro_3: percent("Macro 3", DEFAULT_MACRO),
+                macro_4: percent("Macro 4", DEFAULT_MACRO),
+                morph: percent("Morph", DEFAULT_MORPH),
+            },
+            presets: PresetGroup {
+                next_preset: BoolParam::new("Next Preset", DEFAULT_PRESET_BUTTON)
+                    .with_value_to_string(Arc::new(polycat_formatter)),
+                previous_preset: BoolParam::new("Previous Preset", DEFAULT_PRESET_BUTTON)
+                    .with_value_to_string(Arc::new(polycat_formatter)),
+            },
+            bypass: BypassGroup {
+                bypass: BoolParam::new("Bypass", DEFAULT_BYPASS)
+                    .with_value_to_string(Arc::new(polycat_formatter))
+                    .make_bypass(),
+                bypass_tails: EnumParam::new("Bypass Tails", DEFAULT_BYPASS_TAILS),
+            },
+            layers: LayerGroup {
+                layer_mode: EnumParam::new("Layer Mode", DEFAULT_LAYER_MODE),
+                layer_split_note: IntParam::new(
+                    "Split Note",
+                    DEFAULT_LAYER_SPLIT_NOTE as i32,
+                    IntRange::Linear { min: 0, max: 127 },
+                )
+                .with_value_to_string(Arc::new(note_formatter))
+                .with_string_to_value(Arc::new(note_parser)),
+                layer_split_velocity: percent("Split Velocity", DEFAULT_LAYER_SPLIT_VELOCITY),
+            },
+            keyswitches: KeyswitchGroup {
+                keyswitches: BoolParam::new("Keyswitches", DEFAULT_KEYSWITCHES)
+                    .with_value_to_string(Arc::new(polycat_formatter)),
+                keyswitch_octave: IntParam::new(
+                    "Keyswitch Octave",
+                    DEFAULT_KEYSWITCH_OCTAVE,
+                    IntRange::Linear { min: -1, max: 8 },
+                )
+                .with_value_to_string(Arc::new(|octave| format!("C{}", octave))),
+            },
+            macro_assignments: RwLock::new(MacroAssignments::default()),
+            morph_snapshots: RwLock::new(MorphSnapshots::default()),
+            layer_b: RwLock::new(LayerB::default()),
+            midi_mappings: RwLock::new(None),
+        }
+    }
+}
+
+// Settings past these can get loud enough to hurt, so presets which use them get speaker protection.
+const RISKY_FILTER_Q: f32 = 6.0;
+const RISKY_SELF_OSCILLATION: f32 = 0.5;
+const RISKY_RESONATOR_MIX: f32 = 0.5;
+const RISKY_RESONATOR_DAMPING: f32 = 0.2;
+
+impl Parameters {
+    /// Returns a fingerprint of the resonance and feedback settings if any of them are extreme, or
+    /// None if they are all tame. Two presets with the same risky settings have the same
+    /// fingerprint. This doesn't allocate, so it can be called on the audio thread.
+    pub fn risky_settings(&self) -> Option<u64> {
+        let (filter, filter_2, resonator) = (&self.filter, &self.filter_2, &self.resonator);
+        let second_filter_on = filter_2.filter_routing.value() != FilterRouting::Single;
+        let risky = filter.filter_q.value() >= RISKY_FILTER_Q
+            || filter.filter_self_osc.value() >= RISKY_SELF_OSCILLATION
+            || (second_filter_on && filter_2.filter_2_q.value() >= RISKY_FILTER_Q)
+            || (resonator.resonator_mix.value() >= RISKY_RESONATOR_MIX
+                && resonator.resonator_damping.value() <= RISKY_RESONATOR_DAMPING);
+        if !risky {
+            return None;
+        }
+
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        for value in [
+            filter.filter_q.value(),
+            filter.filter_self_osc.value(),
+            filter_2.filter_2_q.value(),
+            resonator.resonator_mix.value(),
+            resonator.resonator_damping.value(),
+        ] {
+            value.to_bits().hash(&mut hasher);
         }
+        second_filter_on.hash(&mut hasher);
+        Some(hasher.finish())
     }
 }
 
 impl Parameters {
     pub fn dbg_polycat(&self) -> &BoolParam {
-        &self.polycat
+        &self.global.polycat
     }
 
     pub fn dbg_meow_decay(&self) -> &FloatParam {
-        &self.meow_decay
+        &self.envelope.meow_decay
     }
 
     pub fn dbg_meow_release(&self) -> &FloatParam {
-        &self.meow_release
+        &self.envelope.meow_release
     }
 }
 
+/// Sets parameters by their IDs. The parameter pointers are looked up once ahead of time so that
+/// setting a parameter doesn't allocate, which allows this to be used on the audio thread. This is
+/// used to switch presets and to apply macros.
+/// Note that the host is not notified of these changes, but the editor is.
+pub struct ParamTable {
+    // The ID and pointer to every parameter. The pointers are only valid for as long as the
+    // Parameters they were created from is alive, so the ParamTable must not be used after that.
+    params: Vec<(String, ParamPtr, String)>,
+    changes: Arc<ParamChanges>,
+    // If set, the parameters which the table changes are also marked here, so that the host can be
+    // told about them.
+    host_updates: Option<Arc<ParamChanges>>,
+}
+
+impl ParamTable {
+    pub fn new(parameters: &Parameters, changes: Arc<ParamChanges>) -> ParamTable {
+        ParamTable {
+            params: parameters.param_map(),
+            changes,
+            host_updates: None,
+        }
+    }
+
+    /// Also mark the parameters which the table changes in `host_updates`. This is used for the
+    /// parameters which are shared with the host, so that it can be told about every change.
+    pub fn reporting_to_host(self, host_updates: Arc<ParamChanges>) -> ParamTable {
+        ParamTable {
+            host_updates: Some(host_updates),
+            ..self
+        }
+    }
+
+    /// Record the current value of every parameter, except for the macro and morph knobs, the
+    /// preset buttons, the bypass, and the layer and keyswitch settings.
+    pub fn snapshot(&self) -> Snapshot {
+        let values = self
+            .params
+            .iter()
+            .filter(|(_, _, group)| !is_control_group(group))
+            // Safety: See preset_targets.
+            .map(|(id, ptr, _)| (id.clone(), unsafe { ptr.unmodulated_normalized_value() }))
+            .collect();
+        Snapshot { values }
+    }
+
+    /// Set every parameter to the value given by the preset's (parameter ID, plain value) pairs.
+    /// Parameters not mentioned in the preset are reset to their defaults. The preset buttons are
+    /// left alone, since one of them may be what loaded the preset, and so are the bypass and the
+    /// layer and keyswitch settings.
+    pub fn apply_preset<S: AsRef<str>>(&self, values: &[(S, f32)], sample_rate: SampleRate) {
+        for (id, ptr, normalized) in self.preset_targets(values) {
+            // Safety: See preset_targets.
+            unsafe { self.set(id, ptr, normalized, sample_rate) };
+        }
+    }
+
+    /// Set only the parameters given by the (parameter ID, plain value) pairs, leaving the rest
+    /// alone. This is used to switch articulations. Unknown IDs are ignored.
+    pub fn apply_partial(&self, values: &[(&str, f32)], sample_rate: SampleRate) {
+        for (id, plain) in values {
+            if let Some((id, ptr, _)) = self.params.iter().find(|(param_id, _, _)| param_id == id) {
+                // Safety: See preset_targets.
+                unsafe { self.set(id, ptr, ptr.preview_normalized(*plain), sample_rate) };
+            }
+        }
+    }
+
+    /// The ID and pointer of each parameter that loading the preset would set, along with the
+    /// normalized value it would be set to. The editor uses this to load presets through the host.
+    pub fn preset_targets<'a, S: AsRef<str>>(
+        &'a self,
+        values: &'a [(S, f32)],
+    ) -> impl Iterator<Item = (&'a str, &'a ParamPtr, f32)> + 'a {
+        self.params
+            .iter()
+            .filter(|(_, _, group)| {
+                !matches!(
+                    group.as_str(),
+                    "Presets" | "Bypass" | "Layers" | "Keyswitches"
+                )
+            })
+            .map(move |(id, ptr, _)| {
+                let value = values
+                    .iter()
+                    .find(|(preset_id, _)| preset_id.as_ref() == id);
+                // Safety: The pointers point into the Parameters that this was constructed from,
+                // which outlives the ParamTable.
+                let normalized = unsafe {
+                    match value {
+                        Some((_, plain)) => ptr.preview_normalized(*plain),
+                        None => ptr.default_normalized_value(),
+                    }
+                };
+                (id.as_str(), ptr, normalized)
+            })
+    }
+
+    /// Set every parameter in the snapshot to its normalized value. Parameters not in the snapshot
+    /// are reset to their defaults, except for the ones which a snapshot never includes.
+    pub fn apply_snapshot(&self, snapshot: &Snapshot, sample_rate: SampleRate) {
+        for (id, ptr, normalized) in self.snapshot_targets(snapshot) {
+            // Safety: See preset_targets.
+            unsafe { self.set(id, ptr, normalized, sample_rate) };
+        }
+    }
+
+    /// The ID and pointer of each parameter that applying the snapshot would set, along with the
+    /// normalized value it would be set to. The editor uses this to swap layers through the host.
+    pub fn snapshot_targets<'a>(
+        &'a self,
+        snapshot: &'a Snapshot,
+    ) -> impl Iterator<Item = (&'a str, &'a ParamPtr, f32)> + 'a {
+        self.params
+            .iter()
+            .filter(|(_, _, group)| !is_control_group(group))
+            .map(move |(id, ptr, _)| {
+                let value = snapshot
+                    .values
+                    .iter()
+                    .find(|(snapshot_id, _)| snapshot_id == id);
+                // Safety: See preset_targets.
+                let normalized = match value {
+                    Some((_, normalized)) => *normalized,
+                    None => unsafe { ptr.default_normalized_value() },
+                };
+                (id.as_str(), ptr, normalized)
+            })
+    }
+
+    /// The plain value of every parameter which isn't at its default, in the same form as a
+    /// preset's values. The macro and morph knobs, the preset buttons, the bypass, and the layer
+    /// and keyswitch settings are left out.
+    pub fn preset_values(&self) -> Vec<(String, f32)> {
+        self.params
+            .iter()
+            .filter(|(_, _, group)| !is_control_group(group))
+            .filter_map(|(id, ptr, _)| {
+                // Safety: See preset_targets.
+                unsafe {
+                    let normalized = ptr.unmodulated_normalized_value();
+                    if (normalized - ptr.default_normalized_value()).abs() < 1.0e-6 {
+                        None
+                    } else {
+                        Some((id.clone(), ptr.preview_plain(normalized)))
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Update every parameter's smoother for a new sample rate. Smoothers which are in the middle
+    /// of a ramp jump straight to their target, rather than finishing the ramp at the wrong rate.
+    pub fn update_smoothers(&self, sample_rate: SampleRate) {
+        for (_, ptr, _) in &self.params {
+            // Safety: See preset_targets.
+            unsafe { ptr.update_smoother(sample_rate.get(), true) };
+        }
+    }
+
+    /// Set the parameter with the given ID to a normalized value, returning true if the value
+    /// changed. Unknown IDs are ignored.
+    pub fn set_normalized(&self, id: &str, normalized: f32, sample_rate: SampleRate) -> bool {
+        match self.ptr(id) {
+            // Safety: See preset_targets.
+            Some(ptr) => unsafe { self.set(id, &ptr, normalized, sample_rate) },
+            None => false,
+        }
+    }
+
+    /// The parameter with the given ID, if there is one. The pointer is only valid for as long as
+    /// the Parameters that the table was created from.
+    pub fn ptr(&self, id: &str) -> Option<ParamPtr> {
+        self.params
+            .iter()
+            .find(|(param_id, _, _)| param_id == id)
+            .map(|(_, ptr, _)| *ptr)
+    }
+
+    // The smoother also needs to be told about the new value, since otherwise the smoothed value
+    // would stay at the old value until the host next sets the parameter. Returns true if the value
+    // changed.
+    unsafe fn set(
+        &self,
+        id: &str,
+        ptr: &ParamPtr,
+        normalized: f32,
+        sample_rate: SampleRate,
+    ) -> bool {
+        let changed = ptr.set_normalized_value(normalized);
+        if changed {
+            ptr.update_smoother(sample_rate.get(), false);
+            self.changes.notify(id);
+            if let Some(host_updates) = &self.host_updates {
+                host_updates.notify(id);
+            }
+        }
+        changed
+    }
+}
+
+/// True for the groups of parameters which control other parameters or the plugin itself, rather
+/// than the sound. These are left out of snapshots and saved presets.
+pub fn is_control_group(group: &str) -> bool {
+    matches!(
+        group,
+        "Macros" | "Presets" | "Bypass" | "Layers" | "Keyswitches"
+    )
+}
+
+/// The random per-note detune ("stray cat" mode).
+pub struct StrayCatParams {
+    /// The maximum detune, in semitones.
+    pub range: f32,
+    /// If true, the detuned note is snapped to the nearest note in the scale. This may land a
+    /// little past the maximum detune, if the scale has no notes closer in.
+    pub quantize: bool,
+    /// The scale which quantized notes are snapped to. With the scale off, the detune is rounded
+    /// to the nearest semitone.
+    pub scale: Scale,
+    pub root: Root,
+}
+
+impl StrayCatParams {
+    /// Get a random detune for the note, in semitones, given a random value in the [-1.0, 1.0]
+    /// range.
+    pub fn detune(&self, random: f32, note: u8) -> f32 {
+        let detune = random * self.range;
+        if self.quantize {
+            let note = note as f32;
+            self.scale.snap(note + detune, self.root) - note
+        } else {
+            detune
+        }
+    }
+}
+
+/// The MIDI CC numbers which the envelope values are sent on.
+pub struct EnvelopeCCParams {
+    pub amp_cc: u8,
+    pub filter_cc: u8,
+    /// The MIDI channel, from 0 to 15.
+    pub channel: u8,
+}
+
 pub struct ChorusParams {
     pub rate: Hertz,
     pub depth: f32,
     pub min_distance: f32,
     pub mix: f32,
+    pub quality: ChorusQuality,
+    pub voices: u8,
+}
+
+pub struct SupermeowParams {
+    /// The total number of oscillators, including the main oscillator.
+    pub voices: u8,
+    /// The detune of the outermost copies, in cents.
+    pub detune: f32,
+    /// The mix between the main oscillator (0.0) and the detuned copies (1.0).
+    pub blend: f32,
+}
+
+pub struct ResonatorParams {
+    /// The mix between the dry signal (0.0) and the resonated signal (1.0).
+    pub mix: f32,
+    /// The amount of lowpass filtering in the feedback path, in the [0.0, 1.0] range.
+    pub damping: f32,
+}
+
+pub struct PurrParams {
+    /// How deeply the purr pulses the volume, from 0.0 (off) to 1.0 (silent between pulses).
+    pub amount: f32,
+    /// The number of pulses per second.
+    pub speed: Hertz,
+    /// How much each pulse randomly varies, in the [0.0, 1.0] range.
+    pub roughness: f32,
+}
+
+pub struct CompressorParams {
+    pub threshold: Decibel,
+    pub ratio: f32,
+    pub attack: Seconds,
+    pub release: Seconds,
+    pub makeup: Decibel,
+}
+
+pub struct StereoParams {
+    /// The amount that the side signal is scaled by, in the [0.0, 2.0] range.
+    pub width: f32,
+    /// If set, the side signal is highpassed at this frequency, which keeps the bass in mono.
+    pub mono_below: Option<Hertz>,
+}
+
+pub struct FollowerParams {
+    pub attack: Seconds,
+    pub release: Seconds,
+    /// The amount that the follower level is added to the noise mix, in the [-1.0, 1.0] range.
+    pub to_noise: f32,
+    /// The amount that the follower level moves the filter cutoff, in the [-1.0, 1.0] range. At
+    /// 1.0, a full level raises the cutoff by [MAX_FOLLOWER_TO_FILTER] octaves.
+    pub to_filter: f32,
 }
 
 // A set of immutable envelope parameters. The envelope is defined as follows:
@@ -373,10 +2204,110 @@ pub trait EnvelopeParams<T> {
     fn sustain(&self) -> T;
     // In seconds, how long release phase is
     fn release(&self) -> Seconds;
+    // In seconds, how long the sustain phase takes to fade to zero. If None, the sustain phase
+    // is held forever.
+    fn sustain_fade(&self) -> Option<Seconds> {
+        None
+    }
     // In -1.0 to 1.0 range usually. Multiplied by the value given by the ADSR
     fn multiply(&self) -> f32 {
         1.0
     }
+    // The shape of the decay, sustain fade, and release.
+    fn curve(&self) -> EnvelopeCurve {
+        EnvelopeCurve::Classic
+    }
+    // How many time constants fit in each segment of the analog curve.
+    fn time_constants(&self) -> f32 {
+        DEFAULT_ENVELOPE_TIME_CONSTANTS
+    }
+    // Convert an envelope level to the amplitude it is played at. The analog curve discharges in
+    // amplitude, so it converts its levels with this and back with `amp_to_level`.
+    fn level_to_amp(&self, level: T) -> T {
+        level
+    }
+    fn amp_to_level(&self, amp: T) -> T {
+        amp
+    }
+}
+
+/// Per-voice multipliers for the times of each envelope stage.
+#[derive(Debug, Clone, Copy)]
+pub struct EnvelopeTimeScale {
+    pub attack: f32,
+    pub decay: f32,
+    pub release: f32,
+}
+
+impl Default for EnvelopeTimeScale {
+    fn default() -> Self {
+        EnvelopeTimeScale {
+            attack: 1.0,
+            decay: 1.0,
+            release: 1.0,
+        }
+    }
+}
+
+/// A set of envelope parameters with the stage times scaled by an [EnvelopeTimeScale]. The sustain
+/// fade is treated as part of the decay.
+pub struct ScaledEnvelope<'a, P> {
+    params: &'a P,
+    scale: EnvelopeTimeScale,
+}
+
+impl<'a, P> ScaledEnvelope<'a, P> {
+    pub fn new(params: &'a P, scale: EnvelopeTimeScale) -> ScaledEnvelope<'a, P> {
+        ScaledEnvelope { params, scale }
+    }
+}
+
+impl<'a, T, P: EnvelopeParams<T>> EnvelopeParams<T> for ScaledEnvelope<'a, P> {
+    fn attack(&self) -> Seconds {
+        self.params.attack() * self.scale.attack
+    }
+
+    fn hold(&self) -> Seconds {
+        self.params.hold()
+    }
+
+    fn decay(&self) -> Seconds {
+        self.params.decay() * self.scale.decay
+    }
+
+    fn sustain(&self) -> T {
+        self.params.sustain()
+    }
+
+    fn release(&self) -> Seconds {
+        self.params.release() * self.scale.release
+    }
+
+    fn sustain_fade(&self) -> Option<Seconds> {
+        self.params
+            .sustain_fade()
+            .map(|sustain_fade| sustain_fade * self.scale.decay)
+    }
+
+    fn multiply(&self) -> f32 {
+        self.params.multiply()
+    }
+
+    fn curve(&self) -> EnvelopeCurve {
+        self.params.curve()
+    }
+
+    fn time_constants(&self) -> f32 {
+        self.params.time_constants()
+    }
+
+    fn level_to_amp(&self, level: T) -> T {
+        self.params.level_to_amp(level)
+    }
+
+    fn amp_to_level(&self, amp: T) -> T {
+        self.params.amp_to_level(amp)
+    }
 }
 
 pub struct VolumeEnvelopeParams {
@@ -384,6 +2315,23 @@ pub struct VolumeEnvelopeParams {
     decay: Seconds,
     sustain: f32,
     release: Seconds,
+    sustain_fade: Option<Seconds>,
+    curve: EnvelopeCurve,
+    time_constants: f32,
+}
+
+impl VolumeEnvelopeParams {
+    /// The amplitude that a volume envelope level is played at. This curve was computed somewhat
+    /// empirically. See https://www.desmos.com/calculator/r7k5ee8k5j for details.
+    pub fn volume_to_amp(level: f32) -> f32 {
+        (level * level * level + level) / 2.0
+    }
+
+    /// The inverse of [VolumeEnvelopeParams::volume_to_amp], which is the real root of the cubic.
+    pub fn amp_to_volume(amp: f32) -> f32 {
+        let root = (amp * amp + 1.0 / 27.0).sqrt();
+        (amp + root).cbrt() + (amp - root).cbrt()
+    }
 }
 
 impl EnvelopeParams<f32> for VolumeEnvelopeParams {
@@ -406,6 +2354,26 @@ impl EnvelopeParams<f32> for VolumeEnvelopeParams {
     fn release(&self) -> Seconds {
         self.release
     }
+
+    fn sustain_fade(&self) -> Option<Seconds> {
+        self.sustain_fade
+    }
+
+    fn curve(&self) -> EnvelopeCurve {
+        self.curve
+    }
+
+    fn time_constants(&self) -> f32 {
+        self.time_constants
+    }
+
+    fn level_to_amp(&self, level: f32) -> f32 {
+        VolumeEnvelopeParams::volume_to_amp(level)
+    }
+
+    fn amp_to_level(&self, amp: f32) -> f32 {
+        VolumeEnvelopeParams::amp_to_volume(amp)
+    }
 }
 
 pub struct FilterEnvelopeParams {
@@ -413,7 +2381,27 @@ pub struct FilterEnvelopeParams {
     sustain: f32,
     decay: Seconds,
     release: Seconds,
+    sustain_fade: Option<Seconds>,
+    curve: EnvelopeCurve,
+    time_constants: f32,
     pub env_mod: Hertz,
+    pub release_vel: f32,
+    pub env_vel: f32,
+}
+
+impl FilterEnvelopeParams {
+    /// The envelope mod for a note played at the given velocity. At full velocity sensitivity, the
+    /// envelope mod is scaled by the velocity. At zero sensitivity, it is not scaled at all.
+    pub fn env_mod_for(&self, vel: Vel) -> Hertz {
+        self.env_mod * lerp(1.0, vel.eased, self.env_vel)
+    }
+
+    /// The amount to scale the release time by, given a note off velocity. At full release
+    /// velocity sensitivity, a maximum velocity note off halves the release time and a zero
+    /// velocity note off doubles it. A velocity of 0.5 leaves the release time unchanged.
+    pub fn release_scale(&self, release_vel: f32) -> f32 {
+        (self.release_vel * (1.0 - 2.0 * release_vel)).exp2()
+    }
 }
 
 impl EnvelopeParams<f32> for FilterEnvelopeParams {
@@ -436,19 +2424,93 @@ impl EnvelopeParams<f32> for FilterEnvelopeParams {
     fn release(&self) -> Seconds {
         self.release
     }
+
+    fn sustain_fade(&self) -> Option<Seconds> {
+        self.sustain_fade
+    }
+
+    fn curve(&self) -> EnvelopeCurve {
+        self.curve
+    }
+
+    fn time_constants(&self) -> f32 {
+        self.time_constants
+    }
 }
 
 pub struct FilterParams {
     pub cutoff_freq: Hertz,
     pub q_value: f32,
+    /// The gain applied to the filter's output to compensate for the resonance.
+    pub resonance_gain: f32,
+    pub q_limiter: bool,
+    /// The amplitude of the self-oscillation, which is already scaled down at low Qs.
+    pub self_oscillation: f32,
+    pub keytrack: bool,
+    /// How far the oscillator modulates the cutoff, as a fraction of MAX_FILTER_FM.
+    pub fm_amount: f32,
     pub filter_type: biquad::Type<f32>,
-    pub dry_wet: f32,
+    pub mix_law: MixLaw,
+    pub pre_gain: Decibel,
+    pub post_gain: Decibel,
+    pub vintage_cap: bool,
+}
+
+/// The gain which compensates for the filter's resonant peak at the given Q. At full compensation,
+/// the gain drops by 3 dB for each doubling of the Q above a Butterworth (non-resonant) filter's.
+fn resonance_gain(q_value: f32, compensation: f32) -> f32 {
+    let excess_q = (q_value / biquad::Q_BUTTERWORTH_F32).max(1.0);
+    excess_q.powf(-0.5 * compensation)
+}
+
+/// The amplitude of the filter's self-oscillation. The filter only starts to whistle once the Q is
+/// past SELF_OSCILLATION_MIN_Q, and whistles at full volume at the maximum Q.
+fn self_oscillation(q_value: f32, amount: f32) -> f32 {
+    amount * inv_lerp(SELF_OSCILLATION_MIN_Q, MAX_FILTER_Q, q_value)
+}
+
+impl FilterParams {
+    /// The highest cutoff frequency the filter may use at the given sample rate. The biquad
+    /// becomes numerically unstable as the cutoff approaches Nyquist, so the cutoff is kept a
+    /// little below it.
+    pub fn max_cutoff(&self, sample_rate: SampleRate) -> f32 {
+        let max_cutoff = sample_rate.get() * 0.49;
+        if self.vintage_cap {
+            max_cutoff.min(20000.0)
+        } else {
+            max_cutoff
+        }
+    }
+}
+
+pub struct SecondFilterParams {
+    pub routing: FilterRouting,
+    /// The mix between the main filter (0.0) and the second filter (1.0).
+    pub balance: f32,
+    pub split_freq: Hertz,
+    pub filter_type: biquad::Type<f32>,
+    pub cutoff_freq: Hertz,
+    pub q_value: f32,
+}
+
+impl SecondFilterParams {
+    /// The gains of the main and second filter, in that order. Rather than crossfading, each filter
+    /// stays at full volume until the balance passes the center, so that the two bands in split
+    /// mode add back up to the original signal at 50%.
+    pub fn gains(&self) -> (f32, f32) {
+        let main = (2.0 * (1.0 - self.balance)).min(1.0);
+        let second = (2.0 * self.balance).min(1.0);
+        (main, second)
+    }
 }
 
 #[derive(Debug)]
 pub struct VibratoLFOParams {
     pub speed: Hertz,
+    /// The vibrato amount, as a fraction of MAX_VIBRATO_AMOUNT.
     pub amount: f32,
+    pub mode: VibratoMode,
+    pub trill_range: u8,
 }
 
 pub struct VibratoEnvelopeParams {
@@ -477,6 +2539,173 @@ impl EnvelopeParams<f32> for VibratoEnvelopeParams {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum MpeZone {
+    /// MPE is disabled. Pitch bends on every channel apply to all notes.
+    #[name = "Off"]
+    Off,
+    /// The master channel is channel 1, and the member channels are channels 2 through 16.
+    #[name = "Lower"]
+    Lower,
+    /// The master channel is channel 16, and the member channels are channels 1 through 15.
+    #[name = "Upper"]
+    Upper,
+}
+
+impl MpeZone {
+    /// Returns true if the (zero indexed) channel is a member channel of the zone. Pitch bends on
+    /// member channels only apply to the notes on that channel.
+    pub fn is_member_channel(&self, channel: u8) -> bool {
+        match self {
+            MpeZone::Off => false,
+            MpeZone::Lower => channel != 0,
+            MpeZone::Upper => channel != 15,
+        }
+    }
+}
+
+/// What happens to the notes which are playing when the synth is bypassed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum BypassTails {
+    /// The notes keep playing until they are released and their tails have died out. New notes
+    /// aren't played while bypassed.
+    #[name = "Ring"]
+    Ring,
+    /// The output is faded out, and the notes are stopped.
+    #[name = "Cut"]
+    Cut,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum Quality {
+    /// Uses the least CPU. Meant for tracking on slower machines.
+    #[name = "Eco"]
+    Eco,
+    #[name = "Normal"]
+    Normal,
+    /// Uses the most CPU. Meant for bouncing.
+    #[name = "HQ"]
+    HQ,
+}
+
+impl Quality {
+    /// How often, in samples, each voice recomputes its filter coefficients. Recomputing the
+    /// coefficients is expensive, but doing it less often makes fast filter sweeps sound steppy.
+    pub fn filter_update_interval(&self) -> SampleTime {
+        match self {
+            Quality::Eco => 32,
+            Quality::Normal => 16,
+            Quality::HQ => 4,
+        }
+    }
+
+    /// Whether the filter coefficients are ramped between updates, instead of jumping to the new
+    /// coefficients at each update. Ramping costs a little CPU on every sample.
+    pub fn interpolate_filter(&self) -> bool {
+        match self {
+            Quality::Eco => false,
+            Quality::Normal | Quality::HQ => true,
+        }
+    }
+
+    /// How often, in samples, the vibrato LFO is updated. The LFO holds its value in between.
+    pub fn lfo_update_interval(&self) -> SampleTime {
+        match self {
+            Quality::Eco => 16,
+            Quality::Normal => 4,
+            Quality::HQ => 1,
+        }
+    }
+
+    /// How many times over the growl's saturator is oversampled. Oversampling keeps the saturator
+    /// from aliasing, which is most audible on high notes at full growl.
+    pub fn saturation_oversampling(&self) -> usize {
+        match self {
+            Quality::Eco => 1,
+            Quality::Normal => 2,
+            Quality::HQ => 4,
+        }
+    }
+
+    /// The chorus interpolation to use, given the one chosen by the user. Eco and HQ override
+    /// the user's choice.
+    pub fn chorus_quality(&self, chosen: ChorusQuality) -> ChorusQuality {
+        match self {
+            Quality::Eco => ChorusQuality::Low,
+            Quality::Normal => chosen,
+            Quality::HQ => ChorusQuality::High,
+        }
+    }
+}
+
+/// The shape of an envelope's decay and release.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum EnvelopeCurve {
+    /// Ease linearly between the levels. The volume envelope's levels are in decibels, so this is
+    /// already roughly exponential in volume.
+    #[name = "Classic"]
+    Classic,
+    /// Fall quickly at first and then slow down, like a capacitor discharging through a resistor
+    /// in an analog envelope. The discharge happens in amplitude, with the time constant set by
+    /// the envelope's time constants parameter, and the last few milliseconds of each segment
+    /// fade out whatever is left. This gives longer, more natural sounding tails.
+    #[name = "Analog"]
+    Analog,
+}
+
+impl EnvelopeCurve {
+    /// How long the analog curve takes to fade out whatever is left at the end of a segment. An RC
+    /// discharge never actually reaches its target, so this makes sure the segment ends there.
+    const FINAL_FADE: Seconds = Seconds::new(0.005);
+
+    /// For the analog curve, the fraction of the way from the start level to the end level which
+    /// is still left to go, `time` into a segment of the given length. This is measured in
+    /// amplitude, and falls off with a time constant of `length / time_constants`.
+    pub fn analog_remaining(time: Seconds, length: Seconds, time_constants: f32) -> f32 {
+        if time >= length {
+            return 0.0;
+        }
+        let discharge = (-time_constants * (time / length)).exp();
+        let final_fade = ((length - time) / EnvelopeCurve::FINAL_FADE.min(length)).min(1.0);
+        discharge * final_fade
+    }
+}
+
+/// How monocat mode plays a key which is pressed (or uncovered, by releasing the key pressed after
+/// it) while another key is held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum LegatoMode {
+    /// The voice is crossfaded into a new voice, which restarts the envelopes.
+    #[name = "Retrigger"]
+    Retrigger,
+    /// Only the pitch changes, like a cat sliding between cries rather than starting a new one.
+    #[name = "Slide"]
+    Slide,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum PortamentoMode {
+    /// The pitch sweeps continuously from the start note to the end note.
+    #[name = "Glide"]
+    Glide,
+    /// The pitch steps through each semitone between the start note and the end note.
+    #[name = "Glissando"]
+    Glissando,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum VibratoMode {
+    /// The pitch follows the LFO smoothly.
+    #[name = "Smooth"]
+    Smooth,
+    /// The pitch follows the LFO, but is quantized to semitone steps. This makes trills and sirens.
+    #[name = "Trill"]
+    Trill,
+}
+
+/// A tempo synced length, used for the vibrato rate and the tempo synced envelope times. The dotted
+/// lengths are one and a half times as long as the plain lengths, and the triplet lengths are two
+/// thirds as long.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
 pub enum VibratoRate {
     #[name = "4 bar"]
@@ -485,33 +2714,63 @@ pub enum VibratoRate {
     TwoBar,
     #[name = "1 bar"]
     OneBar,
+    #[name = "1/2D"]
+    HalfDotted,
     #[name = "1/2"]
     Half,
+    #[name = "1/4D"]
+    QuarterDotted,
+    #[name = "1/2T"]
+    HalfTriplet,
     #[name = "1/4"]
     Quarter,
+    #[name = "1/8D"]
+    EighthDotted,
+    #[name = "1/4T"]
+    QuarterTriplet,
     #[name = "1/8"]
     Eighth,
-    #[name = "1/12"]
-    Twelfth,
+    #[name = "1/16D"]
+    SixteenthDotted,
+    #[name = "1/8T"]
+    EighthTriplet,
     #[name = "1/16"]
     Sixteenth,
+    #[name = "1/16T"]
+    SixteenthTriplet,
 }
 
 impl VibratoRate {
-    /// Converts the vibrato rate to herts, given a tempo in beats per minute
-    pub fn as_hz(&self, tempo: f32) -> Hertz {
-        let beats_per_seconds = tempo / 60.0;
-        let multiplier = match self {
-            VibratoRate::FourBar => 1.0 / 16.0,
-            VibratoRate::TwoBar => 1.0 / 8.0,
-            VibratoRate::OneBar => 1.0 / 4.0,
-            VibratoRate::Half => 1.0 / 2.0,
+    /// The length of a single cycle.
+    pub fn as_beats(&self) -> Beats {
+        let beats = match self {
+            VibratoRate::FourBar => 16.0,
+            VibratoRate::TwoBar => 8.0,
+            VibratoRate::OneBar => 4.0,
+            VibratoRate::HalfDotted => 3.0,
+            VibratoRate::Half => 2.0,
+            VibratoRate::QuarterDotted => 3.0 / 2.0,
+            VibratoRate::HalfTriplet => 4.0 / 3.0,
             VibratoRate::Quarter => 1.0,
-            VibratoRate::Eighth => 2.0,
-            VibratoRate::Twelfth => 3.0,
-            VibratoRate::Sixteenth => 4.0,
+            VibratoRate::EighthDotted => 3.0 / 4.0,
+            VibratoRate::QuarterTriplet => 2.0 / 3.0,
+            VibratoRate::Eighth => 1.0 / 2.0,
+            VibratoRate::SixteenthDotted => 3.0 / 8.0,
+            VibratoRate::EighthTriplet => 1.0 / 3.0,
+            VibratoRate::Sixteenth => 1.0 / 4.0,
+            VibratoRate::SixteenthTriplet => 1.0 / 6.0,
         };
-        let hertz = beats_per_seconds * multiplier;
-        Hertz::new(hertz)
+        Beats::new(beats)
+    }
+
+    /// Converts the vibrato rate to hertz, given a tempo in beats per minute
+    pub fn as_hz(&self, tempo: f32) -> Hertz {
+        Hertz::new(1.0 / self.as_seconds(tempo).get())
+    }
+
+    /// Converts the vibrato rate to the length of a single cycle, given a tempo in beats per
+    /// minute. This is used when the envelope times are tempo synced.
+    pub fn as_seconds(&self, tempo: f32) -> Seconds {
+        self.as_beats().to_seconds(tempo)
     }
 }
@@ -0,0 +1,365 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        RwLock,
+    },
+};
+
+use nih_plug::prelude::{Plugin, PluginState};
+use serde::{Deserialize, Serialize};
+
+use crate::{handoff::Handoff, issues, settings::settings_dir, state, Nyasynth};
+
+/// A factory preset. A preset only lists the parameters which differ from the defaults, as
+/// (parameter ID, plain value) pairs. Enum parameters use the index of the variant and boolean
+/// parameters use 0.0 or 1.0. Presets which are much quieter or louder than the default sound set
+/// `preset_trim` (in dB) to make up the difference.
+pub struct Preset {
+    pub name: &'static str,
+    /// The categories that the preset belongs to in the preset browser. See [PRESET_TAGS].
+    pub tags: &'static [&'static str],
+    pub values: &'static [(&'static str, f32)],
+}
+
+/// The factory presets, grouped into banks. These are selected with MIDI bank select (CC 0) and
+/// program change messages.
+pub const BANKS: &[&[Preset]] = &[FACTORY_BANK];
+
+/// The tags which the preset browser can filter by.
+pub const PRESET_TAGS: &[&str] = &["lead", "bass", "pad", "sfx"];
+
+const FACTORY_BANK: &[Preset] = &[
+    Preset {
+        name: "Default Meow",
+        tags: &[],
+        values: &[],
+    },
+    Preset {
+        name: "Kitten",
+        tags: &["lead"],
+        values: &[
+            ("meow_attack", 0.005),
+            ("meow_decay", 0.3),
+            ("meow_release", 0.15),
+            ("filter_cutoff_freq", 900.0),
+            ("noise_mix", 0.05),
+            ("preset_trim", 4.0),
+        ],
+    },
+    Preset {
+        name: "Alley Cat",
+        tags: &["lead"],
+        values: &[
+            ("vibrato_amount", 80.0),
+            ("vibrato_attack", 0.3),
+            ("stray_cat", 35.0),
+            ("chorus_mix", 0.3),
+        ],
+    },
+    Preset {
+        name: "Siren",
+        tags: &["sfx"],
+        values: &[
+            ("vibrato_amount", 200.0),
+            ("vibrato_mode", 1.0),
+            ("trill_range", 12.0),
+            ("meow_sustain", 0.0),
+        ],
+    },
+    Preset {
+        name: "Lion",
+        tags: &["bass"],
+        values: &[
+            ("meow_attack", 0.12),
+            ("meow_decay", 3.0),
+            ("meow_release", 1.5),
+            ("filter_cutoff_freq", 120.0),
+            ("filter_q", 5.0),
+            ("portamento_time", 0.4),
+            ("preset_trim", 12.0),
+        ],
+    },
+    Preset {
+        name: "Hiss",
+        tags: &["sfx"],
+        values: &[
+            ("hiss_mode", 1.0),
+            ("meow_attack", 0.005),
+            ("meow_decay", 0.6),
+            ("meow_sustain", -48.0),
+            ("meow_release", 0.3),
+            ("noise_width", 0.5),
+            ("filter_cutoff_freq", 1500.0),
+        ],
+    },
+];
+
+/// A preset in the preset browser. This is either a factory preset or one of the user's presets.
+#[derive(Debug, Clone)]
+pub struct LibraryPreset {
+    pub name: String,
+    pub tags: Vec<String>,
+    /// The (parameter ID, plain value) pairs, like the values of a [Preset].
+    pub values: Vec<(String, f32)>,
+    /// True if this is one of the user's presets.
+    pub user: bool,
+}
+
+impl LibraryPreset {
+    fn factory(preset: &Preset) -> LibraryPreset {
+        LibraryPreset {
+            name: preset.name.to_string(),
+            tags: preset.tags.iter().map(|tag| tag.to_string()).collect(),
+            values: preset
+                .values
+                .iter()
+                .map(|(id, value)| (id.to_string(), *value))
+                .collect(),
+            user: false,
+        }
+    }
+
+    /// True if the preset's name contains the search text (ignoring case) and the preset has every
+    /// one of the given tags.
+    pub fn matches(&self, search: &str, tags: &[&str]) -> bool {
+        let search = search.trim().to_lowercase();
+        self.name.to_lowercase().contains(&search)
+            && tags
+                .iter()
+                .all(|tag| self.tags.iter().any(|preset_tag| preset_tag == tag))
+    }
+}
+
+// A user preset as it is stored on disk. The values are stored as a map, which is easier to edit by
+// hand than a list of pairs.
+#[derive(Debug, Serialize, Deserialize)]
+struct PresetFile {
+    name: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    values: BTreeMap<String, f32>,
+    /// The version of the plugin which saved the preset. Presets saved before this was added are
+    /// from version 1.3.
+    #[serde(default = "first_preset_file_version")]
+    version: String,
+}
+
+fn first_preset_file_version() -> String {
+    "1.3".to_string()
+}
+
+/// The presets shown in the preset browser: the factory presets, followed by the user's presets
+/// from the `presets` folder in the settings directory. The library also tracks which preset is
+/// selected, so that the next and previous preset parameters can step through it.
+pub struct PresetLibrary {
+    /// The presets, for the editor. The audio thread has its own copy, which it receives from
+    /// `audio_presets` whenever the presets change.
+    pub presets: RwLock<Vec<LibraryPreset>>,
+    audio_presets: Handoff<Vec<LibraryPreset>>,
+    current: AtomicUsize,
+    // Set when the editor loads a preset, since only the audio thread can engage speaker
+    // protection.
+    loaded_by_editor: AtomicBool,
+}
+
+impl PresetLibrary {
+    /// Create the library with only the factory presets. The user's presets are added by
+    /// [PresetLibrary::rescan], which reads files and so should be run in the background.
+    pub fn new() -> PresetLibrary {
+        let factory_presets: Vec<LibraryPreset> = BANKS
+            .iter()
+            .flat_map(|bank| bank.iter())
+            .map(LibraryPreset::factory)
+            .collect();
+        let library = PresetLibrary {
+            presets: RwLock::new(vec![]),
+            audio_presets: Handoff::new(),
+            current: AtomicUsize::new(0),
+            loaded_by_editor: AtomicBool::new(false),
+        };
+        library.replace(factory_presets);
+        library
+    }
+
+    /// Reload the user's presets from the presets folder. Files which can't be parsed are reported
+    /// as issues and skipped. This must not be called on the audio thread.
+    pub fn rescan(&self) {
+        let mut presets: Vec<LibraryPreset> = BANKS
+            .iter()
+            .flat_map(|bank| bank.iter())
+            .map(LibraryPreset::factory)
+            .collect();
+
+        let mut user_presets: Vec<LibraryPreset> = presets_dir()
+            .and_then(|dir| std::fs::read_dir(dir).ok())
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().map_or(false, |ext| ext == "json"))
+            .filter_map(|path| match read_preset_file(&path) {
+                Ok(preset) => Some(preset),
+                Err(err) => {
+                    issues::warn(format!(
+                        "Couldn't load the preset {}: {}",
+                        path.display(),
+                        err
+                    ));
+                    None
+                }
+            })
+            .collect();
+        user_presets.sort_by_key(|preset| preset.name.to_lowercase());
+        presets.extend(user_presets);
+        self.replace(presets);
+    }
+
+    /// Replace the presets, keeping the same preset selected if it still exists, and send the new
+    /// presets to the audio thread.
+    fn replace(&self, presets: Vec<LibraryPreset>) {
+        let mut library = self.presets.write().unwrap();
+        let current = library
+            .get(self.current())
+            .and_then(|current| {
+                presets
+                    .iter()
+                    .position(|preset| preset.name == current.name)
+            })
+            .unwrap_or(0);
+        self.audio_presets.send(presets.clone());
+        *library = presets;
+        self.current.store(current, Ordering::Relaxed);
+    }
+
+    /// Pick up the newest presets, if they have changed since the last call. This is called by the
+    /// audio thread, which keeps its own copy of the presets in `presets`. Returns true if the
+    /// presets changed.
+    pub fn receive(&self, presets: &mut Option<Box<Vec<LibraryPreset>>>) -> bool {
+        self.audio_presets.receive(presets)
+    }
+
+    /// Save the given values as a user preset, and add it to the library. Returns the index of the
+    /// new preset.
+    pub fn save(
+        &self,
+        name: &str,
+        tags: Vec<String>,
+        values: Vec<(String, f32)>,
+    ) -> Result<usize, String> {
+        let dir = presets_dir().ok_or("Couldn't find the settings directory")?;
+        let file = PresetFile {
+            name: name.to_string(),
+            tags,
+            values: values.into_iter().collect(),
+            version: Nyasynth::VERSION.to_string(),
+        };
+        // Characters which aren't allowed in file names on some platforms are replaced.
+        let file_name: String = name
+            .chars()
+            .map(|c| match c {
+                '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+                c => c,
+            })
+            .collect();
+        let json = serde_json::to_string_pretty(&file).expect("Presets always serialize");
+        std::fs::create_dir_all(&dir)
+            .and_then(|_| std::fs::write(dir.join(file_name + ".json"), json))
+            .map_err(|err| err.to_string())?;
+
+        // The new preset is added directly, rather than scanning the whole folder again.
+        let preset = LibraryPreset {
+            name: file.name,
+            tags: file.tags,
+            values: file.values.into_iter().collect(),
+            user: true,
+        };
+        let mut presets = self.presets.read().unwrap().clone();
+        presets.retain(|existing| !(existing.user && existing.name == preset.name));
+        let index = presets
+            .iter()
+            .position(|existing| {
+                existing.user && existing.name.to_lowercase() > preset.name.to_lowercase()
+            })
+            .unwrap_or(presets.len());
+        presets.insert(index, preset);
+        self.replace(presets);
+        Ok(index)
+    }
+
+    /// Copy a user preset file from elsewhere into the presets folder, and add it to the library.
+    /// The file is checked first, so that a broken preset never ends up in the folder. Returns the
+    /// index of the new preset.
+    pub fn import(&self, path: &Path) -> Result<usize, String> {
+        let preset = read_preset_file(path)?;
+        // Saving rather than copying the file drops anything the plugin doesn't understand.
+        self.save(&preset.name, preset.tags, preset.values)
+    }
+
+    /// The index of the selected preset.
+    pub fn current(&self) -> usize {
+        self.current.load(Ordering::Relaxed)
+    }
+
+    /// Select a preset. This is called by whatever loaded the preset.
+    pub fn set_current(&self, index: usize) {
+        self.current.store(index, Ordering::Relaxed);
+    }
+
+    /// Note that the editor loaded a preset.
+    pub fn editor_loaded_preset(&self, index: usize) {
+        self.set_current(index);
+        self.loaded_by_editor.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns true if the editor has loaded a preset since the last call.
+    pub fn take_editor_load(&self) -> bool {
+        self.loaded_by_editor.swap(false, Ordering::Relaxed)
+    }
+}
+
+/// Read a user preset file.
+fn read_preset_file(path: &Path) -> Result<LibraryPreset, String> {
+    let json = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let mut preset: PresetFile = serde_json::from_str(&json).map_err(|err| err.to_string())?;
+    if preset.name.trim().is_empty() {
+        return Err("The preset doesn't have a name".to_string());
+    }
+    state::migrate_preset_values(&preset.version, &mut preset.values);
+    Ok(LibraryPreset {
+        name: preset.name,
+        tags: preset.tags,
+        values: preset.values.into_iter().collect(),
+        user: true,
+    })
+}
+
+/// Read the plugin state out of an .fxp file. Only chunk presets ("FPCh") can be read, since the
+/// state is stored in the chunk as JSON. Presets which store a plain list of parameter values
+/// ("FxCk") depend on the order of the parameters, which nih-plug doesn't have.
+pub fn read_fxp(bytes: &[u8]) -> Result<PluginState, String> {
+    const HEADER_LEN: usize = 60;
+    let invalid = || "This isn't a valid .fxp file".to_string();
+    if bytes.len() < HEADER_LEN || &bytes[0..4] != b"CcnK" {
+        return Err(invalid());
+    }
+    match &bytes[8..12] {
+        b"FPCh" => (),
+        b"FxCk" => {
+            return Err("Only .fxp presets which store a chunk can be loaded".to_string());
+        }
+        b"FxBk" | b"FBCh" => return Err("Banks (.fxb) can't be loaded, only presets".to_string()),
+        _ => return Err(invalid()),
+    }
+    let chunk_len = u32::from_be_bytes(bytes[56..60].try_into().unwrap()) as usize;
+    let chunk = bytes
+        .get(HEADER_LEN..HEADER_LEN + chunk_len)
+        .ok_or_else(invalid)?;
+    serde_json::from_slice(chunk)
+        .map_err(|err| format!("The .fxp file isn't a Nyasynth preset: {}", err))
+}
+
+fn presets_dir() -> Option<PathBuf> {
+    settings_dir().map(|dir| dir.join("presets"))
+}
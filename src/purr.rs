@@ -0,0 +1,76 @@
+use std::f32::consts::FRAC_PI_2;
+
+use crate::{
+    common::{Note, SampleRate},
+    params::PurrParams,
+    sound_gen::NoiseGenerator,
+};
+
+// A real cat purrs with its lowest voice, so the purr is strongest on low notes. It plays at full
+// depth up to C3, and fades out over the two octaves above that.
+const FULL_DEPTH_NOTE: f32 = 48.0;
+const FADE_NOTES: f32 = 24.0;
+
+// Each pulse swells over the first part of its cycle and dies away over the rest. The quick swell
+// and slow fall is what makes the purr rattle rather than wobble like a tremolo.
+const SWELL: f32 = 0.25;
+
+// At full roughness, how much the length and height of each pulse may randomly vary.
+const MAX_LENGTH_JITTER: f32 = 0.3;
+const MAX_HEIGHT_JITTER: f32 = 0.5;
+
+/// A low-rate amplitude pulse, which chops a voice up into the rapid bursts of a purr.
+#[derive(Debug)]
+pub struct Purr {
+    // How far through the current pulse the purr is, from 0.0 to 1.0.
+    phase: f32,
+    // The length and height of the current pulse, relative to a regular pulse. These are only
+    // changed by roughness.
+    length: f32,
+    height: f32,
+}
+
+impl Purr {
+    pub fn new() -> Purr {
+        Purr {
+            phase: 0.0,
+            length: 1.0,
+            height: 1.0,
+        }
+    }
+
+    /// Get the gain for the next sample of a voice playing `note`. Roughness randomly varies each
+    /// pulse, using the noise generator.
+    pub fn next_sample(
+        &mut self,
+        params: &PurrParams,
+        note: Note,
+        sample_rate: SampleRate,
+        noise_generator: &mut NoiseGenerator,
+    ) -> f32 {
+        let key_scale = 1.0 - ((note.0 as f32 - FULL_DEPTH_NOTE) / FADE_NOTES).clamp(0.0, 1.0);
+        let depth = params.amount * key_scale;
+        if depth <= 0.0 {
+            return 1.0;
+        }
+
+        let pulse = if self.phase < SWELL {
+            (self.phase / SWELL * FRAC_PI_2).sin()
+        } else {
+            let t = (self.phase - SWELL) / (1.0 - SWELL);
+            (1.0 - t) * (1.0 - t)
+        };
+        let pulse = pulse * self.height;
+
+        self.phase += params.speed.get() / (sample_rate.get() * self.length);
+        if self.phase >= 1.0 {
+            self.phase -= 1.0;
+            let roughness = params.roughness;
+            self.length = 1.0 + noise_generator.next() * roughness * MAX_LENGTH_JITTER;
+            let height_jitter = (noise_generator.next() + 1.0) / 2.0;
+            self.height = 1.0 - height_jitter * roughness * MAX_HEIGHT_JITTER;
+        }
+
+        1.0 - depth * (1.0 - pulse)
+    }
+}
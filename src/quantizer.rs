@@ -0,0 +1,158 @@
+use nih_plug::prelude::{Enum, NoteEvent};
+
+/// The scale which incoming notes are snapped to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum Scale {
+    /// Notes are passed through unchanged.
+    #[name = "Off"]
+    Off,
+    #[name = "Major"]
+    Major,
+    #[name = "Minor"]
+    Minor,
+    #[name = "Harmonic Minor"]
+    HarmonicMinor,
+    #[name = "Dorian"]
+    Dorian,
+    #[name = "Mixolydian"]
+    Mixolydian,
+    #[name = "Major Pentatonic"]
+    MajorPentatonic,
+    #[name = "Minor Pentatonic"]
+    MinorPentatonic,
+    #[name = "Blues"]
+    Blues,
+}
+
+impl Scale {
+    /// The semitones above the root which are in the scale.
+    fn degrees(&self) -> &'static [u8] {
+        match self {
+            Scale::Off => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+            Scale::Major => &[0, 2, 4, 5, 7, 9, 11],
+            Scale::Minor => &[0, 2, 3, 5, 7, 8, 10],
+            Scale::HarmonicMinor => &[0, 2, 3, 5, 7, 8, 11],
+            Scale::Dorian => &[0, 2, 3, 5, 7, 9, 10],
+            Scale::Mixolydian => &[0, 2, 4, 5, 7, 9, 10],
+            Scale::MajorPentatonic => &[0, 2, 4, 7, 9],
+            Scale::MinorPentatonic => &[0, 3, 5, 7, 10],
+            Scale::Blues => &[0, 3, 5, 6, 7, 10],
+        }
+    }
+
+    /// True if the note is in the scale.
+    fn contains(&self, note: i32, root: Root) -> bool {
+        let pitch_class = (note - root as i32).rem_euclid(12) as u8;
+        self.degrees().contains(&pitch_class)
+    }
+
+    /// Snap a MIDI note to the nearest note in the scale. Ties are broken by snapping downwards.
+    pub fn quantize(&self, note: u8, root: Root) -> u8 {
+        let note = note as i32;
+        // Every scale has a note at least once every 6 semitones, so this always finds a note.
+        for distance in 0..12 {
+            for candidate in [note - distance, note + distance] {
+                if (0..128).contains(&candidate) && self.contains(candidate, root) {
+                    return candidate as u8;
+                }
+            }
+        }
+        note as u8
+    }
+
+    /// Snap a note which may be between semitones, such as a detuned note, to the nearest note in
+    /// the scale. Unlike [Scale::quantize], this may go outside of the MIDI note range.
+    pub fn snap(&self, note: f32, root: Root) -> f32 {
+        let below = note.floor() as i32;
+        // Every scale has a note at least once every 6 semitones, so the nearest note is within
+        // this range.
+        (below - 6..=below + 7)
+            .filter(|&candidate| self.contains(candidate, root))
+            .map(|candidate| candidate as f32)
+            .min_by(|a, b| (a - note).abs().total_cmp(&(b - note).abs()))
+            .unwrap_or(note)
+    }
+}
+
+/// The root note of the scale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum Root {
+    #[name = "C"]
+    C,
+    #[name = "C#"]
+    CSharp,
+    #[name = "D"]
+    D,
+    #[name = "D#"]
+    DSharp,
+    #[name = "E"]
+    E,
+    #[name = "F"]
+    F,
+    #[name = "F#"]
+    FSharp,
+    #[name = "G"]
+    G,
+    #[name = "G#"]
+    GSharp,
+    #[name = "A"]
+    A,
+    #[name = "A#"]
+    ASharp,
+    #[name = "B"]
+    B,
+}
+
+/// A MIDI filter which snaps incoming notes to a scale, before they are given to the voices.
+pub struct Quantizer {
+    /// The note that each incoming note was snapped to when it was pressed. This is used so that
+    /// the note off goes to the same note as the note on, even if the scale changed in between.
+    mapping: [u8; 128],
+}
+
+impl Quantizer {
+    pub fn new() -> Quantizer {
+        let mut mapping = [0; 128];
+        for (note, mapped) in mapping.iter_mut().enumerate() {
+            *mapped = note as u8;
+        }
+        Quantizer { mapping }
+    }
+
+    /// Remap the note of a note event according to the scale and root.
+    pub fn process(&mut self, event: NoteEvent<()>, scale: Scale, root: Root) -> NoteEvent<()> {
+        match event {
+            NoteEvent::NoteOn {
+                timing,
+                voice_id,
+                channel,
+                note,
+                velocity,
+            } => {
+                let mapped = scale.quantize(note, root);
+                self.mapping[note as usize] = mapped;
+                NoteEvent::NoteOn {
+                    timing,
+                    voice_id,
+                    channel,
+                    note: mapped,
+                    velocity,
+                }
+            }
+            NoteEvent::NoteOff {
+                timing,
+                voice_id,
+                channel,
+                note,
+                velocity,
+            } => NoteEvent::NoteOff {
+                timing,
+                voice_id,
+                channel,
+                note: self.mapping[note as usize],
+                velocity,
+            },
+            event => event,
+        }
+    }
+}
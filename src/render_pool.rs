@@ -0,0 +1,305 @@
+use std::{
+    cell::UnsafeCell,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+
+use crate::{
+    common::SampleRate,
+    layers::Layer,
+    params::MeowParameters,
+    sound_gen::{NoiseGenerator, SampleModulation, Voice},
+};
+
+// The maximum number of worker threads. The audio thread also renders voices, so up to one more
+// voice than this is rendered at once.
+const MAX_WORKERS: usize = 3;
+
+// How many times a worker checks for new work before going to sleep. Blocks are short, so a worker
+// which just finished a block will usually see the next block soon.
+const SPIN_COUNT: usize = 2000;
+
+/// Renders voices on a small pool of worker threads. The voices are not assigned to workers ahead
+/// of time. Instead, every thread (including the audio thread) repeatedly claims the next
+/// unrendered voice until none are left, so that a thread which gets a cheap voice moves on to
+/// another one instead of sitting idle.
+///
+/// Rendering never allocates or takes a lock. The audio thread wakes the workers and renders voices
+/// itself until none are left. The audio thread never waits for a worker to wake up: once it runs
+/// out of voices, the job is closed, so a worker which hasn't joined by then sits the job out, and
+/// its share of the voices has already been rendered inline. The audio thread only waits for the
+/// workers which are partway through a voice. Each worker renders into its own buffers, which the
+/// audio thread mixes in at the end.
+pub struct RenderPool {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl RenderPool {
+    /// Start the worker threads. Each worker's buffers hold up to `block_size` samples.
+    pub fn new(block_size: usize) -> RenderPool {
+        let num_workers = thread::available_parallelism()
+            .map(|threads| threads.get().saturating_sub(1))
+            .unwrap_or(1)
+            .clamp(1, MAX_WORKERS);
+
+        let outputs = (0..num_workers)
+            .map(|_| UnsafeCell::new(WorkerOutput::new(block_size)))
+            .collect();
+        let shared = Arc::new(Shared {
+            job: UnsafeCell::new(None),
+            generation: AtomicUsize::new(0),
+            open_job: AtomicUsize::new(0),
+            next_voice: AtomicUsize::new(0),
+            busy_workers: AtomicUsize::new(0),
+            shutdown: AtomicBool::new(false),
+            outputs,
+        });
+
+        let workers = (0..num_workers)
+            .map(|index| {
+                let shared = shared.clone();
+                thread::Builder::new()
+                    .name(format!("nyasynth render {}", index))
+                    .spawn(move || worker(&shared, index))
+                    .expect("Couldn't spawn render thread")
+            })
+            .collect();
+
+        RenderPool { shared, workers }
+    }
+
+    /// Render every voice on the layer over a block, adding their output to `left` and `right`.
+    /// This is the same as calling [Voice::render] on each voice, except that the voices are
+    /// rendered in parallel. Returns the largest volume envelope value of any voice over the block.
+    pub fn render(
+        &self,
+        voices: &mut [Voice],
+        layer: Layer,
+        params: &MeowParameters,
+        noise_generator: &mut NoiseGenerator,
+        sample_rate: SampleRate,
+        modulation: &[SampleModulation],
+        left: &mut [f32],
+        right: &mut [f32],
+    ) -> f32 {
+        let shared = &self.shared;
+        let job = Job {
+            voices: voices.as_mut_ptr(),
+            num_voices: voices.len(),
+            layer,
+            params,
+            sample_rate,
+            modulation: modulation.as_ptr(),
+            block_len: modulation.len(),
+        };
+
+        // Safety: The workers only read the job while they are busy, and they are never busy
+        // outside of this function.
+        unsafe { *shared.job.get() = Some(job) };
+        shared.next_voice.store(0, Ordering::Relaxed);
+        // Generations start at 1, so that an open job is never 0.
+        let generation = shared.generation.fetch_add(1, Ordering::Relaxed) + 1;
+        // This publishes the job and the reset counter to the workers.
+        shared.open_job.store(generation, Ordering::SeqCst);
+        for worker in &self.workers {
+            worker.thread().unpark();
+        }
+
+        // Safety: Same as for the workers. See Shared::render_voices.
+        let mut max_volume = unsafe { shared.render_voices(&job, noise_generator, left, right) };
+
+        // Every voice has been claimed, so close the job. Workers which haven't joined yet won't,
+        // so this only waits for the workers which are still rendering the voices they claimed.
+        shared.open_job.store(0, Ordering::SeqCst);
+        while shared.busy_workers.load(Ordering::SeqCst) != 0 {
+            std::hint::spin_loop();
+        }
+        for output in &shared.outputs {
+            // Safety: The workers are done, so nothing else is touching their outputs.
+            let output = unsafe { &*output.get() };
+            // A worker which sat the job out still has the output of an older job.
+            if output.generation != generation {
+                continue;
+            }
+            let block = left.iter_mut().zip(right.iter_mut());
+            for ((left, right), (out_left, out_right)) in block.zip(output.samples()) {
+                *left += out_left;
+                *right += out_right;
+            }
+            max_volume = max_volume.max(output.max_volume);
+        }
+
+        // Safety: The workers are done, so the job (and the pointers in it) is no longer used.
+        unsafe { *shared.job.get() = None };
+        max_volume
+    }
+}
+
+impl Drop for RenderPool {
+    fn drop(&mut self) {
+        self.shared.shutdown.store(true, Ordering::Release);
+        for worker in self.workers.drain(..) {
+            worker.thread().unpark();
+            let _ = worker.join();
+        }
+    }
+}
+
+/// The block that is currently being rendered. The pointers are only valid for the duration of a
+/// call to [RenderPool::render].
+#[derive(Clone, Copy)]
+struct Job {
+    voices: *mut Voice,
+    num_voices: usize,
+    layer: Layer,
+    params: *const MeowParameters,
+    sample_rate: SampleRate,
+    modulation: *const SampleModulation,
+    block_len: usize,
+}
+
+/// The state shared between the audio thread and the workers.
+struct Shared {
+    job: UnsafeCell<Option<Job>>,
+    // Incremented each time a new job is published. This is only touched by the audio thread.
+    generation: AtomicUsize,
+    // The generation of the job which workers may join, or 0 if no job is open.
+    open_job: AtomicUsize,
+    // The index of the next voice which hasn't been claimed yet.
+    next_voice: AtomicUsize,
+    // The number of workers which have joined the current job (or are trying to) and haven't
+    // finished it.
+    busy_workers: AtomicUsize,
+    shutdown: AtomicBool,
+    // Each worker's output. A worker only touches its own output, and only while it is busy.
+    outputs: Vec<UnsafeCell<WorkerOutput>>,
+}
+
+// Safety: Access to the UnsafeCells is coordinated by the open_job and busy_workers counters, as
+// described in RenderPool::render and worker. The voices in a job are only ever accessed by the
+// one thread which claimed them.
+unsafe impl Sync for Shared {}
+unsafe impl Send for Shared {}
+
+impl Shared {
+    /// Claim and render voices until there are none left, adding their output to `left` and
+    /// `right`. Returns the largest volume envelope value of the claimed voices.
+    /// Safety: The job's pointers must be valid, and `next_voice` must have been reset for the job.
+    unsafe fn render_voices(
+        &self,
+        job: &Job,
+        noise_generator: &mut NoiseGenerator,
+        left: &mut [f32],
+        right: &mut [f32],
+    ) -> f32 {
+        let params = &*job.params;
+        let modulation = std::slice::from_raw_parts(job.modulation, job.block_len);
+        let mut max_volume = 0.0f32;
+        loop {
+            let index = self.next_voice.fetch_add(1, Ordering::Relaxed);
+            if index >= job.num_voices {
+                return max_volume;
+            }
+            // Each index is only claimed once, so this is the only reference to the voice.
+            let voice = &mut *job.voices.add(index);
+            if voice.layer != job.layer {
+                continue;
+            }
+            let volume = voice.render(
+                params,
+                noise_generator,
+                job.sample_rate,
+                modulation,
+                left,
+                right,
+            );
+            max_volume = max_volume.max(volume);
+        }
+    }
+}
+
+struct WorkerOutput {
+    left: Vec<f32>,
+    right: Vec<f32>,
+    // The number of samples in the current block.
+    len: usize,
+    max_volume: f32,
+    // The generation of the job which this output is from.
+    generation: usize,
+}
+
+impl WorkerOutput {
+    fn new(block_size: usize) -> WorkerOutput {
+        WorkerOutput {
+            left: vec![0.0; block_size],
+            right: vec![0.0; block_size],
+            len: 0,
+            max_volume: 0.0,
+            generation: 0,
+        }
+    }
+
+    fn samples(&self) -> impl Iterator<Item = (f32, f32)> + '_ {
+        let left = self.left[..self.len].iter();
+        let right = self.right[..self.len].iter();
+        left.zip(right).map(|(left, right)| (*left, *right))
+    }
+}
+
+fn worker(shared: &Shared, index: usize) {
+    // Each worker has its own noise generator, since the noise generator can't be shared.
+    let mut noise_generator = NoiseGenerator::new();
+    let mut seen_generation = 0;
+    loop {
+        // Wait for the next job.
+        let mut spins = 0;
+        let generation = loop {
+            if shared.shutdown.load(Ordering::Acquire) {
+                return;
+            }
+            let generation = shared.open_job.load(Ordering::SeqCst);
+            if generation != 0 && generation != seen_generation {
+                break generation;
+            }
+            if spins < SPIN_COUNT {
+                spins += 1;
+                std::hint::spin_loop();
+            } else {
+                thread::park();
+            }
+        };
+        seen_generation = generation;
+
+        // Join the job, unless the audio thread closed it in the meantime. Since the worker counts
+        // itself as busy before checking, the audio thread either sees it as busy and waits for it,
+        // or closed the job first and doesn't.
+        shared.busy_workers.fetch_add(1, Ordering::SeqCst);
+        if shared.open_job.load(Ordering::SeqCst) != generation {
+            shared.busy_workers.fetch_sub(1, Ordering::SeqCst);
+            continue;
+        }
+
+        // Safety: The audio thread doesn't touch the job or this worker's output until this worker
+        // is no longer busy.
+        unsafe {
+            let job = (*shared.job.get()).expect("Render job published without a job");
+            let output = &mut *shared.outputs[index].get();
+            output.generation = generation;
+            output.len = job.block_len;
+            output.left[..job.block_len].fill(0.0);
+            output.right[..job.block_len].fill(0.0);
+            let (left, right) = (&mut output.left, &mut output.right);
+            output.max_volume = shared.render_voices(
+                &job,
+                &mut noise_generator,
+                &mut left[..job.block_len],
+                &mut right[..job.block_len],
+            );
+        }
+        shared.busy_workers.fetch_sub(1, Ordering::SeqCst);
+    }
+}
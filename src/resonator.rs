@@ -0,0 +1,85 @@
+use crate::{
+    common::{Hertz, SampleRate},
+    ease::lerp,
+    params::ResonatorParams,
+};
+
+// The length of the delay line, in samples. This is a fixed size so that voices never allocate.
+// Notes too low to fit are resonated an octave (or more) up instead.
+const RESONATOR_SIZE: usize = 2048;
+
+// How much of the delayed signal is fed back. The input is scaled down by the same amount, so that
+// a signal at the resonant pitch comes out about as loud as it went in.
+const FEEDBACK: f32 = 0.98;
+
+// The coefficient of the feedback lowpass at 100% damping. This must be less than 1.0, since at
+// 1.0 the lowpass would never let any new signal through.
+const MAX_DAMPING: f32 = 0.9;
+
+/// A comb filter tuned to the note's pitch, which gives the meow a plucked, Karplus-Strong style
+/// resonant body. The feedback path is lowpassed, so that the high harmonics ring out faster than
+/// the low ones, like on a real string.
+#[derive(Debug)]
+pub struct Resonator {
+    delay_line: [f32; RESONATOR_SIZE],
+    write_head: usize,
+    // The state of the one-pole lowpass filter in the feedback path.
+    damped: f32,
+}
+
+impl Resonator {
+    pub fn new() -> Resonator {
+        Resonator {
+            delay_line: [0.0; RESONATOR_SIZE],
+            write_head: 0,
+            damped: 0.0,
+        }
+    }
+
+    /// Clear out the delay line and the feedback filter.
+    pub fn reset(&mut self) {
+        self.delay_line.fill(0.0);
+        self.damped = 0.0;
+    }
+
+    /// Run the resonator on `in_sample`, tuned to `pitch`, and return the mix of the dry and
+    /// resonated signal.
+    pub fn next_sample(
+        &mut self,
+        in_sample: f32,
+        sample_rate: SampleRate,
+        pitch: Hertz,
+        params: &ResonatorParams,
+    ) -> f32 {
+        let mut period = sample_rate.get() / pitch.get().max(1.0);
+        while period > (RESONATOR_SIZE - 2) as f32 {
+            period /= 2.0;
+        }
+        let period = period.max(1.0);
+
+        // Read one period behind the write head, interpolating between the two nearest samples so
+        // that the resonator stays in tune.
+        let len = RESONATOR_SIZE as f32;
+        let read_head = (self.write_head as f32 - period).rem_euclid(len);
+        let lower = read_head.floor();
+        let t = read_head - lower;
+        let lower = lower as usize % RESONATOR_SIZE;
+        let upper = (lower + 1) % RESONATOR_SIZE;
+        let delayed = lerp(self.delay_line[lower], self.delay_line[upper], t);
+
+        self.damped = lerp(delayed, self.damped, params.damping * MAX_DAMPING);
+
+        let out = in_sample * (1.0 - FEEDBACK) + self.damped * FEEDBACK;
+        // Flush any garbage out of the delay line instead of letting it ring forever.
+        let out = if out.is_finite() {
+            out
+        } else {
+            self.damped = 0.0;
+            0.0
+        };
+        self.delay_line[self.write_head] = out;
+        self.write_head = (self.write_head + 1) % RESONATOR_SIZE;
+
+        lerp(in_sample, out, params.mix)
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::VecDeque;
+
+/// Things waiting to happen at set times, measured in samples on the synth's clock (the number of
+/// samples since the synth started). Things scheduled for the same time happen in the order they
+/// were scheduled.
+///
+/// The queue's space is allocated up front, so scheduling never allocates, and the scheduler can be
+/// used on the audio thread.
+pub struct Scheduler<T> {
+    // Sorted by time.
+    queue: VecDeque<(u64, T)>,
+    capacity: usize,
+}
+
+impl<T> Scheduler<T> {
+    /// Create a scheduler which can hold up to `capacity` things at once.
+    pub fn new(capacity: usize) -> Scheduler<T> {
+        Scheduler {
+            queue: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Schedule something to happen at the given time. Returns false, and drops it, if the
+    /// scheduler is full.
+    pub fn schedule(&mut self, time: u64, item: T) -> bool {
+        if self.queue.len() >= self.capacity {
+            return false;
+        }
+        // Usually, things are scheduled in order, so this inserts at the back.
+        let index = self.queue.partition_point(|(other, _)| *other <= time);
+        self.queue.insert(index, (time, item));
+        true
+    }
+
+    /// Schedule something to happen at the given time, making room for it if the scheduler is full.
+    /// Things which `droppable` accepts give way to the rest: if the new thing is droppable, it is
+    /// dropped, and otherwise the latest droppable thing already scheduled is dropped in its place.
+    /// Returns whatever was dropped. If nothing could be dropped, the new thing is handed back as
+    /// an error.
+    pub fn schedule_or_evict(
+        &mut self,
+        time: u64,
+        item: T,
+        droppable: impl Fn(&T) -> bool,
+    ) -> Result<Option<T>, T> {
+        if self.queue.len() < self.capacity {
+            self.schedule(time, item);
+            return Ok(None);
+        }
+        if droppable(&item) {
+            return Ok(Some(item));
+        }
+        let Some(index) = self.queue.iter().rposition(|(_, other)| droppable(other)) else {
+            return Err(item);
+        };
+        let evicted = self.queue.remove(index).map(|(_, evicted)| evicted);
+        self.schedule(time, item);
+        Ok(evicted)
+    }
+
+    /// The time of the next thing to happen, if anything is scheduled.
+    pub fn next_time(&self) -> Option<u64> {
+        self.queue.front().map(|(time, _)| *time)
+    }
+
+    /// Take the next thing to happen, if it is due by `now`.
+    pub fn pop_due(&mut self, now: u64) -> Option<T> {
+        if self.next_time()? > now {
+            return None;
+        }
+        self.queue.pop_front().map(|(_, item)| item)
+    }
+
+    /// Take the next thing to happen, along with its time, if it happens before `end` and `matches`
+    /// it. This is used to pick out the things which can happen partway through a block.
+    pub fn pop_before(&mut self, end: u64, matches: impl Fn(&T) -> bool) -> Option<(u64, T)> {
+        let (time, item) = self.queue.front()?;
+        if *time >= end || !matches(item) {
+            return None;
+        }
+        self.queue.pop_front()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Drop everything that was scheduled.
+    pub fn clear(&mut self) {
+        self.queue.clear();
+    }
+}
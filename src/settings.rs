@@ -0,0 +1,182 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, RwLock,
+    },
+};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::issues;
+use crate::midi_learn::MidiMappings;
+use crate::skin::DEFAULT_SKIN;
+
+// The settings are shared by every instance of the plugin in the process. They are loaded the first
+// time they are used, which is when the first instance is created.
+static SETTINGS: Lazy<Settings> = Lazy::new(Settings::load);
+
+/// Per-user settings which apply to every instance of the plugin, rather than being saved with a
+/// preset or a project. These are stored in a small JSON file in the user's config directory.
+pub struct Settings {
+    // Read by the audio thread when a preset is loaded, so this is kept outside of the lock below.
+    speaker_protection: AtomicBool,
+    /// The CCs which have been bound to parameters with MIDI learn. The audio thread only ever
+    /// uses `try_read` on this.
+    pub midi_mappings: RwLock<MidiMappings>,
+    // The settings which are only used by the editor.
+    editor: Mutex<EditorSettings>,
+    // Set when the settings have changed somewhere that they couldn't be saved from.
+    unsaved: AtomicBool,
+}
+
+/// The settings which only affect the editor.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EditorSettings {
+    /// The editor's zoom level, where 1.0 is 100%.
+    pub gui_scale: f32,
+    /// The name of the skin that the editor uses.
+    pub skin: String,
+}
+
+impl Default for EditorSettings {
+    fn default() -> Self {
+        EditorSettings {
+            gui_scale: 1.0,
+            skin: DEFAULT_SKIN.to_string(),
+        }
+    }
+}
+
+// The settings as they are stored on disk. Settings missing from the file get their default
+// values, so that files written by older versions still load.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct SettingsFile {
+    speaker_protection: bool,
+    #[serde(flatten)]
+    editor: EditorSettings,
+    midi_mappings: MidiMappings,
+}
+
+impl Settings {
+    /// The settings shared by every instance of the plugin. The first call reads the settings
+    /// file, so this must first be called off the audio thread.
+    pub fn get() -> &'static Settings {
+        &SETTINGS
+    }
+
+    // Load the settings from disk. If the file doesn't exist or can't be read, the defaults are
+    // used.
+    fn load() -> Settings {
+        let file: SettingsFile = settings_path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .map(|json| {
+                serde_json::from_str(&json).unwrap_or_else(|err| {
+                    issues::warn(format!(
+                        "Couldn't parse the settings file, using the defaults: {}",
+                        err
+                    ));
+                    SettingsFile::default()
+                })
+            })
+            .unwrap_or_default();
+        Settings {
+            speaker_protection: AtomicBool::new(file.speaker_protection),
+            midi_mappings: RwLock::new(file.midi_mappings),
+            editor: Mutex::new(file.editor),
+            unsaved: AtomicBool::new(false),
+        }
+    }
+
+    /// Write the settings to disk. Failures are reported as issues and otherwise ignored. This must
+    /// not be called on the audio thread.
+    pub fn save(&self) {
+        self.unsaved.store(false, Ordering::Relaxed);
+        let Some(path) = settings_path() else {
+            return;
+        };
+        let file = SettingsFile {
+            speaker_protection: self.speaker_protection(),
+            editor: self.editor.lock().unwrap().clone(),
+            midi_mappings: self.midi_mappings.read().unwrap().clone(),
+        };
+        let result = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| {
+                let json = serde_json::to_string_pretty(&file).expect("Settings always serialize");
+                std::fs::write(&path, json)
+            });
+        if let Err(err) = result {
+            issues::error(format!(
+                "Couldn't save the settings to {}: {}",
+                path.display(),
+                err
+            ));
+        }
+    }
+
+    /// Note that the settings have changed somewhere that they shouldn't be written to disk from,
+    /// such as while a project is being loaded. They are saved the next time the editor updates.
+    pub fn save_later(&self) {
+        self.unsaved.store(true, Ordering::Relaxed);
+    }
+
+    /// Save the settings, if [Settings::save_later] has been called since they were last saved.
+    pub fn save_if_needed(&self) {
+        if self.unsaved.load(Ordering::Relaxed) {
+            self.save();
+        }
+    }
+
+    /// Whether presets with extreme resonance or feedback settings have their output limited the
+    /// first time they are loaded.
+    pub fn speaker_protection(&self) -> bool {
+        self.speaker_protection.load(Ordering::Relaxed)
+    }
+
+    /// Turn speaker protection on or off, and save the change.
+    pub fn set_speaker_protection(&self, enabled: bool) {
+        self.speaker_protection.store(enabled, Ordering::Relaxed);
+        self.save();
+    }
+
+    pub fn editor(&self) -> EditorSettings {
+        self.editor.lock().unwrap().clone()
+    }
+
+    /// Change the editor settings, and save the change.
+    pub fn update_editor(&self, update: impl FnOnce(&mut EditorSettings)) {
+        update(&mut self.editor.lock().unwrap());
+        self.save();
+    }
+}
+
+/// The environment variable which overrides the settings directory. The tests use this to keep
+/// away from the user's real settings.
+pub const SETTINGS_DIR_VAR: &str = "NYASYNTH_SETTINGS_DIR";
+
+/// The directory which holds the settings file and anything else the user can customize, such as
+/// skins. This is in the platform's usual per-user config directory, unless [SETTINGS_DIR_VAR]
+/// says otherwise.
+pub fn settings_dir() -> Option<PathBuf> {
+    let env_dir = |name: &str| std::env::var_os(name).map(PathBuf::from);
+    if let Some(dir) = env_dir(SETTINGS_DIR_VAR) {
+        return Some(dir);
+    }
+    let config_dir = if cfg!(target_os = "windows") {
+        env_dir("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        env_dir("HOME").map(|home| home.join("Library").join("Application Support"))
+    } else {
+        env_dir("XDG_CONFIG_HOME").or_else(|| env_dir("HOME").map(|home| home.join(".config")))
+    };
+    config_dir.map(|dir| dir.join("nyasynth"))
+}
+
+fn settings_path() -> Option<PathBuf> {
+    settings_dir().map(|dir| dir.join("settings.json"))
+}
@@ -0,0 +1,40 @@
+use crate::common::{SampleRate, Seconds};
+
+// Output below this level counts as silence. This is -120 dBFS.
+const SILENCE_LEVEL: f32 = 1.0e-6;
+// How long the output has to stay silent before processing is suspended. The longest tail after
+// the voices is the chorus's delay line, which is only a few tens of milliseconds long, so this
+// leaves plenty of room.
+const SLEEP_AFTER: Seconds = Seconds::new(0.2);
+
+/// Detects when no voices are playing and the effects' tails have died out, so that the synth can
+/// stop rendering silence until the next note arrives.
+pub struct SilenceDetector {
+    // The number of samples in a row which have been silent with no voices playing.
+    silent_samples: usize,
+}
+
+impl SilenceDetector {
+    pub fn new() -> SilenceDetector {
+        SilenceDetector { silent_samples: 0 }
+    }
+
+    /// Update the detector with the next buffer of output.
+    pub fn update(&mut self, left: &[f32], right: &[f32], voices_playing: bool) {
+        let silent = left
+            .iter()
+            .chain(right.iter())
+            .all(|sample| sample.abs() < SILENCE_LEVEL);
+        if voices_playing || !silent {
+            self.silent_samples = 0;
+        } else {
+            self.silent_samples = self.silent_samples.saturating_add(left.len());
+        }
+    }
+
+    /// True if the output has been silent, with no voices playing, for long enough that nothing is
+    /// left ringing.
+    pub fn is_asleep(&self, sample_rate: SampleRate) -> bool {
+        self.silent_samples as f32 >= SLEEP_AFTER.get() * sample_rate.get()
+    }
+}
@@ -0,0 +1,164 @@
+use std::path::PathBuf;
+
+use nih_plug_egui::egui::Color32;
+use serde::Deserialize;
+
+use crate::issues;
+use crate::settings::settings_dir;
+
+/// The name of the skin which is built into the plugin.
+pub const DEFAULT_SKIN: &str = "default";
+
+/// The colors, knob style, and background image used by the editor. Skins are loaded from
+/// `skins/<name>/skin.json` in the settings directory, so that they can be made without
+/// recompiling the plugin. Anything a skin leaves out is taken from the default skin.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Skin {
+    /// The color behind the background image.
+    pub background: SkinColor,
+    /// An image to use instead of the built in background, relative to the skin's directory.
+    pub background_image: Option<PathBuf>,
+    /// The color of the text drawn over the background, such as the pitch readout and the text
+    /// sliders. If unset, the text is white.
+    pub text: Option<SkinColor>,
+    pub knob: KnobSkin,
+    /// The color of the output meters' RMS bars.
+    pub meter: SkinColor,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct KnobSkin {
+    pub style: KnobStyle,
+    pub color: SkinColor,
+    /// The color of the unfilled part of the knob's range. This is only used by the track style.
+    pub track_color: SkinColor,
+    /// The thickness of the arc, or the diameter of the dot.
+    pub width: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KnobStyle {
+    /// An arc which fills up as the value increases.
+    Arc,
+    /// An arc drawn over a dimmer track covering the whole range.
+    Track,
+    /// A single dot which moves around the knob.
+    Dot,
+}
+
+/// A color, written in skin files as a "#RRGGBB" or "#RRGGBBAA" hex string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct SkinColor(pub Color32);
+
+impl TryFrom<String> for SkinColor {
+    type Error = String;
+
+    fn try_from(hex: String) -> Result<Self, Self::Error> {
+        let digits = hex.trim().trim_start_matches('#');
+        let channel = |i: usize| {
+            digits
+                .get(2 * i..2 * i + 2)
+                .and_then(|channel| u8::from_str_radix(channel, 16).ok())
+        };
+        let color = match digits.len() {
+            6 => channel(0)
+                .zip(channel(1))
+                .zip(channel(2))
+                .map(|((r, g), b)| Color32::from_rgb(r, g, b)),
+            8 => channel(0)
+                .zip(channel(1))
+                .zip(channel(2))
+                .zip(channel(3))
+                .map(|(((r, g), b), a)| Color32::from_rgba_unmultiplied(r, g, b, a)),
+            _ => None,
+        };
+        color
+            .map(SkinColor)
+            .ok_or_else(|| format!("{:?} is not a #RRGGBB or #RRGGBBAA color", hex))
+    }
+}
+
+impl Default for Skin {
+    fn default() -> Self {
+        Skin {
+            background: SkinColor(Color32::from_rgb(0x89, 0xA9, 0xBD)),
+            background_image: None,
+            text: None,
+            knob: KnobSkin::default(),
+            meter: SkinColor(Color32::from_rgb(0x6C, 0xC0, 0x6C)),
+        }
+    }
+}
+
+impl Default for KnobSkin {
+    fn default() -> Self {
+        KnobSkin {
+            style: KnobStyle::Arc,
+            color: SkinColor(Color32::YELLOW),
+            track_color: SkinColor(Color32::from_black_alpha(96)),
+            width: 5.0,
+        }
+    }
+}
+
+impl Skin {
+    /// Load the skin with the given name. The default skin is returned if the skin doesn't exist
+    /// or can't be parsed. This reads files, so it must not be called on the audio thread.
+    pub fn load(name: &str) -> Skin {
+        if name == DEFAULT_SKIN {
+            return Skin::default();
+        }
+        let Some(dir) = skin_dir(name) else {
+            return Skin::default();
+        };
+        let skin = std::fs::read_to_string(dir.join("skin.json"))
+            .map_err(|err| err.to_string())
+            .and_then(|json| serde_json::from_str::<Skin>(&json).map_err(|err| err.to_string()));
+        match skin {
+            Ok(mut skin) => {
+                skin.background_image = skin.background_image.map(|image| dir.join(image));
+                skin
+            }
+            Err(err) => {
+                issues::warn(format!(
+                    "Couldn't load the skin {:?}, using the default: {}",
+                    name, err
+                ));
+                Skin::default()
+            }
+        }
+    }
+
+    /// The text color, falling back to white.
+    pub fn text_color(&self) -> Color32 {
+        self.text.map_or(Color32::WHITE, |text| text.0)
+    }
+}
+
+/// The names of the skins which can be picked, starting with the default skin.
+pub fn available_skins() -> Vec<String> {
+    let mut names: Vec<String> = skins_dir()
+        .and_then(|dir| std::fs::read_dir(dir).ok())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().join("skin.json").is_file())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| name != DEFAULT_SKIN)
+        .collect();
+    names.sort();
+    names.insert(0, DEFAULT_SKIN.to_string());
+    names
+}
+
+fn skins_dir() -> Option<PathBuf> {
+    settings_dir().map(|dir| dir.join("skins"))
+}
+
+fn skin_dir(name: &str) -> Option<PathBuf> {
+    skins_dir().map(|dir| dir.join(name))
+}
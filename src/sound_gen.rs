@@ -1,7 +1,17 @@
 use crate::{
-    common::{Hertz, Note, Pitch, Pitchbend, SampleRate, SampleTime, Seconds, Vel},
+    common::{FilterRouting, Hertz, Note, Pitch, Pitchbend, SampleRate, SampleTime, Seconds, Vel},
     ease::lerp,
-    params::{EnvelopeParams, MeowParameters},
+    growl::Growl,
+    layers::Layer,
+    params::{
+        EnvelopeCurve, EnvelopeParams, EnvelopeTimeScale, FilterParams, MeowParameters,
+        PortamentoMode, ScaledEnvelope, VibratoMode, MAX_FILTER_FM, MAX_FOLLOWER_TO_FILTER,
+        MAX_VIBRATO_AMOUNT,
+    },
+    purr::Purr,
+    resonator::Resonator,
+    supermeow::Supermeow,
+    watchdog::{is_sane, WATCHDOG_ENABLED},
 };
 
 use biquad::{Biquad, DirectForm1, ToHertz, Q_BUTTERWORTH_F32};
@@ -13,7 +23,7 @@ const TAU: f32 = std::f32::consts::TAU;
 pub const RETRIGGER_TIME: SampleTime = 88; // 88 samples is about 2 miliseconds.
 
 /// A value in range [0.0, 1.0] which denotes the position wihtin a wave cycle.
-type Angle = f32;
+pub type Angle = f32;
 
 /// A small noise generator using xorshift.
 pub struct NoiseGenerator {
@@ -33,7 +43,8 @@ impl NoiseGenerator {
         NoiseGenerator { state: seed }
     }
 
-    fn next(&mut self) -> f32 {
+    /// Return a random value in the [-1.0, 1.0] range.
+    pub fn next(&mut self) -> f32 {
         // RNG algorithm used here is Xorshift, specifically the one listed at Wikipedia
         // https://en.wikipedia.org/wiki/Xorshift
         let x = self.state;
@@ -75,6 +86,8 @@ where
     fn lerp_decay(start: Self, end: Self, t: f32) -> Self;
     fn lerp_release(start: Self, end: Self, t: f32) -> Self;
     fn lerp_retrigger(start: Self, end: Self, t: f32) -> Self;
+    // Ease linearly in amplitude, which is how the analog curve discharges.
+    fn lerp_analog(start: Self, end: Self, t: f32) -> Self;
     // The value to ease from during attack phase and to ease to during release phase
     fn zero() -> Self;
     // The value to ease to during decay phase and to ease from during decay phase
@@ -94,6 +107,9 @@ impl EnvelopeType for f32 {
     fn lerp_retrigger(start: Self, end: Self, t: f32) -> Self {
         lerp::<Self>(start, end, t)
     }
+    fn lerp_analog(start: Self, end: Self, t: f32) -> Self {
+        lerp::<Self>(start, end, t)
+    }
 
     fn zero() -> Self {
         0.0
@@ -103,14 +119,46 @@ impl EnvelopeType for f32 {
     }
 }
 
+/// The modulation for a single sample which is shared by every voice.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SampleModulation {
+    pub pitch_bend: Pitchbend,
+    /// The vibrato LFO's value, scaled by the vibrato amount.
+    pub vibrato: f32,
+    /// The filter dry/wet. This is smoothed per sample, since it is applied directly to the signal.
+    pub filter_dry_wet: f32,
+    /// The envelope follower's level.
+    pub follower_level: f32,
+}
+
 #[derive(Debug)]
 pub struct Voice {
     pub note: Note,
+    // The host provided voice ID and MIDI channel of the note. These are used to route per-note
+    // events (such as CLAP note expressions) to the correct voice.
+    pub voice_id: Option<i32>,
+    pub channel: u8,
+    // The layer which this voice plays on, which decides the parameters that it plays with.
+    pub layer: Layer,
+    // Per-note expressions set by the host.
+    pub expression: NoteExpression,
+    // The random detune of this note, in semitones.
+    pub detune: f32,
+    // The pitch bend of this note's MPE member channel, in semitones.
+    pub mpe_bend: f32,
+    // Which MIDI output events have been sent for this voice.
+    pub midi_out: MidiOutState,
     // The ending pitch from which portamento ends up at. This and `start_pitch` are unaffected by
     // by pitch bend and pitch modifiers.
     end_pitch: Pitch,
     // The starting pitch from which portamento bends from.
     start_pitch: Pitch,
+    // When the current glide from `start_pitch` to `end_pitch` started, and how long it takes if
+    // it isn't the portamento time. Legato slides start partway through the note.
+    glide_start: SampleTime,
+    glide_time: Option<Seconds>,
+    // The pitch of the most recent sample, including portamento, vibrato, and pitch bend.
+    pitch: Hertz,
     // The velocity of the note that this SoundGenerator is playing, ignoring all
     // amplitude modulation effects. This is a 0.0 - 1.0 normalized value.
     vel: Vel,
@@ -126,12 +174,42 @@ pub struct Voice {
     crossfader: Option<Crossfader>,
     // The signal generating oscillator
     osc: Oscillator,
+    // The detuned copies of the oscillator
+    supermeow: Supermeow,
+    // The comb resonator, which is tuned to the note's pitch
+    resonator: Resonator,
+    // The purr, which pulses the volume
+    purr: Purr,
+    // The growl, which sweeps the filter or the volume, and saturates the voice
+    growl: Growl,
+    // The per-voice scaling of the envelope times. This is computed on note on.
+    env_scale: EnvelopeTimeScale,
+    // The additional scaling of the filter envelope's release time. This is set by the note off
+    // velocity.
+    filter_release_scale: f32,
     // The ADSR volume envelope
     vol_env: Envelope<f32>,
     // The vibrato attack envelope
     vibrato_env: Envelope<f32>,
     // The state for the EQ/filters, applied after the signal is generated
     filter: DirectForm1<f32>,
+    // The filter for the right channel, which is only used when the noise is decorrelated.
+    right_filter: DirectForm1<f32>,
+    // The cutoff frequency from the most recent filter update.
+    filter_cutoff: Hertz,
+    // The sine which is mixed in when the filter self-oscillates.
+    self_oscillator: Oscillator,
+    // The second filter, and the crossover which splits the signal between the filters in split
+    // mode.
+    second_filter: StereoBiquad,
+    crossover: StereoBiquad,
+    // Ramps the filter coefficients between updates. Both filters always share coefficients.
+    filter_ramp: CoefficientRamp,
+    // If true, the filter coefficients are recomputed on the next sample, instead of waiting for
+    // the next scheduled update.
+    filter_stale: bool,
+    // If true, the voice output garbage and was silenced by the output watchdog.
+    muted: bool,
     // The ADSR filter envelope
     filter_env: Envelope<f32>,
 }
@@ -146,143 +224,403 @@ impl Voice {
     ) -> Voice {
         let end_pitch = Pitch::from_note(note);
         let start_pitch = start_pitch.unwrap_or(end_pitch);
+        let coefficients = biquad::Coefficients::<f32>::from_params(
+            biquad::Type::LowPass,
+            sample_rate.hz(),
+            (10000).hz(),
+            Q_BUTTERWORTH_F32,
+        )
+        .unwrap();
+        let filter = DirectForm1::<f32>::new(coefficients);
         Voice {
             note,
+            voice_id: None,
+            channel: 0,
+            layer: Layer::A,
+            expression: NoteExpression::default(),
+            detune: 0.0,
+            mpe_bend: 0.0,
+            midi_out: MidiOutState::Pending,
             start_pitch,
             end_pitch,
+            glide_start: 0,
+            glide_time: None,
+            pitch: start_pitch.into_hertz(),
             vel,
             samples_since_note_on: 0,
             note_state: NoteState::Held,
             filter_sweep: FilterSweeper::new(params, vel),
             crossfader: None,
+            env_scale: Voice::env_scale(params, note, vel),
+            filter_release_scale: 1.0,
             osc: Oscillator::new(),
+            supermeow: Supermeow::new(),
+            resonator: Resonator::new(),
+            purr: Purr::new(),
+            growl: Growl::new(),
             vol_env: Envelope::<f32>::new(),
             vibrato_env: Envelope::<f32>::new(),
             filter_env: Envelope::<f32>::new(),
-            filter: DirectForm1::<f32>::new(
-                biquad::Coefficients::<f32>::from_params(
-                    biquad::Type::LowPass,
-                    sample_rate.hz(),
-                    (10000).hz(),
-                    Q_BUTTERWORTH_F32,
-                )
-                .unwrap(),
-            ),
+            filter,
+            right_filter: filter,
+            second_filter: StereoBiquad::new(coefficients),
+            crossover: StereoBiquad::new(coefficients),
+            filter_ramp: CoefficientRamp::new(coefficients),
+            filter_cutoff: Hertz(10000.0),
+            self_oscillator: Oscillator::new(),
+            // Compute the real coefficients on the first sample.
+            filter_stale: true,
+            muted: false,
         }
     }
 
     /// Returns true if the note is "alive" (playing audio). A note is dead if
     /// it is in the release state and it is after the total release time.
     pub fn is_alive(&self, sample_rate: SampleRate, params: &MeowParameters) -> bool {
+        if self.muted {
+            return false;
+        }
+
+        // Voices which have finished fading out are silent, regardless of their envelope.
+        if self
+            .crossfader
+            .is_some_and(|crossfader| crossfader.is_faded_out())
+        {
+            return false;
+        }
+
         match self.note_state {
             NoteState::Held => true,
             NoteState::Released(release_time) => {
                 // The number of seconds it has been since release
                 let time = sample_rate.to_seconds(self.samples_since_note_on - release_time);
-                time < params.vol_envelope.release()
+                time < params.vol_envelope.release() * self.env_scale.release
             }
         }
     }
 
-    pub fn next_sample(
+    /// Render the voice over a block, adding its output to `left` and `right`. The block is as
+    /// long as `modulation`, which holds the global modulation for each sample. Returns the largest
+    /// volume envelope value over the block.
+    pub fn render(
         &mut self,
         params: &MeowParameters,
         noise_generator: &mut NoiseGenerator,
         sample_rate: SampleRate,
-        pitch_bend: Pitchbend,
-        vibrato_mod: f32,
+        modulation: &[SampleModulation],
+        left: &mut [f32],
+        right: &mut [f32],
+    ) -> f32 {
+        let mut max_volume = 0.0f32;
+        if self.muted {
+            return max_volume;
+        }
+
+        let outputs = left.iter_mut().zip(right.iter_mut());
+        for (modulation, (left, right)) in modulation.iter().zip(outputs) {
+            let (left_value, right_value, total_volume) =
+                self.next_sample(params, noise_generator, sample_rate, *modulation);
+            if WATCHDOG_ENABLED && !(is_sane(left_value) && is_sane(right_value)) {
+                self.mute();
+                return max_volume;
+            }
+            *left += left_value;
+            *right += right_value;
+            max_volume = max_volume.max(total_volume);
+        }
+        max_volume
+    }
+
+    fn next_sample(
+        &mut self,
+        params: &MeowParameters,
+        noise_generator: &mut NoiseGenerator,
+        sample_rate: SampleRate,
+        modulation: SampleModulation,
     ) -> (f32, f32, f32) {
         self.samples_since_note_on += 1;
         let context = self.get_note_context(sample_rate);
 
         // Compute volume from parameters
         let vol_env = {
-            // Easing computed somewhat empirically.
-            // See https://www.desmos.com/calculator/r7k5ee8k5j for details.
-            let x = self.vol_env.get(&params.vol_envelope, context);
-            (x * x * x + x) / 2.0
+            let vol_envelope = ScaledEnvelope::new(&params.vol_envelope, self.env_scale);
+            let x = self.vol_env.get(&vol_envelope, context);
+            vol_envelope.level_to_amp(x)
         };
         let total_volume = self.vel.raw * vol_env.max(0.0);
 
         // Compute pitch modifiers
         let pitch_mod = {
-            let pitch_bend_mod = pitch_bend.get() * (params.pitchbend_max as f32);
+            let pitch_bend_mod = modulation.pitch_bend.get() * (params.pitchbend_max as f32);
 
-            // Both vibrato_mod and vibrato_env are in the 0.0-1.0 range. We multiply by two here to
-            // allow the vibrato to modulate the pitch by up to two semitones.
+            // Both vibrato_mod and vibrato_env are in the 0.0-1.0 range. We multiply by the maximum
+            // vibrato amount here to allow the vibrato to modulate the pitch by up to two
+            // semitones. In trill mode, the pitch instead jumps in whole semitones, up to the trill
+            // range.
             let vibrato_env = self.vibrato_env.get(&params.vibrato_attack, context);
-            let vibrato_mod = vibrato_mod * vibrato_env * 2.0;
+            let vibrato_lfo = &params.vibrato_lfo;
+            let vibrato_mod = modulation.vibrato;
+            let vibrato_mod = match vibrato_lfo.mode {
+                VibratoMode::Smooth => vibrato_mod * vibrato_env * MAX_VIBRATO_AMOUNT / 100.0,
+                VibratoMode::Trill => {
+                    (vibrato_mod * vibrato_env * vibrato_lfo.trill_range as f32).round()
+                }
+            };
 
-            // Given any note, the note a single semitone away is 2^1/12 times the original note
-            // So (2^1/12)^n = 2^(n/12) is n semitones away.
-            Pitch((vibrato_mod + pitch_bend_mod) / 12.0)
+            // The pitch modifiers are all in semitones.
+            let tuning = self.expression.tuning + self.detune + self.mpe_bend;
+            vibrato_mod + pitch_bend_mod + tuning
         };
-        let base_note = self.get_current_pitch(sample_rate, params.portamento_time);
-
-        // Note that we can just add these values together. This is because base_note and pitch_mod
-        // are in the same linear space (specifically: +1.0 maps to one octave, which happens because
-        // converting to and from Hertz uses exp2 and log2).
-        let pitch = (base_note + pitch_mod).into_hertz();
+        let base_note =
+            self.get_current_pitch(sample_rate, params.portamento_time, params.portamento_mode);
+        let pitch = base_note.transpose_semitones(pitch_mod);
+        self.pitch = pitch.into_hertz();
 
         // Get next sample
         let value = self
             .osc
-            .next_sample(sample_rate, NoteShape::Sawtooth, pitch);
+            .next_sample(sample_rate, NoteShape::Sawtooth, self.pitch);
+        // The bare oscillator is also the source for filter FM.
+        let osc_value = value;
+        // In hiss mode, the meow is muted and only the noise plays. The oscillator still runs, since
+        // it is also the source for filter FM.
+        let value = if params.hiss_mode {
+            0.0
+        } else {
+            self.supermeow.next_sample(
+                value,
+                sample_rate,
+                NoteShape::Sawtooth,
+                pitch,
+                &params.supermeow,
+            )
+        };
 
-        // Apply noise, if the noise is turned on.
-        let value = if params.noise_mix > 0.01 {
+        // Apply noise, if the noise is turned on. The right channel's noise is a blend of the left
+        // channel's noise and independent noise, depending on the noise width. The blend is equal
+        // power, so the noise is equally loud at every width.
+        let noise_mix = if params.hiss_mode {
+            1.0
+        } else {
+            (params.noise_mix + params.follower.to_noise * modulation.follower_level)
+                .clamp(0.0, 1.0)
+        };
+        let (left_value, right_value) = if noise_mix > 0.01 {
             let noise = noise_generator.next();
-            value + noise * params.noise_mix
+            let width = params.noise_width;
+            let right_noise = if width > 0.0 {
+                let independent = noise_generator.next();
+                noise * (1.0 - width * width).sqrt() + independent * width
+            } else {
+                noise
+            };
+            (value + noise * noise_mix, value + right_noise * noise_mix)
+        } else {
+            (value, value)
+        };
+
+        // Saturate the voice before it is filtered, so that the filter can tame the harmonics which
+        // the growl adds.
+        let growl = &params.growl;
+        self.growl
+            .next_sample(growl, self.expression.pressure, sample_rate);
+        let (left_value, right_value) = self.growl.saturate(
+            left_value,
+            right_value,
+            params.quality.saturation_oversampling(),
+        );
+
+        // Apply the resonator. The resonator is mono, so the channels are summed before being
+        // resonated, and the resonated signal is mixed into both channels.
+        let (left_value, right_value) = if params.resonator.mix > 0.0 {
+            let resonator = &params.resonator;
+            let mid = (left_value + right_value) / 2.0;
+            let wet = self
+                .resonator
+                .next_sample(mid, sample_rate, self.pitch, resonator);
+            (left_value + (wet - mid), right_value + (wet - mid))
         } else {
-            value
+            (left_value, right_value)
+        };
+
+        // In split mode, the main filter only gets the low band. The high band is saved for the
+        // second filter. The bands are complementary, so they add back up to the original signal.
+        let routing = params.filter_2.routing;
+        let ((left_value, right_value), second_input) = match routing {
+            FilterRouting::Split => {
+                let (low_left, low_right) = self.crossover.run(left_value, right_value);
+                let high = (left_value - low_left, right_value - low_right);
+                ((low_left, low_right), high)
+            }
+            _ => ((left_value, right_value), (left_value, right_value)),
         };
 
         // Apply filter
-        let value = {
-            // Only update the filter once every 16 samples (reduces expensive
-            // biquad::Coefficients::from_params calls without reducing sound quality much.)
-            if self.samples_since_note_on % 16 == 0 {
+        let (left_value, right_value) = {
+            // Only update the filter once every few samples, depending on the quality (reduces
+            // expensive biquad::Coefficients::from_params calls without reducing sound quality
+            // much.)
+            let interval = params.quality.filter_update_interval();
+            if self.filter_stale || self.samples_since_note_on % interval == 0 {
                 let filter = &params.filter;
                 // TODO: investigate if this is correct
-                let filter_env = self.filter_env.get(&params.filter_envelope, context);
+                let filter_env_scale = EnvelopeTimeScale {
+                    release: self.env_scale.release * self.filter_release_scale,
+                    ..self.env_scale
+                };
+                let filter_envelope =
+                    ScaledEnvelope::new(&params.filter_envelope, filter_env_scale);
+                let filter_env = self.filter_env.get(&filter_envelope, context);
 
                 let cutoff_freq = self.filter_sweep.lerp(filter_env);
+                let follower_octaves =
+                    params.follower.to_filter * modulation.follower_level * MAX_FOLLOWER_TO_FILTER;
+                let growl_octaves = self.growl.filter_octaves(growl.target);
+                let cutoff_freq = cutoff_freq * (follower_octaves + growl_octaves).exp2();
+                let cutoff_freq = if filter.keytrack {
+                    cutoff_freq * (self.pitch.get() / KEYTRACK_CENTER)
+                } else {
+                    cutoff_freq
+                };
 
                 // avoid numerical instability encountered at very low
                 // or high frequencies. Clamping at around 20 Hz also
                 // avoids blowing out the speakers.
-                let cutoff_freq = cutoff_freq.clamp(20.0, sample_rate.0 * 0.99 / 2.0);
+                let cutoff_freq = cutoff_freq.clamp(20.0, filter.max_cutoff(sample_rate));
+                self.filter_cutoff = cutoff_freq;
 
-                let coefficents = biquad::Coefficients::<f32>::from_params(
-                    filter.filter_type,
-                    sample_rate.hz(),
-                    cutoff_freq.into(),
-                    filter.q_value.max(0.0),
-                )
-                .unwrap();
-                self.filter.update_coefficients(coefficents);
+                // With filter FM, the coefficients are computed on every sample below instead.
+                if filter.fm_amount == 0.0 {
+                    let coefficents = filter_coefficients(filter, sample_rate, cutoff_freq);
+                    // Stale coefficients are replaced right away, since they might not even be for
+                    // the right sample rate.
+                    if self.filter_stale || !params.quality.interpolate_filter() {
+                        self.filter_ramp.jump_to(coefficents);
+                    } else {
+                        self.filter_ramp.ramp_to(coefficents, interval);
+                    }
+                }
+
+                // The second filter has no envelope, so its coefficients are never ramped.
+                if routing != FilterRouting::Single {
+                    let filter_2 = &params.filter_2;
+                    let max_cutoff = filter.max_cutoff(sample_rate);
+                    let coefficients = biquad::Coefficients::<f32>::from_params(
+                        filter_2.filter_type,
+                        sample_rate.hz(),
+                        filter_2.cutoff_freq.clamp(20.0, max_cutoff).into(),
+                        filter_2.q_value.max(0.0),
+                    )
+                    .unwrap();
+                    self.second_filter.update_coefficients(coefficients);
+
+                    if routing == FilterRouting::Split {
+                        let coefficients = biquad::Coefficients::<f32>::from_params(
+                            biquad::Type::LowPass,
+                            sample_rate.hz(),
+                            filter_2.split_freq.clamp(20.0, max_cutoff).into(),
+                            Q_BUTTERWORTH_F32,
+                        )
+                        .unwrap();
+                        self.crossover.update_coefficients(coefficients);
+                    }
+                }
+                self.filter_stale = false;
+            }
+
+            // Filter FM moves the cutoff at audio rate, which is far too fast for the throttled
+            // updates, so the coefficients are recomputed on every sample. This is expensive, but
+            // only happens when filter FM is turned on.
+            if params.filter.fm_amount > 0.0 {
+                let filter = &params.filter;
+                let fm_octaves = filter.fm_amount * osc_value * MAX_FILTER_FM;
+                let cutoff_freq = (self.filter_cutoff * fm_octaves.exp2())
+                    .clamp(20.0, filter.max_cutoff(sample_rate));
+                let coefficients = filter_coefficients(filter, sample_rate, cutoff_freq);
+                self.filter_ramp.jump_to(coefficients);
+            }
+
+            if let Some(coefficients) = self.filter_ramp.next() {
+                self.filter.update_coefficients(coefficients);
+                self.right_filter.update_coefficients(coefficients);
             }
 
-            let output = self.filter.run(value);
-            if output.is_finite() {
-                lerp(value, output, params.filter.dry_wet)
+            let filter = &params.filter;
+            // A biquad can't really self-oscillate, so a sine at the cutoff is mixed in instead.
+            // Only filters with a resonant peak whistle.
+            let whistle = match filter.filter_type {
+                biquad::Type::LowPass | biquad::Type::HighPass | biquad::Type::BandPass
+                    if filter.self_oscillation > 0.0 =>
+                {
+                    let sine = self.self_oscillator.next_sample(
+                        sample_rate,
+                        NoteShape::Sine,
+                        self.filter_cutoff,
+                    );
+                    sine * filter.self_oscillation
+                }
+                _ => 0.0,
+            };
+            let run_filter = |biquad: &mut DirectForm1<f32>, value: f32| {
+                let output = biquad.run(value * filter.pre_gain.get_amp()) + whistle;
+                if output.is_finite() {
+                    let output = output * filter.post_gain.get_amp() * filter.resonance_gain;
+                    let output = if filter.q_limiter {
+                        output.tanh()
+                    } else {
+                        output
+                    };
+                    filter.mix_law.mix(value, output, modulation.filter_dry_wet)
+                } else {
+                    // If the output happens to be NaN or Infinity, output the
+                    // original  signal instead. Hopefully, this will "reset"
+                    // the filter on the next sample, instead of being filled
+                    // with garbage values.
+                    value
+                }
+            };
+
+            let left = run_filter(&mut self.filter, left_value);
+            // The right channel only needs its own filter if its input differs from the left
+            // channel. Otherwise, the right filter just follows the left filter, so that it can
+            // take over without a click if the channels start to differ.
+            let right = if right_value != left_value {
+                run_filter(&mut self.right_filter, right_value)
             } else {
-                // If the output happens to be NaN or Infinity, output the
-                // original  signal instead. Hopefully, this will "reset"
-                // the filter on the next sample, instead of being filled
-                // with garbage values.
-                value
+                self.right_filter = self.filter;
+                left
+            };
+            (left, right)
+        };
+
+        // Apply the second filter
+        let (left_value, right_value) = match routing {
+            FilterRouting::Single => (left_value, right_value),
+            FilterRouting::Serial => self.second_filter.run(left_value, right_value),
+            FilterRouting::Parallel | FilterRouting::Split => {
+                let (second_left, second_right) =
+                    self.second_filter.run(second_input.0, second_input.1);
+                let (main_gain, second_gain) = params.filter_2.gains();
+                (
+                    left_value * main_gain + second_left * second_gain,
+                    right_value * main_gain + second_right * second_gain,
+                )
             }
         };
-        let value = value * total_volume;
 
-        let value = if let Some(crossfader) = &mut self.crossfader {
-            value * crossfader.next()
+        let purr = self
+            .purr
+            .next_sample(&params.purr, self.note, sample_rate, noise_generator);
+        let gain = total_volume * self.expression.gain * purr * self.growl.gain(growl.target);
+        let gain = if let Some(crossfader) = &mut self.crossfader {
+            gain * crossfader.next()
         } else {
-            value
+            gain
         };
 
-        (value, value, total_volume)
+        let (left, _) = self.expression.pan(left_value * gain);
+        let (_, right) = self.expression.pan(right_value * gain);
+        (left, right, total_volume)
     }
 
     pub fn note_off(&mut self) {
@@ -291,6 +629,16 @@ impl Voice {
         self.note_state = NoteState::Released(self.samples_since_note_on);
     }
 
+    /// Release the note, with the note off velocity affecting the filter envelope's release.
+    pub fn note_off_with_velocity(&mut self, params: &MeowParameters, release_vel: f32) {
+        self.filter_release_scale = params.filter_envelope.release_scale(release_vel);
+        self.note_off();
+    }
+
+    pub fn vel(&self) -> Vel {
+        self.vel
+    }
+
     pub fn is_released(&self) -> bool {
         match self.note_state {
             NoteState::Released(_) => true,
@@ -298,6 +646,29 @@ impl Voice {
         }
     }
 
+    /// Returns the most recently computed volume and filter envelope values, in that order.
+    pub fn envelope_values(&self) -> (f32, f32) {
+        (self.vol_env.last_value(), self.filter_env.last_value())
+    }
+
+    /// Returns true if the voice is fading out, either because it was retriggered or stolen.
+    pub fn is_fading_out(&self) -> bool {
+        matches!(
+            self.crossfader,
+            Some(Crossfader {
+                state: CrossfadeState::FadeOut,
+                ..
+            })
+        )
+    }
+
+    /// Release the voice and quickly fade it out, so that its slot can be given to a new voice.
+    pub fn steal(&mut self, params: &MeowParameters, sample_rate: SampleRate) {
+        self.note_off();
+        let fade_length = sample_rate.to_samples(params.retrigger_fade);
+        self.crossfader = Some(Crossfader::fade_out(fade_length));
+    }
+
     pub fn start_crossfade(
         &mut self,
         params: &MeowParameters,
@@ -309,16 +680,73 @@ impl Voice {
     ) -> Voice {
         self.note_off();
         let start_pitch = if bend_from_current {
-            Some(self.get_current_pitch(sample_rate, portamento_time))
+            Some(self.get_current_pitch(sample_rate, portamento_time, params.portamento_mode))
         } else {
             None
         };
         let mut new_gen = Voice::new(params, start_pitch, new_note, new_vel, sample_rate);
-        self.crossfader = Some(Crossfader::fade_out());
-        new_gen.crossfader = Some(Crossfader::fade_in());
+        new_gen.layer = self.layer;
+        let fade_length = sample_rate.to_samples(params.retrigger_fade);
+        self.crossfader = Some(Crossfader::fade_out(fade_length));
+        new_gen.crossfader = Some(Crossfader::fade_in(fade_length));
         new_gen
     }
 
+    /// Slide the voice to a new note without restarting its envelopes. This is used for legato in
+    /// monocat mode.
+    pub fn slide_to(&mut self, params: &MeowParameters, sample_rate: SampleRate, new_note: Note) {
+        self.start_pitch =
+            self.get_current_pitch(sample_rate, params.portamento_time, params.portamento_mode);
+        self.end_pitch = Pitch::from_note(new_note);
+        self.glide_start = self.samples_since_note_on;
+        self.glide_time = Some(params.slide_time);
+        // The MIDI output has to move to the new note too.
+        if self.midi_out == MidiOutState::NoteOn {
+            self.midi_out = MidiOutState::Slid(self.note, self.channel);
+        }
+        self.note = new_note;
+    }
+
+    /// Compute the envelope time scaling for a note. Higher notes have shorter envelope times,
+    /// depending on the keytrack amounts, and the velocity can also shorten or lengthen the attack
+    /// and decay.
+    fn env_scale(params: &MeowParameters, note: Note, vel: Vel) -> EnvelopeTimeScale {
+        let octaves_from_middle_c = (note.0 as f32 - 60.0) / 12.0;
+        let keytrack = (-params.envelope_keytrack * octaves_from_middle_c).exp2();
+        let attack_keytrack = (-params.attack_keytrack * octaves_from_middle_c).exp2();
+        // This is -1.0 at zero velocity and +1.0 at maximum velocity.
+        let vel = 2.0 * vel.raw - 1.0;
+        EnvelopeTimeScale {
+            attack: attack_keytrack * (-params.vel_to_attack * vel).exp2(),
+            decay: keytrack * (-params.vel_to_decay * vel).exp2(),
+            release: keytrack,
+        }
+    }
+
+    /// Recompute the filter coefficients on the next sample. This should be called when the
+    /// sample rate changes, since the old coefficients are only correct for the old sample rate.
+    pub fn invalidate_filter(&mut self) {
+        self.filter_stale = true;
+    }
+
+    /// Silence the voice for good and clear out its filter state. This is used by the output
+    /// watchdog when the voice outputs garbage. Muted voices are no longer alive, so they are
+    /// removed at the start of the next buffer.
+    fn mute(&mut self) {
+        self.muted = true;
+        self.filter.reset_state();
+        self.right_filter.reset_state();
+        self.second_filter.reset_state();
+        self.crossover.reset_state();
+        self.resonator.reset();
+    }
+
+    /// The pitch that the voice is currently playing at, including portamento, vibrato, and pitch
+    /// bend.
+    pub fn current_pitch(&self) -> Hertz {
+        self.pitch
+    }
+
     fn get_note_context(&self, sample_rate: SampleRate) -> NoteContext {
         NoteContext {
             note_state: self.note_state,
@@ -327,43 +755,117 @@ impl Voice {
         }
     }
 
-    fn get_current_pitch(&self, sample_rate: SampleRate, portamento_time: Seconds) -> Pitch {
-        let time = sample_rate.to_seconds(self.samples_since_note_on);
-        let t = (time / portamento_time).clamp(0.0, 1.0);
-        lerp(self.start_pitch, self.end_pitch, t)
+    fn get_current_pitch(
+        &self,
+        sample_rate: SampleRate,
+        portamento_time: Seconds,
+        portamento_mode: PortamentoMode,
+    ) -> Pitch {
+        let time = sample_rate.to_seconds(self.samples_since_note_on - self.glide_start);
+        let glide_time = self.glide_time.unwrap_or(portamento_time);
+        let t = (time / glide_time).clamp(0.0, 1.0);
+        let pitch = lerp(self.start_pitch, self.end_pitch, t);
+        match portamento_mode {
+            PortamentoMode::Glide => pitch,
+            // Snap to whole semitones relative to the start pitch. This is done relative to the
+            // start pitch (and not to the nearest MIDI note) since the start pitch might be in the
+            // middle of a previous glide. Once the glide is over, the end pitch is used exactly.
+            PortamentoMode::Glissando if t < 1.0 => {
+                let semitones = self.start_pitch.interval_to(pitch).round();
+                self.start_pitch.transpose_semitones(semitones)
+            }
+            PortamentoMode::Glissando => self.end_pitch,
+        }
+    }
+}
+
+/// Tracks which MIDI output events have been sent for a voice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MidiOutState {
+    /// Nothing has been sent yet.
+    Pending,
+    /// A note on has been sent, but not a note off.
+    NoteOn,
+    /// Both a note on and a note off have been sent.
+    NoteOff,
+    /// A note on was sent for the given note, on the given channel, but the voice has since slid to
+    /// another note. The old note needs a note off, and the new note needs a note on.
+    Slid(Note, u8),
+}
+
+/// The per-note expressions which a host may send for an individual voice.
+#[derive(Debug, Clone, Copy)]
+pub struct NoteExpression {
+    /// A linear gain, where 1.0 is unity gain.
+    pub gain: f32,
+    /// The pan, from -1.0 (hard left) to 1.0 (hard right).
+    pub pan: f32,
+    /// The tuning offset, in semitones.
+    pub tuning: f32,
+    /// The aftertouch pressure, from 0.0 to 1.0.
+    pub pressure: f32,
+}
+
+impl NoteExpression {
+    /// Split a mono value into a left and right value using the pan.
+    fn pan(&self, value: f32) -> (f32, f32) {
+        let left = (1.0 - self.pan).min(1.0);
+        let right = (1.0 + self.pan).min(1.0);
+        (value * left, value * right)
+    }
+}
+
+impl Default for NoteExpression {
+    fn default() -> Self {
+        NoteExpression {
+            gain: 1.0,
+            pan: 0.0,
+            tuning: 0.0,
+            pressure: 0.0,
+        }
     }
 }
 
 #[derive(Debug, Clone, Copy)]
 struct Crossfader {
     state: CrossfadeState,
-    samples: usize,
+    samples: SampleTime,
+    // The length of the fade, in samples.
+    length: SampleTime,
 }
 
 impl Crossfader {
-    fn fade_in() -> Crossfader {
+    fn fade_in(length: SampleTime) -> Crossfader {
         Crossfader {
             state: CrossfadeState::FadeIn,
             samples: 0,
+            length,
         }
     }
 
-    fn fade_out() -> Crossfader {
+    fn fade_out(length: SampleTime) -> Crossfader {
         Crossfader {
             state: CrossfadeState::FadeOut,
             samples: 0,
+            length,
+        }
+    }
+
+    fn is_faded_out(&self) -> bool {
+        match self.state {
+            CrossfadeState::FadeIn => false,
+            CrossfadeState::FadeOut => self.samples >= self.length,
         }
     }
 
     fn next(&mut self) -> f32 {
-        const FADE_LENGTH: usize = 44;
-        if self.samples >= FADE_LENGTH {
+        if self.samples >= self.length {
             match self.state {
                 CrossfadeState::FadeIn => 1.0,
                 CrossfadeState::FadeOut => 0.0,
             }
         } else {
-            let t = self.samples as f32 / FADE_LENGTH as f32;
+            let t = self.samples as f32 / self.length as f32;
             self.samples += 1;
 
             match self.state {
@@ -389,7 +891,10 @@ struct FilterSweeper {
 impl FilterSweeper {
     fn new(params: &MeowParameters, base_vel: Vel) -> FilterSweeper {
         let start_freq = params.filter.cutoff_freq;
-        let end_freq = params.filter.cutoff_freq + params.filter_envelope.env_mod * base_vel.eased;
+        // A negative envelope mod closes the filter instead of opening it. The filter can't close
+        // past 20 Hz anyways, so this also keeps the frequency positive.
+        let end_freq = params.filter.cutoff_freq + params.filter_envelope.env_mod_for(base_vel);
+        let end_freq = end_freq.clamp(20.0, f32::INFINITY);
         FilterSweeper {
             start_pitch: Pitch::from_hertz(start_freq),
             end_pitch: Pitch::from_hertz(end_freq),
@@ -402,14 +907,149 @@ impl FilterSweeper {
     }
 }
 
+/// Compute the coefficients for the filter at the given cutoff.
+fn filter_coefficients(
+    filter: &FilterParams,
+    sample_rate: SampleRate,
+    cutoff_freq: Hertz,
+) -> biquad::Coefficients<f32> {
+    biquad::Coefficients::<f32>::from_params(
+        filter.filter_type,
+        sample_rate.hz(),
+        cutoff_freq.into(),
+        filter.q_value.max(0.0),
+    )
+    .unwrap()
+}
+
+// The note at which filter keytracking leaves the cutoff unchanged (middle C). Each octave above
+// this doubles the cutoff, and each octave below halves it.
+const KEYTRACK_CENTER: f32 = 261.63;
+
+/// A biquad filter for each channel, sharing the same coefficients.
+#[derive(Debug, Clone, Copy)]
+struct StereoBiquad {
+    left: DirectForm1<f32>,
+    right: DirectForm1<f32>,
+}
+
+impl StereoBiquad {
+    fn new(coefficients: biquad::Coefficients<f32>) -> StereoBiquad {
+        let filter = DirectForm1::<f32>::new(coefficients);
+        StereoBiquad {
+            left: filter,
+            right: filter,
+        }
+    }
+
+    fn update_coefficients(&mut self, coefficients: biquad::Coefficients<f32>) {
+        self.left.update_coefficients(coefficients);
+        self.right.update_coefficients(coefficients);
+    }
+
+    fn reset_state(&mut self) {
+        self.left.reset_state();
+        self.right.reset_state();
+    }
+
+    /// Filter a sample of each channel. Like the main filter, the right channel's filter is
+    /// skipped (and follows the left's state) whenever both channels are the same.
+    fn run(&mut self, left: f32, right: f32) -> (f32, f32) {
+        let left_out = self.left.run(left);
+        let right_out = if right != left {
+            self.right.run(right)
+        } else {
+            self.right = self.left;
+            left_out
+        };
+        (left_out, right_out)
+    }
+}
+
+/// Ramps a filter's coefficients towards the most recently computed coefficients, one sample at a
+/// time. The coefficients are only recomputed every few samples, and ramping between them keeps
+/// fast filter sweeps from sounding steppy. Ramping directly between the coefficients is safe,
+/// since the set of stable biquad coefficients is convex, so every set on the way between two
+/// stable filters is also stable.
+#[derive(Debug, Clone, Copy)]
+struct CoefficientRamp {
+    current: biquad::Coefficients<f32>,
+    target: biquad::Coefficients<f32>,
+    // The number of samples left until the current coefficients reach the target.
+    remaining: SampleTime,
+}
+
+impl CoefficientRamp {
+    fn new(coefficients: biquad::Coefficients<f32>) -> CoefficientRamp {
+        CoefficientRamp {
+            current: coefficients,
+            target: coefficients,
+            remaining: 0,
+        }
+    }
+
+    /// Switch to the coefficients on the next sample.
+    fn jump_to(&mut self, target: biquad::Coefficients<f32>) {
+        self.target = target;
+        self.remaining = 1;
+    }
+
+    /// Ramp to the coefficients over the given number of samples.
+    fn ramp_to(&mut self, target: biquad::Coefficients<f32>, samples: SampleTime) {
+        self.target = target;
+        self.remaining = samples.max(1);
+    }
+
+    /// Advance the ramp by one sample. Returns the new coefficients, or None if the coefficients
+    /// are not changing.
+    fn next(&mut self) -> Option<biquad::Coefficients<f32>> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        if self.remaining == 1 {
+            self.current = self.target;
+        } else {
+            // Cover an equal share of the remaining distance on each sample.
+            let t = 1.0 / self.remaining as f32;
+            let (current, target) = (self.current, self.target);
+            self.current = biquad::Coefficients {
+                a1: lerp(current.a1, target.a1, t),
+                a2: lerp(current.a2, target.a2, t),
+                b0: lerp(current.b0, target.b0, t),
+                b1: lerp(current.b1, target.b1, t),
+                b2: lerp(current.b2, target.b2, t),
+            };
+        }
+        self.remaining -= 1;
+        Some(self.current)
+    }
+}
+
+/// The number of distinct phase values in one cycle of an [Oscillator]. The phase is stored as a
+/// wrapping 32-bit fixed-point value, so one full cycle is exactly 2^32.
+const PHASE_CYCLE: f64 = 4294967296.0;
+
 #[derive(Debug)]
 pub struct Oscillator {
-    angle: Angle,
+    // The position within the wave cycle, as a fixed point value where 0 maps to 0.0 and u32::MAX
+    // maps to just under 1.0. This is used instead of an f32 angle because adding a small f32
+    // increment to the angle every sample accumulates rounding error, which is audible as pitch
+    // drift and jitter after the oscillator has run for a long time. Wrapping integer addition is
+    // exact, so the phase never drifts no matter how long the oscillator runs.
+    phase: u32,
 }
 
 impl Oscillator {
     pub fn new() -> Oscillator {
-        Oscillator { angle: 0.0 }
+        Oscillator { phase: 0 }
+    }
+
+    /// Create an oscillator which starts at the given position within the wave cycle.
+    pub fn with_phase(angle: Angle) -> Oscillator {
+        Oscillator {
+            phase: (angle.rem_euclid(1.0) as f64 * PHASE_CYCLE) as u32,
+        }
     }
 
     /// Return the next sample from the oscillator
@@ -419,17 +1059,27 @@ impl Oscillator {
     /// pitch - the pitch multiplier to be applied to the base frequency of the
     ///         oscillator.
     pub fn next_sample(&mut self, sample_rate: SampleRate, shape: NoteShape, pitch: Hertz) -> f32 {
-        let value = shape.get(self.angle);
-
-        // Update the angle. Each sample is 1.0 / sample_rate apart for a complete waveform.
-        let angle_delta = pitch.get() / sample_rate.get();
+        let value = shape.get(self.angle());
 
-        // Compute (self.angle + angle_delta) % 1.0.
-        // Note that we use `fract` instead of just doing `% 1.0` since fmod is slow.
-        self.angle = (self.angle + angle_delta).fract();
+        // Update the phase. Each sample is 1.0 / sample_rate apart for a complete waveform. The
+        // increment is computed in f64 so that low frequency LFOs keep their precision.
+        let angle_delta = (pitch.get() as f64 / sample_rate.get() as f64).rem_euclid(1.0);
+        let phase_delta = (angle_delta * PHASE_CYCLE) as u32;
+        self.phase = self.phase.wrapping_add(phase_delta);
 
         value
     }
+
+    /// Returns the waveform at the current position, shifted by `phase_offset` cycles. This does
+    /// not advance the oscillator.
+    pub fn sample_at(&self, shape: NoteShape, phase_offset: Angle) -> f32 {
+        shape.get((self.angle() + phase_offset).fract())
+    }
+
+    /// The current position within the wave cycle, in the [0.0, 1.0) range.
+    fn angle(&self) -> Angle {
+        (self.phase as f64 / PHASE_CYCLE) as Angle
+    }
 }
 
 /// Convience struct for holding the external state a particular note (when it was
@@ -471,32 +1121,42 @@ impl<T: EnvelopeType> Envelope<T> {
         let value = match note_state {
             NoteState::Held => {
                 let time = sample_rate.to_seconds(time);
-                let attack = params.attack();
+                // Jumping straight to the peak or the sustain level clicks, so the attack and decay
+                // always take at least a few samples, even when they are set to 0 ms.
+                let min_segment = sample_rate.to_seconds(MIN_ENVELOPE_SEGMENT);
+                let attack = params.attack().max(min_segment);
                 let hold = params.hold();
-                let decay = params.decay();
+                let decay = params.decay().max(min_segment);
                 let sustain = params.sustain();
-                // We check if the attack time is zero. If so, we skip the attack phase.
-                if time < attack && attack.get() != 0.0 {
+                if time < attack {
                     // Attack
                     T::lerp_attack(T::zero(), T::one(), time / attack)
                 } else if time < attack + hold {
                     // Hold
                     T::one()
-                } else if time < attack + hold + decay && decay.get() != 0.0 {
-                    // Similarly, we check if decay is zero. If so, skikp right to sustain.
+                } else if time < attack + hold + decay {
                     // Decay
                     let time = time - attack - hold;
-                    T::lerp_decay(T::one(), sustain, time / decay)
+                    Envelope::segment(params, T::one(), sustain, time, decay, T::lerp_decay)
                 } else {
-                    // Sustain
-                    sustain
+                    // Sustain. If there is a sustain fade, then the sustain slowly fades to zero.
+                    match params.sustain_fade() {
+                        Some(sustain_fade) => {
+                            let time = (time - attack - hold - decay).min(sustain_fade);
+                            let (zero, lerp) = (T::zero(), T::lerp_release);
+                            Envelope::segment(params, sustain, zero, time, sustain_fade, lerp)
+                        }
+                        None => sustain,
+                    }
                 }
             }
             NoteState::Released(rel_time) => {
                 let time = sample_rate.to_seconds(time - rel_time);
                 // If release is zero, then skip release and drop instantly to zero.
-                if params.release().get() != 0.0 {
-                    T::lerp_release(self.ease_from, T::zero(), time / params.release())
+                let release = params.release();
+                if release.get() != 0.0 {
+                    let (ease_from, zero, lerp) = (self.ease_from, T::zero(), T::lerp_release);
+                    Envelope::segment(params, ease_from, zero, time, release, lerp)
                 } else {
                     T::zero()
                 }
@@ -509,6 +1169,33 @@ impl<T: EnvelopeType> Envelope<T> {
         value * params.multiply()
     }
 
+    /// Ease from `start` to `end`, `time` into a decay or release segment of the given length. The
+    /// classic curve uses `lerp`, while the analog curve discharges towards `end` in amplitude.
+    fn segment(
+        params: &impl EnvelopeParams<T>,
+        start: T,
+        end: T,
+        time: Seconds,
+        length: Seconds,
+        lerp: fn(T, T, f32) -> T,
+    ) -> T {
+        match params.curve() {
+            EnvelopeCurve::Classic => lerp(start, end, time / length),
+            EnvelopeCurve::Analog => {
+                let remaining =
+                    EnvelopeCurve::analog_remaining(time, length, params.time_constants());
+                let start = params.level_to_amp(start);
+                let end = params.level_to_amp(end);
+                params.amp_to_level(T::lerp_analog(end, start, remaining))
+            }
+        }
+    }
+
+    /// The (premultiplied) value computed by the most recent `get` call.
+    fn last_value(&self) -> T {
+        self.last_env_value
+    }
+
     /// Set self.ease_from to the value computed by the most recent `get` call.
     /// This needs to be called JUST BEFORE transitioning from a Held to Released
     /// state or from Released to Retrigger state.
@@ -522,6 +1209,10 @@ impl<T: EnvelopeType> Envelope<T> {
     }
 }
 
+// The shortest an envelope's attack or decay can be, in samples. This is short enough to still
+// sound instant, but long enough to avoid a click.
+const MIN_ENVELOPE_SEGMENT: SampleTime = 12;
+
 /// The state of a note, along with the time and velocity that note has, if
 /// relevant. The typical life cycle of a note is as follows:
 /// None -> Held -> Released -> [removed] or Retrigger -> Held
@@ -0,0 +1,92 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use crate::{
+    common::{SampleRate, Seconds},
+    envelope_follower::one_pole_coefficient,
+    params::Parameters,
+};
+
+// The loudest that the output can get while protection is engaged. This is -12 dBFS.
+const CEILING: f32 = 0.251;
+// How fast the gain recovers after a peak has been limited.
+const RELEASE: Seconds = Seconds::new(0.1);
+// How many risky presets are remembered, so that loading one again doesn't re-engage protection.
+// This is a fixed size so that program changes on the audio thread never allocate.
+const REMEMBERED_PRESETS: usize = 32;
+
+/// Limits the output to -12 dBFS the first time that a preset with extreme resonance or feedback
+/// settings is loaded, in case it is much louder than the user expects. Protection stays engaged
+/// until the user turns it off from the editor, or until another preset is loaded.
+pub struct SpeakerProtection {
+    // Shared with the editor, which shows when protection is engaged and can disengage it.
+    engaged: Arc<AtomicBool>,
+    // The fingerprints of the risky presets which have already been protected.
+    heard: [Option<u64>; REMEMBERED_PRESETS],
+    next_heard: usize,
+    // The gain applied to the output. This is 1.0 unless a peak is being limited.
+    gain: f32,
+}
+
+impl SpeakerProtection {
+    pub fn new() -> SpeakerProtection {
+        SpeakerProtection {
+            engaged: Arc::new(AtomicBool::new(false)),
+            heard: [None; REMEMBERED_PRESETS],
+            next_heard: 0,
+            gain: 1.0,
+        }
+    }
+
+    /// The flag which is set while protection is engaged. Clearing it disengages protection.
+    pub fn engaged(&self) -> Arc<AtomicBool> {
+        self.engaged.clone()
+    }
+
+    /// Check newly loaded parameters, engaging protection if they are risky and haven't been heard
+    /// before. `enabled` is the user's speaker protection setting.
+    pub fn preset_loaded(&mut self, params: &Parameters, enabled: bool) {
+        let fingerprint = params.risky_settings();
+        let engage = match fingerprint {
+            Some(fingerprint) if enabled => !self.heard.contains(&Some(fingerprint)),
+            _ => false,
+        };
+        if engage {
+            self.heard[self.next_heard] = fingerprint;
+            self.next_heard = (self.next_heard + 1) % REMEMBERED_PRESETS;
+        }
+        self.engaged.store(engage, Ordering::Relaxed);
+    }
+
+    /// Limit the output, if protection is engaged.
+    pub fn process(&mut self, left: &mut [f32], right: &mut [f32], sample_rate: SampleRate) {
+        let engaged = self.engaged.load(Ordering::Relaxed);
+        // Once protection is disengaged, the gain still eases back up instead of jumping.
+        if !engaged && self.gain >= 1.0 {
+            return;
+        }
+
+        let release = one_pole_coefficient(RELEASE, sample_rate);
+        for (left, right) in left.iter_mut().zip(right.iter_mut()) {
+            let peak = left.abs().max(right.abs());
+            let target = if engaged && peak > CEILING {
+                CEILING / peak
+            } else {
+                1.0
+            };
+            // Peaks are caught instantly, so that nothing gets past the ceiling.
+            self.gain = if target < self.gain {
+                target
+            } else {
+                target + (self.gain - target) * release
+            };
+            if 1.0 - self.gain < 1.0e-4 {
+                self.gain = 1.0;
+            }
+            *left *= self.gain;
+            *right *= self.gain;
+        }
+    }
+}
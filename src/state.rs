@@ -0,0 +1,252 @@
+use std::collections::BTreeMap;
+
+use nih_plug::prelude::{Plugin, PluginState};
+use nih_plug::wrapper::state::ParamValue;
+
+use crate::issues;
+use crate::layers::LayerB;
+use crate::macros::{MacroAssignments, MorphSnapshots};
+use crate::midi_learn::MidiMappings;
+use crate::params::MAX_VIBRATO_AMOUNT;
+use crate::settings::Settings;
+use crate::Nyasynth;
+
+/// A change to a saved state, which is needed to load states saved before the given plugin version.
+struct Migration {
+    version: &'static str,
+    migrate: fn(&mut PluginState),
+}
+
+/// The migrations, from oldest to newest.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: "1.1",
+        migrate: vibrato_amount_in_cents,
+    },
+    Migration {
+        version: "1.2",
+        migrate: bipolar_filter_envelope_mod,
+    },
+    Migration {
+        version: "1.2",
+        migrate: zero_attack_and_decay,
+    },
+    Migration {
+        version: "1.3",
+        migrate: global_midi_mappings,
+    },
+    Migration {
+        version: "1.4",
+        migrate: twenty_log_decibels,
+    },
+    Migration {
+        version: "1.5",
+        migrate: dotted_and_triplet_rates,
+    },
+];
+
+/// The parameters whose values are in decibels.
+const DECIBEL_PARAMS: &[&str] = &[
+    "meow_sustain",
+    "filter_pre_gain",
+    "filter_post_gain",
+    "comp_threshold",
+    "comp_makeup",
+    "gain",
+    "preset_trim",
+];
+
+/// The parameters which are a [crate::params::VibratoRate].
+const SYNCED_RATE_PARAMS: &[&str] = &[
+    "meow_attack_sync",
+    "meow_decay_sync",
+    "meow_release_sync",
+    "vibrato_rate",
+];
+
+// Before 1.5, there were eight synced rates. This is the index that each of them has now.
+const OLD_SYNCED_RATES: [i32; 8] = [0, 1, 2, 4, 7, 10, 12, 13];
+const SYNCED_RATE_COUNT: usize = 15;
+
+/// Upgrade a state saved by an older version of the plugin, so that it sounds the same when loaded
+/// in this version. States from newer versions are loaded as well as they can be, with a warning.
+pub fn migrate(state: &mut PluginState) {
+    if is_older(Nyasynth::VERSION, &state.version) {
+        issues::warn(format!(
+            "This sound was saved by Nyasynth {}, and may not sound right in Nyasynth {}",
+            state.version,
+            Nyasynth::VERSION
+        ));
+    }
+    for migration in MIGRATIONS {
+        if is_older(&state.version, migration.version) {
+            nih_plug::nih_log!(
+                "Migrating state from version {} to {}",
+                state.version,
+                migration.version
+            );
+            (migration.migrate)(state);
+        }
+    }
+}
+
+/// Upgrade the values in a user preset file saved by an older version of the plugin. Preset files
+/// only store plain parameter values, so only the migrations which change those apply.
+pub fn migrate_preset_values(version: &str, values: &mut BTreeMap<String, f32>) {
+    if is_older(version, "1.4") {
+        for id in DECIBEL_PARAMS {
+            if let Some(db) = values.get_mut(*id) {
+                *db *= 2.0;
+            }
+        }
+    }
+    if is_older(version, "1.5") {
+        for id in SYNCED_RATE_PARAMS {
+            if let Some(index) = values.get_mut(*id) {
+                *index = new_synced_rate(*index as i32) as f32;
+            }
+        }
+    }
+}
+
+/// Returns true if version `a` is older than version `b`. Versions are compared component-wise, so
+/// "1.10" is newer than "1.9".
+fn is_older(a: &str, b: &str) -> bool {
+    fn components(version: &str) -> Vec<u32> {
+        version
+            .split('.')
+            .map(|component| component.parse().unwrap_or(0))
+            .collect()
+    }
+    components(a) < components(b)
+}
+
+// Before 1.1, the vibrato amount was a percentage, where 100% was two semitones.
+fn vibrato_amount_in_cents(state: &mut PluginState) {
+    if let Some(ParamValue::F32(amount)) = state.params.get_mut("vibrato_amount") {
+        *amount *= MAX_VIBRATO_AMOUNT;
+    }
+}
+
+// Before 1.2, the filter envelope mod could only be positive. The plain value is unchanged, but the
+// old range now only covers the upper half of the normalized range.
+fn bipolar_filter_envelope_mod(state: &mut PluginState) {
+    remap_normalized_references(state, "filter_envlope_mod", |normalized| {
+        0.5 + 0.5 * normalized
+    });
+}
+
+// Before 1.2, the meow attack and decay had a minimum of 1 ms instead of 0 ms.
+fn zero_attack_and_decay(state: &mut PluginState) {
+    // Both parameters are skewed with a factor of 2^-2.
+    fn remap(normalized: f32, max: f32) -> f32 {
+        let plain = 0.001 + (max - 0.001) * normalized.powf(4.0);
+        (plain / max).powf(0.25)
+    }
+    remap_normalized_references(state, "meow_attack", |normalized| remap(normalized, 10.0));
+    remap_normalized_references(state, "meow_decay", |normalized| remap(normalized, 5.0));
+}
+
+// Before 1.4, decibels were converted to and from amplitudes with 10 * log10 rather than 20 * log10,
+// so every decibel value was half of what it really was. Doubling them keeps the same levels. Their
+// ranges doubled too, but the normalized values didn't change.
+fn twenty_log_decibels(state: &mut PluginState) {
+    for id in DECIBEL_PARAMS {
+        if let Some(ParamValue::F32(db)) = state.params.get_mut(*id) {
+            *db *= 2.0;
+        }
+    }
+}
+
+// Before 1.5, there were no dotted or triplet synced rates. They were added in order of length, so
+// the old rates moved to new indices. (The old 1/12 rate is now called 1/8T.)
+fn dotted_and_triplet_rates(state: &mut PluginState) {
+    for id in SYNCED_RATE_PARAMS {
+        if let Some(ParamValue::I32(index)) = state.params.get_mut(*id) {
+            *index = new_synced_rate(*index);
+        }
+        remap_normalized_references(state, id, |normalized| {
+            let old_max = (OLD_SYNCED_RATES.len() - 1) as f32;
+            let index = new_synced_rate((normalized * old_max).round() as i32);
+            index as f32 / (SYNCED_RATE_COUNT - 1) as f32
+        });
+    }
+}
+
+fn new_synced_rate(old_index: i32) -> i32 {
+    let old_index = old_index.clamp(0, OLD_SYNCED_RATES.len() as i32 - 1);
+    OLD_SYNCED_RATES[old_index as usize]
+}
+
+// Before 1.3, the MIDI learn mappings were saved with each project. They are now a global setting.
+// If the user hasn't learned any mappings since upgrading, the old project's mappings are adopted,
+// so that they aren't lost. States are loaded while the host is busy, so the adopted mappings are
+// only saved later, by the editor.
+fn global_midi_mappings(state: &mut PluginState) {
+    let Some(json) = state.fields.remove("midi_mappings") else {
+        return;
+    };
+    let Ok(mappings) = serde_json::from_str::<MidiMappings>(&json) else {
+        return;
+    };
+    let settings = Settings::get();
+    let mut global = settings.midi_mappings.write().unwrap();
+    if global.ccs.is_empty() && !mappings.ccs.is_empty() {
+        *global = mappings;
+        settings.save_later();
+    }
+}
+
+// Parameters are saved as plain values, so changing a parameter's range doesn't affect them. But
+// the macros, the morph snapshots, and layer B's snapshot refer to parameters by their normalized
+// values, which do need to be remapped to the new range.
+fn remap_normalized_references(state: &mut PluginState, id: &str, remap: impl Fn(f32) -> f32) {
+    if let Some(json) = state.fields.get_mut("morph_snapshots") {
+        if let Ok(mut snapshots) = serde_json::from_str::<MorphSnapshots>(json) {
+            for snapshot in [&mut snapshots.a, &mut snapshots.b].into_iter().flatten() {
+                for (param_id, value) in &mut snapshot.values {
+                    if param_id == id {
+                        *value = remap(*value);
+                    }
+                }
+            }
+            if let Ok(migrated) = serde_json::to_string(&snapshots) {
+                *json = migrated;
+            }
+        }
+    }
+
+    if let Some(json) = state.fields.get_mut("layer_b") {
+        if let Ok(mut layer_b) = serde_json::from_str::<LayerB>(json) {
+            for (param_id, value) in layer_b
+                .snapshot
+                .iter_mut()
+                .flat_map(|snapshot| snapshot.values.iter_mut())
+            {
+                if param_id == id {
+                    *value = remap(*value);
+                }
+            }
+            if let Ok(migrated) = serde_json::to_string(&layer_b) {
+                *json = migrated;
+            }
+        }
+    }
+
+    if let Some(json) = state.fields.get_mut("macro_assignments") {
+        if let Ok(mut assignments) = serde_json::from_str::<MacroAssignments>(json) {
+            for target in assignments
+                .macros
+                .iter_mut()
+                .flat_map(|macro_| macro_.targets.iter_mut())
+                .filter(|target| target.param_id == id)
+            {
+                target.start = remap(target.start);
+                target.end = remap(target.end);
+            }
+            if let Ok(migrated) = serde_json::to_string(&assignments) {
+                *json = migrated;
+            }
+        }
+    }
+}
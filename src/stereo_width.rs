@@ -0,0 +1,71 @@
+use biquad::{Biquad, DirectForm1, ToHertz, Q_BUTTERWORTH_F32};
+
+use crate::{
+    common::{Hertz, SampleRate},
+    params::StereoParams,
+};
+
+/// Widens or narrows the master output by scaling its side (left minus right) signal. The side
+/// signal can also be highpassed, which keeps everything below the cutoff in the center.
+pub struct StereoWidth {
+    side_highpass: DirectForm1<f32>,
+    // The cutoff and sample rate that the highpass coefficients were computed for.
+    cutoff: Hertz,
+    sample_rate: SampleRate,
+}
+
+impl StereoWidth {
+    pub fn new() -> StereoWidth {
+        StereoWidth {
+            side_highpass: DirectForm1::<f32>::new(biquad::Coefficients {
+                a1: 0.0,
+                a2: 0.0,
+                b0: 1.0,
+                b1: 0.0,
+                b2: 0.0,
+            }),
+            cutoff: Hertz(0.0),
+            sample_rate: SampleRate(0.0),
+        }
+    }
+
+    /// Process the next sample of each channel.
+    pub fn next_sample(
+        &mut self,
+        left: f32,
+        right: f32,
+        sample_rate: SampleRate,
+        params: &StereoParams,
+    ) -> (f32, f32) {
+        let mid = (left + right) * 0.5;
+        let mut side = (left - right) * 0.5;
+
+        if let Some(cutoff) = params.mono_below {
+            if cutoff != self.cutoff || sample_rate != self.sample_rate {
+                self.update_highpass(cutoff, sample_rate);
+            }
+            side = self.side_highpass.run(side);
+            if !side.is_finite() {
+                self.side_highpass.reset_state();
+                side = 0.0;
+            }
+        }
+
+        let side = side * params.width;
+        (mid + side, mid - side)
+    }
+
+    fn update_highpass(&mut self, cutoff: Hertz, sample_rate: SampleRate) {
+        let nyquist = sample_rate.get() * 0.49;
+        let coefficients = biquad::Coefficients::<f32>::from_params(
+            biquad::Type::HighPass,
+            sample_rate.hz(),
+            cutoff.get().clamp(20.0, nyquist).hz(),
+            Q_BUTTERWORTH_F32,
+        )
+        .unwrap();
+        self.side_highpass.update_coefficients(coefficients);
+        self.cutoff = cutoff;
+        self.sample_rate = sample_rate;
+    }
+}
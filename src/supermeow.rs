@@ -0,0 +1,60 @@
+use crate::{
+    common::{Pitch, SampleRate},
+    params::SupermeowParams,
+    sound_gen::{NoteShape, Oscillator},
+};
+
+pub const MAX_SUPERMEOW_VOICES: usize = 7;
+
+/// A stack of detuned copies of the main oscillator, in the style of a supersaw. The copies are
+/// spread evenly across the detune range and blended in with the main oscillator, which stays at
+/// the note's pitch. Unlike stray cat mode, which detunes each note as a whole, this thickens every
+/// single note.
+#[derive(Debug)]
+pub struct Supermeow {
+    // The detuned copies. The main oscillator is not included.
+    oscs: [Oscillator; MAX_SUPERMEOW_VOICES - 1],
+}
+
+impl Supermeow {
+    pub fn new() -> Supermeow {
+        // The copies start at spread out phases. If they all started in phase, every note would
+        // begin with a loud, flanged spike before the copies drift apart.
+        let oscs = std::array::from_fn(|i| {
+            Oscillator::with_phase((i + 1) as f32 / MAX_SUPERMEOW_VOICES as f32)
+        });
+        Supermeow { oscs }
+    }
+
+    /// Blend the detuned copies in with `value`, which is the main oscillator's sample. `pitch`
+    /// is the pitch of the main oscillator.
+    pub fn next_sample(
+        &mut self,
+        value: f32,
+        sample_rate: SampleRate,
+        shape: NoteShape,
+        pitch: Pitch,
+        params: &SupermeowParams,
+    ) -> f32 {
+        let copies = (params.voices as usize).clamp(1, MAX_SUPERMEOW_VOICES) - 1;
+        if copies == 0 {
+            return value;
+        }
+
+        let mut detuned = 0.0;
+        for (i, osc) in self.oscs[..copies].iter_mut().enumerate() {
+            // Spread the copies evenly from -detune to +detune. A lone copy is detuned upwards.
+            let spread = if copies == 1 {
+                1.0
+            } else {
+                (2.0 * i as f32 / (copies - 1) as f32) - 1.0
+            };
+            let detuned_pitch = pitch.transpose_cents(spread * params.detune);
+            detuned += osc.next_sample(sample_rate, shape, detuned_pitch.into_hertz());
+        }
+
+        // The copies are uncorrelated, so they are scaled to keep the same power as one oscillator.
+        let detuned = detuned / (copies as f32).sqrt();
+        value * (1.0 - params.blend) + detuned * params.blend
+    }
+}
@@ -1,32 +1,489 @@
-use std::sync::{atomic::Ordering, Arc};
+use std::{
+    any::Any,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+};
 
 use atomic_float::AtomicF32;
-use nih_plug::prelude::{Editor, Param, ParamSetter};
+use nih_plug::prelude::{
+    AsyncExecutor, Editor, Enum, GuiContext, Param, ParamPtr, ParamSetter, Params,
+    ParentWindowHandle,
+};
 use nih_plug_egui::{
     create_egui_editor,
     egui::{
-        self, pos2, vec2, Color32, ColorImage, FontDefinitions, Frame, Pos2, Rect, Rgba, Sense,
-        Shape, TextureHandle, Ui, Vec2,
+        self, pos2, vec2, Align2, Color32, ColorImage, FontDefinitions, FontId, Frame, Pos2, Rect,
+        Response, Sense, Shape, TextureHandle, Ui, Vec2,
     },
     EguiState,
 };
 
 use crate::{
-    params::Parameters,
+    bounce::{bounce, Phrase, PhraseRecorder, Sound},
+    host_link::HostLink,
+    issues::{self, IssueLog, Severity},
+    macros::{MacroAssignments, MacroCurve, MacroTarget},
+    meters::{ChannelLevels, OutputMeters},
+    midi_learn::{self, CcCurve, MidiLearn, MOD_WHEEL_CC},
+    midi_player::{MidiLoop, MidiPlayer},
+    param_changes::ParamChanges,
+    param_info::param_info,
+    params::{is_control_group, MacroGroup, ParamTable, Parameters},
+    presets::{read_fxp, PresetLibrary, PRESET_TAGS},
+    settings::Settings,
+    skin::{available_skins, KnobSkin, Skin},
     ui_knob::{ArcKnob, TextSlider},
+    voice_pitches::VoicePitches,
+    Nyasynth, Task,
 };
 
 const SCREEN_WIDTH: u32 = 450;
 const SCREEN_HEIGHT: u32 = 300;
 
-fn make_arc_knob(ui: &mut Ui, setter: &ParamSetter, param: &impl Param, center: Pos2) {
+// The zoom levels that the user can pick from.
+const GUI_SCALES: [f32; 4] = [1.0, 1.25, 1.5, 2.0];
+
+fn make_arc_knob(
+    ui: &mut Ui,
+    setter: &ParamSetter,
+    menu: &ParamMenu,
+    skin: &KnobSkin,
+    param: &impl Param,
+    center: Pos2,
+) {
     // Knobs are 140.0x140.0 px, but need to scaled down by a factor of 4.
     let radius = 140.0 / 2.0 / 4.0;
-    ui.add(ArcKnob::for_param(param, setter, radius, center));
+    let knob =
+        ArcKnob::for_param(param, setter, radius, center, skin).with_detent(menu.detent(param));
+    let response = ui.add(knob);
+    menu.show(&response, setter, param);
+}
+
+fn make_text_slider(
+    ui: &mut Ui,
+    setter: &ParamSetter,
+    menu: &ParamMenu,
+    param: &impl Param,
+    location: Rect,
+) {
+    let slider = TextSlider::for_param(param, setter, location).with_detent(menu.detent(param));
+    let response = ui.add(slider);
+    menu.show(&response, setter, param);
+}
+
+// How a CC is named in the parameter menus.
+fn cc_name(cc: u8) -> String {
+    if cc == MOD_WHEEL_CC {
+        "Mod Wheel".to_string()
+    } else {
+        format!("MIDI CC {}", cc)
+    }
+}
+
+/// The right click menu shown for every parameter widget. nih-plug doesn't give access to the
+/// host's own per-parameter context menu (for things like automation lanes), so the editor
+/// provides its own instead. This also shows the help text for the widget in help mode.
+struct ParamMenu {
+    midi_learn: Arc<MidiLearn>,
+    // The ID of every parameter. Widgets only know the parameter itself, so the ID is found by
+    // comparing pointers.
+    ids: Vec<(ParamPtr, String)>,
+    // Used to find the macros which drive each parameter for the help text.
+    params: Arc<Parameters>,
+    // If set, hovering over a widget describes its parameter.
+    help_mode: AtomicBool,
+}
+
+impl ParamMenu {
+    fn new(params: Arc<Parameters>, midi_learn: Arc<MidiLearn>) -> ParamMenu {
+        let ids = params
+            .param_map()
+            .into_iter()
+            .map(|(id, ptr, _group)| (ptr, id))
+            .collect();
+        ParamMenu {
+            midi_learn,
+            ids,
+            params,
+            help_mode: AtomicBool::new(false),
+        }
+    }
+
+    fn help_mode(&self) -> bool {
+        self.help_mode.load(Ordering::Relaxed)
+    }
+
+    fn set_help_mode(&self, help_mode: bool) {
+        self.help_mode.store(help_mode, Ordering::Relaxed);
+    }
+
+    fn id_of(&self, param: &impl Param) -> Option<&str> {
+        let ptr = param.as_ptr();
+        self.ids
+            .iter()
+            .find(|(param_ptr, _)| *param_ptr == ptr)
+            .map(|(_, id)| id.as_str())
+    }
+
+    // The normalized value which the parameter locks to, if it has a detent.
+    fn detent(&self, param: &impl Param) -> Option<f32> {
+        self.id_of(param)
+            .and_then(param_info)
+            .and_then(|info| info.detent)
+            .map(|detent| detent.normalized(param))
+    }
+
+    fn show(&self, response: &Response, setter: &ParamSetter, param: &impl Param) {
+        let id = match self.id_of(param) {
+            Some(id) => id,
+            None => return,
+        };
+
+        if self.help_mode() {
+            response
+                .clone()
+                .on_hover_ui(|ui| self.show_help(ui, id, param));
+        }
+
+        response.clone().context_menu(|ui| {
+            ui.label(param.name());
+            if ui.button("Reset to Default").clicked() {
+                setter.begin_set_parameter(param);
+                setter.set_parameter(param, param.default_plain_value());
+                setter.end_set_parameter(param);
+                ui.close_menu();
+            }
+
+            if self.midi_learn.is_learning(id) {
+                if ui.button("Cancel MIDI Learn").clicked() {
+                    self.midi_learn.cancel();
+                    ui.close_menu();
+                }
+            } else if ui.button("MIDI Learn").clicked() {
+                self.midi_learn.start(id);
+                ui.close_menu();
+            }
+
+            let mappings = &self.params.midi_mappings;
+            let cc = midi_learn::read_mappings(mappings, |mappings| mappings.cc_for(id));
+            if cc != Some(MOD_WHEEL_CC) && ui.button("Assign Mod Wheel").clicked() {
+                midi_learn::edit_mappings(mappings, |mappings| {
+                    mappings.learn(MOD_WHEEL_CC, id.to_string())
+                });
+                ui.close_menu();
+            }
+            if let Some(cc) = cc {
+                if ui.button(format!("Forget {}", cc_name(cc))).clicked() {
+                    midi_learn::edit_mappings(mappings, |mappings| mappings.forget(id));
+                    ui.close_menu();
+                }
+                let mut curve =
+                    midi_learn::read_mappings(mappings, |mappings| mappings.curve_for(id));
+                ui.menu_button("CC Curve", |ui| {
+                    for option in CcCurve::ALL {
+                        if ui.radio_value(&mut curve, option, option.name()).clicked() {
+                            midi_learn::edit_mappings(mappings, |mappings| {
+                                mappings.set_curve(id, curve)
+                            });
+                            ui.close_menu();
+                        }
+                    }
+                });
+            }
+
+            // This applies to every mapping, not just this parameter's.
+            let mut with_project = mappings.read().unwrap().is_some();
+            if ui
+                .checkbox(&mut with_project, "Save MIDI Mappings with Project")
+                .changed()
+            {
+                midi_learn::set_saved_with_project(mappings, with_project);
+            }
+        });
+    }
+
+    /// Describe the parameter: what it does, its range and default, and what modulates it.
+    fn show_help(&self, ui: &mut Ui, id: &str, param: &impl Param) {
+        ui.set_max_width(240.0);
+        ui.label(egui::RichText::new(param.name()).strong());
+        if let Some(info) = param_info(id) {
+            ui.label(info.description);
+        }
+        ui.label(format!(
+            "Range: {} to {}",
+            param.normalized_value_to_string(0.0, true),
+            param.normalized_value_to_string(1.0, true)
+        ));
+        ui.label(format!(
+            "Default: {}",
+            param.normalized_value_to_string(param.default_normalized_value(), true)
+        ));
+
+        let mut sources: Vec<String> = param_info(id)
+            .map(|info| info.modulated_by)
+            .unwrap_or_default()
+            .iter()
+            .map(|source| source.name().to_string())
+            .collect();
+        let assignments = self.params.macro_assignments.read().unwrap();
+        for macro_ in &assignments.macros {
+            if macro_.targets.iter().any(|target| target.param_id == id) {
+                sources.push(macro_.name.clone());
+            }
+        }
+        let snapshots = self.params.morph_snapshots.read().unwrap();
+        if snapshots.a.is_some() && snapshots.b.is_some() && !self.is_control(id) {
+            sources.push("Morph".to_string());
+        }
+        let cc =
+            midi_learn::read_mappings(&self.params.midi_mappings, |mappings| mappings.cc_for(id));
+        if let Some(cc) = cc {
+            sources.push(cc_name(cc));
+        }
+        if !sources.is_empty() {
+            ui.label(format!("Modulated by: {}", sources.join(", ")));
+        }
+    }
+
+    // True if the parameter is a macro, the morph knob, a preset button, or the bypass.
+    fn is_control(&self, id: &str) -> bool {
+        self.params
+            .param_map()
+            .iter()
+            .any(|(param_id, _, group)| param_id == id && is_control_group(group))
+    }
 }
 
-fn make_text_slider(ui: &mut Ui, setter: &ParamSetter, param: &impl Param, location: Rect) {
-    ui.add(TextSlider::for_param(param, setter, location));
+/// Shows the morph knob, along with buttons to store the current parameters as either snapshot.
+fn morph_editor(
+    ui: &mut Ui,
+    setter: &ParamSetter,
+    menu: &ParamMenu,
+    params: &Parameters,
+    param_table: &ParamTable,
+) {
+    ui.horizontal(|ui| {
+        ui.label("Morph");
+        let location = Rect::from_min_size(ui.cursor().min, vec2(80.0, 20.0));
+        make_text_slider(ui, setter, menu, &params.macros.morph, location);
+        if ui.button("Store A").clicked() {
+            params.morph_snapshots.write().unwrap().a = Some(param_table.snapshot());
+        }
+        if ui.button("Store B").clicked() {
+            params.morph_snapshots.write().unwrap().b = Some(param_table.snapshot());
+        }
+    });
+    ui.separator();
+}
+
+/// Shows the layer settings, along with the buttons for storing and swapping layer B's sound.
+/// Returns true if the layers were swapped, which changes every parameter.
+fn layers_editor(
+    ui: &mut Ui,
+    setter: &ParamSetter,
+    menu: &ParamMenu,
+    params: &Parameters,
+    param_table: &ParamTable,
+) -> bool {
+    fn row(ui: &mut Ui, setter: &ParamSetter, menu: &ParamMenu, param: &impl Param) {
+        ui.horizontal(|ui| {
+            let location = Rect::from_min_size(ui.cursor().min, vec2(120.0, 20.0));
+            make_text_slider(ui, setter, menu, param, location);
+            ui.label(param.name());
+        });
+    }
+    let layers = &params.layers;
+    row(ui, setter, menu, &layers.layer_mode);
+    row(ui, setter, menu, &layers.layer_split_note);
+    row(ui, setter, menu, &layers.layer_split_velocity);
+    ui.separator();
+
+    let mut swapped = false;
+    ui.horizontal(|ui| {
+        if ui
+            .button("Copy to B")
+            .on_hover_text("Make layer B play the current sound")
+            .clicked()
+        {
+            params
+                .layer_b
+                .write()
+                .unwrap()
+                .store(param_table.snapshot());
+        }
+        if ui
+            .button("Swap A/B")
+            .on_hover_text("Swap the current sound with layer B's, so that it can be edited")
+            .clicked()
+        {
+            let layer_a = param_table.snapshot();
+            let layer_b = params
+                .layer_b
+                .read()
+                .unwrap()
+                .snapshot
+                .clone()
+                .unwrap_or_default();
+            let targets = || param_table.snapshot_targets(&layer_b);
+            let context = setter.raw_context;
+            // Safety: The pointers point into the editor's parameters, which are still alive.
+            unsafe {
+                for (_, ptr, _) in targets() {
+                    context.raw_begin_set_parameter(*ptr);
+                }
+                for (_, ptr, normalized) in targets() {
+                    context.raw_set_parameter_normalized(*ptr, normalized);
+                }
+                for (_, ptr, _) in targets() {
+                    context.raw_end_set_parameter(*ptr);
+                }
+            }
+            params.layer_b.write().unwrap().store(layer_a);
+            swapped = true;
+        }
+    });
+    if params.layer_b.read().unwrap().snapshot.is_none() {
+        ui.label("Layer B plays the default sound until a sound is copied to it.");
+    }
+    swapped
+}
+
+/// Shows the standalone's MIDI player: the loop which is loaded, its tempo, how far through it
+/// playback is, and the transport buttons.
+fn player_editor(ui: &mut Ui, player: &MidiPlayer) {
+    fn format_time(seconds: f32) -> String {
+        let seconds = seconds.max(0.0);
+        format!("{}:{:04.1}", (seconds / 60.0).floor(), seconds % 60.0)
+    }
+
+    let midi_loop = player.midi_loop();
+    let Some(midi_loop) = midi_loop.as_ref() else {
+        ui.label("Drop a MIDI file onto the editor to loop it.");
+        return;
+    };
+    ui.label(&midi_loop.name);
+    ui.horizontal(|ui| {
+        let playing = player.is_playing();
+        if ui.selectable_label(playing, "Play").clicked() {
+            player.set_playing(true);
+        }
+        if ui.selectable_label(!playing, "Stop").clicked() {
+            player.set_playing(false);
+        }
+        ui.label(format!(
+            "{} / {}",
+            format_time(player.position()),
+            format_time(midi_loop.length())
+        ));
+        ui.label(format!("{:.1} BPM", midi_loop.tempo));
+    });
+}
+
+/// Shows the parameters which are hidden from the host.
+fn internal_editor(ui: &mut Ui, setter: &ParamSetter, menu: &ParamMenu, params: &Parameters) {
+    fn row(ui: &mut Ui, setter: &ParamSetter, menu: &ParamMenu, param: &impl Param) {
+        ui.horizontal(|ui| {
+            let location = Rect::from_min_size(ui.cursor().min, vec2(120.0, 20.0));
+            make_text_slider(ui, setter, menu, param, location);
+            ui.label(param.name());
+        });
+    }
+    row(ui, setter, menu, &params.filter.filter_mix_law);
+    row(ui, setter, menu, &params.filter.filter_q_compensation);
+    row(ui, setter, menu, &params.vibrato.vibrato_note_shape);
+    row(ui, setter, menu, &params.chorus.chorus_note_shape);
+    row(ui, setter, menu, &params.global.preset_trim);
+}
+
+/// Shows the macro knobs along with the targets of each macro. Returns true if the assignments
+/// were edited.
+fn macro_editor(
+    ui: &mut Ui,
+    setter: &ParamSetter,
+    menu: &ParamMenu,
+    macros: &MacroGroup,
+    assignments: &mut MacroAssignments,
+    param_names: &[(String, String)],
+) -> bool {
+    let mut changed = false;
+    for (i, (param, macro_)) in macros
+        .macros()
+        .into_iter()
+        .zip(assignments.macros.iter_mut())
+        .enumerate()
+    {
+        ui.push_id(i, |ui| {
+            ui.horizontal(|ui| {
+                changed |= ui.text_edit_singleline(&mut macro_.name).changed();
+                let location = Rect::from_min_size(ui.cursor().min, vec2(80.0, 20.0));
+                make_text_slider(ui, setter, menu, param, location);
+            });
+
+            let mut removed = None;
+            for (j, target) in macro_.targets.iter_mut().enumerate() {
+                ui.push_id(j, |ui| {
+                    ui.horizontal(|ui| {
+                        let selected = param_names
+                            .iter()
+                            .find(|(id, _)| *id == target.param_id)
+                            .map_or(target.param_id.as_str(), |(_, name)| name.as_str());
+                        egui::ComboBox::from_id_source("param")
+                            .selected_text(selected)
+                            .show_ui(ui, |ui| {
+                                for (id, name) in param_names {
+                                    changed |= ui
+                                        .selectable_value(
+                                            &mut target.param_id,
+                                            id.clone(),
+                                            name.as_str(),
+                                        )
+                                        .changed();
+                                }
+                            });
+
+                        for value in [&mut target.start, &mut target.end] {
+                            let drag_value = egui::DragValue::new(value)
+                                .clamp_range(0.0..=1.0)
+                                .speed(0.01);
+                            changed |= ui.add(drag_value).changed();
+                        }
+
+                        egui::ComboBox::from_id_source("curve")
+                            .selected_text(MacroCurve::variants()[target.curve.to_index()])
+                            .show_ui(ui, |ui| {
+                                for (index, name) in MacroCurve::variants().iter().enumerate() {
+                                    let curve = MacroCurve::from_index(index);
+                                    changed |= ui
+                                        .selectable_value(&mut target.curve, curve, *name)
+                                        .changed();
+                                }
+                            });
+
+                        if ui.button("Remove").clicked() {
+                            removed = Some(j);
+                        }
+                    });
+                });
+            }
+            if let Some(j) = removed {
+                macro_.targets.remove(j);
+                changed = true;
+            }
+
+            if ui.button("Add Target").clicked() {
+                let (id, _) = &param_names[0];
+                let target = MacroTarget::new(id, 0.0, 1.0, MacroCurve::Linear);
+                macro_.targets.push(target);
+                changed = true;
+            }
+            ui.separator();
+        });
+    }
+    changed
 }
 
 struct WidgetLocations {
@@ -148,23 +605,80 @@ impl WidgetLocations {
 struct EditorState {
     cat_images: Vec<TextureHandle>,
     brushed_metal: Option<TextureHandle>,
+    skin: Skin,
+    // The skin's own background image, if it has one.
+    skin_background: Option<TextureHandle>,
+    // The skins which could be found when the editor was opened.
+    skin_names: Vec<String>,
     polycat_on: Option<TextureHandle>,
     polycat_state: bool,
     widget_location: WidgetLocations,
     envelope_amount: Arc<AtomicF32>,
+    voice_pitches: Arc<VoicePitches>,
+    meters: Arc<OutputMeters>,
+    /// Set while speaker protection is limiting the output.
+    protection_engaged: Arc<AtomicBool>,
+    macros_open: bool,
+    internal_open: bool,
+    layers_open: bool,
+    player_open: bool,
+    log_open: bool,
+    presets: PresetBrowser,
+    bouncer: Bouncer,
+    toasts: Toasts,
+    zoom: Zoom,
+    param_changes: Arc<ParamChanges>,
+    /// The ID and name of every parameter that a macro can drive.
+    param_names: Vec<(String, String)>,
 }
 
 impl EditorState {
-    fn new(polycat_state: bool, envelope_amount: Arc<AtomicF32>) -> EditorState {
+    fn new(
+        params: &Parameters,
+        envelope_amount: Arc<AtomicF32>,
+        voice_pitches: Arc<VoicePitches>,
+        meters: Arc<OutputMeters>,
+        presets: Arc<PresetLibrary>,
+        async_executor: AsyncExecutor<Nyasynth>,
+        phrase_recorder: Arc<PhraseRecorder>,
+        protection_engaged: Arc<AtomicBool>,
+        zoom: f32,
+        param_changes: Arc<ParamChanges>,
+    ) -> EditorState {
+        // Macros can't drive other macros, or switch presets.
+        let param_names = params
+            .param_map()
+            .into_iter()
+            .filter(|(_, _, group)| !is_control_group(group))
+            // Safety: The pointers point into `params`, which is still alive.
+            .map(|(id, ptr, _)| (id, unsafe { ptr.name() }.to_string()))
+            .collect();
         EditorState {
             widget_location: WidgetLocations::from_spine_json(
                 serde_json::from_str(include_str!("../assets/spine_json/Spine.json")).unwrap(),
             ),
             cat_images: vec![],
             brushed_metal: None,
+            skin: Skin::default(),
+            skin_background: None,
+            skin_names: available_skins(),
             polycat_on: None,
-            polycat_state,
+            polycat_state: params.global.polycat.value(),
             envelope_amount,
+            voice_pitches,
+            meters,
+            protection_engaged,
+            macros_open: false,
+            internal_open: false,
+            layers_open: false,
+            player_open: false,
+            log_open: false,
+            presets: PresetBrowser::new(presets, async_executor),
+            bouncer: Bouncer::new(phrase_recorder),
+            toasts: Toasts::new(),
+            zoom: Zoom::new(zoom),
+            param_changes,
+            param_names,
         }
     }
 
@@ -175,8 +689,50 @@ impl EditorState {
         self.cat_images[i].clone()
     }
 
-    fn brushed_metal(&self) -> TextureHandle {
-        self.brushed_metal.clone().unwrap()
+    /// The note and tuning of the newest few voices, newest first, such as "A4 +12".
+    fn pitch_readout(&self) -> String {
+        let pitches = self.voice_pitches.load();
+        pitches
+            .iter()
+            .rev()
+            .take(4)
+            .map(|pitch| {
+                let (note, cents) = pitch.to_note();
+                format!("{} {:+.0}", note.name(), cents)
+            })
+            .collect::<Vec<_>>()
+            .join("  ")
+    }
+
+    /// The skin's background image, or the built in background if the skin doesn't have one.
+    fn background(&self) -> TextureHandle {
+        self.skin_background
+            .clone()
+            .unwrap_or_else(|| self.brushed_metal.clone().unwrap())
+    }
+
+    /// Load and switch to the skin with the given name. This reads files, but the editor never
+    /// runs on the audio thread.
+    fn set_skin(&mut self, cx: &egui::Context, name: &str) {
+        self.skin = Skin::load(name);
+        self.skin_background = self.skin.background_image.as_ref().and_then(|path| {
+            let image = std::fs::read(path)
+                .map_err(|err| err.to_string())
+                .and_then(|bytes| load_image_from_memory(&bytes).map_err(|err| err.to_string()));
+            match image {
+                Ok(image) => Some(cx.load_texture("skin-background", image, Default::default())),
+                Err(err) => {
+                    issues::warn(format!(
+                        "Couldn't load the skin's background image: {}",
+                        err
+                    ));
+                    None
+                }
+            }
+        });
+        let mut visuals = egui::Visuals::dark();
+        visuals.override_text_color = self.skin.text.map(|color| color.0);
+        cx.set_visuals(visuals);
     }
 
     fn polycat_on(&self) -> TextureHandle {
@@ -184,6 +740,508 @@ impl EditorState {
     }
 }
 
+/// Scales the editor by the user's zoom level, on top of the display's own scale.
+struct Zoom {
+    // The zoom level that the editor was opened with. The window can't be resized once it is
+    // open, so changes to the zoom setting only apply the next time the editor is created.
+    zoom: f32,
+    // The pixels per point that the editor last set.
+    applied: Option<f32>,
+}
+
+impl Zoom {
+    fn new(zoom: f32) -> Zoom {
+        Zoom {
+            zoom,
+            applied: None,
+        }
+    }
+
+    /// Set egui's scale for the next frame. This must be called every frame.
+    fn update(&mut self, cx: &egui::Context) {
+        // The window integration only reports the display's scale when it changes, and egui
+        // otherwise keeps the last scale that was set. So a scale other than the one set here
+        // means that the display's scale has changed.
+        let current = cx.pixels_per_point();
+        if self.applied != Some(current) {
+            self.applied = Some(current * self.zoom);
+        }
+        if let Some(applied) = self.applied {
+            cx.set_pixels_per_point(applied);
+        }
+    }
+}
+
+/// Returns the allowed zoom level closest to the given one.
+fn closest_gui_scale(scale: f32) -> f32 {
+    GUI_SCALES
+        .into_iter()
+        .min_by(|a, b| (a - scale).abs().total_cmp(&(b - scale).abs()))
+        .unwrap()
+}
+
+/// The zoom level picker. Returns the zoom level that the user picked, if they picked a new one.
+fn zoom_picker(ui: &mut Ui, location: Rect, active_zoom: f32) -> Option<f32> {
+    let saved = closest_gui_scale(Settings::get().editor().gui_scale);
+    let mut picked = saved;
+    let label = |scale: f32| format!("{:.0}%", scale * 100.0);
+    ui.allocate_ui_at_rect(location, |ui| {
+        let mut combo_box = egui::ComboBox::from_id_source("gui_scale").width(60.0);
+        combo_box = if saved == active_zoom {
+            combo_box.selected_text(format!("Zoom {}", label(saved)))
+        } else {
+            // The new zoom level only applies once the editor is recreated.
+            combo_box.selected_text(format!("Zoom {} (on reload)", label(saved)))
+        };
+        combo_box.show_ui(ui, |ui| {
+            for scale in GUI_SCALES {
+                ui.selectable_value(&mut picked, scale, label(scale));
+            }
+        });
+    });
+    (picked != saved).then_some(picked)
+}
+
+/// The skin picker. Returns the name of the skin that the user picked, if they picked a new one.
+fn skin_picker(ui: &mut Ui, location: Rect, skin_names: &[String]) -> Option<String> {
+    let current = Settings::get().editor().skin;
+    let mut picked = current.clone();
+    ui.allocate_ui_at_rect(location, |ui| {
+        egui::ComboBox::from_id_source("skin")
+            .width(100.0)
+            .selected_text(format!("Skin: {}", current))
+            .show_ui(ui, |ui| {
+                for name in skin_names {
+                    ui.selectable_value(&mut picked, name.clone(), name);
+                }
+            });
+    });
+    (picked != current).then_some(picked)
+}
+
+/// The state of the preset browser window.
+struct PresetBrowser {
+    library: Arc<PresetLibrary>,
+    /// Runs the preset folder scans in the background.
+    async_executor: AsyncExecutor<Nyasynth>,
+    open: bool,
+    search: String,
+    /// The tags that the list is filtered by.
+    tags: Vec<&'static str>,
+    /// The name and tags to save the current settings with.
+    save_name: String,
+    save_tags: Vec<&'static str>,
+    /// The result of the last save.
+    save_status: Option<String>,
+}
+
+impl PresetBrowser {
+    fn new(library: Arc<PresetLibrary>, async_executor: AsyncExecutor<Nyasynth>) -> PresetBrowser {
+        PresetBrowser {
+            library,
+            async_executor,
+            open: false,
+            search: String::new(),
+            tags: vec![],
+            save_name: String::new(),
+            save_tags: vec![],
+            save_status: None,
+        }
+    }
+
+    /// The name of the selected preset.
+    fn current_name(&self) -> String {
+        let presets = self.library.presets.read().unwrap();
+        presets
+            .get(self.library.current())
+            .map_or(String::new(), |preset| preset.name.clone())
+    }
+
+    /// Load the preset `step` places after the selected one, wrapping around at either end.
+    fn step(&self, setter: &ParamSetter, param_table: &ParamTable, step: isize) {
+        let len = self.library.presets.read().unwrap().len();
+        if len > 0 {
+            let index = (self.library.current() as isize + step).rem_euclid(len as isize);
+            self.load(setter, param_table, index as usize);
+        }
+    }
+
+    /// Load a preset. The parameters are set through the host, so that the host knows about the
+    /// change and can undo it. Every parameter's gesture is open while the preset is applied, so
+    /// that hosts which record automation treat the whole preset change as a single edit.
+    fn load(&self, setter: &ParamSetter, param_table: &ParamTable, index: usize) {
+        let presets = self.library.presets.read().unwrap();
+        let Some(preset) = presets.get(index) else {
+            return;
+        };
+        let context = setter.raw_context;
+        // Safety: The pointers point into the editor's parameters, which are still alive.
+        unsafe {
+            for (_, ptr, _) in param_table.preset_targets(&preset.values) {
+                context.raw_begin_set_parameter(*ptr);
+            }
+            for (_, ptr, normalized) in param_table.preset_targets(&preset.values) {
+                context.raw_set_parameter_normalized(*ptr, normalized);
+            }
+            for (_, ptr, _) in param_table.preset_targets(&preset.values) {
+                context.raw_end_set_parameter(*ptr);
+            }
+        }
+        self.library.editor_loaded_preset(index);
+    }
+
+    /// Show the search box, tag filters, preset list, and the controls for saving a preset.
+    /// Returns true if a preset was loaded.
+    fn show(&mut self, ui: &mut Ui, setter: &ParamSetter, param_table: &ParamTable) -> bool {
+        let mut loaded = false;
+        ui.horizontal(|ui| {
+            ui.label("Search");
+            ui.text_edit_singleline(&mut self.search);
+            if ui.button("Rescan").clicked() {
+                self.async_executor.execute_background(Task::RescanPresets);
+            }
+        });
+        ui.horizontal(|ui| {
+            for tag in PRESET_TAGS {
+                tag_toggle(ui, &mut self.tags, tag);
+            }
+        });
+        ui.separator();
+
+        let mut picked = None;
+        egui::ScrollArea::vertical()
+            .max_height(150.0)
+            .show(ui, |ui| {
+                let presets = self.library.presets.read().unwrap();
+                let current = self.library.current();
+                for (i, preset) in presets.iter().enumerate() {
+                    if !preset.matches(&self.search, &self.tags) {
+                        continue;
+                    }
+                    let label = if preset.tags.is_empty() {
+                        preset.name.clone()
+                    } else {
+                        format!("{}  [{}]", preset.name, preset.tags.join(", "))
+                    };
+                    if ui.selectable_label(i == current, label).clicked() {
+                        picked = Some(i);
+                    }
+                }
+            });
+        if let Some(index) = picked {
+            self.load(setter, param_table, index);
+            loaded = true;
+        }
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.label("Name");
+            ui.text_edit_singleline(&mut self.save_name);
+        });
+        ui.horizontal(|ui| {
+            for tag in PRESET_TAGS {
+                tag_toggle(ui, &mut self.save_tags, tag);
+            }
+            let name = self.save_name.trim();
+            if ui
+                .add_enabled(!name.is_empty(), egui::Button::new("Save"))
+                .clicked()
+            {
+                let tags = self.save_tags.iter().map(|tag| tag.to_string()).collect();
+                self.save_status = Some(
+                    match self.library.save(name, tags, param_table.preset_values()) {
+                        Ok(index) => {
+                            self.library.set_current(index);
+                            format!("Saved {}", name)
+                        }
+                        Err(err) => format!("Couldn't save the preset: {}", err),
+                    },
+                );
+            }
+        });
+        if let Some(status) = &self.save_status {
+            ui.label(status);
+        }
+        loaded
+    }
+}
+
+/// Renders the current sound to a WAV file. Bounces are rendered on their own thread, so that the
+/// editor keeps running while they render.
+struct Bouncer {
+    recorder: Arc<PhraseRecorder>,
+    // The thread rendering the current bounce, if there is one.
+    rendering: Option<JoinHandle<Result<PathBuf, String>>>,
+}
+
+impl Bouncer {
+    fn new(recorder: Arc<PhraseRecorder>) -> Bouncer {
+        Bouncer {
+            recorder,
+            rendering: None,
+        }
+    }
+
+    /// Show the buttons for bouncing the last phrase played or the demo riff with the current
+    /// sound. The bounce is named after `preset_name`.
+    fn show(&mut self, ui: &mut Ui, params: &Parameters, preset_name: &str) {
+        let mut phrase = None;
+        ui.horizontal(|ui| {
+            ui.label("Bounce");
+            let idle = self.rendering.is_none();
+            let last_phrase = self.recorder.phrase();
+            if ui
+                .add_enabled(
+                    idle && last_phrase.is_some(),
+                    egui::Button::new("Last Phrase"),
+                )
+                .on_hover_text("Render the last phrase played to a WAV file")
+                .clicked()
+            {
+                phrase = last_phrase;
+            }
+            if ui
+                .add_enabled(idle, egui::Button::new("Demo Riff"))
+                .on_hover_text("Render a short riff to a WAV file")
+                .clicked()
+            {
+                phrase = Some(Phrase::demo_riff(self.recorder.sample_rate()));
+            }
+            if !idle {
+                ui.label("Rendering...");
+            }
+        });
+        if let Some(phrase) = phrase {
+            let sound = Sound::capture(params);
+            let name = if preset_name.is_empty() {
+                "Nyasynth".to_string()
+            } else {
+                preset_name.to_string()
+            };
+            self.rendering = Some(std::thread::spawn(move || bounce(&sound, &phrase, &name)));
+        }
+    }
+
+    /// Tell the user about the bounce once it is done.
+    fn poll(&mut self, cx: &egui::Context, toasts: &mut Toasts) {
+        if !self
+            .rendering
+            .as_ref()
+            .map_or(false, |rendering| rendering.is_finished())
+        {
+            return;
+        }
+        let result = self.rendering.take().unwrap().join();
+        match result {
+            Ok(Ok(path)) => toasts.push(cx, format!("Bounced to {}", path.display()), false),
+            Ok(Err(err)) => toasts.push(cx, format!("Couldn't bounce: {}", err), true),
+            Err(_) => toasts.push(cx, "Couldn't bounce: The render failed".to_string(), true),
+        }
+    }
+}
+
+// The color of error messages.
+const ERROR_COLOR: Color32 = Color32::from_rgb(0xFF, 0x80, 0x80);
+
+/// The issues reported so far, newest first, so that non-fatal problems such as unreadable preset
+/// files can be looked at after their toasts are gone.
+fn issue_log(ui: &mut Ui) {
+    let issues = IssueLog::get().all();
+    if issues.is_empty() {
+        ui.label("Nothing has gone wrong.");
+        return;
+    }
+    egui::ScrollArea::vertical()
+        .max_height(200.0)
+        .show(ui, |ui| {
+            ui.set_width(360.0);
+            for issue in issues.iter().rev() {
+                match issue.severity {
+                    Severity::Error => ui.colored_label(ERROR_COLOR, &issue.text),
+                    Severity::Warning => ui.label(&issue.text),
+                };
+            }
+        });
+    if ui.button("Clear").clicked() {
+        IssueLog::get().clear();
+    }
+}
+
+// How long a toast stays on screen, in seconds.
+const TOAST_SECONDS: f64 = 4.0;
+
+/// Short messages shown at the bottom of the editor for a few seconds, such as whether a dropped
+/// file could be loaded. Errors go through the issue log, so that they also end up in the editor's
+/// log, and every new issue is shown as a toast.
+struct Toasts {
+    toasts: Vec<Toast>,
+    // The ID of the first issue which hasn't been shown yet.
+    next_issue: u64,
+}
+
+struct Toast {
+    text: String,
+    error: bool,
+    /// The time, in egui's clock, after which the toast is removed.
+    expires: f64,
+}
+
+impl Toasts {
+    fn new() -> Toasts {
+        // Issues from before the editor was opened are only shown in the log.
+        Toasts {
+            toasts: vec![],
+            next_issue: IssueLog::get().next_id(),
+        }
+    }
+
+    fn push(&mut self, cx: &egui::Context, text: String, error: bool) {
+        if error {
+            issues::error(text);
+            return;
+        }
+        let expires = cx.input().time + TOAST_SECONDS;
+        self.toasts.push(Toast {
+            text,
+            error,
+            expires,
+        });
+    }
+
+    fn show(&mut self, cx: &egui::Context) {
+        let now = cx.input().time;
+        for issue in IssueLog::get().since(self.next_issue) {
+            self.next_issue = issue.id + 1;
+            self.toasts.push(Toast {
+                text: issue.text,
+                error: issue.severity == Severity::Error,
+                expires: now + TOAST_SECONDS,
+            });
+        }
+        self.toasts.retain(|toast| toast.expires > now);
+        if self.toasts.is_empty() {
+            return;
+        }
+        egui::Area::new("toasts")
+            .anchor(Align2::CENTER_BOTTOM, vec2(0.0, -28.0))
+            .show(cx, |ui| {
+                for toast in &self.toasts {
+                    Frame::popup(ui.style()).show(ui, |ui| {
+                        let color = if toast.error {
+                            ERROR_COLOR
+                        } else {
+                            ui.visuals().text_color()
+                        };
+                        ui.colored_label(color, &toast.text);
+                    });
+                }
+            });
+    }
+}
+
+/// Load the files which were dropped onto the editor. User presets (.json) are added to the
+/// preset browser and loaded, and .fxp presets replace the whole plugin state. In the standalone
+/// application, MIDI files are loaded into the MIDI player. Returns true if a preset was loaded.
+fn load_dropped_files(
+    cx: &egui::Context,
+    setter: &ParamSetter,
+    param_table: &ParamTable,
+    presets: &PresetBrowser,
+    midi_player: Option<&MidiPlayer>,
+    toasts: &mut Toasts,
+) -> bool {
+    let dropped_files = std::mem::take(&mut cx.input_mut().raw.dropped_files);
+    let mut loaded = false;
+    for file in dropped_files {
+        let Some(path) = file.path else {
+            toasts.push(cx, format!("Couldn't read {}", file.name), true);
+            continue;
+        };
+        let file_name = path
+            .file_name()
+            .map_or(String::new(), |name| name.to_string_lossy().to_string());
+        let extension = path
+            .extension()
+            .map_or(String::new(), |ext| ext.to_string_lossy().to_lowercase());
+        let result = match extension.as_str() {
+            "json" => presets.library.import(&path).map(|index| {
+                presets.load(setter, param_table, index);
+            }),
+            "fxp" => std::fs::read(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|bytes| read_fxp(&bytes))
+                .map(|state| setter.raw_context.set_state(state)),
+            "mid" | "midi" => {
+                let Some(midi_player) = midi_player else {
+                    let err = "MIDI files can only be played in the standalone application";
+                    toasts.push(cx, err.to_string(), true);
+                    continue;
+                };
+                match std::fs::read(&path)
+                    .map_err(|err| err.to_string())
+                    .and_then(|bytes| MidiLoop::parse(file_name.clone(), &bytes))
+                {
+                    Ok(midi_loop) => {
+                        midi_player.load(midi_loop);
+                        toasts.push(cx, format!("Playing {}", file_name), false);
+                    }
+                    Err(err) => {
+                        toasts.push(cx, format!("Couldn't load {}: {}", file_name, err), true)
+                    }
+                }
+                continue;
+            }
+            // The oscillators are fixed shapes, so there is nothing for a WAV file to load into.
+            "wav" => Err("Nyasynth doesn't have wavetables".to_string()),
+            _ => Err("Only .json and .fxp presets can be loaded".to_string()),
+        };
+        match result {
+            Ok(()) => {
+                toasts.push(cx, format!("Loaded {}", file_name), false);
+                loaded = true;
+            }
+            Err(err) => toasts.push(cx, format!("Couldn't load {}: {}", file_name, err), true),
+        }
+    }
+    loaded
+}
+
+/// While files are dragged over the editor, dim it and say that they can be dropped.
+fn show_drop_target(cx: &egui::Context, standalone: bool) {
+    if cx.input().raw.hovered_files.is_empty() {
+        return;
+    }
+    let painter = cx.layer_painter(egui::LayerId::new(
+        egui::Order::Foreground,
+        egui::Id::new("drop_target"),
+    ));
+    let screen = cx.input().screen_rect();
+    painter.rect_filled(screen, 0.0, Color32::from_black_alpha(160));
+    painter.text(
+        screen.center(),
+        Align2::CENTER_CENTER,
+        if standalone {
+            "Drop a preset or MIDI file to load it"
+        } else {
+            "Drop a preset to load it"
+        },
+        FontId::monospace(16.0),
+        Color32::WHITE,
+    );
+}
+
+/// A button which adds or removes a tag from a list of tags.
+fn tag_toggle(ui: &mut Ui, tags: &mut Vec<&'static str>, tag: &'static str) {
+    let selected = tags.contains(&tag);
+    if ui.selectable_label(selected, tag).clicked() {
+        if selected {
+            tags.retain(|t| *t != tag);
+        } else {
+            tags.push(tag);
+        }
+    }
+}
+
 fn load_image_from_memory(image_data: &[u8]) -> Result<ColorImage, image::ImageError> {
     let image = image::load_from_memory(image_data)?;
     let size = [image.width() as _, image.height() as _];
@@ -192,14 +1250,101 @@ fn load_image_from_memory(image_data: &[u8]) -> Result<ColorImage, image::ImageE
     Ok(ColorImage::from_rgba_unmultiplied(size, pixels.as_slice()))
 }
 
+/// Wraps the egui editor so that the parameter changes which the host tells the editor about are
+/// recorded in the [ParamChanges], and so that the [HostLink] can use the editor's context while the
+/// editor is open.
+struct NotifyingEditor {
+    editor: Box<dyn Editor>,
+    param_changes: Arc<ParamChanges>,
+    host_link: Arc<HostLink>,
+}
+
+/// The open editor window. Closing the window disconnects the [HostLink].
+struct EditorHandle {
+    _handle: Box<dyn Any + Send>,
+    host_link: Arc<HostLink>,
+}
+
+impl Drop for EditorHandle {
+    fn drop(&mut self) {
+        self.host_link.disconnect();
+    }
+}
+
+impl Editor for NotifyingEditor {
+    fn spawn(
+        &self,
+        parent: ParentWindowHandle,
+        context: Arc<dyn GuiContext>,
+    ) -> Box<dyn Any + Send> {
+        self.host_link.connect(context.clone());
+        Box::new(EditorHandle {
+            _handle: self.editor.spawn(parent, context),
+            host_link: self.host_link.clone(),
+        })
+    }
+
+    fn size(&self) -> (u32, u32) {
+        self.editor.size()
+    }
+
+    fn set_scale_factor(&self, factor: f32) -> bool {
+        self.editor.set_scale_factor(factor)
+    }
+
+    fn param_value_changed(&self, id: &str, normalized_value: f32) {
+        self.param_changes.notify(id);
+        self.editor.param_value_changed(id, normalized_value)
+    }
+
+    fn param_modulation_changed(&self, id: &str, modulation_offset: f32) {
+        self.param_changes.notify(id);
+        self.editor.param_modulation_changed(id, modulation_offset)
+    }
+
+    fn param_values_changed(&self) {
+        self.param_changes.notify_all();
+        self.editor.param_values_changed()
+    }
+}
+
 pub fn get_editor(
+    async_executor: AsyncExecutor<Nyasynth>,
     params: Arc<Parameters>,
     envelope_amount: Arc<AtomicF32>,
+    voice_pitches: Arc<VoicePitches>,
+    meters: Arc<OutputMeters>,
+    presets: Arc<PresetLibrary>,
+    protection_engaged: Arc<AtomicBool>,
+    param_changes: Arc<ParamChanges>,
+    host_link: Arc<HostLink>,
+    dropped_events: Arc<AtomicUsize>,
+    midi_learn: Arc<MidiLearn>,
+    phrase_recorder: Arc<PhraseRecorder>,
+    midi_player: Option<Arc<MidiPlayer>>,
 ) -> Option<Box<dyn Editor>> {
-    let egui_state = EguiState::from_size(SCREEN_WIDTH, SCREEN_HEIGHT);
-    let editor_state = EditorState::new(params.polycat.value(), envelope_amount);
+    // The window is sized for the zoom level, and the contents are scaled up to fill it.
+    let zoom = closest_gui_scale(Settings::get().editor().gui_scale);
+    let egui_state = EguiState::from_size(
+        (SCREEN_WIDTH as f32 * zoom).round() as u32,
+        (SCREEN_HEIGHT as f32 * zoom).round() as u32,
+    );
+    let editor_state = EditorState::new(
+        &params,
+        envelope_amount,
+        voice_pitches,
+        meters,
+        presets,
+        async_executor,
+        phrase_recorder,
+        protection_engaged,
+        zoom,
+        param_changes.clone(),
+    );
+    let param_table = ParamTable::new(&params, param_changes.clone());
+    let param_menu = ParamMenu::new(params.clone(), midi_learn.clone());
 
-    create_egui_editor(
+    let editor = create_egui_editor(
         egui_state,
         editor_state,
         |cx, editor_state| {
@@ -233,6 +1378,7 @@ pub fn get_editor(
             let brushed_metal =
                 load_image_from_memory(include_bytes!("../assets/ui_2x_v2.png")).unwrap();
             editor_state.brushed_metal = Some(load_image("metal-knob", brushed_metal));
+            editor_state.set_skin(cx, &Settings::get().editor().skin);
 
             let polycat_on =
                 load_image_from_memory(include_bytes!("../assets/spine_json/POLYCAT ON.png"))
@@ -257,51 +1403,417 @@ pub fn get_editor(
         },
         move |cx, setter, editor_state| {
             cx.set_debug_on_hover(true);
+            editor_state.zoom.update(cx);
+
+            // The audio thread only records the learned CC, so the mapping is stored here.
+            midi_learn.finish(|cc, id| {
+                midi_learn::edit_mappings(&params.midi_mappings, |mappings| mappings.learn(cc, id))
+            });
+
+            // Only the widget state which the editor keeps a copy of needs to be refreshed here,
+            // since every other widget reads its parameter directly.
+            editor_state.param_changes.drain(|id| {
+                if id == "polycat" {
+                    editor_state.polycat_state = params.global.polycat.value();
+                }
+            });
+
+            // Settings changed while loading a project are saved here, rather than holding up the
+            // host.
+            Settings::get().save_if_needed();
+
+            // The audio thread can't report anything itself, so the events it had to drop are
+            // reported here.
+            let dropped = dropped_events.swap(0, Ordering::Relaxed);
+            if dropped > 0 {
+                issues::warn(format!(
+                    "{} MIDI events were dropped because too many arrived at once.",
+                    dropped
+                ));
+            }
+
+            let mut picked_skin = None;
+            let mut preset_loaded = false;
             egui::CentralPanel::default()
-                .frame(
-                    Frame::none()
-                        .fill(Rgba::from_srgba_premultiplied(0x89, 0xA9, 0xBD, 0xFF).into()),
-                )
+                .frame(Frame::none().fill(editor_state.skin.background.0))
                 .show(cx, |ui| {
                     let locs = &editor_state.widget_location;
+                    let skin = &editor_state.skin;
+                    let knob_skin = &skin.knob;
 
                     // UI Background
-                    let background = image_shape(editor_state.brushed_metal(), ui.max_rect());
+                    let background = image_shape(editor_state.background(), ui.max_rect());
                     ui.painter().add(background);
 
                     // Cat Image
                     let image = image_shape(editor_state.cat_image(), locs.cat_image);
                     ui.painter().add(image);
 
+                    // Pitch readout
+                    ui.painter().text(
+                        pos2(SCREEN_WIDTH as f32 - 4.0, 4.0),
+                        Align2::RIGHT_TOP,
+                        editor_state.pitch_readout(),
+                        FontId::monospace(12.0),
+                        skin.text_color(),
+                    );
+
+                    // Output meters, just below the pitch readout. Clicking them clears the clip
+                    // indicators.
+                    let meters_rect = Rect::from_min_size(
+                        pos2(SCREEN_WIDTH as f32 - 4.0 - METER_WIDTH, 20.0),
+                        vec2(METER_WIDTH, 2.0 * METER_HEIGHT + 2.0),
+                    );
+                    let meters = &editor_state.meters;
+                    if ui.allocate_rect(meters_rect, Sense::click()).clicked() {
+                        meters.clear_clipped();
+                    }
+                    let snapshot = meters.snapshot();
+                    let meter_size = vec2(METER_WIDTH, METER_HEIGHT);
+                    let left_rect = Rect::from_min_size(meters_rect.min, meter_size);
+                    let right_rect = left_rect.translate(vec2(0.0, METER_HEIGHT + 2.0));
+                    draw_meter(ui, left_rect, snapshot.left, skin.meter.0);
+                    draw_meter(ui, right_rect, snapshot.right, skin.meter.0);
+
                     // Knobs
-                    make_arc_knob(ui, &setter, &params.meow_attack, locs.meow_attack);
-                    make_arc_knob(ui, &setter, &params.meow_decay, locs.meow_decay);
-                    make_arc_knob(ui, &setter, &params.meow_sustain, locs.meow_sustain);
-                    make_arc_knob(ui, &setter, &params.meow_release, locs.meow_release);
-                    make_arc_knob(ui, &setter, &params.vibrato_amount, locs.vibrato_amount);
-                    make_arc_knob(ui, &setter, &params.vibrato_attack, locs.vibrato_attack);
-                    make_text_slider(ui, setter, &params.vibrato_rate, locs.vibrato_speed);
-                    make_arc_knob(ui, &setter, &params.portamento_time, locs.portamento_time);
-                    make_arc_knob(ui, &setter, &params.noise_mix, locs.noise_mix);
-                    make_arc_knob(ui, &setter, &params.chorus_mix, locs.chorus_mix);
-                    make_text_slider(ui, setter, &params.pitch_bend, locs.pitch_bend);
+                    let (envelope, vibrato) = (&params.envelope, &params.vibrato);
+                    let (chorus, global) = (&params.chorus, &params.global);
+                    make_arc_knob(
+                        ui,
+                        &setter,
+                        &param_menu,
+                        knob_skin,
+                        &envelope.meow_attack,
+                        locs.meow_attack,
+                    );
+                    make_arc_knob(
+                        ui,
+                        &setter,
+                        &param_menu,
+                        knob_skin,
+                        &envelope.meow_decay,
+                        locs.meow_decay,
+                    );
+                    make_arc_knob(
+                        ui,
+                        &setter,
+                        &param_menu,
+                        knob_skin,
+                        &envelope.meow_sustain,
+                        locs.meow_sustain,
+                    );
+                    make_arc_knob(
+                        ui,
+                        &setter,
+                        &param_menu,
+                        knob_skin,
+                        &envelope.meow_release,
+                        locs.meow_release,
+                    );
+                    make_arc_knob(
+                        ui,
+                        &setter,
+                        &param_menu,
+                        knob_skin,
+                        &vibrato.vibrato_amount,
+                        locs.vibrato_amount,
+                    );
+                    make_arc_knob(
+                        ui,
+                        &setter,
+                        &param_menu,
+                        knob_skin,
+                        &vibrato.vibrato_attack,
+                        locs.vibrato_attack,
+                    );
+                    make_text_slider(
+                        ui,
+                        setter,
+                        &param_menu,
+                        &vibrato.vibrato_rate,
+                        locs.vibrato_speed,
+                    );
+                    make_arc_knob(
+                        ui,
+                        &setter,
+                        &param_menu,
+                        knob_skin,
+                        &global.portamento_time,
+                        locs.portamento_time,
+                    );
+                    make_arc_knob(
+                        ui,
+                        &setter,
+                        &param_menu,
+                        knob_skin,
+                        &global.noise_mix,
+                        locs.noise_mix,
+                    );
+                    make_arc_knob(
+                        ui,
+                        &setter,
+                        &param_menu,
+                        knob_skin,
+                        &chorus.chorus_mix,
+                        locs.chorus_mix,
+                    );
+                    make_text_slider(ui, setter, &param_menu, &global.pitch_bend, locs.pitch_bend);
 
                     // Polycat Button
                     let button = ui.allocate_rect(locs.polycat_button, Sense::click());
                     if button.clicked() {
                         editor_state.polycat_state = !editor_state.polycat_state;
-                        setter.begin_set_parameter(&params.polycat);
-                        setter.set_parameter(&params.polycat, editor_state.polycat_state);
-                        setter.end_set_parameter(&params.polycat);
+                        setter.begin_set_parameter(&global.polycat);
+                        setter.set_parameter(&global.polycat, editor_state.polycat_state);
+                        setter.end_set_parameter(&global.polycat);
                     }
                     if editor_state.polycat_state {
                         let shape = image_shape(editor_state.polycat_on(), locs.polycat_on);
                         ui.painter().add(shape);
                     };
+
+                    let macros_button = Rect::from_min_size(pos2(4.0, 4.0), vec2(60.0, 18.0));
+                    if ui.put(macros_button, egui::Button::new("Macros")).clicked() {
+                        editor_state.macros_open = true;
+                    }
+
+                    let internal_button = Rect::from_min_size(pos2(212.0, 26.0), vec2(60.0, 18.0));
+                    if ui
+                        .put(internal_button, egui::Button::new("Internal"))
+                        .clicked()
+                    {
+                        editor_state.internal_open = true;
+                    }
+
+                    let layers_button = Rect::from_min_size(pos2(276.0, 26.0), vec2(60.0, 18.0));
+                    if ui.put(layers_button, egui::Button::new("Layers")).clicked() {
+                        editor_state.layers_open = true;
+                    }
+
+                    // The MIDI player only exists in the standalone application.
+                    if midi_player.is_some() {
+                        let player_button =
+                            Rect::from_min_size(pos2(340.0, 26.0), vec2(60.0, 18.0));
+                        if ui.put(player_button, egui::Button::new("Player")).clicked() {
+                            editor_state.player_open = true;
+                        }
+                    }
+
+                    // In help mode, hovering over a knob describes it.
+                    let help_toggle = Rect::from_min_size(pos2(212.0, 4.0), vec2(18.0, 18.0));
+                    let help_mode = param_menu.help_mode();
+                    let help_button = ui
+                        .put(help_toggle, egui::SelectableLabel::new(help_mode, "?"))
+                        .on_hover_text("Help mode");
+                    if help_button.clicked() {
+                        param_menu.set_help_mode(!help_mode);
+                    }
+
+                    // The log button turns red while there are errors in the log.
+                    let log_toggle = Rect::from_min_size(pos2(234.0, 4.0), vec2(18.0, 18.0));
+                    let has_errors = IssueLog::get()
+                        .all()
+                        .iter()
+                        .any(|issue| issue.severity == Severity::Error);
+                    let log_label = if has_errors {
+                        egui::RichText::new("!").color(ERROR_COLOR)
+                    } else {
+                        egui::RichText::new("!")
+                    };
+                    if ui
+                        .put(log_toggle, egui::Button::new(log_label))
+                        .on_hover_text("Log")
+                        .clicked()
+                    {
+                        editor_state.log_open = true;
+                    }
+
+                    // Speaker protection is a per-user setting, so changing it is saved right
+                    // away instead of going through the parameters.
+                    let settings = Settings::get();
+                    let mut protection = settings.speaker_protection();
+                    let protection_toggle = Rect::from_min_size(pos2(68.0, 4.0), vec2(140.0, 18.0));
+                    let checkbox = egui::Checkbox::new(&mut protection, "Speaker Protection");
+                    if ui.put(protection_toggle, checkbox).changed() {
+                        settings.set_speaker_protection(protection);
+                        if !protection {
+                            editor_state
+                                .protection_engaged
+                                .store(false, Ordering::Relaxed);
+                        }
+                    }
+                    if editor_state.protection_engaged.load(Ordering::Relaxed) {
+                        let indicator = Rect::from_min_size(pos2(4.0, 26.0), vec2(204.0, 18.0));
+                        let label = egui::RichText::new("Limited to -12 dB (click to unlimit)")
+                            .color(Color32::RED);
+                        if ui.put(indicator, egui::Button::new(label)).clicked() {
+                            editor_state
+                                .protection_engaged
+                                .store(false, Ordering::Relaxed);
+                        }
+                    }
+
+                    let zoom_location = Rect::from_min_size(
+                        pos2(4.0, SCREEN_HEIGHT as f32 - 22.0),
+                        vec2(150.0, 18.0),
+                    );
+                    if let Some(zoom) = zoom_picker(ui, zoom_location, editor_state.zoom.zoom) {
+                        Settings::get().update_editor(|settings| settings.gui_scale = zoom);
+                    }
+
+                    let skin_location = Rect::from_min_size(
+                        pos2(158.0, SCREEN_HEIGHT as f32 - 22.0),
+                        vec2(150.0, 18.0),
+                    );
+                    picked_skin = skin_picker(ui, skin_location, &editor_state.skin_names);
+
+                    // The selected preset, with buttons to step through the presets.
+                    let presets = &mut editor_state.presets;
+                    let bottom = SCREEN_HEIGHT as f32 - 22.0;
+                    let previous = Rect::from_min_size(pos2(312.0, bottom), vec2(18.0, 18.0));
+                    let browser = Rect::from_min_size(pos2(332.0, bottom), vec2(94.0, 18.0));
+                    let next = Rect::from_min_size(pos2(428.0, bottom), vec2(18.0, 18.0));
+                    if ui.put(previous, egui::Button::new("<")).clicked() {
+                        presets.step(setter, &param_table, -1);
+                        preset_loaded = true;
+                    }
+                    let name = presets.current_name();
+                    if ui.put(browser, egui::Button::new(name)).clicked() {
+                        presets.open = !presets.open;
+                    }
+                    if ui.put(next, egui::Button::new(">")).clicked() {
+                        presets.step(setter, &param_table, 1);
+                        preset_loaded = true;
+                    }
                     button
                 });
+            if let Some(name) = picked_skin {
+                editor_state.set_skin(cx, &name);
+                Settings::get().update_editor(|settings| settings.skin = name);
+            }
+
+            let presets = &mut editor_state.presets;
+            let mut presets_open = presets.open;
+            egui::Window::new("Presets")
+                .open(&mut presets_open)
+                .resizable(false)
+                .show(cx, |ui| {
+                    preset_loaded |= presets.show(ui, setter, &param_table);
+                    ui.separator();
+                    editor_state
+                        .bouncer
+                        .show(ui, &params, &presets.current_name());
+                });
+            presets.open = presets_open;
+
+            show_drop_target(cx, midi_player.is_some());
+            preset_loaded |= load_dropped_files(
+                cx,
+                setter,
+                &param_table,
+                &editor_state.presets,
+                midi_player.as_deref(),
+                &mut editor_state.toasts,
+            );
+            editor_state.bouncer.poll(cx, &mut editor_state.toasts);
+            editor_state.toasts.show(cx);
+            // Every widget is refreshed after a preset is loaded, rather than working out which
+            // parameters the preset changed.
+            if preset_loaded {
+                editor_state.param_changes.notify_all();
+            }
+
+            egui::Window::new("Log")
+                .open(&mut editor_state.log_open)
+                .resizable(false)
+                .show(cx, issue_log);
+
+            egui::Window::new("Internal Parameters")
+                .open(&mut editor_state.internal_open)
+                .resizable(false)
+                .show(cx, |ui| internal_editor(ui, setter, &param_menu, &params));
+
+            if let Some(midi_player) = &midi_player {
+                egui::Window::new("MIDI Player")
+                    .open(&mut editor_state.player_open)
+                    .resizable(false)
+                    .show(cx, |ui| player_editor(ui, midi_player));
+            }
+
+            let mut layers_swapped = false;
+            egui::Window::new("Layers")
+                .open(&mut editor_state.layers_open)
+                .resizable(false)
+                .show(cx, |ui| {
+                    layers_swapped = layers_editor(ui, setter, &param_menu, &params, &param_table);
+                });
+            if layers_swapped {
+                editor_state.param_changes.notify_all();
+            }
+
+            // The assignments are edited on a copy, so that the audio thread is never blocked
+            // for long by the GUI.
+            egui::Window::new("Macros & Morph")
+                .open(&mut editor_state.macros_open)
+                .resizable(false)
+                .vscroll(true)
+                .show(cx, |ui| {
+                    morph_editor(ui, setter, &param_menu, &params, &param_table);
+
+                    let mut assignments = params.macro_assignments.read().unwrap().clone();
+                    let param_names = &editor_state.param_names;
+                    let macros = &params.macros;
+                    if macro_editor(
+                        ui,
+                        setter,
+                        &param_menu,
+                        macros,
+                        &mut assignments,
+                        param_names,
+                    ) {
+                        *params.macro_assignments.write().unwrap() = assignments;
+                    }
+                });
         },
-    )
+    )?;
+    Some(Box::new(NotifyingEditor {
+        editor,
+        param_changes,
+        host_link,
+    }))
+}
+
+// The size of each channel's output meter, in points.
+const METER_WIDTH: f32 = 80.0;
+const METER_HEIGHT: f32 = 4.0;
+// The quietest level that the meters show, in dBFS.
+const METER_FLOOR_DB: f32 = -60.0;
+
+/// Draw one channel's meter: a dim bar for the RMS level, a brighter tick for the peak level, and
+/// a red light at the end if the channel has clipped.
+fn draw_meter(ui: &Ui, rect: Rect, levels: ChannelLevels, color: Color32) {
+    let painter = ui.painter();
+    let light_left = rect.right() - rect.height();
+    let bar = Rect::from_min_max(rect.min, pos2(light_left - 1.0, rect.max.y));
+    let light = Rect::from_min_max(pos2(light_left, rect.min.y), rect.max);
+    let x_for = |db: f32| {
+        let t = ((db - METER_FLOOR_DB) / -METER_FLOOR_DB).clamp(0.0, 1.0);
+        bar.left() + t * bar.width()
+    };
+
+    painter.rect_filled(bar, 0.0, Color32::from_black_alpha(96));
+    let rms = Rect::from_min_max(bar.min, pos2(x_for(levels.rms_dbfs()), bar.max.y));
+    painter.rect_filled(rms, 0.0, color);
+    let peak_x = x_for(levels.peak_dbfs());
+    let peak = Rect::from_min_max(pos2(peak_x - 1.0, bar.min.y), pos2(peak_x, bar.max.y));
+    painter.rect_filled(peak, 0.0, Color32::WHITE);
+
+    let light_color = if levels.clipped {
+        Color32::RED
+    } else {
+        Color32::from_black_alpha(96)
+    };
+    painter.rect_filled(light, 0.0, light_color);
 }
 
 fn image_shape(texture_handle: TextureHandle, rect: Rect) -> Shape {
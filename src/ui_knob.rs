@@ -2,18 +2,22 @@ use std::f32::consts::TAU;
 
 use nih_plug::prelude::{Param, ParamSetter};
 use nih_plug_egui::egui::{
-    epaint::PathShape, pos2, vec2, Align2, Color32, FontId, Id, Pos2, Rect, Response, Rgba, Sense,
-    Shape, Stroke, Ui, Widget,
+    epaint::PathShape, pos2, vec2, Align2, Color32, FontId, Id, Pos2, Rect, Response, Sense, Shape,
+    Stroke, Ui, Widget,
 };
 use once_cell::sync::Lazy;
 
 use crate::ease::lerp;
+use crate::param_info::in_detent;
+use crate::skin::{KnobSkin, KnobStyle};
 
 static DRAG_AMOUNT_MEMORY_ID: Lazy<Id> = Lazy::new(|| Id::new("drag_amount_memory_id"));
 
 struct SliderRegion<'a, P: Param> {
     param: &'a P,
     param_setter: &'a ParamSetter<'a>,
+    // If set, dragging close to this normalized value locks the value to it.
+    detent: Option<f32>,
 }
 
 impl<'a, P: Param> SliderRegion<'a, P> {
@@ -21,11 +25,15 @@ impl<'a, P: Param> SliderRegion<'a, P> {
         SliderRegion {
             param,
             param_setter,
+            detent: None,
         }
     }
 
     // Handle the input for a given response. Returns an f32 containing the normalized value of
     // the parameter.
+    // The host is told that the parameter is being touched for as long as the mouse is held down on
+    // the widget, and not just while it moves, so that recorded automation punches in on the press
+    // and punches out on the release.
     fn handle_response(&self, ui: &Ui, response: &Response) -> f32 {
         let value = self.param.unmodulated_normalized_value();
         if response.drag_started() {
@@ -40,13 +48,27 @@ impl<'a, P: Param> SliderRegion<'a, P> {
             let mut memory = ui.memory();
             let value = memory.data.get_temp_mut_or(*DRAG_AMOUNT_MEMORY_ID, value);
             *value = (*value + delta / 100.0).clamp(0.0, 1.0);
+            // The unsnapped value is remembered, so that the knob sticks at the center for a
+            // little while and then carries on past it.
+            let snapped = match self.detent {
+                Some(detent) if in_detent(*value, detent) => detent,
+                _ => *value,
+            };
             self.param_setter
-                .set_parameter_normalized(self.param, *value);
+                .set_parameter_normalized(self.param, snapped);
         }
 
         if response.drag_released() {
             self.param_setter.end_set_parameter(self.param);
         }
+
+        // Double clicking snaps the parameter back to its default.
+        if response.double_clicked() {
+            self.param_setter.begin_set_parameter(self.param);
+            self.param_setter
+                .set_parameter(self.param, self.param.default_plain_value());
+            self.param_setter.end_set_parameter(self.param);
+        }
         value
     }
 
@@ -59,16 +81,30 @@ pub struct ArcKnob<'a, P: Param> {
     slider_region: SliderRegion<'a, P>,
     radius: f32,
     center: Pos2,
+    skin: &'a KnobSkin,
 }
 
 impl<'a, P: Param> ArcKnob<'a, P> {
-    pub fn for_param(param: &'a P, param_setter: &'a ParamSetter, radius: f32, pos: Pos2) -> Self {
+    pub fn for_param(
+        param: &'a P,
+        param_setter: &'a ParamSetter,
+        radius: f32,
+        pos: Pos2,
+        skin: &'a KnobSkin,
+    ) -> Self {
         ArcKnob {
             slider_region: SliderRegion::new(param, param_setter),
             radius,
             center: pos,
+            skin,
         }
     }
+
+    /// Lock the knob to the given normalized value when it's dragged close to it.
+    pub fn with_detent(mut self, detent: Option<f32>) -> Self {
+        self.slider_region.detent = detent;
+        self
+    }
 }
 
 impl<'a, P: Param> Widget for ArcKnob<'a, P> {
@@ -82,38 +118,67 @@ impl<'a, P: Param> Widget for ArcKnob<'a, P> {
         let center = response.rect.center();
 
         // Draw the arc
-        let stroke_width = 5.0;
+        let skin = self.skin;
+        let stroke_width = skin.width;
         let radius = self.radius - stroke_width - 2.0;
-        let stroke = Stroke::new(stroke_width, Rgba::from_rgb(1.0, 1.0, 0.0));
-        let shape = Shape::Path(PathShape {
-            points: get_arc_points(center, radius, value, 0.03),
-            closed: false,
-            fill: Color32::TRANSPARENT,
-            stroke,
-        });
-        painter.add(shape);
+        // The arc is split into segments about two physical pixels long, so that it stays smooth
+        // when the editor is zoomed in or shown on a high DPI display.
+        let physical_radius = radius * ui.ctx().pixels_per_point();
+        let max_arc_distance = MAX_SEGMENT_PIXELS / (physical_radius.max(1.0) * TAU);
+        let arc = |value: f32, color: Color32| {
+            Shape::Path(PathShape {
+                points: get_arc_points(center, radius, value, max_arc_distance),
+                closed: false,
+                fill: Color32::TRANSPARENT,
+                stroke: Stroke::new(stroke_width, color),
+            })
+        };
+        match skin.style {
+            KnobStyle::Arc => {
+                painter.add(arc(value, skin.color.0));
+            }
+            KnobStyle::Track => {
+                painter.add(arc(1.0, skin.track_color.0));
+                painter.add(arc(value, skin.color.0));
+            }
+            KnobStyle::Dot => {
+                let position = arc_point(center, radius, value);
+                painter.circle_filled(position, stroke_width / 2.0, skin.color.0);
+            }
+        }
         response
     }
 }
 
-fn get_arc_points(center: Pos2, radius: f32, value: f32, max_arc_distance: f32) -> Vec<Pos2> {
-    let start_turns: f32 = 0.625;
-    let arc_length = lerp(0.0, -0.75, value);
-    let end_turns = start_turns + arc_length;
+// The longest that a segment of a knob's arc can be, in physical pixels.
+const MAX_SEGMENT_PIXELS: f32 = 2.0;
+
+// Where a knob's arc starts, and how far around it goes, in turns.
+const ARC_START: f32 = 0.625;
+const ARC_LENGTH: f32 = -0.75;
 
-    let points = (arc_length.abs() / max_arc_distance).ceil() as usize;
+/// The points along a knob's arc. `max_arc_distance` is the longest that each segment can be, in
+/// turns.
+fn get_arc_points(center: Pos2, radius: f32, value: f32, max_arc_distance: f32) -> Vec<Pos2> {
+    let arc_length = lerp(0.0, ARC_LENGTH, value);
+    let points = ((arc_length.abs() / max_arc_distance).ceil() as usize).max(1);
 
     (0..=points)
         .map(|i| {
-            let t = i as f32 / (points - 1) as f32;
-            let angle = lerp(start_turns * TAU, end_turns * TAU, t);
-            let x = radius * angle.cos();
-            let y = -radius * angle.sin();
-            pos2(x, y) + center.to_vec2()
+            let t = i as f32 / points as f32;
+            arc_point(center, radius, value * t)
         })
         .collect()
 }
 
+/// The point on a knob's arc for the given value.
+fn arc_point(center: Pos2, radius: f32, value: f32) -> Pos2 {
+    let angle = (ARC_START + lerp(0.0, ARC_LENGTH, value)) * TAU;
+    let x = radius * angle.cos();
+    let y = -radius * angle.sin();
+    pos2(x, y) + center.to_vec2()
+}
+
 pub struct TextSlider<'a, P: Param> {
     slider_region: SliderRegion<'a, P>,
     location: Rect,
@@ -126,6 +191,12 @@ impl<'a, P: Param> TextSlider<'a, P> {
             location,
         }
     }
+
+    /// Lock the slider to the given normalized value when it's dragged close to it.
+    pub fn with_detent(mut self, detent: Option<f32>) -> Self {
+        self.slider_region.detent = detent;
+        self
+    }
 }
 
 impl<'a, P: Param> Widget for TextSlider<'a, P> {
@@ -139,7 +210,8 @@ impl<'a, P: Param> Widget for TextSlider<'a, P> {
         // Draw the text
         let text = self.slider_region.get_string();
         let anchor = Align2::CENTER_CENTER;
-        let color = Color32::from(Rgba::WHITE);
+        // Skins can change the text color through the editor's visuals.
+        let color = ui.visuals().override_text_color.unwrap_or(Color32::WHITE);
         let font = FontId::monospace(16.0);
         painter.text(center, anchor, text, font, color);
         response
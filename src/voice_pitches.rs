@@ -0,0 +1,42 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use atomic_float::AtomicF32;
+
+use crate::common::Hertz;
+use crate::params::MAX_VOICES;
+
+/// The current pitch of each playing voice, shared with the editor so that it can show what the
+/// synth is playing. This is updated once per buffer. Since the pitches are stored separately, the
+/// editor may occasionally see a mix of two buffers' pitches, which is fine for a display.
+pub struct VoicePitches {
+    pitches: [AtomicF32; MAX_VOICES],
+    len: AtomicUsize,
+}
+
+impl VoicePitches {
+    pub fn new() -> VoicePitches {
+        VoicePitches {
+            pitches: std::array::from_fn(|_| AtomicF32::new(0.0)),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    /// Replace the stored pitches, oldest voice first. Only the first MAX_VOICES pitches are kept.
+    pub fn store(&self, pitches: impl Iterator<Item = Hertz>) {
+        let mut len = 0;
+        for (stored, pitch) in self.pitches.iter().zip(pitches) {
+            stored.store(pitch.get(), Ordering::Relaxed);
+            len += 1;
+        }
+        self.len.store(len, Ordering::Relaxed);
+    }
+
+    /// Returns the pitch of each playing voice, oldest voice first.
+    pub fn load(&self) -> Vec<Hertz> {
+        let len = self.len.load(Ordering::Relaxed);
+        self.pitches[..len]
+            .iter()
+            .map(|pitch| Hertz::new(pitch.load(Ordering::Relaxed)))
+            .collect()
+    }
+}
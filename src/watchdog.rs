@@ -0,0 +1,12 @@
+// The output watchdog is always on in debug builds. Release builds only get it with the "watchdog"
+// feature, since it costs a couple of comparisons per sample.
+pub const WATCHDOG_ENABLED: bool = cfg!(any(debug_assertions, feature = "watchdog"));
+
+// Samples louder than this (about +40 dB) are treated as garbage. No reasonable patch gets anywhere
+// near this loud, so anything past it means that a filter or delay line has blown up.
+const MAX_SANE_SAMPLE: f32 = 100.0;
+
+/// Returns true if the sample is finite and not absurdly loud.
+pub fn is_sane(sample: f32) -> bool {
+    sample.abs() <= MAX_SANE_SAMPLE
+}
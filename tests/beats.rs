@@ -0,0 +1,19 @@
+//! Checks the conversions between beats and seconds.
+
+use nyasynth::common::{Beats, Seconds};
+
+#[test]
+fn beats_and_seconds() {
+    // At 120 BPM, a beat lasts half a second.
+    assert_eq!(Beats::new(1.0).to_seconds(120.0), Seconds::new(0.5));
+    assert_eq!(Beats::new(4.0).to_seconds(60.0), Seconds::new(4.0));
+    assert_eq!(
+        Beats::from_seconds(Seconds::new(3.0), 90.0),
+        Beats::new(4.5)
+    );
+    for tempo in [60.0, 97.5, 120.0, 174.0] {
+        let beats = Beats::new(2.5);
+        let round_trip = Beats::from_seconds(beats.to_seconds(tempo), tempo);
+        assert!((round_trip.get() - beats.get()).abs() < 1.0e-5);
+    }
+}
@@ -0,0 +1,189 @@
+//! A minimal host for running the plugin in tests.
+
+// Each test only uses some of the helpers.
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use nih_plug::context::process::Transport;
+use nih_plug::context::PluginApi;
+use nih_plug::prelude::*;
+use nyasynth::dsp::SETTINGS_DIR_VAR;
+use nyasynth::{Nyasynth, Task};
+
+pub type MidiEvent = PluginNoteEvent<Nyasynth>;
+
+pub const SAMPLE_RATE: f32 = 44100.0;
+
+struct TestContext;
+
+impl InitContext<Nyasynth> for TestContext {
+    fn plugin_api(&self) -> PluginApi {
+        PluginApi::Standalone
+    }
+
+    fn execute(&self, _task: Task) {}
+
+    fn set_latency_samples(&self, _samples: u32) {}
+
+    fn set_current_voice_capacity(&self, _capacity: u32) {}
+}
+
+struct TestProcessContext {
+    events: Vec<MidiEvent>,
+    event_index: usize,
+    transport: Transport,
+}
+
+impl ProcessContext<Nyasynth> for TestProcessContext {
+    fn plugin_api(&self) -> PluginApi {
+        PluginApi::Standalone
+    }
+
+    fn execute_background(&self, _task: Task) {}
+
+    fn execute_gui(&self, _task: Task) {}
+
+    fn transport(&self) -> &Transport {
+        &self.transport
+    }
+
+    fn next_event(&mut self) -> Option<MidiEvent> {
+        let event = self.events.get(self.event_index);
+        self.event_index += 1;
+        event.copied()
+    }
+
+    fn peek_event(&self) -> Option<&MidiEvent> {
+        self.events.get(self.event_index)
+    }
+
+    fn send_event(&mut self, _event: MidiEvent) {}
+
+    fn set_latency_samples(&self, _samples: u32) {}
+
+    fn set_current_voice_capacity(&self, _capacity: u32) {}
+}
+
+/// Point the settings at an empty directory of their own, so that the tests never read or write the
+/// user's real settings. Returns the directory. This has to happen before the settings are first
+/// used, so every plugin the tests create goes through [plugin].
+pub fn isolate_settings() -> &'static PathBuf {
+    static SETTINGS_DIR: OnceLock<PathBuf> = OnceLock::new();
+    SETTINGS_DIR.get_or_init(|| {
+        let dir = std::env::temp_dir().join(format!("nyasynth-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::env::set_var(SETTINGS_DIR_VAR, &dir);
+        dir
+    })
+}
+
+/// Create a plugin which uses the tests' own settings.
+pub fn plugin() -> Nyasynth {
+    isolate_settings();
+    Nyasynth::default()
+}
+
+/// Create a plugin, initialized for buffers of up to `max_buffer_size` samples.
+pub fn initialized_plugin(max_buffer_size: usize) -> Nyasynth {
+    let mut nyasynth = plugin();
+    let buffer_config = BufferConfig {
+        sample_rate: SAMPLE_RATE,
+        min_buffer_size: None,
+        max_buffer_size: max_buffer_size as u32,
+        process_mode: ProcessMode::Offline,
+    };
+    nyasynth.initialize(
+        &Nyasynth::AUDIO_IO_LAYOUTS[0],
+        &buffer_config,
+        &mut TestContext,
+    );
+    nyasynth.reset();
+    nyasynth
+}
+
+/// Process one buffer, with the given events. The events are handed to the plugin in the order
+/// given, which should be sorted by timing unless a test is checking how unsorted events are handled.
+pub fn process(
+    nyasynth: &mut Nyasynth,
+    events: Vec<MidiEvent>,
+    left: &mut [f32],
+    right: &mut [f32],
+) {
+    process_at(nyasynth, events, SAMPLE_RATE, left, right);
+}
+
+/// Like [process], but with the host running at the given sample rate. The plugin is expected to
+/// notice when the sample rate changes between buffers.
+pub fn process_at(
+    nyasynth: &mut Nyasynth,
+    events: Vec<MidiEvent>,
+    sample_rate: f32,
+    left: &mut [f32],
+    right: &mut [f32],
+) {
+    let mut transport = Transport::new(sample_rate);
+    transport.tempo = Some(120.0);
+    let mut context = TestProcessContext {
+        events,
+        event_index: 0,
+        transport,
+    };
+
+    let num_samples = left.len();
+    let mut buffer = Buffer::default();
+    unsafe {
+        buffer.set_slices(num_samples, |output_slices| {
+            *output_slices = vec![left, right];
+        });
+    }
+    let mut aux = AuxiliaryBuffers {
+        inputs: &mut [],
+        outputs: &mut [],
+    };
+    nyasynth.process(&mut buffer, &mut aux, &mut context);
+}
+
+/// Process one buffer of `buffer_size` samples with the given events, returning the left and right
+/// channels.
+pub fn play(
+    nyasynth: &mut Nyasynth,
+    events: Vec<MidiEvent>,
+    buffer_size: usize,
+) -> (Vec<f32>, Vec<f32>) {
+    let mut left = vec![0.0; buffer_size];
+    let mut right = vec![0.0; buffer_size];
+    process(nyasynth, events, &mut left, &mut right);
+    (left, right)
+}
+
+pub fn note_on(timing: u32, note: u8) -> MidiEvent {
+    NoteEvent::NoteOn {
+        timing,
+        voice_id: None,
+        channel: 0,
+        note,
+        velocity: 0.8,
+    }
+}
+
+pub fn note_off(timing: u32, note: u8) -> MidiEvent {
+    NoteEvent::NoteOff {
+        timing,
+        voice_id: None,
+        channel: 0,
+        note,
+        velocity: 0.5,
+    }
+}
+
+/// Set a parameter by its ID to the given normalized value, as the host would.
+pub fn set_param(nyasynth: &Nyasynth, id: &str, normalized: f32) {
+    let param_map = nyasynth.params().param_map();
+    let (_, param, _) = param_map
+        .iter()
+        .find(|(param_id, _, _)| param_id == id)
+        .unwrap_or_else(|| panic!("No parameter with the ID {}", id));
+    unsafe { param.set_normalized_value(normalized) };
+}
@@ -0,0 +1,163 @@
+//! Checks the decibel conversions, and that states saved with the old half-size decibels keep
+//! their levels when loaded.
+
+mod common;
+
+use std::collections::BTreeMap;
+
+use common::{note_off, note_on};
+use nih_plug::prelude::*;
+use nih_plug::wrapper::state::ParamValue;
+use nyasynth::common::{DbTaper, Decibel};
+use nyasynth::Nyasynth;
+
+const BUFFER_SIZE: usize = 512;
+
+#[test]
+fn amplitude_conversions() {
+    // Halving the amplitude is about -6 dB, and doubling it is about +6 dB.
+    assert!((Decibel::from_amp(0.5).get_db() + 6.0206).abs() < 1.0e-3);
+    assert!((Decibel::from_amp(2.0).get_db() - 6.0206).abs() < 1.0e-3);
+    assert!((Decibel::from_amp(0.1).get_db() + 20.0).abs() < 1.0e-3);
+    assert!((Decibel::from_db(-20.0).get_amp() - 0.1).abs() < 1.0e-6);
+    assert_eq!(Decibel::zero_db().get_amp(), 1.0);
+    for db in [-60.0, -24.0, -6.0, 0.0, 3.0, 12.0] {
+        let round_trip = Decibel::from_amp(Decibel::from_db(db).get_amp()).get_db();
+        assert!(
+            (round_trip - db).abs() < 1.0e-3,
+            "{} dB came back as {}",
+            db,
+            round_trip
+        );
+    }
+}
+
+#[test]
+fn old_states_keep_their_levels() {
+    let old_values = [
+        ("gain", -6.0),
+        ("meow_sustain", -15.0),
+        ("comp_threshold", -12.0),
+    ];
+    let mut state = PluginState {
+        version: "1.3".to_string(),
+        params: old_values
+            .iter()
+            .map(|(id, db)| (id.to_string(), ParamValue::F32(*db)))
+            .collect(),
+        fields: BTreeMap::new(),
+    };
+    Nyasynth::filter_state(&mut state);
+    for (id, old_db) in old_values {
+        // The old decibels were 10 * log10 of the amplitude.
+        let old_amp = 10.0f32.powf(old_db / 10.0);
+        let Some(ParamValue::F32(db)) = state.params.get(id) else {
+            panic!("{} is missing from the migrated state", id);
+        };
+        let amp = Decibel::from_db(*db).get_amp();
+        assert!((amp - old_amp).abs() < 1.0e-6, "{} changed level", id);
+    }
+}
+
+#[test]
+fn releases_keep_their_shape() {
+    // Releases lerp in decibels down to the silence floor, so the floor has to be twice as far down
+    // as it was on the old half-size scale for the tail to fade out the same way.
+    let old_floor = -70.0;
+    for old_db in [-3.0, -15.0, -40.0] {
+        for i in 0..=100 {
+            let t = i as f32 / 100.0;
+            let old_amp = if t == 1.0 {
+                0.0
+            } else {
+                10.0f32.powf((old_db + (old_floor - old_db) * t) / 10.0)
+            };
+            let start = Decibel::from_db(2.0 * old_db);
+            let amp = Decibel::lerp_db(start.get_db(), Decibel::neg_inf_db().get_db(), t).get_amp();
+            assert!(
+                (amp - old_amp).abs() < 1.0e-6,
+                "A release from {} dB changed level at {}",
+                old_db,
+                t
+            );
+        }
+    }
+}
+
+/// Load the state into a new plugin, then play a note and release it, returning the loudness of
+/// each buffer.
+fn render_release(mut state: PluginState) -> Vec<f32> {
+    Nyasynth::filter_state(&mut state);
+    let mut nyasynth = common::initialized_plugin(BUFFER_SIZE);
+    let param_map = nyasynth.params().param_map();
+    for (id, value) in &state.params {
+        let ParamValue::F32(value) = value else {
+            continue;
+        };
+        let (_, param, _) = param_map
+            .iter()
+            .find(|(param_id, _, _)| param_id == id)
+            .unwrap();
+        unsafe { param.set_normalized_value(param.preview_normalized(*value)) };
+    }
+    let mut events = vec![vec![note_on(0, 60)]];
+    events.extend(std::iter::repeat(vec![]).take(20));
+    events.push(vec![note_off(0, 60)]);
+    events.extend(std::iter::repeat(vec![]).take(100));
+    events
+        .into_iter()
+        .map(|events| {
+            let (left, right) = common::play(&mut nyasynth, events, BUFFER_SIZE);
+            let sum: f32 = left.iter().chain(right.iter()).map(|x| x * x).sum();
+            (sum / (2 * BUFFER_SIZE) as f32).sqrt()
+        })
+        .collect()
+}
+
+#[test]
+fn old_release_tails_are_unchanged() {
+    let state = |version: &str, scale: f32| PluginState {
+        version: version.to_string(),
+        params: [("gain", -6.0), ("meow_sustain", -15.0)]
+            .iter()
+            .map(|(id, db)| (id.to_string(), ParamValue::F32(db * scale)))
+            .chain([("meow_release".to_string(), ParamValue::F32(1.0))])
+            .collect(),
+        fields: BTreeMap::new(),
+    };
+    // The same sound, saved before and after the decibels were fixed.
+    let old = render_release(state("1.3", 1.0));
+    let new = render_release(state(Nyasynth::VERSION, 2.0));
+    for (i, (old, new)) in old.iter().zip(new.iter()).enumerate() {
+        assert!(
+            (old - new).abs() <= 0.05 * new + 1.0e-4,
+            "Buffer {} is {} after migrating, but should be {}",
+            i,
+            old,
+            new
+        );
+    }
+    // The release fades out completely.
+    assert!(*old.last().unwrap() < 1.0e-4);
+}
+
+#[test]
+fn tapers_fade_to_silence() {
+    let taper = DbTaper::new(-60.0, 0.25);
+    let split_level = Decibel::from_db(-40.0);
+    // Above the split point, the level is left alone.
+    let level = taper.apply(0.5, Decibel::from_db(-12.0), split_level);
+    assert_eq!(level.get_db(), -12.0);
+    // Below it, the level fades from the split level down to the threshold, which is silent.
+    let level = taper.apply(0.125, Decibel::from_db(-45.0), split_level);
+    assert!((level.get_db() + 50.0).abs() < 1.0e-4);
+    assert_eq!(
+        taper
+            .apply(0.0, Decibel::from_db(-48.0), split_level)
+            .get_amp(),
+        0.0
+    );
+    // Without a taper, only levels below the usual threshold are silent.
+    let level = DbTaper::NONE.apply(0.0, Decibel::from_db(-65.0), split_level);
+    assert_eq!(level.get_db(), -65.0);
+}
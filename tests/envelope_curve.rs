@@ -0,0 +1,49 @@
+//! Checks the analog envelope curve's RC discharge.
+
+use nyasynth::{
+    common::Seconds,
+    dsp::{EnvelopeCurve, VolumeEnvelopeParams},
+};
+
+#[test]
+fn analog_segments_discharge_with_the_time_constant() {
+    let length = Seconds::new(1.0);
+    assert_eq!(
+        EnvelopeCurve::analog_remaining(Seconds::ZERO, length, 5.0),
+        1.0
+    );
+    // Away from the final fade, each time constant leaves 1/e of what was left.
+    for time_constants in [1.0, 5.0, 10.0] {
+        let remaining = EnvelopeCurve::analog_remaining(Seconds::new(0.5), length, time_constants);
+        let expected = (-time_constants * 0.5f32).exp();
+        assert!(
+            (remaining - expected).abs() < 1e-6,
+            "{remaining} != {expected}"
+        );
+    }
+}
+
+#[test]
+fn analog_segments_reach_the_end_level() {
+    for length in [0.001, 0.01, 1.0] {
+        let length = Seconds::new(length);
+        let mut last = 1.0;
+        for i in 0..=100 {
+            let time = length * (i as f32 / 100.0);
+            let remaining = EnvelopeCurve::analog_remaining(time, length, 1.0);
+            assert!(remaining <= last, "the curve went back up at {i}%");
+            last = remaining;
+        }
+        assert_eq!(last, 0.0);
+    }
+}
+
+#[test]
+fn volume_levels_round_trip_through_amplitude() {
+    for i in 0..=100 {
+        let level = i as f32 / 100.0;
+        let amp = VolumeEnvelopeParams::volume_to_amp(level);
+        let round_trip = VolumeEnvelopeParams::amp_to_volume(amp);
+        assert!((round_trip - level).abs() < 1e-5, "{level} -> {round_trip}");
+    }
+}
@@ -0,0 +1,75 @@
+//! Checks that oversampling the growl's saturator cuts down on aliasing.
+
+use std::f32::consts::TAU;
+
+use nyasynth::{
+    common::{Hertz, SampleRate},
+    dsp::{Growl, GrowlParams, GrowlTarget},
+};
+
+const SAMPLE_RATE: SampleRate = SampleRate(44100.0);
+
+/// The level of the given frequency in the signal.
+fn level_at(signal: &[f32], freq: f32) -> f32 {
+    let (re, im) = signal
+        .iter()
+        .enumerate()
+        .fold((0.0, 0.0), |(re, im), (i, sample)| {
+            let phase = TAU * freq * i as f32 / SAMPLE_RATE.get();
+            (re + sample * phase.cos(), im + sample * phase.sin())
+        });
+    (re * re + im * im).sqrt() / signal.len() as f32
+}
+
+/// Saturate a loud 15 kHz sine at full growl. The saturator's third harmonic, at 45 kHz, is past
+/// Nyquist and folds back down to 900 Hz.
+fn saturated_sine(oversampling: usize) -> Vec<f32> {
+    let params = GrowlParams {
+        amount: 1.0,
+        speed: Hertz(0.0),
+        target: GrowlTarget::Filter,
+        aftertouch: 0.0,
+    };
+    let mut growl = Growl::new();
+    (0..SAMPLE_RATE.get() as usize)
+        .map(|i| {
+            growl.next_sample(&params, 0.0, SAMPLE_RATE);
+            let input = (TAU * 15000.0 * i as f32 / SAMPLE_RATE.get()).sin();
+            growl.saturate(input, input, oversampling).0
+        })
+        .collect()
+}
+
+#[test]
+fn oversampling_reduces_aliasing() {
+    let plain = level_at(&saturated_sine(1), 900.0);
+    let oversampled = level_at(&saturated_sine(4), 900.0);
+    assert!(
+        oversampled < plain / 2.0,
+        "aliasing at 900 Hz: {} without oversampling, {} with",
+        plain,
+        oversampled
+    );
+}
+
+#[test]
+fn oversampling_leaves_low_notes_alone() {
+    // A low note's harmonics are far below Nyquist, so oversampling shouldn't change it beyond
+    // the interpolation's slight delay.
+    let params = GrowlParams {
+        amount: 1.0,
+        speed: Hertz(0.0),
+        target: GrowlTarget::Filter,
+        aftertouch: 0.0,
+    };
+    let mut plain = Growl::new();
+    let mut oversampled = Growl::new();
+    for i in 0..4410 {
+        plain.next_sample(&params, 0.0, SAMPLE_RATE);
+        oversampled.next_sample(&params, 0.0, SAMPLE_RATE);
+        let input = (TAU * 110.0 * i as f32 / SAMPLE_RATE.get()).sin();
+        let (plain, _) = plain.saturate(input, input, 1);
+        let (oversampled, _) = oversampled.saturate(input, input, 4);
+        assert!((plain - oversampled).abs() < 0.05, "sample {}", i);
+    }
+}
@@ -0,0 +1,71 @@
+//! Checks that 14-bit CC pairs and pitch bends keep their full resolution, whichever order the
+//! halves of a CC pair arrive in.
+
+use nyasynth::common::Pitchbend;
+use nyasynth::dsp::HiResCCs;
+
+// The CC value that nih-plug would hand the synth for a 7-bit value.
+fn cc_value(raw: u8) -> f32 {
+    raw as f32 / 127.0
+}
+
+fn fourteen_bit(msb: u8, lsb: u8) -> f32 {
+    (((msb as u16) << 7) | lsb as u16) as f32 / 16383.0
+}
+
+#[test]
+fn msb_then_lsb() {
+    let mut ccs = HiResCCs::new();
+    assert_eq!(ccs.process(1, cc_value(64)), (1, fourteen_bit(64, 0)));
+    assert_eq!(ccs.process(33, cc_value(37)), (1, fourteen_bit(64, 37)));
+}
+
+#[test]
+fn lsb_then_msb() {
+    let mut ccs = HiResCCs::new();
+    ccs.process(33, cc_value(37));
+    // The LSB which came first is kept, rather than being reset by the MSB.
+    assert_eq!(ccs.process(1, cc_value(64)), (1, fourteen_bit(64, 37)));
+    // The next pair arrives in the same order.
+    ccs.process(33, cc_value(5));
+    assert_eq!(ccs.process(1, cc_value(65)), (1, fourteen_bit(65, 5)));
+}
+
+#[test]
+fn msb_only() {
+    // A controller which only sends the MSB gets the plain 7-bit value.
+    let mut ccs = HiResCCs::new();
+    assert_eq!(ccs.process(7, cc_value(100)), (7, fourteen_bit(100, 0)));
+    assert_eq!(ccs.process(7, cc_value(127)), (7, fourteen_bit(127, 0)));
+    // An MSB resets the LSB of a previous pair.
+    ccs.process(39, cc_value(90));
+    ccs.process(7, cc_value(10));
+    assert_eq!(ccs.process(7, cc_value(11)), (7, fourteen_bit(11, 0)));
+}
+
+#[test]
+fn other_ccs_are_unchanged() {
+    let mut ccs = HiResCCs::new();
+    assert_eq!(ccs.process(74, 0.25), (74, 0.25));
+}
+
+#[test]
+fn pitch_bends_keep_every_step() {
+    // The center is exactly no bend, and the ends are exactly the full bend.
+    assert_eq!(Pitchbend::from_zero_one_range(8192.0 / 16383.0).get(), 0.0);
+    assert_eq!(Pitchbend::from_zero_one_range(0.5).get(), 0.0);
+    assert_eq!(Pitchbend::from_zero_one_range(0.0).get(), -1.0);
+    assert_eq!(Pitchbend::from_zero_one_range(1.0).get(), 1.0);
+    // Every 14-bit value comes through as a distinct bend.
+    let mut last = -2.0;
+    for raw in 0..16384u16 {
+        let bend = Pitchbend::from_zero_one_range(raw as f32 / 16383.0).get();
+        assert_eq!(bend, Pitchbend::from_14_bit(raw).get());
+        assert!(
+            bend > last,
+            "{} doesn't bend further than the value before it",
+            raw
+        );
+        last = bend;
+    }
+}
@@ -0,0 +1,69 @@
+//! Plays keyswitches and checks that the articulation switches at the next note, and that the
+//! keyswitches themselves don't make a sound.
+
+mod common;
+
+use common::{note_off, note_on, set_param, MidiEvent};
+use nih_plug::prelude::*;
+use nyasynth::Nyasynth;
+
+const BUFFER_SIZE: usize = 256;
+
+// The keyswitches in the default octave (C0), and a note to play with them.
+const LONG_MEOW: u8 = 14; // D0
+const HISS: u8 = 17; // F0
+const NOTE: u8 = 60;
+
+fn hiss_mode(nyasynth: &Nyasynth) -> bool {
+    let param_map = nyasynth.params().param_map();
+    let (_, param, _) = param_map
+        .iter()
+        .find(|(param_id, _, _)| param_id == "hiss_mode")
+        .unwrap();
+    unsafe { param.modulated_normalized_value() > 0.5 }
+}
+
+/// Process one buffer, returning the loudest sample.
+fn play(nyasynth: &mut Nyasynth, events: Vec<MidiEvent>) -> f32 {
+    let (left, right) = common::play(nyasynth, events, BUFFER_SIZE);
+    left.iter()
+        .chain(right.iter())
+        .fold(0.0, |peak, sample| peak.max(sample.abs()))
+}
+
+#[test]
+fn keyswitches_are_silent() {
+    let mut nyasynth = common::initialized_plugin(BUFFER_SIZE);
+    set_param(&nyasynth, "keyswitches", 1.0);
+    let peak = play(&mut nyasynth, vec![note_on(0, HISS)]);
+    assert_eq!(peak, 0.0);
+    let peak = play(&mut nyasynth, vec![note_off(0, HISS)]);
+    assert_eq!(peak, 0.0);
+}
+
+#[test]
+fn articulation_switches_at_the_next_note() {
+    let mut nyasynth = common::initialized_plugin(BUFFER_SIZE);
+    set_param(&nyasynth, "keyswitches", 1.0);
+    play(&mut nyasynth, vec![note_on(0, HISS), note_off(1, HISS)]);
+    assert!(!hiss_mode(&nyasynth), "Switched before the next note");
+
+    play(&mut nyasynth, vec![note_on(0, NOTE)]);
+    assert!(hiss_mode(&nyasynth));
+
+    play(
+        &mut nyasynth,
+        vec![note_off(0, NOTE), note_on(1, LONG_MEOW)],
+    );
+    play(&mut nyasynth, vec![note_on(0, NOTE)]);
+    assert!(!hiss_mode(&nyasynth));
+}
+
+#[test]
+fn keyswitches_play_notes_when_turned_off() {
+    let mut nyasynth = common::initialized_plugin(BUFFER_SIZE);
+    play(&mut nyasynth, vec![note_on(0, HISS)]);
+    let peak = play(&mut nyasynth, vec![]);
+    assert!(peak > 0.0);
+    assert!(!hiss_mode(&nyasynth));
+}
@@ -0,0 +1,42 @@
+//! Checks that the MIDI mappings saved with projects from before 1.3 are adopted as the global
+//! mappings, without writing the settings file while the project is loading.
+
+mod common;
+
+use std::collections::BTreeMap;
+
+use nih_plug::prelude::*;
+use nyasynth::dsp::{MidiMappings, Settings};
+use nyasynth::Nyasynth;
+
+#[test]
+fn old_project_mappings_are_adopted_in_memory() {
+    let settings_dir = common::isolate_settings();
+    let mappings = MidiMappings {
+        ccs: vec![(74, "filter_cutoff_freq".to_string())],
+        curves: vec![],
+    };
+    let mut state = PluginState {
+        version: "1.2".to_string(),
+        params: BTreeMap::new(),
+        fields: BTreeMap::from([(
+            "midi_mappings".to_string(),
+            serde_json::to_string(&mappings).unwrap(),
+        )]),
+    };
+    Nyasynth::filter_state(&mut state);
+    assert!(!state.fields.contains_key("midi_mappings"));
+
+    let settings = Settings::get();
+    assert_eq!(*settings.midi_mappings.read().unwrap(), mappings);
+    let settings_file = settings_dir.join("settings.json");
+    assert!(
+        !settings_file.exists(),
+        "The settings were saved while the project was loading"
+    );
+
+    // The editor saves them the next time it updates.
+    settings.save_if_needed();
+    let saved = std::fs::read_to_string(&settings_file).unwrap();
+    assert!(saved.contains("filter_cutoff_freq"));
+}
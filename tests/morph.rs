@@ -0,0 +1,41 @@
+//! Checks that the morph knob matches up the two snapshots by parameter ID.
+
+use nyasynth::dsp::{MorphSnapshots, Snapshot};
+
+fn snapshot(values: &[(&str, f32)]) -> Snapshot {
+    Snapshot {
+        values: values
+            .iter()
+            .map(|(id, value)| (id.to_string(), *value))
+            .collect(),
+    }
+}
+
+#[test]
+fn morphs_between_matching_parameters() {
+    // The second snapshot is missing a parameter and has one the first doesn't, so the values
+    // don't line up by position.
+    let snapshots = MorphSnapshots {
+        a: Some(snapshot(&[
+            ("meow_attack", 0.0),
+            ("noise_mix", 0.2),
+            ("gain", 0.5),
+        ])),
+        b: Some(snapshot(&[
+            ("meow_attack", 1.0),
+            ("gain", 0.7),
+            ("chorus_mix", 1.0),
+        ])),
+    };
+    let morphed: Vec<(&str, f32)> = snapshots.morph(0.5).collect();
+    assert_eq!(morphed, vec![("meow_attack", 0.5), ("gain", 0.6)]);
+}
+
+#[test]
+fn morph_needs_both_snapshots() {
+    let snapshots = MorphSnapshots {
+        a: Some(snapshot(&[("gain", 0.5)])),
+        b: None,
+    };
+    assert_eq!(snapshots.morph(0.5).count(), 0);
+}
@@ -0,0 +1,103 @@
+//! Floods the synth with notes. The audio thread asserts that it never allocates in debug builds,
+//! so these tests fail if a burst of notes makes the voices or the held keys outgrow their storage.
+
+mod common;
+
+use common::{note_off, note_on, set_param, MidiEvent};
+use nih_plug::prelude::NoteEvent;
+use nyasynth::Nyasynth;
+
+const BUFFER_SIZE: usize = 256;
+
+/// Play the events, then render a few more buffers so the voices can be released and cleaned up.
+fn play(nyasynth: &mut Nyasynth, buffers: Vec<Vec<MidiEvent>>) {
+    for events in buffers.into_iter().chain(std::iter::repeat(vec![]).take(8)) {
+        let (left, right) = common::play(nyasynth, events, BUFFER_SIZE);
+        assert!(
+            left.iter()
+                .chain(right.iter())
+                .all(|sample| sample.is_finite()),
+            "Non-finite output"
+        );
+    }
+}
+
+// Every MIDI note at once, then every note released at once.
+fn all_notes_at_once() -> Vec<Vec<MidiEvent>> {
+    vec![
+        (0..128).map(|note| note_on(0, note)).collect(),
+        (0..128).map(|note| note_off(0, note)).collect(),
+    ]
+}
+
+// Every MIDI note, one sample apart, with each note released as the next one starts.
+fn all_notes_in_a_row() -> Vec<Vec<MidiEvent>> {
+    let events = (0..128u8)
+        .flat_map(|note| {
+            let timing = note as u32;
+            let off = note
+                .checked_sub(1)
+                .map(|previous| note_off(timing, previous));
+            off.into_iter().chain(Some(note_on(timing, note)))
+        })
+        .chain(Some(note_off(128, 127)))
+        .collect();
+    vec![events]
+}
+
+#[test]
+fn polycat_note_storm() {
+    let mut nyasynth = common::initialized_plugin(BUFFER_SIZE);
+    set_param(&nyasynth, "polycat", 1.0);
+    set_param(&nyasynth, "max_voices", 1.0);
+    play(&mut nyasynth, all_notes_at_once());
+    play(&mut nyasynth, all_notes_in_a_row());
+}
+
+#[test]
+fn monocat_note_storm() {
+    let mut nyasynth = common::initialized_plugin(BUFFER_SIZE);
+    set_param(&nyasynth, "polycat", 0.0);
+    play(&mut nyasynth, all_notes_at_once());
+    play(&mut nyasynth, all_notes_in_a_row());
+}
+
+#[test]
+fn repeated_note_storm() {
+    // The same key pressed over and over, without ever being released.
+    let mut nyasynth = common::initialized_plugin(BUFFER_SIZE);
+    let events = (0..128).map(|timing| note_on(timing, 60)).collect();
+    play(&mut nyasynth, vec![events, vec![note_off(0, 60)]]);
+}
+
+#[test]
+fn note_offs_survive_a_cc_storm() {
+    // More CCs than the synth can queue at once, with the note offs at the very end. The CCs are
+    // dropped to make room, rather than the note offs, so no notes are left hanging.
+    let mut nyasynth = common::initialized_plugin(BUFFER_SIZE);
+    let notes = 60..64;
+    common::play(
+        &mut nyasynth,
+        notes.clone().map(|note| note_on(0, note)).collect(),
+        BUFFER_SIZE,
+    );
+    let storm = (0..5000).map(|i| NoteEvent::MidiCC {
+        timing: 0,
+        channel: 0,
+        cc: 1,
+        value: (i % 128) as f32 / 127.0,
+    });
+    let events = storm.chain(notes.map(|note| note_off(1, note))).collect();
+    common::play(&mut nyasynth, events, BUFFER_SIZE);
+    // Give the release plenty of time to finish.
+    let mut last = (vec![], vec![]);
+    for _ in 0..400 {
+        last = common::play(&mut nyasynth, vec![], BUFFER_SIZE);
+    }
+    let peak = last
+        .0
+        .iter()
+        .chain(last.1.iter())
+        .fold(0.0f32, |peak, x| peak.max(x.abs()));
+    assert!(peak < 1e-3, "a note was left hanging: {peak}");
+}
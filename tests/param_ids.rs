@@ -0,0 +1,139 @@
+//! Guards the parameter IDs. The IDs are what the plugin state, host automation, CLAP and VST3
+//! parameter IDs, presets, and MIDI learn mappings refer to, so the parameters can be reordered or
+//! regrouped freely, but an ID must never change once it has been released.
+
+use nih_plug::prelude::*;
+use nyasynth::Nyasynth;
+
+/// Every parameter ID that has been released. If a parameter really has to be renamed or removed,
+/// add a migration to `state.rs` that moves its saved value, rather than editing this list.
+/// (`filter_envlope_mod` is misspelled, but renaming it would break old sessions.)
+const RELEASED_IDS: &[&str] = &[
+    "meow_attack",
+    "meow_decay",
+    "meow_sustain",
+    "meow_release",
+    "meow_sustain_fade",
+    "envelope_tempo_sync",
+    "meow_attack_sync",
+    "meow_decay_sync",
+    "meow_release_sync",
+    "envelope_keytrack",
+    "attack_keytrack",
+    "vel_to_attack",
+    "vel_to_decay",
+    "envelope_cc_output",
+    "amp_envelope_cc",
+    "filter_envelope_cc",
+    "envelope_cc_channel",
+    "vol_env_curve",
+    "filter_env_curve",
+    "vol_env_time_constants",
+    "filter_env_time_constants",
+    "vibrato_amount",
+    "vibrato_attack",
+    "vibrato_rate",
+    "vibrato_note_shape",
+    "vibrato_mode",
+    "trill_range",
+    "filter_envlope_mod",
+    "filter_dry_wet",
+    "filter_mix_law",
+    "filter_pre_gain",
+    "filter_post_gain",
+    "filter_release_vel",
+    "filter_env_vel",
+    "filter_q",
+    "filter_q_compensation",
+    "filter_q_limiter",
+    "filter_self_osc",
+    "filter_keytrack",
+    "filter_fm",
+    "filter_type",
+    "filter_cutoff_freq",
+    "filter_vintage_cap",
+    "filter_routing",
+    "filter_balance",
+    "filter_split_freq",
+    "filter_2_type",
+    "filter_2_cutoff_freq",
+    "filter_2_q",
+    "chorus_mix",
+    "chorus_depth",
+    "chorus_distance",
+    "chorus_rate",
+    "chorus_quality",
+    "chorus_voices",
+    "chorus_note_shape",
+    "supermeow_voices",
+    "supermeow_detune",
+    "supermeow_blend",
+    "resonator_mix",
+    "resonator_damping",
+    "follower_attack",
+    "follower_release",
+    "follower_to_noise",
+    "follower_to_filter",
+    "comp_threshold",
+    "comp_ratio",
+    "comp_attack",
+    "comp_release",
+    "comp_makeup",
+    "stereo_width",
+    "mono_bass",
+    "mono_bass_freq",
+    "portamento_time",
+    "portamento_mode",
+    "noise_mix",
+    "noise_width",
+    "pitch_bend",
+    "pitch_bend_smoothing",
+    "mpe_zone",
+    "mpe_bend_range",
+    "polycat",
+    "max_voices",
+    "midi_out",
+    "hires_cc",
+    "scale",
+    "scale_root",
+    "stray_cat",
+    "stray_cat_quantize",
+    "retrigger_fade",
+    "quality",
+    "gain",
+    "macro_1",
+    "macro_2",
+    "macro_3",
+    "macro_4",
+    "morph",
+    "next_preset",
+    "previous_preset",
+];
+
+#[test]
+fn released_ids_still_exist() {
+    let nyasynth = Nyasynth::default();
+    let param_map = nyasynth.params().param_map();
+    for id in RELEASED_IDS {
+        assert!(
+            param_map.iter().any(|(param_id, _, _)| param_id == id),
+            "The parameter ID {:?} was removed or renamed",
+            id
+        );
+    }
+}
+
+#[test]
+fn ids_are_unique() {
+    let nyasynth = Nyasynth::default();
+    let mut ids: Vec<String> = nyasynth
+        .params()
+        .param_map()
+        .into_iter()
+        .map(|(id, _, _)| id)
+        .collect();
+    let len = ids.len();
+    ids.sort();
+    ids.dedup();
+    assert_eq!(ids.len(), len, "Two parameters share an ID");
+}
@@ -0,0 +1,119 @@
+//! Checks that the parameter help table stays in step with the parameters themselves. The help
+//! table is written by hand, so it's easy to forget to update it when a parameter is added, or to
+//! give an entry details which don't fit its parameter.
+
+mod common;
+
+use nih_plug::prelude::*;
+use nyasynth::common::{DbTaper, Decibel};
+use nyasynth::param_info::{param_info, Detent, PARAM_INFO};
+
+#[test]
+fn every_parameter_has_help_text() {
+    let nyasynth = common::plugin();
+    let ids: Vec<String> = nyasynth
+        .params()
+        .param_map()
+        .into_iter()
+        .map(|(id, _, _)| id)
+        .collect();
+    let info_ids: Vec<&str> = PARAM_INFO.iter().map(|info| info.id).collect();
+    // The table is in the same order as the parameters, so this also catches duplicates.
+    assert_eq!(ids, info_ids);
+}
+
+#[test]
+fn help_text_is_filled_in() {
+    for info in PARAM_INFO {
+        assert!(
+            !info.description.trim().is_empty(),
+            "{} has no description",
+            info.id
+        );
+    }
+}
+
+#[test]
+fn decibel_tapers_are_in_range() {
+    for info in PARAM_INFO {
+        let taper = info.db_taper;
+        assert!(
+            taper.neg_inf_db >= Decibel::NEG_INF_DB_THRESHOLD,
+            "{} goes silent below the -inf threshold",
+            info.id
+        );
+        assert!(
+            (0.0..1.0).contains(&taper.split_at),
+            "{} has a split point outside of the knob",
+            info.id
+        );
+    }
+}
+
+#[test]
+fn center_detents_are_halfway() {
+    // On a skewed range, the center of the knob isn't halfway between the ends of the range, so
+    // those parameters need their detent at a plain value instead.
+    let nyasynth = common::plugin();
+    let param_map = nyasynth.params().param_map();
+    for (id, param, _) in &param_map {
+        let Some(Detent::Center) = param_info(id).and_then(|info| info.detent) else {
+            continue;
+        };
+        let (bottom, center, top) = unsafe {
+            (
+                param.preview_plain(0.0),
+                param.preview_plain(0.5),
+                param.preview_plain(1.0),
+            )
+        };
+        assert!(
+            (center - (bottom + top) / 2.0).abs() <= 1.0e-4 * (top - bottom),
+            "{} has a center detent, but its center is {} on a range from {} to {}",
+            id,
+            center,
+            bottom,
+            top
+        );
+    }
+}
+
+#[test]
+fn detents_are_in_range() {
+    let nyasynth = common::plugin();
+    for (id, param, _) in &nyasynth.params().param_map() {
+        let Some(Detent::At(plain)) = param_info(id).and_then(|info| info.detent) else {
+            continue;
+        };
+        let (normalized, round_trip) = unsafe {
+            let normalized = param.preview_normalized(plain);
+            (normalized, param.preview_plain(normalized))
+        };
+        assert!(
+            (0.0..=1.0).contains(&normalized) && (round_trip - plain).abs() < 1.0e-3,
+            "{} has a detent at {}, which is outside of its range",
+            id,
+            plain
+        );
+    }
+}
+
+#[test]
+fn decibel_tapers_are_on_decibel_parameters() {
+    let nyasynth = common::plugin();
+    for (id, param, _) in &nyasynth.params().param_map() {
+        let Some(info) = param_info(id) else {
+            continue;
+        };
+        if info.db_taper == DbTaper::NONE {
+            continue;
+        }
+        let unit = unsafe { param.unit() };
+        assert!(
+            unit.trim().eq_ignore_ascii_case("db"),
+            "{} has a decibel taper, but its unit is {:?}",
+            id,
+            unit
+        );
+    }
+}
@@ -0,0 +1,86 @@
+//! Checks the semitone and cent helpers on pitches and frequencies against known intervals.
+
+use nyasynth::common::{Hertz, Note, Pitch};
+
+// How far apart two frequencies may be, in cents, and still count as the same.
+const CENTS_TOLERANCE: f32 = 0.01;
+
+fn assert_same_hz(actual: Hertz, expected: f32) {
+    let cents = Hertz::new(expected).interval_to(actual) * 100.0;
+    assert!(
+        cents.abs() < CENTS_TOLERANCE,
+        "expected {} Hz, got {} Hz",
+        expected,
+        actual.get()
+    );
+}
+
+#[test]
+fn transposing_hertz() {
+    let a4 = Hertz::new(440.0);
+    assert_same_hz(a4.transpose_semitones(12.0), 880.0);
+    assert_same_hz(a4.transpose_semitones(-12.0), 220.0);
+    assert_same_hz(a4.transpose_semitones(3.0), 523.2511);
+    assert_same_hz(a4.transpose_cents(1200.0), 880.0);
+    assert_same_hz(a4.transpose_cents(-100.0), 415.3047);
+    assert_same_hz(a4.transpose_semitones(0.0), 440.0);
+}
+
+#[test]
+fn transposing_pitch() {
+    let a4 = Pitch::from_note(Note(69));
+    assert_same_hz(a4.transpose_semitones(7.0).into_hertz(), 659.2551);
+    assert_same_hz(a4.transpose_cents(-1200.0).into_hertz(), 220.0);
+    // Transposing by a whole number of semitones lands on the MIDI note that many semitones away.
+    for semitones in -24..=24 {
+        let transposed = a4.transpose_semitones(semitones as f32).into_hertz();
+        let note = Pitch::from_note(Note((69 + semitones) as u8)).into_hertz();
+        assert_same_hz(transposed, note.get());
+    }
+}
+
+#[test]
+fn intervals() {
+    let c4 = Pitch::from_note(Note(60));
+    let g4 = Pitch::from_note(Note(67));
+    assert!((c4.interval_to(g4) - 7.0).abs() < 1.0e-4);
+    assert!((g4.interval_to(c4) + 7.0).abs() < 1.0e-4);
+    assert_eq!(c4.interval_to(c4), 0.0);
+
+    let a4 = Hertz::new(440.0);
+    assert!((a4.interval_to(Hertz::new(880.0)) - 12.0).abs() < 1.0e-4);
+    assert!((a4.interval_to(Hertz::new(220.0)) + 12.0).abs() < 1.0e-4);
+}
+
+#[test]
+fn transposing_undoes_the_interval() {
+    let pitches = [27.5, 100.0, 261.6256, 440.0, 1234.5, 4186.009].map(Hertz::new);
+    for from in pitches {
+        for to in pitches {
+            let interval = from.interval_to(to);
+            assert_same_hz(from.transpose_semitones(interval), to.get());
+            let from_pitch = Pitch::from_hertz(from);
+            let to_pitch = Pitch::from_hertz(to);
+            let interval = from_pitch.interval_to(to_pitch);
+            assert_same_hz(
+                from_pitch.transpose_semitones(interval).into_hertz(),
+                to.get(),
+            );
+        }
+    }
+}
+
+#[test]
+fn nearest_note() {
+    let (note, cents) = Hertz::new(440.0).to_note();
+    assert_eq!(note, Note(69));
+    assert!(cents.abs() < CENTS_TOLERANCE);
+
+    let (note, cents) = Hertz::new(440.0).transpose_cents(-30.0).to_note();
+    assert_eq!(note, Note(69));
+    assert!((cents + 30.0).abs() < CENTS_TOLERANCE);
+
+    let (note, cents) = Hertz::new(440.0).transpose_cents(70.0).to_note();
+    assert_eq!(note, Note(70));
+    assert!((cents + 30.0).abs() < CENTS_TOLERANCE);
+}
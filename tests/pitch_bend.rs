@@ -0,0 +1,64 @@
+//! Checks that the pitch bend envelope joins up consecutive blocks without jumps.
+
+use nyasynth::common::Pitchbend;
+
+const BLOCK_LEN: usize = 64;
+
+/// Render the envelope for one block, returning its samples and the value the next block starts
+/// from.
+fn block(points: &[(f32, i32)], start: f32) -> (Vec<f32>, f32) {
+    let points: Vec<(Pitchbend, i32)> = points
+        .iter()
+        .map(|(bend, time)| (Pitchbend::new(*bend), *time))
+        .collect();
+    let (envelope, end) = Pitchbend::to_pitch_envelope(&points, Pitchbend::new(start), BLOCK_LEN);
+    (envelope.map(|bend| bend.get()).collect(), end.get())
+}
+
+#[test]
+fn envelope_fills_the_block() {
+    for points in [vec![], vec![(0.5, 0)], vec![(0.1, 10), (0.3, 20)]] {
+        let (envelope, _) = block(&points, 0.0);
+        assert_eq!(envelope.len(), BLOCK_LEN);
+    }
+}
+
+#[test]
+fn no_jump_at_block_boundaries() {
+    // A bend moving steadily upwards, sent every 16 samples, split across two blocks.
+    let (first, end) = block(&[(0.08, 16), (0.16, 32), (0.24, 48)], 0.0);
+    let (second, _) = block(&[(0.32, 0), (0.4, 16)], end);
+    let step = 0.005;
+    let samples = first.iter().chain(second.iter()).collect::<Vec<_>>();
+    for (i, pair) in samples.windows(2).enumerate() {
+        assert!(
+            (pair[1] - pair[0] - step).abs() < 1.0e-4,
+            "The bend jumps from {} to {} at sample {}",
+            pair[0],
+            pair[1],
+            i
+        );
+    }
+}
+
+#[test]
+fn extrapolation_continues_the_bend() {
+    // The last two points are moving upwards, so the rest of the block keeps moving upwards.
+    let (envelope, end) = block(&[(0.1, 10), (0.2, 20)], 0.0);
+    assert!((envelope[BLOCK_LEN - 1] - 0.63).abs() < 1.0e-4);
+    assert!((end - 0.64).abs() < 1.0e-4);
+}
+
+#[test]
+fn extrapolation_is_clamped() {
+    let (envelope, end) = block(&[(0.5, 1), (1.0, 2)], 0.0);
+    assert_eq!(end, 1.0);
+    assert!(envelope.iter().all(|bend| (-1.0..=1.0).contains(bend)));
+}
+
+#[test]
+fn holds_without_new_bends() {
+    let (envelope, end) = block(&[], 0.25);
+    assert!(envelope.iter().all(|bend| *bend == 0.25));
+    assert_eq!(end, 0.25);
+}
@@ -0,0 +1,206 @@
+//! Property tests for the plugin's parameters and MIDI handling. These check invariants which
+//! should hold for any input, such as hosts being able to read back the parameter values they set,
+//! and the synth surviving whatever MIDI bytes it is sent.
+
+mod common;
+
+use nih_plug::prelude::*;
+use proptest::prelude::*;
+use proptest::sample::Index;
+
+use common::MidiEvent;
+use nyasynth::dsp::EventNormalizer;
+
+const BUFFER_SIZE: usize = 256;
+
+// How far apart two normalized values may be and still count as the same value. Converting between
+// normalized and plain values isn't exact in floating point.
+const NORMALIZED_TOLERANCE: f32 = 1.0e-6;
+// How long to render after every note is released. This is well past the default release time.
+const RELEASE_BUFFERS: usize = (2.0 * common::SAMPLE_RATE) as usize / BUFFER_SIZE;
+// The output counts as silent once it is quieter than this.
+const SILENCE: f32 = 1.0e-5;
+
+/// A note on or note off, with any channel and note number, including out of range ones.
+fn note_event() -> impl Strategy<Value = MidiEvent> {
+    (any::<bool>(), 0..BUFFER_SIZE as u32, 0u8..18, any::<u8>()).prop_map(
+        |(on, timing, channel, note)| {
+            if on {
+                NoteEvent::NoteOn {
+                    timing,
+                    voice_id: None,
+                    channel,
+                    note,
+                    velocity: 0.8,
+                }
+            } else {
+                NoteEvent::NoteOff {
+                    timing,
+                    voice_id: None,
+                    channel,
+                    note,
+                    velocity: 0.5,
+                }
+            }
+        },
+    )
+}
+
+proptest! {
+    /// Setting a parameter to the value that was read back from it doesn't change the value. Hosts
+    /// rely on this when saving and restoring automation.
+    #[test]
+    fn normalized_values_round_trip(index in any::<Index>(), normalized in 0.0f32..=1.0) {
+        let nyasynth = common::plugin();
+        let param_map = nyasynth.params().param_map();
+        let (id, param, _) = index.get(&param_map);
+
+        unsafe {
+            param.set_normalized_value(normalized);
+            let first = param.unmodulated_normalized_value();
+            prop_assert!((0.0..=1.0).contains(&first), "{}: read back {}", id, first);
+
+            param.set_normalized_value(first);
+            let second = param.unmodulated_normalized_value();
+            prop_assert!(
+                (first - second).abs() <= NORMALIZED_TOLERANCE,
+                "{}: {} became {}",
+                id,
+                first,
+                second
+            );
+        }
+    }
+
+    /// Typing a parameter's displayed value back in gives a value which displays the same way.
+    #[test]
+    fn formatted_values_round_trip(index in any::<Index>(), normalized in 0.0f32..=1.0) {
+        let nyasynth = common::plugin();
+        let param_map = nyasynth.params().param_map();
+        let (id, param, _) = index.get(&param_map);
+
+        unsafe {
+            for include_unit in [true, false] {
+                let string = param.normalized_value_to_string(normalized, include_unit);
+                // Not every string has to parse, but any that does should parse to the same value.
+                if let Some(parsed) = param.string_to_normalized_value(&string) {
+                    prop_assert!((0.0..=1.0).contains(&parsed), "{}: {:?} parsed to {}", id, string, parsed);
+                    let reformatted = param.normalized_value_to_string(parsed, include_unit);
+                    prop_assert_eq!(&string, &reformatted, "{}", id);
+                }
+            }
+        }
+    }
+
+    /// Arbitrary MIDI messages, including malformed and truncated ones, never panic the synth or
+    /// make it output garbage.
+    #[test]
+    fn arbitrary_midi_never_panics(
+        messages in prop::collection::vec(
+            (0..BUFFER_SIZE as u32, prop::collection::vec(any::<u8>(), 1..=3)),
+            0..64,
+        )
+    ) {
+        let mut events: Vec<MidiEvent> = messages
+            .iter()
+            .filter_map(|(timing, bytes)| NoteEvent::from_midi(*timing, bytes).ok())
+            .collect();
+        events.sort_by_key(|event| event.timing());
+
+        let mut nyasynth = common::initialized_plugin(BUFFER_SIZE);
+        let mut left = vec![0.0; BUFFER_SIZE];
+        let mut right = vec![0.0; BUFFER_SIZE];
+        // Render a few buffers after the events, so that any bad state has a chance to show up.
+        for buffer in 0..4 {
+            let events = if buffer == 0 { std::mem::take(&mut events) } else { vec![] };
+            left.fill(0.0);
+            right.fill(0.0);
+            common::process(&mut nyasynth, events, &mut left, &mut right);
+            prop_assert!(
+                left.iter().chain(right.iter()).all(|sample| sample.is_finite()),
+                "Non-finite output in buffer {}",
+                buffer
+            );
+        }
+    }
+}
+
+proptest! {
+    /// The normalizer only lets through note ons for keys which aren't held, and note offs for keys
+    /// which are, so the notes which reach the voices are always balanced.
+    #[test]
+    fn normalized_notes_are_balanced(events in prop::collection::vec(note_event(), 0..256)) {
+        let mut normalizer = EventNormalizer::new();
+        let mut held = std::collections::HashSet::new();
+        for event in events {
+            let passed = normalizer.process(event);
+            match event {
+                NoteEvent::NoteOn { channel, note, .. } => {
+                    let valid = channel < 16 && note < 128;
+                    let expected = valid && !held.contains(&(channel, note));
+                    prop_assert_eq!(passed.is_some(), expected, "{:?}", event);
+                    if expected {
+                        held.insert((channel, note));
+                    }
+                }
+                NoteEvent::NoteOff { channel, note, .. } => {
+                    let expected = held.remove(&(channel, note));
+                    prop_assert_eq!(passed.is_some(), expected, "{:?}", event);
+                }
+                _ => unreachable!(),
+            }
+            prop_assert_eq!(passed.is_some(), passed == Some(event));
+        }
+        for channel in 0..16 {
+            for note in 0..128 {
+                prop_assert_eq!(normalizer.is_held(channel, note), held.contains(&(channel, note)));
+            }
+        }
+    }
+}
+
+proptest! {
+    // Each case renders a couple of seconds of audio, so fewer cases are run.
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    /// No matter how messy the notes are (duplicated, unmatched, or out of order), releasing every
+    /// key lets the synth fall silent. If the voices and the held keys ever got out of sync, a
+    /// voice would be left droning.
+    #[test]
+    fn messy_notes_never_hang(
+        events in prop::collection::vec(note_event(), 0..64),
+        polycat in any::<bool>(),
+    ) {
+        let mut nyasynth = common::initialized_plugin(BUFFER_SIZE);
+        let param_map = nyasynth.params().param_map();
+        let (_, polycat_param, _) = param_map.iter().find(|(id, _, _)| id == "polycat").unwrap();
+        unsafe { polycat_param.set_normalized_value(if polycat { 1.0 } else { 0.0 }) };
+
+        // Every key on every channel is released, whether or not it was ever pressed.
+        let all_notes_off = (0..16)
+            .flat_map(|channel| {
+                (0..128).map(move |note| NoteEvent::NoteOff {
+                    timing: 0,
+                    voice_id: None,
+                    channel,
+                    note,
+                    velocity: 0.5,
+                })
+            })
+            .collect();
+
+        let mut left = vec![0.0; BUFFER_SIZE];
+        let mut right = vec![0.0; BUFFER_SIZE];
+        // The events are deliberately left unsorted.
+        let buffers = [events, all_notes_off]
+            .into_iter()
+            .chain(std::iter::repeat_with(Vec::new).take(RELEASE_BUFFERS));
+        for events in buffers {
+            left.fill(0.0);
+            right.fill(0.0);
+            common::process(&mut nyasynth, events, &mut left, &mut right);
+        }
+        let peak = left.iter().chain(right.iter()).fold(0.0f32, |peak, x| peak.max(x.abs()));
+        prop_assert!(peak < SILENCE, "Still playing at {} after every note was released", peak);
+    }
+}
@@ -0,0 +1,72 @@
+//! Checks that the synth keeps playing smoothly when the host changes the sample rate in the
+//! middle of a note.
+
+mod common;
+
+use common::{initialized_plugin, note_on, process_at, set_param, SAMPLE_RATE};
+
+const BUFFER_SIZE: usize = 4096;
+
+/// The largest jump between neighboring samples.
+fn largest_step(samples: &[f32]) -> f32 {
+    samples
+        .windows(2)
+        .map(|pair| (pair[1] - pair[0]).abs())
+        .fold(0.0, f32::max)
+}
+
+#[test]
+fn sample_rate_changes_dont_glitch() {
+    let mut nyasynth = initialized_plugin(BUFFER_SIZE);
+    // Sustain at full volume, so that the note's level holds steady across the changes.
+    set_param(&nyasynth, "meow_sustain", 1.0);
+    let mut events = vec![note_on(0, 57)];
+    let mut previous: Option<Vec<f32>> = None;
+    for sample_rate in [SAMPLE_RATE, 48000.0, 96000.0, 22050.0, SAMPLE_RATE] {
+        // Each sample rate gets two buffers, so that the second one shows how the synth sounds
+        // once it has settled in.
+        let mut buffers = Vec::new();
+        for _ in 0..2 {
+            let mut left = vec![0.0; BUFFER_SIZE];
+            let mut right = vec![0.0; BUFFER_SIZE];
+            process_at(
+                &mut nyasynth,
+                std::mem::take(&mut events),
+                sample_rate,
+                &mut left,
+                &mut right,
+            );
+            assert!(
+                left.iter().chain(&right).all(|sample| sample.is_finite()),
+                "non-finite output at {} Hz",
+                sample_rate
+            );
+            buffers.push(left);
+        }
+
+        if let Some(previous) = previous {
+            // A glitch shows up as a jump across the change which is much larger than anything
+            // the note does by itself, at either sample rate.
+            let boundary = (buffers[0][0] - previous[BUFFER_SIZE - 1]).abs();
+            let usual = largest_step(&previous).max(largest_step(&buffers[1]));
+            assert!(
+                boundary <= usual * 1.5 + 1.0e-3,
+                "jumped by {} when switching to {} Hz, but usually steps by at most {}",
+                boundary,
+                sample_rate,
+                usual
+            );
+            // The note keeps playing at about the same level.
+            let level = |samples: &[f32]| samples.iter().fold(0.0f32, |max, s| max.max(s.abs()));
+            let (before, after) = (level(&previous), level(&buffers[1]));
+            assert!(
+                after > before * 0.5 && after < before * 2.0,
+                "the level went from {} to {} when switching to {} Hz",
+                before,
+                after,
+                sample_rate
+            );
+        }
+        previous = buffers.pop();
+    }
+}
@@ -0,0 +1,55 @@
+//! Checks that quantized stray cat detunes land on the scale.
+
+use nyasynth::dsp::{Root, Scale, StrayCatParams};
+
+fn stray_cat(scale: Scale, root: Root) -> StrayCatParams {
+    StrayCatParams {
+        range: 3.0,
+        quantize: true,
+        scale,
+        root,
+    }
+}
+
+#[test]
+fn quantized_detunes_are_in_the_scale() {
+    let params = stray_cat(Scale::MajorPentatonic, Root::D);
+    // D major pentatonic: D, E, F#, A, and B.
+    let pitch_classes = [2, 4, 6, 9, 11];
+    for note in [50, 61, 64, 70] {
+        for i in -20..=20 {
+            let random = i as f32 / 20.0;
+            let detuned = note as f32 + params.detune(random, note);
+            assert_eq!(detuned, detuned.round(), "{} + {}", note, random);
+            let pitch_class = (detuned as i32).rem_euclid(12);
+            assert!(
+                pitch_classes.contains(&pitch_class),
+                "{} detuned by {} landed on {}",
+                note,
+                random,
+                detuned
+            );
+        }
+    }
+}
+
+#[test]
+fn quantized_detunes_snap_to_the_nearest_note() {
+    let params = stray_cat(Scale::Major, Root::C);
+    // From E (64), 1.4 semitones up is closer to F (65) than to G (67).
+    assert_eq!(params.detune(1.4 / 3.0, 64), 1.0);
+    // From C (60), 1.6 semitones down is closer to B (59) than to A (57).
+    assert_eq!(params.detune(-1.6 / 3.0, 60), -1.0);
+}
+
+#[test]
+fn without_a_scale_detunes_round_to_semitones() {
+    let params = stray_cat(Scale::Off, Root::C);
+    assert_eq!(params.detune(0.6, 60), 2.0);
+    assert_eq!(params.detune(-0.1, 61), 0.0);
+    let unquantized = StrayCatParams {
+        quantize: false,
+        ..params
+    };
+    assert_eq!(unquantized.detune(0.5, 60), 1.5);
+}
@@ -0,0 +1,33 @@
+//! Checks that states saved before the dotted and triplet synced rates were added keep their rates.
+
+use std::collections::BTreeMap;
+
+use nih_plug::prelude::*;
+use nih_plug::wrapper::state::ParamValue;
+use nyasynth::Nyasynth;
+
+#[test]
+fn old_states_keep_their_rates() {
+    // (old index, new index): 1 bar, 1/4, 1/12 (now 1/8T), and 1/16.
+    let rates = [
+        ("vibrato_rate", 2, 2),
+        ("meow_attack_sync", 4, 7),
+        ("meow_decay_sync", 6, 12),
+        ("meow_release_sync", 7, 13),
+    ];
+    let mut state = PluginState {
+        version: "1.4".to_string(),
+        params: rates
+            .iter()
+            .map(|(id, old, _)| (id.to_string(), ParamValue::I32(*old)))
+            .collect(),
+        fields: BTreeMap::new(),
+    };
+    Nyasynth::filter_state(&mut state);
+    for (id, _, new) in rates {
+        let Some(ParamValue::I32(index)) = state.params.get(id) else {
+            panic!("{} is missing from the migrated state", id);
+        };
+        assert_eq!(*index, new, "{} changed rate", id);
+    }
+}